@@ -12,12 +12,18 @@ pub trait CacheBackend: Send + Sync + 'static {
     /// 读取对象数据
     async fn read(&self, storage_id: u64) -> Result<Vec<u8>>;
 
+    /// 读取对象数据中 `[offset, offset + length)` 的字节
+    async fn read_range(&self, storage_id: u64, offset: u64, length: u64) -> Result<Vec<u8>>;
+
     /// 删除对象
     async fn delete(&self, storage_id: u64) -> Result<()>;
 
     /// 读取 xattrs
     async fn read_xattrs(&self, storage_id: u64) -> Result<CacheXattrs>;
 
+    /// 覆盖 xattrs (如延长过期时间)，数据不变
+    async fn write_xattrs(&self, storage_id: u64, xattrs: &CacheXattrs) -> Result<()>;
+
     /// 列出所有存储对象（启动时重建索引用）
     async fn list_all(&self) -> Result<Vec<(u64, CacheXattrs)>>;
 
@@ -37,6 +43,8 @@ pub struct CacheXattrs {
     pub checksum: Option<String>,
     pub content_type: Option<String>,
     pub etag: Option<String>,
+    /// 对象属性的 (HTTP 头名称, 值)，见 `coldstore_common::attributes`
+    pub attributes: Vec<(String, String)>,
     /// staging = 暂存数据 (PutObject), restored = 解冻数据
     pub category: CacheCategory,
 }
@@ -1,8 +1,10 @@
 use crate::backend::{CacheBackend, CacheCategory, CacheXattrs};
 use anyhow::Result;
+use std::io::SeekFrom;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tracing::debug;
 
 pub struct HddBackend {
@@ -49,6 +51,8 @@ struct XattrsJson {
     checksum: Option<String>,
     content_type: Option<String>,
     etag: Option<String>,
+    #[serde(default)]
+    attributes: Vec<(String, String)>,
     category: String,
 }
 
@@ -63,6 +67,7 @@ fn to_json(x: &CacheXattrs) -> XattrsJson {
         checksum: x.checksum.clone(),
         content_type: x.content_type.clone(),
         etag: x.etag.clone(),
+        attributes: x.attributes.clone(),
         category: match x.category {
             CacheCategory::Staging => "staging".into(),
             CacheCategory::Restored => "restored".into(),
@@ -81,6 +86,7 @@ fn from_json(j: &XattrsJson) -> CacheXattrs {
         checksum: j.checksum.clone(),
         content_type: j.content_type.clone(),
         etag: j.etag.clone(),
+        attributes: j.attributes.clone(),
         category: if j.category == "staging" {
             CacheCategory::Staging
         } else {
@@ -104,6 +110,15 @@ impl CacheBackend for HddBackend {
         Ok(fs::read(self.data_path(id, x.category)).await?)
     }
 
+    async fn read_range(&self, id: u64, offset: u64, length: u64) -> Result<Vec<u8>> {
+        let x = self.read_xattrs(id).await?;
+        let mut file = fs::File::open(self.data_path(id, x.category)).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut data = vec![0; usize::try_from(length)?];
+        file.read_exact(&mut data).await?;
+        Ok(data)
+    }
+
     async fn delete(&self, id: u64) -> Result<()> {
         let x = self.read_xattrs(id).await?;
         let _ = fs::remove_file(self.data_path(id, x.category)).await;
@@ -117,6 +132,11 @@ impl CacheBackend for HddBackend {
         Ok(from_json(&j))
     }
 
+    async fn write_xattrs(&self, id: u64, xattrs: &CacheXattrs) -> Result<()> {
+        fs::write(self.meta_path(id), serde_json::to_vec(&to_json(xattrs))?).await?;
+        Ok(())
+    }
+
     async fn list_all(&self) -> Result<Vec<(u64, CacheXattrs)>> {
         let mut out = Vec::new();
         let mut rd = fs::read_dir(self.base_path.join("meta")).await?;
@@ -3,23 +3,42 @@ pub mod hdd;
 pub mod service;
 
 use anyhow::Result;
+use coldstore_common::client::MetadataClientPool;
 use coldstore_common::config::CacheConfig;
+use coldstore_common::membership::{shutdown_signal, Membership};
+use std::sync::Arc;
 use tonic::transport::Server;
-use tracing::info;
+use tracing::{info, warn};
 
 pub async fn run(config: CacheConfig) -> Result<()> {
     let addr = config.listen.parse()?;
 
-    let cache_service = service::CacheServiceImpl::new(&config).await?;
+    let cache_service = Arc::new(service::CacheServiceImpl::new(&config).await?);
+
+    let metadata = Arc::new(MetadataClientPool::new(
+        &config.metadata_addrs,
+        &config.tls,
+        config.metadata_client.clone(),
+    )?);
+    let membership = Membership::start(metadata, cache_service.clone(), &config.membership).await;
 
     info!("Cache Worker 启动在 {}", config.listen);
 
-    Server::builder()
+    let mut server = Server::builder();
+    if let Some(tls) = config.tls.server_tls_config()? {
+        server = server.tls_config(tls)?;
+    }
+    server
         .add_service(
-            coldstore_proto::cache::cache_service_server::CacheServiceServer::new(cache_service),
+            coldstore_proto::cache::cache_service_server::CacheServiceServer::from_arc(
+                cache_service,
+            ),
         )
-        .serve(addr)
+        .serve_with_shutdown(addr, shutdown_signal())
         .await?;
 
+    if let Err(status) = membership.stop().await {
+        warn!("从 Metadata 注销失败: {}", status.message());
+    }
     Ok(())
 }
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use coldstore_common::config::CacheConfig;
+use coldstore_common::config::{self, CacheConfig};
 use tracing::info;
 
 #[tokio::main]
@@ -11,9 +11,11 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    info!("启动 ColdStore Cache Worker...");
+    let Some(config) = config::load_from_args::<CacheConfig>()? else {
+        return Ok(());
+    };
 
-    let config = CacheConfig::default();
+    info!("启动 ColdStore Cache Worker...");
 
     coldstore_cache::run(config).await
 }
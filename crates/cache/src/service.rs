@@ -1,17 +1,30 @@
 use crate::backend::{CacheBackend, CacheCategory, CacheXattrs};
 use crate::hdd::HddBackend;
 use anyhow::Result;
+use coldstore_common::attributes;
+use coldstore_common::checksum::verify_optional_sha256;
+use coldstore_common::client::MetadataClientPool;
 use coldstore_common::config::{CacheBackendConfig, CacheConfig};
+use coldstore_common::error::status_with_reason;
+use coldstore_common::membership::{Registration, WorkerMembership};
+use coldstore_proto::cache::cache_service_client::CacheServiceClient;
 use coldstore_proto::cache::cache_service_server::CacheService;
 use coldstore_proto::cache::*;
+use coldstore_proto::common;
+use coldstore_proto::metadata::{heartbeat_request, CacheHeartbeat, UpdateStagingLocationRequest};
 use prost_types::Timestamp;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
 use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
 use tonic::{Request, Response, Status, Streaming};
+use tracing::warn;
 
 const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+/// 排空时每个心跳周期迁移的暂存对象数，避免单轮耗时超过心跳超时
+const DRAIN_MIGRATE_BATCH: usize = 16;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct CacheKey {
@@ -57,8 +70,10 @@ struct CacheIndex {
 
 pub struct CacheServiceImpl {
     backend: Arc<dyn CacheBackend>,
-    _config: CacheConfig,
+    config: CacheConfig,
     index: Arc<RwLock<CacheIndex>>,
+    /// 排空期间拒绝新的暂存写入，已有暂存数据迁移到其它 Cache Worker
+    draining: AtomicBool,
 }
 
 impl CacheServiceImpl {
@@ -74,8 +89,9 @@ impl CacheServiceImpl {
 
         let svc = Self {
             backend,
-            _config: config.clone(),
+            config: config.clone(),
             index: Arc::new(RwLock::new(CacheIndex::default())),
+            draining: AtomicBool::new(false),
         };
         svc.rebuild_index().await?;
         Ok(svc)
@@ -199,15 +215,44 @@ impl CacheServiceImpl {
         Ok(entry)
     }
 
+    /// `ranges` 为空时读取整个对象，否则只从后端读取各区间并按顺序拼接
     async fn build_get_stream(
         &self,
         entry: StoredEntry,
+        ranges: &[common::ByteRange],
     ) -> Result<Response<ReceiverStream<Result<GetResponse, Status>>>, Status> {
-        let data = self
-            .backend
-            .read(entry.storage_id)
-            .await
-            .map_err(internal_status)?;
+        let data = if ranges.is_empty() {
+            self.backend
+                .read(entry.storage_id)
+                .await
+                .map_err(internal_status)?
+        } else {
+            let mut data = Vec::new();
+            for range in ranges {
+                if range.length == 0
+                    || range
+                        .offset
+                        .checked_add(range.length)
+                        .is_none_or(|end| end > entry.xattrs.size)
+                {
+                    return Err(status_with_reason(
+                        tonic::Code::OutOfRange,
+                        format!(
+                            "range {}+{} is outside object of {} bytes",
+                            range.offset, range.length, entry.xattrs.size
+                        ),
+                        common::ErrorReason::InvalidRange,
+                    ));
+                }
+                data.extend(
+                    self.backend
+                        .read_range(entry.storage_id, range.offset, range.length)
+                        .await
+                        .map_err(internal_status)?,
+                );
+            }
+            data
+        };
         let (tx, rx) = mpsc::channel(8);
         tokio::spawn(async move {
             let meta = GetResponse {
@@ -217,6 +262,13 @@ impl CacheServiceImpl {
                     content_type: entry.xattrs.content_type.clone(),
                     etag: entry.xattrs.etag.clone(),
                     checksum: entry.xattrs.checksum.clone(),
+                    attributes: Some(attributes::from_headers(
+                        entry
+                            .xattrs
+                            .attributes
+                            .iter()
+                            .map(|(name, value)| (name.as_str(), value.as_str())),
+                    )),
                 })),
             };
             let _ = tx.send(Ok(meta)).await;
@@ -273,6 +325,12 @@ impl CacheService for CacheServiceImpl {
         &self,
         req: Request<Streaming<PutStagingRequest>>,
     ) -> std::result::Result<Response<PutStagingResponse>, Status> {
+        if self.draining.load(Ordering::Acquire) {
+            return Err(Status::unavailable(format!(
+                "cache worker {} is draining",
+                self.config.membership.node_id
+            )));
+        }
         let mut stream = req.into_inner();
         let mut meta: Option<PutStagingMeta> = None;
         let mut data = Vec::new();
@@ -291,6 +349,8 @@ impl CacheService for CacheServiceImpl {
                 "staging object size does not match payload",
             ));
         }
+        let checksum =
+            verify_optional_sha256(&data, meta.checksum.as_deref(), "cache.put_staging")?;
 
         let key = CacheKey::new(
             meta.bucket.clone(),
@@ -304,9 +364,10 @@ impl CacheService for CacheServiceImpl {
             size: meta.size,
             expire_at: 0,
             cached_at: now_unix(),
-            checksum: meta.checksum,
+            checksum: Some(checksum),
             content_type: meta.content_type,
             etag: meta.etag,
+            attributes: Vec::new(),
             category: CacheCategory::Staging,
         };
         let storage_id = self
@@ -341,6 +402,8 @@ impl CacheService for CacheServiceImpl {
                 "restored object size does not match payload",
             ));
         }
+        let checksum =
+            verify_optional_sha256(&data, meta.checksum.as_deref(), "cache.put_restored")?;
         let expire_at = meta
             .expire_at
             .ok_or_else(|| Status::invalid_argument("restored object missing expire_at"))?;
@@ -357,9 +420,10 @@ impl CacheService for CacheServiceImpl {
             size: meta.size,
             expire_at: expire_at.seconds,
             cached_at: now_unix(),
-            checksum: meta.checksum,
+            checksum: Some(checksum),
             content_type: meta.content_type,
             etag: meta.etag,
+            attributes: attributes::to_headers(&meta.attributes.unwrap_or_default()),
             category: CacheCategory::Restored,
         };
         self.put_bytes(key, data, xattrs)
@@ -381,6 +445,28 @@ impl CacheService for CacheServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn extend_restored(
+        &self,
+        req: Request<ExtendRestoredRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        let req = req.into_inner();
+        let expire_at = req
+            .expire_at
+            .ok_or_else(|| Status::invalid_argument("extend_restored missing expire_at"))?;
+        let key = CacheKey::new(req.bucket, req.key, req.version_id);
+        let mut entry = match self.find_entry(&key, CacheCategory::Restored).await {
+            Some(entry) if !is_expired(entry.xattrs.expire_at) => entry,
+            _ => return Err(Status::not_found("restored object not found in cache")),
+        };
+        entry.xattrs.expire_at = expire_at.seconds;
+        self.backend
+            .write_xattrs(entry.storage_id, &entry.xattrs)
+            .await
+            .map_err(internal_status)?;
+        self.insert_entry(key, entry).await;
+        Ok(Response::new(()))
+    }
+
     type GetStream = ReceiverStream<Result<GetResponse, Status>>;
 
     async fn get(
@@ -390,7 +476,7 @@ impl CacheService for CacheServiceImpl {
         let req = req.into_inner();
         let key = CacheKey::new(req.bucket, req.key, req.version_id);
         let entry = self.read_restored(&key).await?;
-        self.build_get_stream(entry).await
+        self.build_get_stream(entry, &req.ranges).await
     }
 
     async fn contains(
@@ -534,6 +620,171 @@ fn is_expired(expire_at: i64) -> bool {
     expire_at > 0 && expire_at <= now_unix()
 }
 
+impl CacheServiceImpl {
+    /// 选择剩余容量最大的其它在线 Cache Worker 作为迁移目标，返回其节点 ID 与客户端
+    async fn migration_target(
+        &self,
+        metadata: &MetadataClientPool,
+    ) -> std::result::Result<(u64, CacheServiceClient<Channel>), Status> {
+        let node_id = self.config.membership.node_id;
+        let target = metadata
+            .call(|mut client| async move { client.list_online_cache_workers(()).await })
+            .await?
+            .workers
+            .into_iter()
+            .filter(|worker| worker.node_id != node_id)
+            .max_by_key(|worker| worker.total_capacity.saturating_sub(worker.used_capacity))
+            .ok_or_else(|| {
+                Status::unavailable("no online cache worker to migrate staging data to")
+            })?;
+        let channel = self
+            .config
+            .tls
+            .endpoint(&target.addr)
+            .map_err(Status::from)?
+            .connect()
+            .await
+            .map_err(|err| Status::unavailable(format!("connect {}: {err}", target.addr)))?;
+        Ok((target.node_id, CacheServiceClient::new(channel)))
+    }
+
+    /// 将一批暂存对象写入迁移目标，在 Metadata 中更新副本位置后删除本地副本
+    async fn migrate_staging(
+        &self,
+        metadata: &MetadataClientPool,
+    ) -> std::result::Result<(), Status> {
+        let batch: Vec<_> = {
+            let index = self.index.read().await;
+            index
+                .staging
+                .iter()
+                .take(DRAIN_MIGRATE_BATCH)
+                .map(|(key, entry)| (key.clone(), entry.clone()))
+                .collect()
+        };
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let (target_id, mut target) = self.migration_target(metadata).await?;
+        for (key, entry) in batch {
+            let data = self
+                .backend
+                .read(entry.storage_id)
+                .await
+                .map_err(internal_status)?;
+            let xattrs = entry.xattrs;
+            let location = UpdateStagingLocationRequest {
+                bucket: xattrs.bucket.clone(),
+                key: xattrs.key.clone(),
+                version_id: xattrs.version_id.clone(),
+                staging_worker_id: Some(target_id),
+                error: None,
+            };
+            let mut requests = vec![PutStagingRequest {
+                payload: Some(put_staging_request::Payload::Meta(PutStagingMeta {
+                    bucket: xattrs.bucket,
+                    key: xattrs.key,
+                    version_id: xattrs.version_id,
+                    size: xattrs.size,
+                    checksum: xattrs.checksum,
+                    content_type: xattrs.content_type,
+                    etag: xattrs.etag,
+                })),
+            }];
+            requests.extend(
+                data.chunks(STREAM_CHUNK_SIZE)
+                    .map(|chunk| PutStagingRequest {
+                        payload: Some(put_staging_request::Payload::Data(chunk.to_vec())),
+                    }),
+            );
+            target.put_staging(tokio_stream::iter(requests)).await?;
+            let updated = metadata
+                .call(|mut client| {
+                    let location = location.clone();
+                    async move { client.update_staging_location(location).await }
+                })
+                .await;
+            // 对象已被删除时暂存副本无人引用，直接丢弃本地副本
+            match updated {
+                Err(status) if status.code() != tonic::Code::NotFound => return Err(status),
+                _ => {}
+            }
+            self.delete_entry(&key, CacheCategory::Staging)
+                .await
+                .map_err(internal_status)?;
+        }
+        Ok(())
+    }
+
+    /// 心跳与注册使用的容量统计；读取失败时按空缓存上报
+    async fn current_stats(&self) -> CacheStats {
+        match self.stats(Request::new(())).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => {
+                warn!("读取缓存统计失败: {}", status.message());
+                CacheStats::default()
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl WorkerMembership for CacheServiceImpl {
+    async fn registration(&self) -> Registration {
+        let stats = self.current_stats().await;
+        let bdev_name = match &self.config.backend {
+            CacheBackendConfig::Hdd { path, .. } => path.clone(),
+            CacheBackendConfig::Spdk { bdev_name, .. } => bdev_name.clone(),
+        };
+        Registration::Cache(common::CacheWorkerInfo {
+            node_id: self.config.membership.node_id,
+            addr: self.config.membership.addr(&self.config.listen).to_string(),
+            status: common::NodeStatus::NodeOnline as i32,
+            last_heartbeat: None,
+            bdev_name,
+            total_capacity: stats.total_capacity,
+            used_capacity: stats.used_capacity,
+            blob_count: stats.object_count,
+            io_unit_size: 0,
+            drain: None,
+        })
+    }
+
+    async fn heartbeat(&self) -> heartbeat_request::Payload {
+        let stats = self.current_stats().await;
+        heartbeat_request::Payload::Cache(CacheHeartbeat {
+            used_capacity: stats.used_capacity,
+            blob_count: stats.object_count,
+        })
+    }
+
+    async fn status_changed(&self, status: common::NodeStatus) {
+        self.draining.store(
+            status == common::NodeStatus::NodeDraining,
+            Ordering::Release,
+        );
+    }
+
+    /// 解冻数据可从磁带重新取回，只迁移尚未归档的暂存数据
+    async fn drain(&self, metadata: &MetadataClientPool) -> common::DrainProgress {
+        let migrated = self.migrate_staging(metadata).await;
+        let index = self.index.read().await;
+        let remaining_items = index.staging.len() as u64;
+        let remaining_bytes = index.staging.values().map(|entry| entry.xattrs.size).sum();
+        let detail = match migrated {
+            Ok(()) => format!("{remaining_items} staging objects left to migrate"),
+            Err(status) => format!("migrate staging data: {}", status.message()),
+        };
+        common::DrainProgress {
+            remaining_items,
+            remaining_bytes,
+            complete: remaining_items == 0,
+            detail: Some(detail),
+        }
+    }
+}
+
 fn internal_status(err: anyhow::Error) -> Status {
     Status::internal(err.to_string())
 }
@@ -595,6 +846,7 @@ mod tests {
             content_type: Some("text/plain".into()),
             etag: Some("etag-1".into()),
             category: CacheCategory::Restored,
+            attributes: Vec::new(),
         };
         svc.put_bytes(key, b"hello world".to_vec(), xattrs)
             .await
@@ -616,6 +868,7 @@ mod tests {
                 bucket: "docs".into(),
                 key: "guide.txt".into(),
                 version_id: Some("v1".into()),
+                ..Default::default()
             }))
             .await
             .expect("get should succeed")
@@ -635,6 +888,46 @@ mod tests {
             Some(get_response::Payload::Data(bytes)) => assert_eq!(bytes, b"hello world"),
             other => panic!("unexpected second payload: {other:?}"),
         }
+
+        let ranged = |ranges: Vec<common::ByteRange>| GetRequest {
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            version_id: Some("v1".into()),
+            ranges,
+        };
+        let mut stream = svc
+            .get(Request::new(ranged(vec![
+                common::ByteRange {
+                    offset: 6,
+                    length: 5,
+                },
+                common::ByteRange {
+                    offset: 0,
+                    length: 1,
+                },
+            ])))
+            .await
+            .expect("ranged get should succeed")
+            .into_inner();
+        let meta = stream.next().await.expect("meta chunk").expect("meta ok");
+        match meta.payload {
+            Some(get_response::Payload::Meta(meta)) => assert_eq!(meta.size, 11),
+            other => panic!("unexpected first payload: {other:?}"),
+        }
+        let data = stream.next().await.expect("data chunk").expect("data ok");
+        match data.payload {
+            Some(get_response::Payload::Data(bytes)) => assert_eq!(bytes, b"worldh"),
+            other => panic!("unexpected second payload: {other:?}"),
+        }
+
+        let err = svc
+            .get(Request::new(ranged(vec![common::ByteRange {
+                offset: 10,
+                length: 2,
+            }])))
+            .await
+            .expect_err("range past the end");
+        assert_eq!(err.code(), tonic::Code::OutOfRange);
     }
 
     #[tokio::test]
@@ -654,6 +947,7 @@ mod tests {
             content_type: None,
             etag: Some("etag-2".into()),
             category: CacheCategory::Staging,
+            attributes: Vec::new(),
         };
         svc.put_bytes(key, b"draft".to_vec(), xattrs)
             .await
@@ -672,4 +966,142 @@ mod tests {
         assert_eq!(listed.entries[0].bucket, "docs");
         assert_eq!(listed.entries[0].key, "draft.txt");
     }
+
+    #[tokio::test]
+    async fn draining_cache_rejects_staging_and_migrates_it_to_another_worker() {
+        use coldstore_common::client::ClientConfig;
+        use coldstore_common::config::MetadataConfig;
+        use coldstore_common::tls::TlsConfig;
+        use coldstore_metadata::service::MetadataServiceImpl;
+        use coldstore_proto::cache::cache_service_server::CacheServiceServer;
+        use coldstore_proto::metadata::metadata_service_server::MetadataServiceServer;
+        use tonic::transport::Server;
+
+        let free_addr = || {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+            listener.local_addr().expect("addr")
+        };
+        let (metadata_addr, target_addr) = (free_addr(), free_addr());
+        let metadata_service = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("metadata init");
+        let mut target_config = test_config();
+        target_config.membership.node_id = 2;
+        target_config.listen = target_addr.to_string();
+        let target = Arc::new(
+            CacheServiceImpl::new(&target_config)
+                .await
+                .expect("target init"),
+        );
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let served = target.clone();
+        tokio::spawn(async move {
+            let metadata = Server::builder()
+                .add_service(MetadataServiceServer::new(metadata_service))
+                .serve(metadata_addr);
+            let cache = Server::builder()
+                .add_service(CacheServiceServer::from_arc(served))
+                .serve(target_addr);
+            tokio::select! {
+                _ = metadata => {}
+                _ = cache => {}
+                _ = shutdown_rx => {}
+            }
+        });
+        let metadata = MetadataClientPool::new(
+            &[metadata_addr.to_string()],
+            &TlsConfig::default(),
+            ClientConfig::default(),
+        )
+        .expect("metadata pool");
+        let Registration::Cache(info) = target.registration().await else {
+            unreachable!("cache worker registers as cache");
+        };
+        coldstore_common::membership::register(&metadata, Registration::Cache(info))
+            .await
+            .expect("register target");
+
+        let svc = CacheServiceImpl::new(&test_config())
+            .await
+            .expect("service init");
+        let mut metadata_client = metadata.client().await.expect("metadata leader");
+        metadata_client
+            .create_bucket(Request::new(common::BucketInfo {
+                name: "docs".into(),
+                ..Default::default()
+            }))
+            .await
+            .expect("create bucket");
+        metadata_client
+            .put_object(Request::new(common::ObjectMetadata {
+                bucket: "docs".into(),
+                key: "draft.txt".into(),
+                size: 5,
+                storage_class: common::StorageClass::ColdPending as i32,
+                staging_worker_id: Some(1),
+                ..Default::default()
+            }))
+            .await
+            .expect("put object");
+
+        let key = CacheKey::new("docs".into(), "draft.txt".into(), None);
+        svc.put_bytes(
+            key.clone(),
+            b"draft".to_vec(),
+            CacheXattrs {
+                bucket: "docs".into(),
+                key: "draft.txt".into(),
+                version_id: None,
+                size: 5,
+                expire_at: 0,
+                cached_at: now_unix(),
+                checksum: None,
+                content_type: None,
+                etag: Some("etag-2".into()),
+                category: CacheCategory::Staging,
+                attributes: Vec::new(),
+            },
+        )
+        .await
+        .expect("put staging should succeed");
+
+        svc.status_changed(common::NodeStatus::NodeDraining).await;
+        let progress = svc.drain(&metadata).await;
+        assert!(progress.complete, "{progress:?}");
+        assert_eq!(progress.remaining_items, 0);
+        assert!(svc.find_entry(&key, CacheCategory::Staging).await.is_none());
+        let migrated = target
+            .find_entry(&key, CacheCategory::Staging)
+            .await
+            .expect("staging object migrated to target");
+        assert_eq!(migrated.xattrs.etag.as_deref(), Some("etag-2"));
+        let object = metadata_client
+            .head_object(Request::new(coldstore_proto::metadata::HeadObjectRequest {
+                bucket: "docs".into(),
+                key: "draft.txt".into(),
+            }))
+            .await
+            .expect("head object")
+            .into_inner();
+        assert_eq!(object.staging_worker_id, Some(2));
+
+        target
+            .status_changed(common::NodeStatus::NodeDraining)
+            .await;
+        let mut client = CacheServiceClient::connect(format!("http://{target_addr}"))
+            .await
+            .expect("connect target");
+        let err = client
+            .put_staging(tokio_stream::iter(vec![PutStagingRequest {
+                payload: Some(put_staging_request::Payload::Meta(PutStagingMeta {
+                    bucket: "docs".into(),
+                    key: "late.txt".into(),
+                    ..Default::default()
+                })),
+            }]))
+            .await
+            .expect_err("draining cache rejects staging writes");
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        let _ = shutdown_tx.send(());
+    }
 }
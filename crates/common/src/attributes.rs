@@ -0,0 +1,146 @@
+//! 对象属性 (用户元数据与 HTTP 系统头) 与 HTTP 头之间的映射
+//!
+//! Gateway 按此解析 PUT 请求头并生成 HEAD/GET 响应头；磁带对象头与缓存副本的
+//! 扩展属性以同样的 (头名称, 值) 形式保存，元数据丢失时仍可从磁带恢复。
+
+use coldstore_proto::common::ObjectAttributes;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// 用户元数据头前缀
+pub const USER_METADATA_PREFIX: &str = "x-amz-meta-";
+/// 对象标签头，值为 URL 编码的 `key=value&...`
+pub const TAGGING_HEADER: &str = "x-amz-tagging";
+
+const CONTENT_ENCODING: &str = "content-encoding";
+const CONTENT_DISPOSITION: &str = "content-disposition";
+const CACHE_CONTROL: &str = "cache-control";
+const EXPIRES: &str = "expires";
+
+/// URL 编码时保留的字符 (RFC 3986 unreserved)
+const TAG_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// 从 HTTP 头解析对象属性；名称大小写不敏感，无关的头被忽略
+pub fn from_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> ObjectAttributes {
+    let mut attributes = ObjectAttributes::default();
+    for (name, value) in headers {
+        let name = name.to_ascii_lowercase();
+        let value = value.to_string();
+        match name.as_str() {
+            CONTENT_ENCODING => attributes.content_encoding = Some(value),
+            CONTENT_DISPOSITION => attributes.content_disposition = Some(value),
+            CACHE_CONTROL => attributes.cache_control = Some(value),
+            EXPIRES => attributes.expires = Some(value),
+            TAGGING_HEADER => attributes.tags = parse_tagging(&value),
+            _ => {
+                if let Some(key) = name.strip_prefix(USER_METADATA_PREFIX) {
+                    attributes.user_metadata.insert(key.to_string(), value);
+                }
+            }
+        }
+    }
+    attributes
+}
+
+/// 将对象属性展开为 HTTP 头 (名称小写，按名称排序)
+pub fn to_headers(attributes: &ObjectAttributes) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = [
+        (CACHE_CONTROL, &attributes.cache_control),
+        (CONTENT_DISPOSITION, &attributes.content_disposition),
+        (CONTENT_ENCODING, &attributes.content_encoding),
+        (EXPIRES, &attributes.expires),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+    .collect();
+    headers.extend(
+        attributes
+            .user_metadata
+            .iter()
+            .map(|(key, value)| (format!("{USER_METADATA_PREFIX}{key}"), value.clone())),
+    );
+    if !attributes.tags.is_empty() {
+        headers.push((TAGGING_HEADER.to_string(), format_tagging(attributes)));
+    }
+    headers.sort();
+    headers
+}
+
+/// 解析 `x-amz-tagging` 的值；没有 `=` 的项视为空值标签
+fn parse_tagging(value: &str) -> std::collections::BTreeMap<String, String> {
+    let decode = |part: &str| {
+        percent_decode_str(&part.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    value
+        .split('&')
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (key, value) = item.split_once('=').unwrap_or((item, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn format_tagging(attributes: &ObjectAttributes) -> String {
+    attributes
+        .tags
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "{}={}",
+                utf8_percent_encode(key, TAG_ENCODE_SET),
+                utf8_percent_encode(value, TAG_ENCODE_SET)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_round_trip_through_attributes() {
+        let attributes = from_headers([
+            ("Content-Type", "text/plain"),
+            ("Content-Encoding", "gzip"),
+            ("Content-Disposition", "attachment; filename=\"a.txt\""),
+            ("Cache-Control", "no-cache"),
+            ("Expires", "Thu, 01 Dec 2044 16:00:00 GMT"),
+            ("X-Amz-Meta-Project", "Apollo"),
+            ("x-amz-meta-owner", "ops team"),
+            ("x-amz-tagging", "env=prod&team=data%20eng&flag"),
+        ]);
+        assert_eq!(attributes.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(attributes.user_metadata["project"], "Apollo");
+        assert_eq!(attributes.tags["team"], "data eng");
+        assert_eq!(attributes.tags["flag"], "");
+
+        let headers = to_headers(&attributes);
+        assert_eq!(
+            headers
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "cache-control",
+                "content-disposition",
+                "content-encoding",
+                "expires",
+                "x-amz-meta-owner",
+                "x-amz-meta-project",
+                "x-amz-tagging",
+            ]
+        );
+        let pairs = headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()));
+        assert_eq!(from_headers(pairs), attributes);
+    }
+}
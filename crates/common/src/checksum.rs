@@ -0,0 +1,83 @@
+//! 端到端数据完整性校验
+//!
+//! 对象 checksum 统一为 SHA-256 hex 字符串，由 Gateway 计算并随数据
+//! 经过 Scheduler → Cache 暂存 → 磁带归档包 → 解冻缓存 各环节传递，
+//! 每一跳收到数据后都重新计算并与上游声明的值比对。
+
+use crate::error::{Error, Result};
+use sha2::{Digest, Sha256};
+
+/// 计算 SHA-256，返回小写 hex
+pub fn sha256_hex(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// 校验数据的 SHA-256 是否与期望值一致，成功时返回实际值
+///
+/// `expected` 大小写不敏感；`context` 用于错误信息定位是哪一跳出错。
+#[allow(clippy::result_large_err)]
+pub fn verify_sha256(data: &[u8], expected: &str, context: &str) -> Result<String> {
+    let actual = sha256_hex(data);
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(actual)
+    } else {
+        Err(Error::ChecksumMismatch {
+            context: context.to_string(),
+            expected: expected.to_string(),
+            actual,
+        })
+    }
+}
+
+/// 若上游提供了 checksum 则校验，否则直接计算；返回实际 SHA-256
+#[allow(clippy::result_large_err)]
+pub fn verify_optional_sha256(
+    data: &[u8],
+    expected: Option<&str>,
+    context: &str,
+) -> Result<String> {
+    match expected.filter(|value| !value.is_empty()) {
+        Some(expected) => verify_sha256(data, expected, context),
+        None => Ok(sha256_hex(data)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_hex_matches_known_vector() {
+        assert_eq!(
+            sha256_hex(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn verify_sha256_accepts_uppercase_and_rejects_mismatch() {
+        let expected = sha256_hex(b"hello").to_uppercase();
+        assert!(verify_sha256(b"hello", &expected, "test").is_ok());
+
+        let err = verify_sha256(b"hell0", &expected, "cache.put_staging")
+            .expect_err("mismatch should fail");
+        assert!(matches!(err, Error::ChecksumMismatch { .. }));
+        let status = tonic::Status::from(err);
+        assert_eq!(status.code(), tonic::Code::DataLoss);
+        assert!(status.message().contains("cache.put_staging"));
+    }
+
+    #[test]
+    fn verify_optional_sha256_computes_when_absent() {
+        assert_eq!(
+            verify_optional_sha256(b"hello", None, "test").unwrap(),
+            sha256_hex(b"hello")
+        );
+        assert_eq!(
+            verify_optional_sha256(b"hello", Some(""), "test").unwrap(),
+            sha256_hex(b"hello")
+        );
+    }
+}
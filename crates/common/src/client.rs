@@ -0,0 +1,451 @@
+//! 组件间 gRPC 客户端
+//!
+//! - [`ChannelPool`]: 对一组同类节点 (如 Scheduler Worker) 各保持一个复用的 channel，
+//!   轮询选择健康节点；连接失败的节点在 `unhealthy_cooldown_ms` 内被跳过
+//! - [`MetadataClientPool`]: 在 `ChannelPool` 之上通过 `GetClusterInfo` 发现
+//!   Metadata leader，写请求只发给 leader；收到 [`not_leader`] 或 UNAVAILABLE 时
+//!   按退避策略重新发现并重试
+
+use crate::error::Result;
+use crate::tls::TlsConfig;
+use coldstore_proto::metadata::metadata_service_client::MetadataServiceClient;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tonic::transport::Channel;
+use tonic::{Code, Status};
+use tracing::{debug, warn};
+
+/// NotLeader 响应中携带 leader 地址的 gRPC metadata 键
+pub const LEADER_HINT_KEY: &str = "x-coldstore-leader";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientConfig {
+    pub connect_timeout_ms: u64,
+    /// 单次请求超时；0 表示不限制 (流式大对象传输)
+    pub request_timeout_ms: u64,
+    /// 首次请求失败后的最大重试次数
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// 连接失败的节点被跳过的时长
+    pub unhealthy_cooldown_ms: u64,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: 3_000,
+            request_timeout_ms: 0,
+            max_retries: 3,
+            initial_backoff_ms: 50,
+            max_backoff_ms: 2_000,
+            unhealthy_cooldown_ms: 5_000,
+        }
+    }
+}
+
+impl ClientConfig {
+    pub fn validate(&self, field: &str, problems: &mut Vec<String>) {
+        if self.connect_timeout_ms == 0 {
+            problems.push(format!("{field}.connect_timeout_ms must be positive"));
+        }
+        if self.initial_backoff_ms == 0 || self.max_backoff_ms < self.initial_backoff_ms {
+            problems.push(format!(
+                "{field}.max_backoff_ms must be >= {field}.initial_backoff_ms > 0"
+            ));
+        }
+    }
+
+    /// 第 `attempt` 次重试前的等待时间 (指数退避，封顶 `max_backoff_ms`)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.min(16);
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
+
+/// 非 leader 节点拒绝请求时返回的状态；`leader_addr` 已知时附带在 metadata 中
+pub fn not_leader(leader_addr: Option<&str>) -> Status {
+    let mut status = Status::failed_precondition("not leader");
+    if let Some(addr) = leader_addr.and_then(|addr| addr.parse().ok()) {
+        status.metadata_mut().insert(LEADER_HINT_KEY, addr);
+    }
+    status
+}
+
+pub fn is_not_leader(status: &Status) -> bool {
+    status.code() == Code::FailedPrecondition && status.message() == "not leader"
+}
+
+/// NotLeader 响应中的 leader 地址
+pub fn leader_hint(status: &Status) -> Option<String> {
+    status
+        .metadata()
+        .get(LEADER_HINT_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+struct PooledChannel {
+    addr: String,
+    channel: Channel,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl PooledChannel {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until
+            .lock()
+            .unwrap()
+            .is_none_or(|until| until <= now)
+    }
+}
+
+pub struct ChannelPool {
+    channels: Vec<PooledChannel>,
+    next: AtomicUsize,
+    config: ClientConfig,
+}
+
+impl ChannelPool {
+    /// 为每个地址建立惰性连接的 channel；连接在首次请求时建立并在之后复用
+    #[allow(clippy::result_large_err)]
+    pub fn new(addrs: &[String], tls: &TlsConfig, config: ClientConfig) -> Result<Self> {
+        let mut channels = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            let mut endpoint = tls
+                .endpoint(addr)?
+                .connect_timeout(Duration::from_millis(config.connect_timeout_ms));
+            if config.request_timeout_ms > 0 {
+                endpoint = endpoint.timeout(Duration::from_millis(config.request_timeout_ms));
+            }
+            channels.push((addr.clone(), endpoint.connect_lazy()));
+        }
+        Ok(Self::from_channels(channels, config))
+    }
+
+    /// 使用已建立的 channel (测试或自定义传输)
+    pub fn from_channels(channels: Vec<(String, Channel)>, config: ClientConfig) -> Self {
+        Self {
+            channels: channels
+                .into_iter()
+                .map(|(addr, channel)| PooledChannel {
+                    addr,
+                    channel,
+                    unhealthy_until: Mutex::new(None),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    pub fn addr(&self, index: usize) -> &str {
+        &self.channels[index].addr
+    }
+
+    pub fn channel(&self, index: usize) -> Channel {
+        self.channels[index].channel.clone()
+    }
+
+    pub fn position(&self, addr: &str) -> Option<usize> {
+        self.channels.iter().position(|pooled| pooled.addr == addr)
+    }
+
+    /// 轮询选择下一个健康节点；全部不健康时仍按轮询返回，避免彻底不可用
+    pub fn pick(&self) -> Option<usize> {
+        if self.channels.is_empty() {
+            return None;
+        }
+        let now = Instant::now();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.channels.len();
+        let index = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&index| self.channels[index].is_healthy(now))
+            .unwrap_or(start % len);
+        Some(index)
+    }
+
+    /// 健康节点在前的遍历顺序 (leader 发现用)
+    pub fn probe_order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) =
+            (0..self.channels.len()).partition(|&index| self.channels[index].is_healthy(now));
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    pub fn mark_unhealthy(&self, index: usize) {
+        warn!("节点 {} 不可用，暂时跳过", self.channels[index].addr);
+        *self.channels[index].unhealthy_until.lock().unwrap() =
+            Some(Instant::now() + Duration::from_millis(self.config.unhealthy_cooldown_ms));
+    }
+
+    pub fn mark_healthy(&self, index: usize) {
+        *self.channels[index].unhealthy_until.lock().unwrap() = None;
+    }
+
+    /// 在健康节点间轮询执行 `op`，UNAVAILABLE 时换节点退避重试
+    pub async fn call<T, F, Fut>(&self, mut op: F) -> std::result::Result<T, Status>
+    where
+        F: FnMut(Channel) -> Fut,
+        Fut: Future<Output = std::result::Result<T, Status>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let index = self
+                .pick()
+                .ok_or_else(|| Status::unavailable("no endpoints configured"))?;
+            match op(self.channel(index)).await {
+                Err(status) if status.code() == Code::Unavailable => {
+                    self.mark_unhealthy(index);
+                    if attempt >= self.config.max_retries {
+                        return Err(status);
+                    }
+                    tokio::time::sleep(self.config.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => {
+                    self.mark_healthy(index);
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// 连接全部 Metadata 节点并把请求路由到 leader
+pub struct MetadataClientPool {
+    pool: ChannelPool,
+    leader: Mutex<Option<usize>>,
+}
+
+impl MetadataClientPool {
+    #[allow(clippy::result_large_err)]
+    pub fn new(addrs: &[String], tls: &TlsConfig, config: ClientConfig) -> Result<Self> {
+        Ok(Self::from_pool(ChannelPool::new(addrs, tls, config)?))
+    }
+
+    pub fn from_pool(pool: ChannelPool) -> Self {
+        Self {
+            pool,
+            leader: Mutex::new(None),
+        }
+    }
+
+    /// 当前已知的 leader 地址
+    pub fn leader_addr(&self) -> Option<String> {
+        let leader = *self.leader.lock().unwrap();
+        leader.map(|index| self.pool.addr(index).to_owned())
+    }
+
+    /// leader 的客户端；尚未发现 leader 时先发现
+    pub async fn client(&self) -> std::result::Result<MetadataServiceClient<Channel>, Status> {
+        let index = self.leader_index().await?;
+        Ok(MetadataServiceClient::new(self.pool.channel(index)))
+    }
+
+    /// 在 leader 上执行 `op`；NotLeader / UNAVAILABLE 时重新发现 leader 并退避重试
+    pub async fn call<T, F, Fut>(&self, mut op: F) -> std::result::Result<T, Status>
+    where
+        F: FnMut(MetadataServiceClient<Channel>) -> Fut,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, Status>>,
+    {
+        self.call_channel(|channel| op(MetadataServiceClient::new(channel)))
+            .await
+    }
+
+    /// 同 [`Self::call`]，`op` 拿到 leader 的 channel，用于 Metadata 上的其它 gRPC 服务
+    pub async fn call_channel<T, F, Fut>(&self, mut op: F) -> std::result::Result<T, Status>
+    where
+        F: FnMut(Channel) -> Fut,
+        Fut: Future<Output = std::result::Result<tonic::Response<T>, Status>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let status = match self.leader_index().await {
+                Ok(index) => match op(self.pool.channel(index)).await {
+                    Ok(response) => return Ok(response.into_inner()),
+                    Err(status) if is_not_leader(&status) => {
+                        self.follow_hint(&status);
+                        status
+                    }
+                    Err(status) if status.code() == Code::Unavailable => {
+                        self.pool.mark_unhealthy(index);
+                        self.forget_leader(index);
+                        status
+                    }
+                    Err(status) => return Err(status),
+                },
+                Err(status) => status,
+            };
+            if attempt >= self.pool.config().max_retries {
+                return Err(status);
+            }
+            debug!(
+                "Metadata 请求失败，重试 ({}): {}",
+                attempt + 1,
+                status.message()
+            );
+            tokio::time::sleep(self.pool.config().backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn leader_index(&self) -> std::result::Result<usize, Status> {
+        if let Some(index) = *self.leader.lock().unwrap() {
+            return Ok(index);
+        }
+        let index = self.discover().await?;
+        *self.leader.lock().unwrap() = Some(index);
+        Ok(index)
+    }
+
+    /// 依次询问各节点的 `GetClusterInfo`，取第一个能给出 leader 的应答
+    async fn discover(&self) -> std::result::Result<usize, Status> {
+        let mut last_error = Status::unavailable("no metadata endpoints configured");
+        for index in self.pool.probe_order() {
+            let mut client = MetadataServiceClient::new(self.pool.channel(index));
+            match client.get_cluster_info(()).await {
+                Ok(response) => {
+                    self.pool.mark_healthy(index);
+                    let info = response.into_inner();
+                    let leader_addr = info.leader_id.and_then(|leader_id| {
+                        info.metadata_nodes
+                            .iter()
+                            .find(|node| node.node_id == leader_id)
+                            .map(|node| node.addr.clone())
+                    });
+                    // leader 地址不在配置列表中时 (如监听 0.0.0.0)，使用应答节点
+                    return Ok(leader_addr
+                        .and_then(|addr| self.pool.position(&addr))
+                        .unwrap_or(index));
+                }
+                Err(status) if is_not_leader(&status) => {
+                    if let Some(leader) = leader_hint(&status).and_then(|a| self.pool.position(&a))
+                    {
+                        return Ok(leader);
+                    }
+                    last_error = status;
+                }
+                Err(status) => {
+                    if status.code() == Code::Unavailable {
+                        self.pool.mark_unhealthy(index);
+                    }
+                    last_error = status;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    fn follow_hint(&self, status: &Status) {
+        let hinted = leader_hint(status).and_then(|addr| self.pool.position(&addr));
+        *self.leader.lock().unwrap() = hinted;
+    }
+
+    fn forget_leader(&self, index: usize) {
+        let mut leader = self.leader.lock().unwrap();
+        if *leader == Some(index) {
+            *leader = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lazy_pool(addrs: &[&str]) -> ChannelPool {
+        ChannelPool::from_channels(
+            addrs
+                .iter()
+                .map(|addr| {
+                    (
+                        addr.to_string(),
+                        Channel::from_shared(format!("http://{addr}"))
+                            .unwrap()
+                            .connect_lazy(),
+                    )
+                })
+                .collect(),
+            ClientConfig::default(),
+        )
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        let config = ClientConfig::default();
+        assert_eq!(config.backoff(0), Duration::from_millis(50));
+        assert_eq!(config.backoff(2), Duration::from_millis(200));
+        assert_eq!(config.backoff(10), Duration::from_millis(2_000));
+        assert_eq!(config.backoff(u32::MAX), Duration::from_millis(2_000));
+    }
+
+    #[test]
+    fn not_leader_carries_leader_hint() {
+        let status = not_leader(Some("10.0.0.2:21001"));
+        assert!(is_not_leader(&status));
+        assert_eq!(leader_hint(&status).as_deref(), Some("10.0.0.2:21001"));
+        assert!(leader_hint(&not_leader(None)).is_none());
+        assert!(!is_not_leader(&Status::failed_precondition(
+            "object is cold"
+        )));
+    }
+
+    #[tokio::test]
+    async fn pick_round_robins_and_skips_unhealthy_endpoints() {
+        let pool = lazy_pool(&["127.0.0.1:1", "127.0.0.1:2", "127.0.0.1:3"]);
+        let picked: Vec<_> = (0..3).map(|_| pool.pick().unwrap()).collect();
+        assert_eq!(picked, vec![0, 1, 2]);
+
+        pool.mark_unhealthy(1);
+        let picked: Vec<_> = (0..4).map(|_| pool.pick().unwrap()).collect();
+        assert!(!picked.contains(&1));
+        assert_eq!(pool.probe_order(), vec![0, 2, 1]);
+
+        pool.mark_healthy(1);
+        assert!((0..3).any(|_| pool.pick() == Some(1)));
+    }
+
+    #[tokio::test]
+    async fn call_fails_over_after_unavailable() {
+        let pool = lazy_pool(&["127.0.0.1:1", "127.0.0.1:2"]);
+        let mut seen = Vec::new();
+        let result = pool
+            .call(|channel| {
+                seen.push(channel);
+                let attempt = seen.len();
+                async move {
+                    if attempt == 1 {
+                        Err(Status::unavailable("connection refused"))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 2);
+        // 第一个节点被标记为不健康，后续请求直接落在第二个节点
+        assert_eq!(pool.probe_order(), vec![1, 0]);
+    }
+}
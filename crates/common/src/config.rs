@@ -1,4 +1,178 @@
+//! 各组件配置
+//!
+//! 二进制通过 [`load_from_args`] 加载配置，优先级从低到高:
+//! 内置默认值 → `--config` 指定的 YAML/TOML 文件 → `COLDSTORE_*` 环境变量。
+//! 文件按组件分节 (`gateway:`、`metadata:`、`scheduler:`、`cache:`、`tape:`)，
+//! 环境变量以 `__` 分隔层级，例如 `COLDSTORE_SCHEDULER__ARCHIVE__BATCH_SIZE=500`；
+//! 地址列表类字段用逗号分隔。
+
+use crate::client::ClientConfig;
+use crate::error::{Error, Result};
+use crate::media::tape_format_capacity_bytes;
+use crate::tls::TlsConfig;
+use config::{Config, ConfigError, Environment, File};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 环境变量前缀
+pub const ENV_PREFIX: &str = "COLDSTORE";
+
+/// 可从配置文件加载的组件配置
+pub trait ServiceConfig: Default + Serialize + DeserializeOwned {
+    /// 配置文件中的分节名，同时是环境变量的第一级 (`COLDSTORE_<SECTION>__...`)
+    const SECTION: &'static str;
+    /// 环境变量中按逗号拆分为列表的字段 (相对分节的路径)
+    const LIST_KEYS: &'static [&'static str] = &[];
+
+    /// 校验跨字段约束，返回全部问题
+    fn validate(&self) -> Vec<String>;
+}
+
+/// 加载配置: 默认值 → 配置文件 → 环境变量，最后校验
+#[allow(clippy::result_large_err)]
+pub fn load<T: ServiceConfig>(path: Option<&Path>) -> Result<T> {
+    load_with_env(path, None)
+}
+
+/// 同 [`load`]，`env` 为 `Some` 时代替进程环境变量 (测试用)
+#[allow(clippy::result_large_err)]
+pub fn load_with_env<T: ServiceConfig>(
+    path: Option<&Path>,
+    env: Option<HashMap<String, String>>,
+) -> Result<T> {
+    let defaults = BTreeMap::from([(T::SECTION, T::default())]);
+    let mut builder = Config::builder().add_source(Config::try_from(&defaults)?);
+    if let Some(path) = path {
+        if !path.exists() {
+            return Err(
+                ConfigError::Message(format!("config file {} not found", path.display())).into(),
+            );
+        }
+        builder = builder.add_source(File::from(path));
+    }
+
+    let mut environment = Environment::with_prefix(ENV_PREFIX)
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+        .source(env);
+    // 未指定 list key 时 config 会把所有变量都按分隔符拆成列表，只在需要时启用
+    if !T::LIST_KEYS.is_empty() {
+        environment = environment.list_separator(",");
+        for key in T::LIST_KEYS {
+            environment = environment.with_list_parse_key(&format!("{}.{key}", T::SECTION));
+        }
+    }
+
+    let config: T = builder.add_source(environment).build()?.get(T::SECTION)?;
+    let problems = config.validate();
+    if !problems.is_empty() {
+        return Err(ConfigError::Message(format!(
+            "invalid {} config: {}",
+            T::SECTION,
+            problems.join("; ")
+        ))
+        .into());
+    }
+    Ok(config)
+}
+
+/// 以 YAML 输出默认配置 (含分节)
+#[allow(clippy::result_large_err)]
+pub fn default_config_yaml<T: ServiceConfig>() -> Result<String> {
+    let defaults = BTreeMap::from([(T::SECTION, T::default())]);
+    serde_yaml::to_string(&defaults)
+        .map_err(|err| ConfigError::Message(format!("render default config: {err}")).into())
+}
+
+/// 解析命令行 `--config <path>` / `--print-default-config`
+///
+/// 指定 `--print-default-config` 时打印默认配置并返回 `None`，调用方应直接退出。
+#[allow(clippy::result_large_err)]
+pub fn load_from_args<T: ServiceConfig>() -> Result<Option<T>> {
+    let mut path: Option<PathBuf> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" | "-c" => {
+                let value = args.next().ok_or_else(|| {
+                    Error::Config(ConfigError::Message(format!("missing value for {arg}")))
+                })?;
+                path = Some(PathBuf::from(value));
+            }
+            "--print-default-config" => {
+                print!("{}", default_config_yaml::<T>()?);
+                return Ok(None);
+            }
+            other => {
+                return Err(ConfigError::Message(format!(
+                    "unknown argument: {other} (usage: --config <path> | --print-default-config)"
+                ))
+                .into())
+            }
+        }
+    }
+    load(path.as_deref()).map(Some)
+}
+
+fn check_listen(problems: &mut Vec<String>, field: &str, value: &str) {
+    if value.parse::<SocketAddr>().is_err() {
+        problems.push(format!("{field} must be host:port, got {value:?}"));
+    }
+}
+
+fn check_addrs(problems: &mut Vec<String>, field: &str, addrs: &[String]) {
+    if addrs.is_empty() {
+        problems.push(format!("{field} must not be empty"));
+    }
+    if addrs.iter().any(|addr| addr.trim().is_empty()) {
+        problems.push(format!("{field} contains an empty address"));
+    }
+}
+
+/// Worker 向 Metadata 注册与发送心跳 (Scheduler / Cache / Tape)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MembershipConfig {
+    /// 同类 Worker 内唯一
+    pub node_id: u64,
+    /// 注册到 Metadata 的对外地址；为空时使用 `listen`
+    pub advertise_addr: String,
+    pub heartbeat_interval_secs: u64,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        Self {
+            node_id: 1,
+            advertise_addr: String::new(),
+            heartbeat_interval_secs: 5,
+        }
+    }
+}
+
+impl MembershipConfig {
+    /// 注册使用的地址
+    pub fn addr<'a>(&'a self, listen: &'a str) -> &'a str {
+        if self.advertise_addr.is_empty() {
+            listen
+        } else {
+            &self.advertise_addr
+        }
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        if self.node_id == 0 {
+            problems.push("membership.node_id must be positive".to_string());
+        }
+        if self.heartbeat_interval_secs == 0 {
+            problems.push("membership.heartbeat_interval_secs must be positive".to_string());
+        }
+    }
+}
 
 // ---------------------------------------------------------------------------
 //  Gateway 配置
@@ -8,6 +182,31 @@ use serde::{Deserialize, Serialize};
 pub struct GatewayConfig {
     pub listen: String,
     pub scheduler_addrs: Vec<String>,
+    pub auth: GatewayAuthConfig,
+    /// S3 端点 TLS (证书文件变化时热加载)
+    pub tls: TlsConfig,
+    /// 连接 Scheduler 的 gRPC TLS (客户端)
+    pub scheduler_tls: TlsConfig,
+    /// Scheduler 连接池: 超时、重试与不健康节点的跳过时长
+    pub scheduler_client: ClientConfig,
+}
+
+/// S3 SigV4 认证
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayAuthConfig {
+    /// 关闭后接受匿名请求，仅用于本地开发
+    pub enabled: bool,
+    /// 请求时间 (X-Amz-Date) 与网关时钟允许的最大偏差
+    pub max_clock_skew_secs: u64,
+    /// 静态访问密钥 (空列表在合并默认值时会丢失，需显式给出默认)
+    #[serde(default)]
+    pub access_keys: Vec<AccessKeyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessKeyConfig {
+    pub access_key_id: String,
+    pub secret_access_key: String,
 }
 
 impl Default for GatewayConfig {
@@ -15,10 +214,45 @@ impl Default for GatewayConfig {
         Self {
             listen: "0.0.0.0:9000".to_string(),
             scheduler_addrs: vec!["127.0.0.1:22001".to_string()],
+            auth: GatewayAuthConfig {
+                enabled: true,
+                max_clock_skew_secs: 900,
+                access_keys: Vec::new(),
+            },
+            tls: TlsConfig::default(),
+            scheduler_tls: TlsConfig::default(),
+            scheduler_client: ClientConfig::default(),
         }
     }
 }
 
+impl ServiceConfig for GatewayConfig {
+    const SECTION: &'static str = "gateway";
+    const LIST_KEYS: &'static [&'static str] = &["scheduler_addrs"];
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_listen(&mut problems, "listen", &self.listen);
+        check_addrs(&mut problems, "scheduler_addrs", &self.scheduler_addrs);
+        if self.auth.max_clock_skew_secs == 0 {
+            problems.push("auth.max_clock_skew_secs must be positive".to_string());
+        }
+        for key in &self.auth.access_keys {
+            if key.access_key_id.is_empty() || key.secret_access_key.is_empty() {
+                problems.push(
+                    "auth.access_keys entries need access_key_id and secret_access_key".to_string(),
+                );
+            }
+        }
+        self.tls.validate("tls", true, &mut problems);
+        self.scheduler_tls
+            .validate("scheduler_tls", false, &mut problems);
+        self.scheduler_client
+            .validate("scheduler_client", &mut problems);
+        problems
+    }
+}
+
 // ---------------------------------------------------------------------------
 //  Metadata 节点配置
 // ---------------------------------------------------------------------------
@@ -30,6 +264,61 @@ pub struct MetadataConfig {
     pub cluster: String,
     pub data_path: String,
     pub rocksdb: RocksDbConfig,
+    /// 超过该时长未收到心跳的 Worker 被标记为 NodeOffline，其进行中的任务重新排队
+    pub worker_timeout_secs: u64,
+    /// 归档/取回任务认领后的租约时长，由 Scheduler 心跳续约
+    pub task_lease_secs: u64,
+    /// 失败或租约过期任务的重试策略
+    pub task_retry: TaskRetryConfig,
+    /// 评估桶生命周期规则与解冻副本过期的周期
+    pub lifecycle_interval_secs: u64,
+    /// gRPC 服务端 TLS；配置 `ca_path` 时要求客户端证书 (mTLS)
+    pub tls: TlsConfig,
+    /// 加密访问密钥 secret 的 AES-256 密钥文件 (base64 编码的 32 字节)，各节点须相同；
+    /// 未配置时不能创建或轮换访问密钥
+    pub access_key_encryption_key_path: Option<String>,
+}
+
+/// 归档/取回任务的重试策略
+///
+/// 可重试的失败 (介质、驱动、节点不可用等) 与租约过期使任务退回 Pending，
+/// 按指数退避等待后才能再次认领；重试 `max_retries` 次后或遇到不可重试的
+/// 失败 (checksum 不一致、数据缺失) 时任务标记为 Failed，进入死信列表。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRetryConfig {
+    pub max_retries: u32,
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for TaskRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff_secs: 30,
+            max_backoff_secs: 3_600,
+        }
+    }
+}
+
+impl TaskRetryConfig {
+    pub fn validate(&self, field: &str, problems: &mut Vec<String>) {
+        if self.initial_backoff_secs == 0 || self.max_backoff_secs < self.initial_backoff_secs {
+            problems.push(format!(
+                "{field}.max_backoff_secs must be >= {field}.initial_backoff_secs > 0"
+            ));
+        }
+    }
+
+    /// 第 `retry_count` 次重试前的等待时间 (指数退避，封顶 `max_backoff_secs`)
+    pub fn backoff(&self, retry_count: u32) -> Duration {
+        let factor = 1u64 << retry_count.saturating_sub(1).min(16);
+        Duration::from_secs(
+            self.initial_backoff_secs
+                .saturating_mul(factor)
+                .min(self.max_backoff_secs),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,7 +340,52 @@ impl Default for MetadataConfig {
                 write_buffer_size_mb: 64,
                 max_background_jobs: 4,
             },
+            worker_timeout_secs: 30,
+            task_lease_secs: 60,
+            task_retry: TaskRetryConfig::default(),
+            lifecycle_interval_secs: 3_600,
+            tls: TlsConfig::default(),
+            access_key_encryption_key_path: None,
+        }
+    }
+}
+
+impl ServiceConfig for MetadataConfig {
+    const SECTION: &'static str = "metadata";
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_listen(&mut problems, "listen", &self.listen);
+        if self.data_path.trim().is_empty() {
+            problems.push("data_path must not be empty".to_string());
+        }
+        let mut node_ids = Vec::new();
+        for entry in self.cluster.split(',') {
+            match entry
+                .split_once(':')
+                .and_then(|(id, _)| id.parse::<u64>().ok())
+            {
+                Some(id) => node_ids.push(id),
+                None => problems.push(format!(
+                    "cluster entry {entry:?} must be <node_id>:<host>:<port>"
+                )),
+            }
+        }
+        if !node_ids.contains(&self.node_id) {
+            problems.push(format!("node_id {} is not listed in cluster", self.node_id));
+        }
+        if self.worker_timeout_secs == 0 {
+            problems.push("worker_timeout_secs must be positive".to_string());
+        }
+        if self.task_lease_secs == 0 {
+            problems.push("task_lease_secs must be positive".to_string());
+        }
+        if self.lifecycle_interval_secs == 0 {
+            problems.push("lifecycle_interval_secs must be positive".to_string());
         }
+        self.task_retry.validate("task_retry", &mut problems);
+        self.tls.validate("tls", true, &mut problems);
+        problems
     }
 }
 
@@ -65,6 +399,15 @@ pub struct SchedulerConfig {
     pub metadata_addrs: Vec<String>,
     pub archive: ArchiveSchedulerConfig,
     pub recall: RecallSchedulerConfig,
+    pub reclamation: ReclamationConfig,
+    pub scrub: ScrubConfig,
+    /// gRPC TLS: 同时用于本节点服务端与连接 Metadata/Cache/Tape 的客户端
+    pub tls: TlsConfig,
+    /// Metadata 连接池: 超时、leader 切换时的重试与退避
+    pub metadata_client: ClientConfig,
+    pub membership: MembershipConfig,
+    /// 配对的 Cache Worker；未配置时优先选择同主机的在线 Cache Worker，其次剩余空间最多者
+    pub cache_worker_id: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,6 +420,49 @@ pub struct ArchiveSchedulerConfig {
     pub aggregation_window_secs: u64,
     pub write_buffer_mb: u64,
     pub block_size: u32,
+    /// 每个归档包写入的磁带副本数 (桶未单独配置时使用)
+    pub copies: u32,
+    /// 桶未配置 `tape_pools` 的副本写入的磁带池，空为默认池
+    pub default_tape_pool: String,
+}
+
+/// 磁带空间回收：有效字节占比低于阈值的磁带迁出剩余对象后转为 Scratch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReclamationConfig {
+    /// 是否周期运行回收作业
+    pub enabled: bool,
+    /// 有效字节 / (有效 + 失效字节) 低于该值的磁带成为回收候选，取值 (0, 1]
+    pub live_ratio_threshold: f64,
+    /// 检查回收候选的周期 (秒)
+    pub scan_interval_secs: u64,
+}
+
+impl Default for ReclamationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            live_ratio_threshold: 0.5,
+            scan_interval_secs: 3600,
+        }
+    }
+}
+
+/// 磁带巡检：驱动空闲时回读超过校验周期的磁带并逐条核对 checksum
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrubConfig {
+    /// 两次整盘校验的最小间隔 (天)
+    pub verify_interval_days: u64,
+    /// 检查到期磁带的周期 (秒)
+    pub scan_interval_secs: u64,
+}
+
+impl Default for ScrubConfig {
+    fn default() -> Self {
+        Self {
+            verify_interval_days: 180,
+            scan_interval_secs: 3600,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,6 +491,8 @@ impl Default for SchedulerConfig {
                 aggregation_window_secs: 300,
                 write_buffer_mb: 128,
                 block_size: 262144,
+                copies: 1,
+                default_tape_pool: String::new(),
             },
             recall: RecallSchedulerConfig {
                 max_concurrent_restores: 10,
@@ -112,10 +500,73 @@ impl Default for SchedulerConfig {
                 restore_timeout_secs: 3600,
                 read_buffer_mb: 64,
             },
+            reclamation: ReclamationConfig::default(),
+            scrub: ScrubConfig::default(),
+            tls: TlsConfig::default(),
+            metadata_client: ClientConfig::default(),
+            membership: MembershipConfig::default(),
+            cache_worker_id: None,
         }
     }
 }
 
+impl ServiceConfig for SchedulerConfig {
+    const SECTION: &'static str = "scheduler";
+    const LIST_KEYS: &'static [&'static str] = &["metadata_addrs"];
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_listen(&mut problems, "listen", &self.listen);
+        check_addrs(&mut problems, "metadata_addrs", &self.metadata_addrs);
+        let archive = &self.archive;
+        if archive.min_archive_size_mb > archive.max_archive_size_mb {
+            problems.push(format!(
+                "archive.min_archive_size_mb ({}) must not exceed archive.max_archive_size_mb ({})",
+                archive.min_archive_size_mb, archive.max_archive_size_mb
+            ));
+        }
+        if archive.batch_size == 0 {
+            problems.push("archive.batch_size must be positive".to_string());
+        }
+        if archive.block_size == 0 || !archive.block_size.is_multiple_of(512) {
+            problems.push(format!(
+                "archive.block_size must be a positive multiple of 512, got {}",
+                archive.block_size
+            ));
+        }
+        if archive.copies == 0 {
+            problems.push("archive.copies must be at least 1".to_string());
+        }
+        if self.recall.max_concurrent_restores == 0 {
+            problems.push("recall.max_concurrent_restores must be positive".to_string());
+        }
+        let threshold = self.reclamation.live_ratio_threshold;
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            problems.push(format!(
+                "reclamation.live_ratio_threshold must be in (0, 1], got {threshold}"
+            ));
+        }
+        if self.reclamation.enabled && self.reclamation.scan_interval_secs == 0 {
+            problems.push("reclamation.scan_interval_secs must be positive".to_string());
+        }
+        if self.scrub.verify_interval_days == 0 {
+            problems.push("scrub.verify_interval_days must be positive".to_string());
+        }
+        if self.scrub.scan_interval_secs == 0 {
+            problems.push("scrub.scan_interval_secs must be positive".to_string());
+        }
+        self.tls.validate("tls", true, &mut problems);
+        if self.tls.enabled && self.tls.ca_path.is_none() {
+            // Scheduler 同时作为 Metadata/Cache/Tape 的客户端
+            problems.push("tls.ca_path is required to verify peer services".to_string());
+        }
+        self.metadata_client
+            .validate("metadata_client", &mut problems);
+        self.membership.validate(&mut problems);
+        problems
+    }
+}
+
 // ---------------------------------------------------------------------------
 //  Cache Worker 配置
 // ---------------------------------------------------------------------------
@@ -129,6 +580,10 @@ pub struct CacheConfig {
     pub eviction_policy: String,
     pub eviction_batch_size: usize,
     pub eviction_low_watermark: f64,
+    /// gRPC TLS: 本节点服务端，同时用于连接 Metadata
+    pub tls: TlsConfig,
+    pub metadata_client: ClientConfig,
+    pub membership: MembershipConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -162,7 +617,47 @@ impl Default for CacheConfig {
             eviction_policy: "Lru".to_string(),
             eviction_batch_size: 64,
             eviction_low_watermark: 0.8,
+            tls: TlsConfig::default(),
+            metadata_client: ClientConfig::default(),
+            membership: MembershipConfig::default(),
+        }
+    }
+}
+
+impl ServiceConfig for CacheConfig {
+    const SECTION: &'static str = "cache";
+    const LIST_KEYS: &'static [&'static str] = &["metadata_addrs"];
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_listen(&mut problems, "listen", &self.listen);
+        check_addrs(&mut problems, "metadata_addrs", &self.metadata_addrs);
+        if !(self.eviction_low_watermark > 0.0 && self.eviction_low_watermark < 1.0) {
+            problems.push(format!(
+                "eviction_low_watermark must be within (0, 1), got {}",
+                self.eviction_low_watermark
+            ));
+        }
+        if self.eviction_batch_size == 0 {
+            problems.push("eviction_batch_size must be positive".to_string());
+        }
+        let max_size_gb = match &self.backend {
+            CacheBackendConfig::Hdd { path, max_size_gb } => {
+                if path.trim().is_empty() {
+                    problems.push("backend.path must not be empty".to_string());
+                }
+                *max_size_gb
+            }
+            CacheBackendConfig::Spdk { max_size_gb, .. } => *max_size_gb,
+        };
+        if max_size_gb == 0 {
+            problems.push("backend.max_size_gb must be positive".to_string());
         }
+        self.tls.validate("tls", true, &mut problems);
+        self.metadata_client
+            .validate("metadata_client", &mut problems);
+        self.membership.validate(&mut problems);
+        problems
     }
 }
 
@@ -180,6 +675,13 @@ pub struct TapeConfig {
     pub supported_formats: Vec<String>,
     pub tape_hold_secs: u64,
     pub drive_acquire_timeout_secs: u64,
+    /// `sdk_backend = "virtual"` 时使用的内存带库 (开发/测试用)
+    pub virtual_library: Option<VirtualLibraryConfig>,
+    pub drive_health: DriveHealthConfig,
+    /// gRPC TLS: 本节点服务端，同时用于连接 Metadata
+    pub tls: TlsConfig,
+    pub metadata_client: ClientConfig,
+    pub membership: MembershipConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -189,6 +691,41 @@ pub struct ScsiConfig {
     pub buffer_size_mb: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualLibraryConfig {
+    /// 初始放入槽位的空白磁带条码
+    pub tapes: Vec<String>,
+    pub capacity_bytes: u64,
+    /// import/export (邮件) 槽位数，磁带经此移出/放回带库
+    #[serde(default = "default_import_export_slots")]
+    pub import_export_slots: usize,
+    /// 清洁带条码，驱动请求清洁时自动装入
+    #[serde(default)]
+    pub cleaning_tapes: Vec<String>,
+}
+
+/// 驱动健康监测：TapeAlert 标志与读写错误计数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriveHealthConfig {
+    /// 硬错误累计达到该次数后驱动转为 Offline，需 `ResetDrive` 恢复
+    pub max_hard_errors: u32,
+    /// 每盘清洁带可用次数，用尽后报告 Expired cleaning media
+    pub cleaning_cartridge_uses: u32,
+}
+
+impl Default for DriveHealthConfig {
+    fn default() -> Self {
+        Self {
+            max_hard_errors: 3,
+            cleaning_cartridge_uses: 50,
+        }
+    }
+}
+
+fn default_import_export_slots() -> usize {
+    2
+}
+
 impl Default for TapeConfig {
     fn default() -> Self {
         Self {
@@ -208,6 +745,243 @@ impl Default for TapeConfig {
             supported_formats: vec!["LTO-9".to_string(), "LTO-10".to_string()],
             tape_hold_secs: 300,
             drive_acquire_timeout_secs: 600,
+            virtual_library: None,
+            drive_health: DriveHealthConfig::default(),
+            tls: TlsConfig::default(),
+            metadata_client: ClientConfig::default(),
+            membership: MembershipConfig::default(),
+        }
+    }
+}
+
+impl ServiceConfig for TapeConfig {
+    const SECTION: &'static str = "tape";
+    const LIST_KEYS: &'static [&'static str] =
+        &["metadata_addrs", "scsi.devices", "supported_formats"];
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_listen(&mut problems, "listen", &self.listen);
+        check_addrs(&mut problems, "metadata_addrs", &self.metadata_addrs);
+        if self.scsi.devices.is_empty() {
+            problems.push("scsi.devices must not be empty".to_string());
+        }
+        if self.scsi.block_size == 0 || !self.scsi.block_size.is_multiple_of(512) {
+            problems.push(format!(
+                "scsi.block_size must be a positive multiple of 512, got {}",
+                self.scsi.block_size
+            ));
+        }
+        if self.supported_formats.is_empty() {
+            problems.push("supported_formats must not be empty".to_string());
+        }
+        for format in &self.supported_formats {
+            if tape_format_capacity_bytes(format).is_none() {
+                problems.push(format!("supported_formats: unknown tape format {format:?}"));
+            }
+        }
+        if self.sdk_backend == "virtual" && self.virtual_library.is_none() {
+            problems.push("sdk_backend \"virtual\" requires virtual_library".to_string());
+        }
+        if self.drive_health.max_hard_errors == 0 {
+            problems.push("drive_health.max_hard_errors must be positive".to_string());
+        }
+        if self.drive_health.cleaning_cartridge_uses == 0 {
+            problems.push("drive_health.cleaning_cartridge_uses must be positive".to_string());
+        }
+        self.tls.validate("tls", true, &mut problems);
+        self.metadata_client
+            .validate("metadata_client", &mut problems);
+        self.membership.validate(&mut problems);
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("coldstore-{}-{name}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        Some(
+            vars.iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn defaults_are_valid_for_every_component() {
+        assert!(GatewayConfig::default().validate().is_empty());
+        assert!(MetadataConfig::default().validate().is_empty());
+        assert!(SchedulerConfig::default().validate().is_empty());
+        assert!(CacheConfig::default().validate().is_empty());
+        assert!(TapeConfig::default().validate().is_empty());
+    }
+
+    #[test]
+    fn tape_formats_must_have_known_capacity() {
+        let config = TapeConfig {
+            supported_formats: vec!["lto-10".into(), "DLT-S4".into()],
+            ..TapeConfig::default()
+        };
+        assert_eq!(
+            config.validate(),
+            ["supported_formats: unknown tape format \"DLT-S4\""]
+        );
+    }
+
+    #[test]
+    fn yaml_file_overrides_defaults_and_env_overrides_file() {
+        let path = write_temp(
+            "scheduler.yaml",
+            "scheduler:\n  listen: 127.0.0.1:32001\n  archive:\n    batch_size: 500\n    copies: 2\n",
+        );
+        let config: SchedulerConfig = load_with_env(
+            Some(&path),
+            env(&[
+                ("COLDSTORE_SCHEDULER__ARCHIVE__BATCH_SIZE", "250"),
+                (
+                    "COLDSTORE_SCHEDULER__METADATA_ADDRS",
+                    "10.0.0.1:21001,10.0.0.2:21001",
+                ),
+                ("COLDSTORE_GATEWAY__LISTEN", "ignored"),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.listen, "127.0.0.1:32001");
+        assert_eq!(config.archive.batch_size, 250);
+        assert_eq!(config.archive.copies, 2);
+        assert_eq!(config.archive.block_size, 262144);
+        assert_eq!(config.metadata_addrs, ["10.0.0.1:21001", "10.0.0.2:21001"]);
+    }
+
+    #[test]
+    fn toml_file_selects_cache_backend() {
+        let path = write_temp(
+            "cache.toml",
+            "[cache]\neviction_low_watermark = 0.6\n\n[cache.backend]\ntype = \"hdd\"\npath = \"/data/cache\"\nmax_size_gb = 20\n",
+        );
+        let config: CacheConfig = load_with_env(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.eviction_low_watermark, 0.6);
+        match config.backend {
+            CacheBackendConfig::Hdd { path, max_size_gb } => {
+                assert_eq!(path, "/data/cache");
+                assert_eq!(max_size_gb, 20);
+            }
+            other => panic!("unexpected backend {other:?}"),
         }
     }
+
+    #[test]
+    fn cross_field_constraints_are_reported_together() {
+        let err = load_with_env::<SchedulerConfig>(
+            None,
+            env(&[
+                ("COLDSTORE_SCHEDULER__ARCHIVE__MIN_ARCHIVE_SIZE_MB", "2048"),
+                ("COLDSTORE_SCHEDULER__ARCHIVE__MAX_ARCHIVE_SIZE_MB", "1024"),
+                ("COLDSTORE_SCHEDULER__ARCHIVE__COPIES", "0"),
+                (
+                    "COLDSTORE_SCHEDULER__RECLAMATION__LIVE_RATIO_THRESHOLD",
+                    "0",
+                ),
+                ("COLDSTORE_SCHEDULER__SCRUB__VERIFY_INTERVAL_DAYS", "0"),
+            ]),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("invalid scheduler config"), "{err}");
+        assert!(err.contains("min_archive_size_mb"), "{err}");
+        assert!(err.contains("archive.copies"), "{err}");
+        assert!(err.contains("reclamation.live_ratio_threshold"), "{err}");
+        assert!(err.contains("scrub.verify_interval_days"), "{err}");
+
+        let err = load_with_env::<CacheConfig>(
+            None,
+            env(&[("COLDSTORE_CACHE__EVICTION_LOW_WATERMARK", "1.5")]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("eviction_low_watermark"));
+
+        let err =
+            load_with_env::<MetadataConfig>(None, env(&[("COLDSTORE_METADATA__NODE_ID", "9")]))
+                .unwrap_err();
+        assert!(err.to_string().contains("node_id 9"));
+    }
+
+    #[test]
+    fn tls_settings_load_from_env() {
+        let config: GatewayConfig = load_with_env(
+            None,
+            env(&[
+                ("COLDSTORE_GATEWAY__TLS__ENABLED", "true"),
+                ("COLDSTORE_GATEWAY__TLS__CERT_PATH", "/etc/coldstore/s3.pem"),
+                ("COLDSTORE_GATEWAY__TLS__KEY_PATH", "/etc/coldstore/s3.key"),
+            ]),
+        )
+        .unwrap();
+        assert!(config.tls.enabled);
+        assert_eq!(config.tls.key_path, "/etc/coldstore/s3.key");
+        assert!(!config.scheduler_tls.enabled);
+
+        let err = load_with_env::<MetadataConfig>(
+            None,
+            env(&[
+                ("COLDSTORE_METADATA__TLS__ENABLED", "true"),
+                (
+                    "COLDSTORE_METADATA__TLS__CERT_PATH",
+                    "/etc/coldstore/meta.pem",
+                ),
+            ]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("must be set together"), "{err}");
+
+        let err = load_with_env::<GatewayConfig>(
+            None,
+            env(&[("COLDSTORE_GATEWAY__SCHEDULER_TLS__ENABLED", "true")]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("scheduler_tls.ca_path"), "{err}");
+    }
+
+    #[test]
+    fn missing_config_file_is_an_error() {
+        let err = load_with_env::<GatewayConfig>(
+            Some(Path::new("/nonexistent/coldstore.yaml")),
+            env(&[]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn printed_default_config_round_trips() {
+        let yaml = default_config_yaml::<TapeConfig>().unwrap();
+        assert!(yaml.starts_with("tape:"));
+        let path = write_temp("tape.yaml", &yaml);
+        let config: TapeConfig = load_with_env(Some(&path), env(&[])).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(config.scsi.devices, TapeConfig::default().scsi.devices);
+    }
+
+    #[test]
+    fn task_retry_backoff_doubles_up_to_the_cap() {
+        let retry = TaskRetryConfig {
+            max_retries: 5,
+            initial_backoff_secs: 30,
+            max_backoff_secs: 100,
+        };
+        let delays: Vec<_> = (1..=4).map(|n| retry.backoff(n).as_secs()).collect();
+        assert_eq!(delays, vec![30, 60, 100, 100]);
+    }
 }
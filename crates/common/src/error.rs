@@ -1,3 +1,5 @@
+use coldstore_proto::common::{ErrorDetail, ErrorReason};
+use prost::Message;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -19,6 +21,9 @@ pub enum Error {
     #[error("gRPC 传输错误: {0}")]
     Transport(#[from] tonic::transport::Error),
 
+    #[error("TLS 配置错误: {0}")]
+    Tls(String),
+
     #[error("S3 协议错误: {code}: {message}")]
     S3Protocol { code: String, message: String },
 
@@ -43,6 +48,13 @@ pub enum Error {
     #[error("容量不足: {0}")]
     InsufficientCapacity(String),
 
+    #[error("校验和不匹配 ({context}): expected {expected}, actual {actual}")]
+    ChecksumMismatch {
+        context: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("状态流转无效: {from:?} -> {to:?}")]
     InvalidStateTransition { from: String, to: String },
 
@@ -59,7 +71,12 @@ impl From<Error> for tonic::Status {
             Error::TapeOffline(_) => tonic::Status::unavailable(err.to_string()),
             Error::DriveUnavailable(_) => tonic::Status::unavailable(err.to_string()),
             Error::CacheMiss { .. } => tonic::Status::not_found(err.to_string()),
-            Error::InsufficientCapacity(_) => tonic::Status::resource_exhausted(err.to_string()),
+            Error::InsufficientCapacity(_) => status_with_reason(
+                tonic::Code::ResourceExhausted,
+                err.to_string(),
+                ErrorReason::SlowDown,
+            ),
+            Error::ChecksumMismatch { .. } => tonic::Status::data_loss(err.to_string()),
             Error::InvalidStateTransition { .. } => {
                 tonic::Status::failed_precondition(err.to_string())
             }
@@ -67,3 +84,48 @@ impl From<Error> for tonic::Status {
         }
     }
 }
+
+/// 构造带结构化错误原因 ([`ErrorDetail`]) 的 gRPC Status
+pub fn status_with_reason(
+    code: tonic::Code,
+    message: impl Into<String>,
+    reason: ErrorReason,
+) -> tonic::Status {
+    let detail = ErrorDetail {
+        reason: reason as i32,
+    };
+    tonic::Status::with_details(code, message, detail.encode_to_vec().into())
+}
+
+/// 读取 Status 中的错误原因；没有或无法解码时返回 `None`
+pub fn error_reason(status: &tonic::Status) -> Option<ErrorReason> {
+    if status.details().is_empty() {
+        return None;
+    }
+    let detail = ErrorDetail::decode(status.details()).ok()?;
+    match ErrorReason::try_from(detail.reason) {
+        Ok(ErrorReason::Unspecified) | Err(_) => None,
+        Ok(reason) => Some(reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_reason_round_trips_through_status_details() {
+        let status = status_with_reason(
+            tonic::Code::FailedPrecondition,
+            "bucket is not empty",
+            ErrorReason::BucketNotEmpty,
+        );
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert_eq!(error_reason(&status), Some(ErrorReason::BucketNotEmpty));
+        assert_eq!(error_reason(&tonic::Status::internal("boom")), None);
+
+        let status: tonic::Status = Error::InsufficientCapacity("cache full".into()).into();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(error_reason(&status), Some(ErrorReason::SlowDown));
+    }
+}
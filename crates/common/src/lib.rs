@@ -1,5 +1,11 @@
+pub mod attributes;
+pub mod checksum;
+pub mod client;
 pub mod config;
 pub mod error;
+pub mod media;
+pub mod membership;
 pub mod models;
+pub mod tls;
 
 pub use error::{Error, Result};
@@ -0,0 +1,67 @@
+//! 磁带介质：格式默认容量与可读/可写判定
+
+use coldstore_proto::common::{TapeInfo, TapeMediaState, TapeStatus};
+
+/// 常见 LTO 代际的原生 (未压缩) 容量，登记磁带未指定容量时使用
+pub fn tape_format_capacity_bytes(format: &str) -> Option<u64> {
+    const TB: u64 = 1_000_000_000_000;
+    match format.trim().to_ascii_uppercase().as_str() {
+        "LTO-7" => Some(6 * TB),
+        "LTO-8" => Some(12 * TB),
+        "LTO-9" => Some(18 * TB),
+        "LTO-10" => Some(30 * TB),
+        _ => None,
+    }
+}
+
+/// 可以继续追加归档包的在线磁带；未设置介质状态的旧记录视为可追加
+pub fn tape_appendable(tape: &TapeInfo) -> bool {
+    tape.status == TapeStatus::TapeOnline as i32
+        && !matches!(
+            TapeMediaState::try_from(tape.media_state),
+            Ok(TapeMediaState::TapeMediaFull | TapeMediaState::TapeMediaReadOnly)
+        )
+}
+
+/// 可以装入驱动读取的磁带：离线、故障、退役与空白磁带除外
+pub fn tape_readable(tape: &TapeInfo) -> bool {
+    !matches!(
+        TapeStatus::try_from(tape.status),
+        Ok(TapeStatus::TapeOffline
+            | TapeStatus::TapeError
+            | TapeStatus::TapeRetired
+            | TapeStatus::TapeScratch)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_capacity_is_case_insensitive() {
+        assert_eq!(
+            tape_format_capacity_bytes("LTO-9"),
+            Some(18_000_000_000_000)
+        );
+        assert_eq!(
+            tape_format_capacity_bytes(" lto-10"),
+            Some(30_000_000_000_000)
+        );
+        assert_eq!(tape_format_capacity_bytes("DLT-S4"), None);
+    }
+
+    #[test]
+    fn only_online_tapes_with_room_are_appendable() {
+        let mut tape = TapeInfo {
+            status: TapeStatus::TapeOnline as i32,
+            ..Default::default()
+        };
+        assert!(tape_appendable(&tape));
+        tape.media_state = TapeMediaState::TapeMediaFull as i32;
+        assert!(!tape_appendable(&tape));
+        tape.media_state = TapeMediaState::TapeMediaAppendable as i32;
+        tape.status = TapeStatus::TapeScratch as i32;
+        assert!(!tape_appendable(&tape));
+    }
+}
@@ -0,0 +1,277 @@
+//! Worker 在 Metadata 中的成员关系
+//!
+//! Scheduler / Cache / Tape Worker 启动后通过 [`Membership::start`] 在后台注册
+//! (携带驱动、缓存容量等能力)，之后每 `heartbeat_interval_secs` 发送一次心跳；
+//! 心跳返回 NotFound (Metadata 已注销该节点或状态丢失) 时重新注册。
+//! 优雅退出时 [`Membership::stop`] 停止心跳并注销。
+//!
+//! 心跳应答携带 Metadata 中记录的节点状态。被置为 NodeDraining 后 Worker 停止
+//! 接收新工作，每个心跳周期推进一次排空，并随心跳上报 [`common::DrainProgress`]。
+//!
+//! Metadata leader 将超过 `worker_timeout_secs` 未收到心跳的 Worker 标记为
+//! NodeOffline，并把其进行中的任务重新排队。
+
+use crate::client::MetadataClientPool;
+use crate::config::MembershipConfig;
+use coldstore_proto::common;
+use coldstore_proto::metadata::{heartbeat_request, DeregisterWorkerRequest, HeartbeatRequest};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tonic::{Code, Status};
+use tracing::{info, warn};
+
+/// 注册到 Metadata 的 Worker 信息
+#[derive(Debug, Clone)]
+pub enum Registration {
+    Scheduler(common::SchedulerWorkerInfo),
+    Cache(common::CacheWorkerInfo),
+    Tape(common::TapeWorkerInfo),
+}
+
+impl Registration {
+    pub fn worker_type(&self) -> common::WorkerType {
+        match self {
+            Self::Scheduler(_) => common::WorkerType::WorkerScheduler,
+            Self::Cache(_) => common::WorkerType::WorkerCache,
+            Self::Tape(_) => common::WorkerType::WorkerTape,
+        }
+    }
+
+    pub fn node_id(&self) -> u64 {
+        match self {
+            Self::Scheduler(info) => info.node_id,
+            Self::Cache(info) => info.node_id,
+            Self::Tape(info) => info.node_id,
+        }
+    }
+}
+
+/// Worker 提供注册信息与心跳负载
+#[tonic::async_trait]
+pub trait WorkerMembership: Send + Sync + 'static {
+    /// 每次 (重新) 注册时调用，返回当前能力
+    async fn registration(&self) -> Registration;
+
+    async fn heartbeat(&self) -> heartbeat_request::Payload;
+
+    /// Metadata 中本节点的状态变化时调用；进入 NodeDraining 后应停止接收新工作
+    async fn status_changed(&self, status: common::NodeStatus);
+
+    /// NodeDraining 期间每个心跳周期调用一次，推进排空并返回进度
+    async fn drain(&self, metadata: &MetadataClientPool) -> common::DrainProgress;
+}
+
+/// 后台心跳任务的句柄
+pub struct Membership {
+    metadata: Arc<MetadataClientPool>,
+    worker_type: common::WorkerType,
+    node_id: u64,
+    stop_tx: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Membership {
+    /// 启动后台注册与心跳；Metadata 暂不可用时按心跳间隔重试注册
+    pub async fn start<W: WorkerMembership>(
+        metadata: Arc<MetadataClientPool>,
+        worker: Arc<W>,
+        config: &MembershipConfig,
+    ) -> Self {
+        let registration = worker.registration().await;
+        let worker_type = registration.worker_type();
+        let node_id = registration.node_id();
+        let interval = Duration::from_secs(config.heartbeat_interval_secs);
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let task = tokio::spawn(heartbeat_loop(
+            metadata.clone(),
+            worker,
+            HeartbeatRequest {
+                worker_type: worker_type as i32,
+                node_id,
+                payload: None,
+                drain: None,
+            },
+            interval,
+            stop_rx,
+        ));
+        Self {
+            metadata,
+            worker_type,
+            node_id,
+            stop_tx,
+            task,
+        }
+    }
+
+    /// 停止心跳并从 Metadata 注销
+    pub async fn stop(self) -> std::result::Result<(), Status> {
+        let _ = self.stop_tx.send(());
+        let _ = self.task.await;
+        deregister(&self.metadata, self.worker_type, self.node_id).await?;
+        info!(
+            "{} Worker {} 已从 Metadata 注销",
+            self.worker_type.as_str_name(),
+            self.node_id
+        );
+        Ok(())
+    }
+}
+
+async fn heartbeat_loop<W: WorkerMembership>(
+    metadata: Arc<MetadataClientPool>,
+    worker: Arc<W>,
+    mut heartbeat: HeartbeatRequest,
+    interval: Duration,
+    mut stop_rx: oneshot::Receiver<()>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut registered = false;
+    let mut status = common::NodeStatus::NodeOnline;
+    loop {
+        tokio::select! {
+            _ = &mut stop_rx => return,
+            _ = ticker.tick() => {}
+        }
+        if registered {
+            heartbeat.drain = if status == common::NodeStatus::NodeDraining {
+                Some(worker.drain(&metadata).await)
+            } else {
+                None
+            };
+            heartbeat.payload = Some(worker.heartbeat().await);
+            let request = heartbeat.clone();
+            let sent = metadata
+                .call(|mut client| {
+                    let request = request.clone();
+                    async move { client.heartbeat(request).await }
+                })
+                .await;
+            match sent {
+                Ok(response) => {
+                    let next = common::NodeStatus::try_from(response.status).unwrap_or(status);
+                    if next != status {
+                        info!("节点状态变为 {}", next.as_str_name());
+                        worker.status_changed(next).await;
+                        status = next;
+                    }
+                    continue;
+                }
+                Err(status) if status.code() == Code::NotFound => {
+                    warn!("Metadata 中没有本节点的注册信息，重新注册");
+                }
+                Err(status) => {
+                    warn!("心跳发送失败: {}", status.message());
+                    continue;
+                }
+            }
+        }
+        let registration = worker.registration().await;
+        match register(&metadata, registration.clone()).await {
+            Ok(()) => {
+                info!(
+                    "{} Worker {} 已注册到 Metadata",
+                    registration.worker_type().as_str_name(),
+                    registration.node_id()
+                );
+                registered = true;
+            }
+            Err(status) => {
+                warn!("注册到 Metadata 失败: {}", status.message());
+                registered = false;
+            }
+        }
+    }
+}
+
+/// 注册 (或以最新能力覆盖) Worker 信息
+pub async fn register(
+    metadata: &MetadataClientPool,
+    registration: Registration,
+) -> std::result::Result<(), Status> {
+    match registration {
+        Registration::Scheduler(info) => {
+            metadata
+                .call(|mut client| {
+                    let info = info.clone();
+                    async move { client.register_scheduler_worker(info).await }
+                })
+                .await
+        }
+        Registration::Cache(info) => {
+            metadata
+                .call(|mut client| {
+                    let info = info.clone();
+                    async move { client.register_cache_worker(info).await }
+                })
+                .await
+        }
+        Registration::Tape(info) => {
+            metadata
+                .call(|mut client| {
+                    let info = info.clone();
+                    async move { client.register_tape_worker(info).await }
+                })
+                .await
+        }
+    }
+}
+
+pub async fn deregister(
+    metadata: &MetadataClientPool,
+    worker_type: common::WorkerType,
+    node_id: u64,
+) -> std::result::Result<(), Status> {
+    let request = DeregisterWorkerRequest { node_id };
+    match worker_type {
+        common::WorkerType::WorkerScheduler => {
+            metadata
+                .call(|mut client| async move { client.deregister_scheduler_worker(request).await })
+                .await
+        }
+        common::WorkerType::WorkerCache => {
+            metadata
+                .call(|mut client| async move { client.deregister_cache_worker(request).await })
+                .await
+        }
+        common::WorkerType::WorkerTape => {
+            metadata
+                .call(|mut client| async move { client.deregister_tape_worker(request).await })
+                .await
+        }
+        common::WorkerType::Unspecified => Err(Status::invalid_argument("unknown worker type")),
+    }
+}
+
+/// 收到 Ctrl-C 或 SIGTERM 时完成，用于 gRPC 服务优雅退出
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!("监听 Ctrl-C 失败: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                warn!("监听 SIGTERM 失败: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    info!("收到退出信号，开始优雅退出");
+}
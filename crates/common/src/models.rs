@@ -34,6 +34,16 @@ pub enum TapeStatus {
     Error,
     Retired,
     Unknown,
+    Reclaiming,
+    Scratch,
+}
+
+/// 磁带介质写入状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TapeMediaState {
+    Appendable,
+    Full,
+    ReadOnly,
 }
 
 /// 驱动状态
@@ -0,0 +1,416 @@
+//! TLS 配置
+//!
+//! - Gateway S3 端点: [`TlsConfig::rustls_server_config`] 构造 rustls 服务端配置，
+//!   证书文件变化由 [`TlsConfig::files_fingerprint`] 检测后热加载
+//! - 内部 gRPC: 同一份 `TlsConfig` 同时用于服务端与客户端。配置 `ca_path` 后
+//!   服务端要求客户端出示由该 CA 签发的证书 (mTLS)，客户端以 `cert_path` /
+//!   `key_path` 作为自身身份，并用 `ca_path` 校验服务端证书
+//! - 返回密钥等敏感数据的内部 gRPC 接口用 [`require_client_certificate`] 拒绝未出示
+//!   客户端证书的调用方
+
+use crate::error::{Error, Result};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tonic::transport::{
+    Certificate, Channel, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM 证书链
+    pub cert_path: String,
+    /// PEM 私钥 (PKCS#8 / PKCS#1 / SEC1)
+    pub key_path: String,
+    /// PEM CA 证书: 服务端用于校验客户端证书 (mTLS)，客户端用于校验服务端证书
+    pub ca_path: Option<String>,
+    /// 客户端校验服务端证书时使用的名称，默认取连接地址的主机部分
+    pub server_name: Option<String>,
+    /// 证书文件变化的检测间隔 (Gateway 热加载)
+    pub reload_interval_secs: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: String::new(),
+            key_path: String::new(),
+            ca_path: None,
+            server_name: None,
+            reload_interval_secs: 30,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// 校验配置；`serves` 为 true 时要求服务端证书与私钥
+    pub fn validate(&self, field: &str, serves: bool, problems: &mut Vec<String>) {
+        if !self.enabled {
+            return;
+        }
+        if self.cert_path.is_empty() != self.key_path.is_empty() {
+            problems.push(format!(
+                "{field}.cert_path and {field}.key_path must be set together"
+            ));
+        } else if serves && self.cert_path.is_empty() {
+            problems.push(format!(
+                "{field}.cert_path and {field}.key_path are required"
+            ));
+        }
+        if !serves && self.ca_path.is_none() {
+            problems.push(format!(
+                "{field}.ca_path is required to verify server certificates"
+            ));
+        }
+        if self.reload_interval_secs == 0 {
+            problems.push(format!("{field}.reload_interval_secs must be positive"));
+        }
+    }
+
+    pub fn scheme(&self) -> &'static str {
+        if self.enabled {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    /// `host:port` → `http(s)://host:port`
+    pub fn endpoint_url(&self, addr: &str) -> String {
+        format!("{}://{addr}", self.scheme())
+    }
+
+    /// tonic 服务端 TLS；未启用时返回 `None`
+    #[allow(clippy::result_large_err)]
+    pub fn server_tls_config(&self) -> Result<Option<ServerTlsConfig>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let mut tls = ServerTlsConfig::new().identity(self.identity()?);
+        if let Some(ca_path) = &self.ca_path {
+            tls = tls.client_ca_root(Certificate::from_pem(read_file(ca_path)?));
+        }
+        Ok(Some(tls))
+    }
+
+    /// tonic 客户端 TLS；未启用时返回 `None`
+    #[allow(clippy::result_large_err)]
+    pub fn client_tls_config(&self, addr: &str) -> Result<Option<ClientTlsConfig>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let ca_path = self
+            .ca_path
+            .as_deref()
+            .ok_or_else(|| Error::Tls("ca_path is required for TLS clients".into()))?;
+        let mut tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(read_file(ca_path)?))
+            .domain_name(self.server_name.clone().unwrap_or_else(|| host_of(addr)));
+        if !self.cert_path.is_empty() {
+            tls = tls.identity(self.identity()?);
+        }
+        Ok(Some(tls))
+    }
+
+    /// 按配置连接 gRPC 服务
+    #[allow(clippy::result_large_err)]
+    pub async fn connect(&self, addr: &str) -> Result<Channel> {
+        Ok(self.endpoint(addr)?.connect().await?)
+    }
+
+    /// 按配置构造 gRPC endpoint (未连接)
+    #[allow(clippy::result_large_err)]
+    pub fn endpoint(&self, addr: &str) -> Result<Endpoint> {
+        let mut endpoint = Endpoint::from_shared(self.endpoint_url(addr))?;
+        if let Some(tls) = self.client_tls_config(addr)? {
+            endpoint = endpoint.tls_config(tls)?;
+        }
+        Ok(endpoint)
+    }
+
+    /// rustls 服务端配置 (HTTP/1.1 与 h2)，配置 `ca_path` 时要求客户端证书
+    #[allow(clippy::result_large_err)]
+    pub fn rustls_server_config(&self) -> Result<rustls::ServerConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?;
+        let builder = match &self.ca_path {
+            Some(ca_path) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in load_certs(ca_path)? {
+                    roots.add(cert).map_err(tls_error)?;
+                }
+                let verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(roots),
+                    provider,
+                )
+                .build()
+                .map_err(tls_error)?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder
+            .with_single_cert(load_certs(&self.cert_path)?, load_key(&self.key_path)?)
+            .map_err(tls_error)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    /// 证书相关文件的 (修改时间, 大小)，任一变化即需要重新加载
+    pub fn files_fingerprint(&self) -> Vec<Option<(SystemTime, u64)>> {
+        [
+            Some(&self.cert_path),
+            Some(&self.key_path),
+            self.ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| {
+            std::fs::metadata(path)
+                .ok()
+                .and_then(|meta| Some((meta.modified().ok()?, meta.len())))
+        })
+        .collect()
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn identity(&self) -> Result<Identity> {
+        Ok(Identity::from_pem(
+            read_file(&self.cert_path)?,
+            read_file(&self.key_path)?,
+        ))
+    }
+}
+
+/// 只接受出示了客户端证书 (mTLS) 的调用方；明文连接或未启用 mTLS 时返回 `PermissionDenied`
+#[allow(clippy::result_large_err)]
+pub fn require_client_certificate<T>(
+    request: &tonic::Request<T>,
+) -> std::result::Result<(), tonic::Status> {
+    match request.peer_certs() {
+        Some(certs) if !certs.is_empty() => Ok(()),
+        _ => Err(tonic::Status::permission_denied(
+            "internal interface requires a client certificate (mTLS)",
+        )),
+    }
+}
+
+fn tls_error(err: impl std::fmt::Display) -> Error {
+    Error::Tls(err.to_string())
+}
+
+#[allow(clippy::result_large_err)]
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(Path::new(path)).map_err(|err| Error::Tls(format!("read {path}: {err}")))
+}
+
+#[allow(clippy::result_large_err)]
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = read_file(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|err| Error::Tls(format!("parse certificates in {path}: {err}")))?;
+    if certs.is_empty() {
+        return Err(Error::Tls(format!("no certificates found in {path}")));
+    }
+    Ok(certs)
+}
+
+#[allow(clippy::result_large_err)]
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let pem = read_file(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .map_err(|err| Error::Tls(format!("parse private key in {path}: {err}")))?
+        .ok_or_else(|| Error::Tls(format!("no private key found in {path}")))
+}
+
+/// `host:port` / `[v6]:port` 的主机部分
+fn host_of(addr: &str) -> String {
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => addr,
+    };
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string()
+}
+
+/// 测试用 PKI: 运行时生成 CA 以及由其签发的服务端/客户端证书
+#[cfg(any(test, feature = "test-certs"))]
+pub mod testing {
+    use super::TlsConfig;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+        KeyUsagePurpose,
+    };
+    use std::path::{Path, PathBuf};
+
+    pub struct TestPki {
+        pub dir: PathBuf,
+    }
+
+    impl TestPki {
+        /// 在临时目录生成 `ca.pem`、`server.{pem,key}`、`client.{pem,key}`；
+        /// 服务端证书覆盖 `localhost` 与 `127.0.0.1`
+        pub fn generate() -> Self {
+            let dir = std::env::temp_dir().join(format!("coldstore-pki-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).expect("create pki dir");
+            let pki = Self { dir };
+            pki.regenerate();
+            pki
+        }
+
+        /// 用新的 CA 重新签发全部证书 (模拟证书轮换)
+        pub fn regenerate(&self) {
+            let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            ca_params
+                .distinguished_name
+                .push(DnType::CommonName, "ColdStore Test CA");
+            ca_params.key_usages = vec![
+                KeyUsagePurpose::KeyCertSign,
+                KeyUsagePurpose::CrlSign,
+                KeyUsagePurpose::DigitalSignature,
+            ];
+            let ca_key = KeyPair::generate().unwrap();
+            let ca = ca_params.self_signed(&ca_key).unwrap();
+            write(&self.dir.join("ca.pem"), &ca.pem());
+
+            for (name, usage) in [
+                ("server", ExtendedKeyUsagePurpose::ServerAuth),
+                ("client", ExtendedKeyUsagePurpose::ClientAuth),
+            ] {
+                let mut params =
+                    CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+                        .unwrap();
+                params
+                    .distinguished_name
+                    .push(DnType::CommonName, format!("coldstore-{name}"));
+                params.extended_key_usages = vec![usage];
+                let key = KeyPair::generate().unwrap();
+                let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+                write(&self.dir.join(format!("{name}.pem")), &cert.pem());
+                write(&self.dir.join(format!("{name}.key")), &key.serialize_pem());
+            }
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.dir.join(name).display().to_string()
+        }
+
+        /// 服务端配置；`mutual` 为 true 时要求客户端证书
+        pub fn server_config(&self, mutual: bool) -> TlsConfig {
+            TlsConfig {
+                enabled: true,
+                cert_path: self.path("server.pem"),
+                key_path: self.path("server.key"),
+                ca_path: mutual.then(|| self.path("ca.pem")),
+                server_name: None,
+                reload_interval_secs: 1,
+            }
+        }
+
+        /// 客户端配置；`with_identity` 为 false 时不出示客户端证书
+        pub fn client_config(&self, with_identity: bool) -> TlsConfig {
+            TlsConfig {
+                enabled: true,
+                cert_path: if with_identity {
+                    self.path("client.pem")
+                } else {
+                    String::new()
+                },
+                key_path: if with_identity {
+                    self.path("client.key")
+                } else {
+                    String::new()
+                },
+                ca_path: Some(self.path("ca.pem")),
+                server_name: Some("localhost".into()),
+                reload_interval_secs: 1,
+            }
+        }
+
+        pub fn ca_pem(&self) -> Vec<u8> {
+            std::fs::read(self.dir.join("ca.pem")).unwrap()
+        }
+    }
+
+    impl Drop for TestPki {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::write(path, contents).expect("write pki file");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::TestPki;
+    use super::*;
+
+    #[test]
+    fn builds_server_configs_from_generated_certificates() {
+        let pki = TestPki::generate();
+        let server = pki.server_config(true);
+
+        let rustls = server.rustls_server_config().unwrap();
+        assert_eq!(rustls.alpn_protocols[0], b"h2");
+        assert!(server.server_tls_config().unwrap().is_some());
+        assert!(pki
+            .client_config(true)
+            .client_tls_config("127.0.0.1:1")
+            .unwrap()
+            .is_some());
+        assert!(TlsConfig::default().server_tls_config().unwrap().is_none());
+
+        let before = server.files_fingerprint();
+        assert_eq!(before.len(), 3);
+        assert!(before.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn reports_unreadable_or_empty_files() {
+        let pki = TestPki::generate();
+        let mut config = pki.server_config(false);
+        config.cert_path = pki.dir.join("missing.pem").display().to_string();
+        let err = config.rustls_server_config().unwrap_err();
+        assert!(err.to_string().contains("missing.pem"), "{err}");
+
+        config.cert_path = pki.dir.join("server.key").display().to_string();
+        let err = config.rustls_server_config().unwrap_err();
+        assert!(err.to_string().contains("no certificates"), "{err}");
+    }
+
+    #[test]
+    fn validates_and_formats_endpoints() {
+        let mut problems = Vec::new();
+        let client = TlsConfig {
+            enabled: true,
+            ..TlsConfig::default()
+        };
+        client.validate("tls", true, &mut problems);
+        client.validate("scheduler_tls", false, &mut problems);
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems[1].contains("ca_path"));
+
+        assert_eq!(
+            client.endpoint_url("10.0.0.1:22001"),
+            "https://10.0.0.1:22001"
+        );
+        assert_eq!(
+            TlsConfig::default().endpoint_url("10.0.0.1:22001"),
+            "http://10.0.0.1:22001"
+        );
+        assert_eq!(host_of("meta-1.internal:21001"), "meta-1.internal");
+        assert_eq!(host_of("[::1]:21001"), "::1");
+    }
+}
@@ -1,15 +1,40 @@
-use crate::protocol::{format_restore_header, is_restore_request, S3ErrorCode, S3ErrorResponse};
+use crate::auth::Principal;
+use crate::lifecycle::{lifecycle_configuration_xml, parse_lifecycle_configuration};
+use crate::protocol::{
+    format_content_range, format_http_date, format_restore_header, is_restore_request, parse_range,
+    parse_restore_request, s3_error_code_for_status, validate_object_attributes,
+    verify_checksum_sha256, verify_content_md5, PreconditionOutcome, Preconditions, RangeRequest,
+    S3ErrorCode, S3ErrorResponse, MAX_PUT_OBJECT_SIZE,
+};
 use crate::{DownloadedObject, GatewayState};
 use axum::body::{Body, Bytes};
-use axum::extract::{Path, Query, State};
+use axum::extract::rejection::BytesRejection;
+use axum::extract::{DefaultBodyLimit, Extension, Path, Query, State};
 use axum::http::{header::HeaderName, HeaderMap, HeaderValue, StatusCode};
 use axum::response::Response;
-use axum::{routing::get, Router};
+use axum::{middleware, routing::get, Router};
+use chrono::{DateTime, Utc};
+use coldstore_common::attributes;
+use coldstore_proto::common::ByteRange;
+use coldstore_proto::scheduler::HeadObjectResponse;
+use quick_xml::escape::escape;
 use std::collections::HashMap;
 use std::sync::Arc;
 
 pub fn router(state: Arc<GatewayState>) -> Router {
-    build_router().with_state(state)
+    // layer 由内向外包裹: 先分配请求 ID，再认证，最后按桶策略授权
+    build_router()
+        .layer(DefaultBodyLimit::max(MAX_PUT_OBJECT_SIZE))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::policy::authorize,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            crate::auth::authenticate,
+        ))
+        .layer(middleware::from_fn(crate::request_id::assign))
+        .with_state(state)
 }
 
 fn build_router() -> Router<Arc<GatewayState>> {
@@ -35,16 +60,19 @@ fn build_router() -> Router<Arc<GatewayState>> {
 
 #[cfg(test)]
 fn test_router(state: Arc<GatewayState>) -> Router {
-    build_router().with_state(state)
+    router(state)
 }
 
 async fn health() -> &'static str {
     "OK"
 }
 
-async fn list_buckets(State(state): State<Arc<GatewayState>>) -> Response {
+async fn list_buckets(
+    State(state): State<Arc<GatewayState>>,
+    principal: Option<Extension<Principal>>,
+) -> Response {
     match state.backend.list_buckets().await {
-        Ok(response) => list_buckets_xml_response(&response),
+        Ok(response) => list_buckets_xml_response(&response, principal.as_ref().map(|p| &p.0)),
         Err(status) => grpc_status_to_s3_response(status, "/"),
     }
 }
@@ -52,8 +80,18 @@ async fn list_buckets(State(state): State<Arc<GatewayState>>) -> Response {
 async fn create_bucket(
     State(state): State<Arc<GatewayState>>,
     Path(bucket): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    principal: Option<Extension<Principal>>,
+    body: Bytes,
 ) -> Response {
-    match state.backend.create_bucket(&bucket).await {
+    if query.contains_key("policy") {
+        return put_bucket_policy(&state, &bucket, &body).await;
+    }
+    if query.contains_key("lifecycle") {
+        return put_bucket_lifecycle(&state, &bucket, &body).await;
+    }
+    let owner = principal.as_ref().map(|p| p.owner.as_str());
+    match state.backend.create_bucket(&bucket, owner).await {
         Ok(()) => empty_response(StatusCode::OK),
         Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}")),
     }
@@ -62,7 +100,20 @@ async fn create_bucket(
 async fn delete_bucket(
     State(state): State<Arc<GatewayState>>,
     Path(bucket): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
 ) -> Response {
+    if query.contains_key("policy") {
+        return match state.backend.delete_bucket_policy(&bucket).await {
+            Ok(()) => empty_response(StatusCode::NO_CONTENT),
+            Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}")),
+        };
+    }
+    if query.contains_key("lifecycle") {
+        return match state.backend.delete_bucket_lifecycle(&bucket).await {
+            Ok(()) => empty_response(StatusCode::NO_CONTENT),
+            Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}")),
+        };
+    }
     match state.backend.delete_bucket(&bucket).await {
         Ok(()) => empty_response(StatusCode::NO_CONTENT),
         Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}")),
@@ -84,6 +135,18 @@ async fn list_objects(
     Path(bucket): Path<String>,
     Query(query): Query<HashMap<String, String>>,
 ) -> Response {
+    if query.contains_key("policy") {
+        return get_bucket_policy(&state, &bucket).await;
+    }
+    if query.contains_key("lifecycle") {
+        return match state.backend.get_bucket_lifecycle(&bucket).await {
+            Ok(lifecycle) => xml_response(
+                StatusCode::OK,
+                lifecycle_configuration_xml(&lifecycle.rules),
+            ),
+            Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}")),
+        };
+    }
     let prefix = query.get("prefix").map(String::as_str);
     let marker = query.get("marker").map(String::as_str);
     let delimiter = query.get("delimiter").map(String::as_str);
@@ -101,36 +164,229 @@ async fn list_objects(
     }
 }
 
+async fn put_bucket_policy(state: &GatewayState, bucket: &str, body: &[u8]) -> Response {
+    let resource = format!("/{bucket}");
+    let Ok(document) = std::str::from_utf8(body) else {
+        return s3_error_response(
+            S3ErrorCode::MalformedPolicy,
+            "policy must be UTF-8 JSON",
+            &resource,
+        );
+    };
+    if let Err(err) = crate::policy::BucketPolicy::parse(bucket, document) {
+        return s3_error_response(S3ErrorCode::MalformedPolicy, &err.0, &resource);
+    }
+    match state
+        .backend
+        .put_bucket_policy(bucket, document.to_string())
+        .await
+    {
+        Ok(()) => empty_response(StatusCode::NO_CONTENT),
+        Err(status) => grpc_status_to_s3_response(status, &resource),
+    }
+}
+
+async fn put_bucket_lifecycle(state: &GatewayState, bucket: &str, body: &[u8]) -> Response {
+    let resource = format!("/{bucket}");
+    let rules = match parse_lifecycle_configuration(body) {
+        Ok(rules) => rules,
+        Err((code, message)) => return s3_error_response(code, &message, &resource),
+    };
+    match state.backend.put_bucket_lifecycle(bucket, rules).await {
+        Ok(()) => empty_response(StatusCode::OK),
+        Err(status) => grpc_status_to_s3_response(status, &resource),
+    }
+}
+
+async fn get_bucket_policy(state: &GatewayState, bucket: &str) -> Response {
+    let resource = format!("/{bucket}");
+    match state.backend.get_bucket_policy(bucket).await {
+        Ok(response) => match response.policy {
+            Some(policy) => {
+                let mut http = Response::new(Body::from(policy));
+                http.headers_mut().insert(
+                    axum::http::header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+                http
+            }
+            None => s3_error_response(
+                S3ErrorCode::NoSuchBucketPolicy,
+                "The bucket policy does not exist",
+                &resource,
+            ),
+        },
+        Err(status) => grpc_status_to_s3_response(status, &resource),
+    }
+}
+
 async fn get_object(
     State(state): State<Arc<GatewayState>>,
     Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
 ) -> Response {
-    match state.backend.get_object(&bucket, &key).await {
-        Ok(object) => get_object_success_response(object),
-        Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}/{key}")),
+    let resource = format!("/{bucket}/{key}");
+    let preconditions = preconditions(&headers);
+    let range = headers
+        .get(axum::http::header::RANGE)
+        .and_then(|value| value.to_str().ok());
+
+    // 条件与区间都需要对象的 ETag、修改时间和长度，先 HEAD 再只读取需要的区间
+    let mut ranges = Vec::new();
+    if range.is_some() || !preconditions.is_empty() {
+        let head = match state.backend.head_object(&bucket, &key).await {
+            Ok(head) => head,
+            Err(status) => return grpc_status_to_s3_response(status, &resource),
+        };
+        if let Some(response) = precondition_response(&preconditions, &head, &resource) {
+            return response;
+        }
+        match range.map(|range| parse_range(range, head.content_length)) {
+            None | Some(RangeRequest::Full) => {}
+            Some(RangeRequest::Partial(partial)) => ranges = partial,
+            Some(RangeRequest::Unsatisfiable) => {
+                let mut response = s3_error_response(
+                    S3ErrorCode::InvalidRange,
+                    "the requested range is not satisfiable",
+                    &resource,
+                );
+                response.headers_mut().insert(
+                    axum::http::header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{}", head.content_length)).unwrap(),
+                );
+                return response;
+            }
+        }
+    }
+
+    match state.backend.get_object(&bucket, &key, &ranges).await {
+        Ok(object) => get_object_success_response(object, &ranges),
+        Err(status) => grpc_status_to_s3_response(status, &resource),
     }
 }
 
+fn preconditions(headers: &HeaderMap) -> Preconditions<'_> {
+    let header = |name: axum::http::header::HeaderName| {
+        headers.get(name).and_then(|value| value.to_str().ok())
+    };
+    Preconditions {
+        if_match: header(axum::http::header::IF_MATCH),
+        if_none_match: header(axum::http::header::IF_NONE_MATCH),
+        if_modified_since: header(axum::http::header::IF_MODIFIED_SINCE),
+        if_unmodified_since: header(axum::http::header::IF_UNMODIFIED_SINCE),
+    }
+}
+
+/// 条件不满足时返回 304 或 412 响应
+fn precondition_response(
+    preconditions: &Preconditions,
+    head: &HeadObjectResponse,
+    resource: &str,
+) -> Option<Response> {
+    match preconditions.evaluate(&head.etag, last_modified(head)) {
+        PreconditionOutcome::Proceed => None,
+        PreconditionOutcome::NotModified => {
+            let mut response = empty_response(StatusCode::NOT_MODIFIED);
+            let headers = response.headers_mut();
+            headers.insert(
+                axum::http::header::ETAG,
+                HeaderValue::from_str(&head.etag).unwrap(),
+            );
+            if let Some(date) = last_modified(head) {
+                headers.insert(
+                    axum::http::header::LAST_MODIFIED,
+                    HeaderValue::from_str(&format_http_date(date)).unwrap(),
+                );
+            }
+            Some(response)
+        }
+        PreconditionOutcome::Failed => Some(s3_error_response(
+            S3ErrorCode::PreconditionFailed,
+            "at least one of the preconditions you specified did not hold",
+            resource,
+        )),
+    }
+}
+
+fn last_modified(head: &HeadObjectResponse) -> Option<DateTime<Utc>> {
+    head.last_modified
+        .as_ref()
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp.seconds, 0))
+}
+
 async fn put_object(
     State(state): State<Arc<GatewayState>>,
     Path((bucket, key)): Path<(String, String)>,
     headers: HeaderMap,
-    body: Bytes,
+    body: Result<Bytes, BytesRejection>,
 ) -> Response {
+    let resource = format!("/{bucket}/{key}");
+    let body = match body {
+        Ok(body) => body,
+        Err(rejection) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            return s3_error_response(
+                S3ErrorCode::EntityTooLarge,
+                &format!("object exceeds the maximum PUT size of {MAX_PUT_OBJECT_SIZE} bytes"),
+                &resource,
+            );
+        }
+        Err(rejection) => {
+            return s3_error_response(
+                S3ErrorCode::IncompleteBody,
+                &rejection.body_text(),
+                &resource,
+            );
+        }
+    };
     let content_type = headers
         .get(axum::http::header::CONTENT_TYPE)
         .and_then(|value| value.to_str().ok())
         .map(str::to_string);
+    if let Err(code) = verify_client_digests(&headers, &body) {
+        return s3_error_response(code, "client supplied digest check failed", &resource);
+    }
+    let attributes = attributes::from_headers(
+        headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?))),
+    );
+    if let Err((code, message)) = validate_object_attributes(&attributes) {
+        return s3_error_response(code, &message, &resource);
+    }
+    let checksum_sha256 = coldstore_common::checksum::sha256_hex(&body);
     match state
         .backend
-        .put_object(&bucket, &key, body.to_vec(), content_type)
+        .put_object(
+            &bucket,
+            &key,
+            body.to_vec(),
+            content_type,
+            Some(checksum_sha256),
+            attributes,
+        )
         .await
     {
         Ok(response) => put_object_success_response(response),
-        Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}/{key}")),
+        Err(status) => grpc_status_to_s3_response(status, &resource),
     }
 }
 
+fn verify_client_digests(headers: &HeaderMap, body: &[u8]) -> Result<(), S3ErrorCode> {
+    if let Some(value) = headers.get("content-md5") {
+        verify_content_md5(
+            value.to_str().map_err(|_| S3ErrorCode::InvalidDigest)?,
+            body,
+        )?;
+    }
+    if let Some(value) = headers.get("x-amz-checksum-sha256") {
+        verify_checksum_sha256(
+            value.to_str().map_err(|_| S3ErrorCode::InvalidDigest)?,
+            body,
+        )?;
+    }
+    Ok(())
+}
+
 async fn delete_object(
     State(state): State<Arc<GatewayState>>,
     Path((bucket, key)): Path<(String, String)>,
@@ -144,10 +400,13 @@ async fn delete_object(
 async fn head_object(
     State(state): State<Arc<GatewayState>>,
     Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
 ) -> Response {
+    let resource = format!("/{bucket}/{key}");
     match state.backend.head_object(&bucket, &key).await {
-        Ok(head) => head_object_success_response(head),
-        Err(status) => grpc_status_to_s3_response(status, &format!("/{bucket}/{key}")),
+        Ok(head) => precondition_response(&preconditions(&headers), &head, &resource)
+            .unwrap_or_else(|| head_object_success_response(head)),
+        Err(status) => grpc_status_to_s3_response(status, &resource),
     }
 }
 
@@ -155,6 +414,7 @@ async fn post_object(
     State(state): State<Arc<GatewayState>>,
     Path((bucket, key)): Path<(String, String)>,
     Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
 ) -> Response {
     let raw_query = if query.is_empty() {
         None
@@ -178,19 +438,18 @@ async fn post_object(
         return bad_request_response("UnsupportedPostAction", &resource);
     }
 
-    let days = query
-        .get("days")
-        .and_then(|value| value.parse::<u32>().ok())
-        .unwrap_or(1);
-    let tier = match query.get("tier").map(String::as_str) {
-        Some("Expedited") => coldstore_proto::common::RestoreTier::Expedited,
-        Some("Bulk") => coldstore_proto::common::RestoreTier::Bulk,
-        _ => coldstore_proto::common::RestoreTier::Standard,
+    let request = match parse_restore_request(&body) {
+        Ok(request) => request,
+        Err(err) => return s3_error_response(err.code, &err.message, &resource),
     };
+    if let Some(description) = &request.description {
+        tracing::info!("解冻 {resource}: {description}");
+    }
 
+    // 已解冻的对象返回 200 并按新的 Days 延长过期时间
     match state
         .backend
-        .restore_object(&bucket, &key, days, tier)
+        .restore_object(&bucket, &key, request.days, request.tier)
         .await
     {
         Ok(response) => empty_response(if response.status_code == 200 {
@@ -202,18 +461,27 @@ async fn post_object(
     }
 }
 
+fn owner_xml(owner: &str) -> String {
+    let owner = escape(owner);
+    format!("<Owner><ID>{owner}</ID><DisplayName>{owner}</DisplayName></Owner>")
+}
+
 fn list_buckets_xml_response(
     response: &coldstore_proto::scheduler::ListBucketsResponse,
+    principal: Option<&Principal>,
 ) -> Response {
     let buckets_xml = response
         .buckets
         .iter()
-        .map(|bucket| format!("<Bucket><Name>{}</Name></Bucket>", bucket.name))
+        .map(|bucket| format!("<Bucket><Name>{}</Name></Bucket>", escape(&bucket.name)))
         .collect::<Vec<_>>()
         .join("");
+    let owner = principal
+        .map(|principal| owner_xml(&principal.owner))
+        .unwrap_or_default();
     let body = format!(
-        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListAllMyBucketsResult><Buckets>{}</Buckets></ListAllMyBucketsResult>",
-        buckets_xml
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListAllMyBucketsResult>{}<Buckets>{}</Buckets></ListAllMyBucketsResult>",
+        owner, buckets_xml
     );
     xml_response(StatusCode::OK, body)
 }
@@ -221,20 +489,26 @@ fn list_buckets_xml_response(
 fn list_objects_xml_response(
     response: &coldstore_proto::scheduler::ListObjectsResponse,
 ) -> Response {
+    let owner = response.owner.as_deref().map(owner_xml).unwrap_or_default();
     let contents = response
         .contents
         .iter()
         .map(|entry| {
             format!(
-                "<Contents><Key>{}</Key><ETag>{}</ETag><Size>{}</Size><StorageClass>{}</StorageClass></Contents>",
-                entry.key, entry.etag, entry.size, entry.storage_class
+                "<Contents><Key>{}</Key><ETag>{}</ETag><Size>{}</Size>{}<StorageClass>{}</StorageClass></Contents>",
+                escape(&entry.key),
+                escape(&entry.etag),
+                entry.size,
+                owner,
+                entry.storage_class
             )
         })
         .collect::<Vec<_>>()
         .join("");
     let body = format!(
         "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult><Name>{}</Name>{}</ListBucketResult>",
-        response.bucket, contents
+        escape(&response.bucket),
+        contents
     );
     xml_response(StatusCode::OK, body)
 }
@@ -250,11 +524,73 @@ fn put_object_success_response(
     http
 }
 
-fn get_object_success_response(object: DownloadedObject) -> Response {
-    let mut response = Response::new(Body::from(object.body));
-    *response.status_mut() = StatusCode::OK;
-    apply_object_headers(response.headers_mut(), &object.head);
-    response
+/// `ranges` 为空时返回 200 与整个对象；单个区间返回 206 与 `Content-Range`，
+/// 多个区间返回 206 与 `multipart/byteranges` 正文
+fn get_object_success_response(object: DownloadedObject, ranges: &[ByteRange]) -> Response {
+    let size = object.head.content_length;
+    match ranges {
+        [] => {
+            let mut response = Response::new(Body::from(object.body));
+            *response.status_mut() = StatusCode::OK;
+            apply_object_headers(response.headers_mut(), &object.head);
+            response
+        }
+        [range] => {
+            let mut response = Response::new(Body::from(object.body));
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            let headers = response.headers_mut();
+            apply_object_headers(headers, &object.head);
+            headers.insert(
+                axum::http::header::CONTENT_LENGTH,
+                HeaderValue::from_str(&range.length.to_string()).unwrap(),
+            );
+            headers.insert(
+                axum::http::header::CONTENT_RANGE,
+                HeaderValue::from_str(&format_content_range(range, size)).unwrap(),
+            );
+            response
+        }
+        ranges => {
+            let boundary = uuid::Uuid::new_v4().simple().to_string();
+            let content_type = object
+                .head
+                .content_type
+                .as_deref()
+                .unwrap_or("application/octet-stream");
+            let mut body = Vec::new();
+            let mut data = object.body.as_slice();
+            for range in ranges {
+                let (part, rest) = data.split_at((range.length as usize).min(data.len()));
+                data = rest;
+                body.extend_from_slice(
+                    format!(
+                        "--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+                        format_content_range(range, size)
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(part);
+                body.extend_from_slice(b"\r\n");
+            }
+            body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+            let length = body.len();
+            let mut response = Response::new(Body::from(body));
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            let headers = response.headers_mut();
+            apply_object_headers(headers, &object.head);
+            headers.insert(
+                axum::http::header::CONTENT_LENGTH,
+                HeaderValue::from_str(&length.to_string()).unwrap(),
+            );
+            headers.insert(
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_str(&format!("multipart/byteranges; boundary={boundary}"))
+                    .unwrap(),
+            );
+            response
+        }
+    }
 }
 
 fn head_object_success_response(head: coldstore_proto::scheduler::HeadObjectResponse) -> Response {
@@ -282,6 +618,43 @@ fn apply_object_headers(
         axum::http::header::ETAG,
         HeaderValue::from_str(&head.etag).unwrap(),
     );
+    let storage_class = head.storage_class();
+    if storage_class != coldstore_proto::common::StorageClass::Unspecified {
+        headers.insert(
+            HeaderName::from_static("x-amz-storage-class"),
+            HeaderValue::from_static(storage_class.as_str_name()),
+        );
+    }
+    headers.insert(
+        axum::http::header::ACCEPT_RANGES,
+        HeaderValue::from_static("bytes"),
+    );
+    if let Some(date) = last_modified(head) {
+        headers.insert(
+            axum::http::header::LAST_MODIFIED,
+            HeaderValue::from_str(&format_http_date(date)).unwrap(),
+        );
+    }
+    if let Some(object_attributes) = &head.attributes {
+        // 与 S3 一致，GET/HEAD 只返回标签数量
+        for (name, value) in attributes::to_headers(object_attributes) {
+            if name == attributes::TAGGING_HEADER {
+                continue;
+            }
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        if !object_attributes.tags.is_empty() {
+            headers.insert(
+                HeaderName::from_static("x-amz-tagging-count"),
+                HeaderValue::from(object_attributes.tags.len()),
+            );
+        }
+    }
     if let Some(restore_info) = &head.restore_info {
         headers.insert(
             HeaderName::from_static("x-amz-restore"),
@@ -302,36 +675,42 @@ fn normalize_restore_info(restore_info: &str) -> String {
 
 fn bad_request_response(operation: &str, resource: &str) -> Response {
     let message = format!("unsupported POST action for {operation}");
-    let body = S3ErrorResponse {
-        code: S3ErrorCode::NotImplemented,
-        message: &message,
+    s3_error_xml_response(
+        StatusCode::BAD_REQUEST,
+        S3ErrorCode::NotImplemented,
+        &message,
         resource,
+    )
+}
+
+pub(crate) fn grpc_status_to_s3_response(status: tonic::Status, resource: &str) -> Response {
+    let code = s3_error_code_for_status(&status, resource);
+    if code == S3ErrorCode::InternalError {
+        tracing::warn!("{resource} 后端错误: {status}");
     }
-    .to_xml();
-    s3_xml_response(StatusCode::BAD_REQUEST, body)
+    s3_error_response(code, status.message(), resource)
 }
 
-fn grpc_status_to_s3_response(status: tonic::Status, resource: &str) -> Response {
-    let (code, http_status) = match status.code() {
-        tonic::Code::AlreadyExists => (S3ErrorCode::NotImplemented, StatusCode::CONFLICT),
-        tonic::Code::NotFound => {
-            let code = if resource.matches('/').count() > 1 {
-                S3ErrorCode::NoSuchKey
-            } else {
-                S3ErrorCode::NoSuchBucket
-            };
-            (code, StatusCode::NOT_FOUND)
-        }
-        tonic::Code::Unimplemented => (S3ErrorCode::NotImplemented, StatusCode::NOT_IMPLEMENTED),
-        _ => (S3ErrorCode::NotImplemented, StatusCode::BAD_GATEWAY),
-    };
+pub(crate) fn s3_error_response(code: S3ErrorCode, message: &str, resource: &str) -> Response {
+    let status = StatusCode::from_u16(code.http_status()).unwrap_or(StatusCode::BAD_REQUEST);
+    s3_error_xml_response(status, code, message, resource)
+}
+
+fn s3_error_xml_response(
+    status: StatusCode,
+    code: S3ErrorCode,
+    message: &str,
+    resource: &str,
+) -> Response {
     let body = S3ErrorResponse {
         code,
-        message: status.message(),
+        message,
         resource,
+        request_id: &crate::request_id::current(),
+        host_id: crate::request_id::host_id(),
     }
     .to_xml();
-    s3_xml_response(http_status, body)
+    s3_xml_response(status, body)
 }
 
 fn s3_xml_response(status: StatusCode, body: String) -> Response {
@@ -360,13 +739,21 @@ mod tests {
     use crate::GatewayBackend;
     use axum::body::to_bytes;
     use axum::http::Request;
+    use coldstore_common::error::status_with_reason;
+    use coldstore_proto::common::ErrorReason;
     use coldstore_proto::scheduler::{
-        BucketEntry, HeadObjectResponse, ListBucketsResponse, ListObjectsResponse, ObjectEntry,
-        PutObjectResponse, RestoreObjectResponse,
+        BucketEntry, GetBucketPolicyResponse, HeadObjectResponse, ListBucketsResponse,
+        ListObjectsResponse, ObjectEntry, PutObjectResponse, RestoreObjectResponse,
     };
     use tower::util::ServiceExt;
 
-    struct MockGatewayBackend;
+    #[derive(Default)]
+    struct MockGatewayBackend {
+        policy: std::sync::Mutex<Option<String>>,
+        lifecycle: std::sync::Mutex<Option<Vec<coldstore_proto::common::LifecycleRule>>>,
+        restore: std::sync::Mutex<Option<(u32, coldstore_proto::common::RestoreTier)>>,
+        put_attributes: std::sync::Mutex<Option<coldstore_proto::common::ObjectAttributes>>,
+    }
 
     #[tonic::async_trait]
     impl GatewayBackend for MockGatewayBackend {
@@ -375,12 +762,19 @@ mod tests {
                 buckets: vec![BucketEntry {
                     name: "docs".into(),
                     creation_date: None,
+                    owner: Some("alice".into()),
                 }],
             })
         }
 
-        async fn create_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status> {
-            if bucket == "existing" {
+        async fn create_bucket(
+            &self,
+            bucket: &str,
+            owner: Option<&str>,
+        ) -> std::result::Result<(), tonic::Status> {
+            if owner.is_some_and(|owner| owner != "alice") {
+                Err(tonic::Status::invalid_argument("unexpected owner"))
+            } else if bucket == "existing" {
                 Err(tonic::Status::already_exists("bucket already exists"))
             } else {
                 Ok(())
@@ -390,6 +784,12 @@ mod tests {
         async fn delete_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status> {
             if bucket == "docs" {
                 Ok(())
+            } else if bucket == "full" {
+                Err(status_with_reason(
+                    tonic::Code::FailedPrecondition,
+                    "bucket is not empty",
+                    ErrorReason::BucketNotEmpty,
+                ))
             } else {
                 Err(tonic::Status::not_found("bucket missing"))
             }
@@ -429,6 +829,7 @@ mod tests {
                     storage_class: "COLD".into(),
                 }],
                 common_prefixes: vec![],
+                owner: Some("alice".into()),
             })
         }
 
@@ -436,9 +837,17 @@ mod tests {
             &self,
             _bucket: &str,
             _key: &str,
-            _body: Vec<u8>,
+            body: Vec<u8>,
             _content_type: Option<String>,
+            checksum_sha256: Option<String>,
+            attributes: coldstore_proto::common::ObjectAttributes,
         ) -> std::result::Result<PutObjectResponse, tonic::Status> {
+            if checksum_sha256.as_deref()
+                != Some(coldstore_common::checksum::sha256_hex(&body).as_str())
+            {
+                return Err(tonic::Status::data_loss("checksum mismatch"));
+            }
+            *self.put_attributes.lock().unwrap() = Some(attributes);
             Ok(PutObjectResponse {
                 etag: "etag-put".into(),
                 version_id: "v1".into(),
@@ -449,10 +858,27 @@ mod tests {
             &self,
             bucket: &str,
             key: &str,
+            ranges: &[ByteRange],
         ) -> std::result::Result<DownloadedObject, tonic::Status> {
+            if key == "cold.bin" {
+                return Err(status_with_reason(
+                    tonic::Code::FailedPrecondition,
+                    "object docs/cold.bin must be restored before it can be read",
+                    ErrorReason::InvalidObjectState,
+                ));
+            }
+            let body = b"hello world";
             Ok(DownloadedObject {
                 head: self.head_object(bucket, key).await?,
-                body: b"hello world".to_vec(),
+                body: if ranges.is_empty() {
+                    body.to_vec()
+                } else {
+                    ranges
+                        .iter()
+                        .flat_map(|r| &body[r.offset as usize..(r.offset + r.length) as usize])
+                        .copied()
+                        .collect()
+                },
             })
         }
 
@@ -475,12 +901,21 @@ mod tests {
         ) -> std::result::Result<HeadObjectResponse, tonic::Status> {
             if bucket == "docs" && key == "readme.txt" {
                 Ok(HeadObjectResponse {
-                    content_length: 42,
+                    content_length: 11,
                     content_type: Some("text/plain".into()),
                     etag: "etag-1".into(),
                     storage_class: 2,
                     restore_info: Some("ongoing-request=\"false\", expiry-ts=\"123\"".into()),
-                    last_modified: None,
+                    // Wed, 01 Jan 2025 12:00:00 GMT
+                    last_modified: Some(prost_types::Timestamp {
+                        seconds: 1_735_732_800,
+                        nanos: 0,
+                    }),
+                    attributes: Some(attributes::from_headers([
+                        ("x-amz-meta-project", "apollo"),
+                        ("content-disposition", "attachment"),
+                        ("x-amz-tagging", "env=prod&team=data"),
+                    ])),
                 })
             } else {
                 Err(tonic::Status::not_found("object missing"))
@@ -491,23 +926,395 @@ mod tests {
             &self,
             bucket: &str,
             key: &str,
-            _days: u32,
-            _tier: coldstore_proto::common::RestoreTier,
+            days: u32,
+            tier: coldstore_proto::common::RestoreTier,
         ) -> std::result::Result<RestoreObjectResponse, tonic::Status> {
             if bucket == "docs" && key == "readme.txt" {
+                *self.restore.lock().unwrap() = Some((days, tier));
                 Ok(RestoreObjectResponse { status_code: 202 })
             } else {
                 Err(tonic::Status::not_found("object missing"))
             }
         }
+
+        async fn put_bucket_policy(
+            &self,
+            bucket: &str,
+            policy: String,
+        ) -> std::result::Result<(), tonic::Status> {
+            self.head_bucket(bucket).await?;
+            *self.policy.lock().unwrap() = Some(policy);
+            Ok(())
+        }
+
+        async fn get_bucket_policy(
+            &self,
+            bucket: &str,
+        ) -> std::result::Result<GetBucketPolicyResponse, tonic::Status> {
+            self.head_bucket(bucket).await?;
+            Ok(GetBucketPolicyResponse {
+                policy: self.policy.lock().unwrap().clone(),
+                owner: Some("alice".into()),
+            })
+        }
+
+        async fn delete_bucket_policy(
+            &self,
+            bucket: &str,
+        ) -> std::result::Result<(), tonic::Status> {
+            self.head_bucket(bucket).await?;
+            *self.policy.lock().unwrap() = None;
+            Ok(())
+        }
+
+        async fn put_bucket_lifecycle(
+            &self,
+            bucket: &str,
+            rules: Vec<coldstore_proto::common::LifecycleRule>,
+        ) -> std::result::Result<(), tonic::Status> {
+            self.head_bucket(bucket).await?;
+            if rules.iter().any(|rule| rule.expiration_days == Some(0)) {
+                return Err(tonic::Status::invalid_argument("days must be positive"));
+            }
+            *self.lifecycle.lock().unwrap() = Some(rules);
+            Ok(())
+        }
+
+        async fn get_bucket_lifecycle(
+            &self,
+            bucket: &str,
+        ) -> std::result::Result<coldstore_proto::common::BucketLifecycle, tonic::Status> {
+            self.head_bucket(bucket).await?;
+            match self.lifecycle.lock().unwrap().clone() {
+                Some(rules) => Ok(coldstore_proto::common::BucketLifecycle {
+                    bucket: bucket.to_string(),
+                    rules,
+                    updated_at: None,
+                }),
+                None => Err(status_with_reason(
+                    tonic::Code::NotFound,
+                    "no lifecycle configuration",
+                    ErrorReason::NoSuchLifecycleConfiguration,
+                )),
+            }
+        }
+
+        async fn delete_bucket_lifecycle(
+            &self,
+            bucket: &str,
+        ) -> std::result::Result<(), tonic::Status> {
+            self.head_bucket(bucket).await?;
+            *self.lifecycle.lock().unwrap() = None;
+            Ok(())
+        }
     }
 
     fn state() -> Arc<GatewayState> {
         Arc::new(GatewayState {
-            backend: Arc::new(MockGatewayBackend),
+            backend: Arc::new(MockGatewayBackend::default()),
+            auth: None,
+        })
+    }
+
+    fn authenticated_state() -> Arc<GatewayState> {
+        Arc::new(GatewayState {
+            backend: Arc::new(MockGatewayBackend::default()),
+            auth: Some(Arc::new(crate::auth::tests::test_auth())),
         })
     }
 
+    #[tokio::test]
+    async fn unsigned_request_is_denied_when_auth_enabled() {
+        let response = test_router(authenticated_state())
+            .oneshot(
+                Request::builder()
+                    .uri("/docs")
+                    .header("host", "localhost:9000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>AccessDenied</Code>"));
+
+        let response = test_router(authenticated_state())
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn signed_put_reaches_backend() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("localhost:9000"));
+        crate::auth::tests::sign_request(
+            "PUT",
+            "/docs/readme.txt",
+            None,
+            &mut headers,
+            crate::auth::UNSIGNED_PAYLOAD,
+            chrono::Utc::now(),
+        );
+        let mut request = Request::builder()
+            .method("PUT")
+            .uri("/docs/readme.txt")
+            .body(Body::from("hello world"))
+            .unwrap();
+        *request.headers_mut() = headers;
+        let response = test_router(authenticated_state())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["etag"], "etag-put");
+    }
+
+    #[tokio::test]
+    async fn signed_requests_carry_owner() {
+        let signed = |method: &str, uri: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("host", HeaderValue::from_static("localhost:9000"));
+            crate::auth::tests::sign_request(
+                method,
+                uri,
+                None,
+                &mut headers,
+                crate::auth::UNSIGNED_PAYLOAD,
+                chrono::Utc::now(),
+            );
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            *request.headers_mut() = headers;
+            request
+        };
+
+        let response = test_router(authenticated_state())
+            .oneshot(signed("PUT", "/new-bucket"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_router(authenticated_state())
+            .oneshot(signed("GET", "/"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Owner><ID>alice</ID><DisplayName>alice</DisplayName></Owner>"));
+    }
+
+    fn signed_request(
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        body: &'static str,
+        source: &str,
+    ) -> Request<Body> {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", HeaderValue::from_static("localhost:9000"));
+        crate::auth::tests::sign_request(
+            method,
+            path,
+            query,
+            &mut headers,
+            crate::auth::UNSIGNED_PAYLOAD,
+            chrono::Utc::now(),
+        );
+        let uri = match query {
+            Some(query) => format!("{path}?{query}"),
+            None => path.to_string(),
+        };
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body))
+            .unwrap();
+        *request.headers_mut() = headers;
+        request.extensions_mut().insert(axum::extract::ConnectInfo(
+            source.parse::<std::net::SocketAddr>().unwrap(),
+        ));
+        request
+    }
+
+    const RESTORE: &str = "<RestoreRequest><Days>1</Days></RestoreRequest>";
+
+    #[tokio::test]
+    async fn bucket_policy_routes_store_and_enforce_policy() {
+        const POLICY: &str = r#"{"Version":"2012-10-17","Statement":[{"Effect":"Deny","Principal":"*","Action":"s3:RestoreObject","Resource":"arn:aws:s3:::docs/*","Condition":{"NotIpAddress":{"aws:SourceIp":"10.0.0.0/8"}}}]}"#;
+        let state = authenticated_state();
+        let outside = "192.168.0.7:4000";
+        let inside = "10.0.0.5:4000";
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request("GET", "/docs", Some("policy"), "", outside))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request(
+                "PUT",
+                "/docs",
+                Some("policy"),
+                r#"{"Statement":[{"Effect":"Allow","Principal":"*","Action":"s3:GetObject","Resource":"arn:aws:s3:::other/*"}]}"#,
+                outside,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>MalformedPolicy</Code>"));
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request(
+                "PUT",
+                "/docs",
+                Some("policy"),
+                POLICY,
+                outside,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request("GET", "/docs", Some("policy"), "", outside))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.as_ref(), POLICY.as_bytes());
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request(
+                "POST",
+                "/docs/readme.txt",
+                Some("restore"),
+                RESTORE,
+                outside,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request(
+                "POST",
+                "/docs/readme.txt",
+                Some("restore"),
+                RESTORE,
+                inside,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = test_router(state.clone())
+            .oneshot(signed_request(
+                "DELETE",
+                "/docs",
+                Some("policy"),
+                "",
+                outside,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = test_router(state)
+            .oneshot(signed_request(
+                "POST",
+                "/docs/readme.txt",
+                Some("restore"),
+                RESTORE,
+                outside,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn bucket_lifecycle_routes_round_trip_configuration() {
+        const LIFECYCLE: &str = "<LifecycleConfiguration><Rule><ID>logs</ID><Filter><Prefix>logs/</Prefix></Filter><Status>Enabled</Status><Expiration><Days>30</Days></Expiration></Rule></LifecycleConfiguration>";
+        let state = state();
+        let request = |method: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri("/docs?lifecycle")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = test_router(state.clone())
+            .oneshot(request("GET", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>NoSuchLifecycleConfiguration</Code>"));
+
+        let response = test_router(state.clone())
+            .oneshot(request("PUT", LIFECYCLE))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_router(state.clone())
+            .oneshot(request("GET", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<ID>logs</ID><Filter><Prefix>logs/</Prefix></Filter>"));
+        assert!(body.contains("<Expiration><Days>30</Days></Expiration>"));
+
+        let response = test_router(state.clone())
+            .oneshot(request("PUT", &LIFECYCLE.replace("30", "0")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = test_router(state.clone())
+            .oneshot(request(
+                "PUT",
+                "<LifecycleConfiguration><Rule><Status>Enabled</Status><Transition><Days>1</Days></Transition></Rule></LifecycleConfiguration>",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+
+        let response = test_router(state.clone())
+            .oneshot(request("DELETE", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = test_router(state)
+            .oneshot(request("GET", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[tokio::test]
     async fn health_route_returns_ok() {
         let response = test_router(state())
@@ -546,6 +1353,46 @@ mod tests {
         let text = String::from_utf8(body.to_vec()).unwrap();
         assert!(text.contains("<ListBucketResult>"));
         assert!(text.contains("<Key>readme.txt</Key>"));
+        assert!(text.contains("<Owner><ID>alice</ID><DisplayName>alice</DisplayName></Owner>"));
+    }
+
+    #[tokio::test]
+    async fn listing_xml_escapes_owner_and_bucket_names() {
+        let owner = "R&D <ops>";
+        let buckets = list_buckets_xml_response(
+            &coldstore_proto::scheduler::ListBucketsResponse {
+                buckets: vec![coldstore_proto::scheduler::BucketEntry {
+                    name: "a&b".into(),
+                    creation_date: None,
+                    owner: Some(owner.into()),
+                }],
+            },
+            Some(&Principal {
+                access_key_id: "AKID".into(),
+                owner: owner.into(),
+            }),
+        );
+        let body = to_bytes(buckets.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains(
+            "<Owner><ID>R&amp;D &lt;ops&gt;</ID><DisplayName>R&amp;D &lt;ops&gt;</DisplayName></Owner>"
+        ));
+        assert!(text.contains("<Bucket><Name>a&amp;b</Name></Bucket>"));
+
+        let objects = list_objects_xml_response(&coldstore_proto::scheduler::ListObjectsResponse {
+            bucket: "a&b".into(),
+            contents: vec![coldstore_proto::scheduler::ObjectEntry {
+                key: "k".into(),
+                ..Default::default()
+            }],
+            owner: Some(owner.into()),
+            ..Default::default()
+        });
+        let body = to_bytes(objects.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("<ListBucketResult><Name>a&amp;b</Name>"));
+        assert!(text.contains("<ID>R&amp;D &lt;ops&gt;</ID>"));
+        assert!(!text.contains("R&D"));
     }
 
     #[tokio::test]
@@ -595,6 +1442,108 @@ mod tests {
         assert_eq!(response.headers()["etag"], "etag-put");
     }
 
+    #[tokio::test]
+    async fn put_object_route_keeps_user_metadata_and_system_headers() {
+        let backend = Arc::new(MockGatewayBackend::default());
+        let state = Arc::new(GatewayState {
+            backend: backend.clone(),
+            auth: None,
+        });
+        let response = test_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/docs/readme.txt")
+                    .header("x-amz-meta-Project", "apollo")
+                    .header("content-encoding", "gzip")
+                    .header("cache-control", "max-age=60")
+                    .header("x-amz-tagging", "env=prod")
+                    .body(Body::from("hello world"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let stored = backend.put_attributes.lock().unwrap().clone().unwrap();
+        assert_eq!(stored.user_metadata["project"], "apollo");
+        assert_eq!(stored.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(stored.cache_control.as_deref(), Some("max-age=60"));
+        assert_eq!(stored.tags["env"], "prod");
+
+        let response = test_router(state)
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/docs/readme.txt")
+                    .header("x-amz-meta-note", "x".repeat(4096))
+                    .body(Body::from("hello world"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>MetadataTooLarge</Code>"));
+    }
+
+    #[tokio::test]
+    async fn put_object_route_verifies_content_md5() {
+        use base64::Engine;
+        use md5::Digest;
+        let good = base64::engine::general_purpose::STANDARD.encode(md5::Md5::digest(b"hello"));
+        let response = test_router(state())
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/docs/readme.txt")
+                    .header("content-md5", good.as_str())
+                    .body(Body::from("hello"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_router(state())
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/docs/readme.txt")
+                    .header("content-md5", good.as_str())
+                    .body(Body::from("tampered"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>BadDigest</Code>"));
+    }
+
+    #[tokio::test]
+    async fn put_object_route_rejects_malformed_checksum_header() {
+        let response = test_router(state())
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/docs/readme.txt")
+                    .header("x-amz-checksum-sha256", "%%%")
+                    .body(Body::from("hello"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>InvalidDigest</Code>"));
+    }
+
     #[tokio::test]
     async fn get_object_route_returns_body_from_backend() {
         let response = test_router(state())
@@ -612,33 +1561,147 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn delete_object_route_uses_backend() {
-        let response = test_router(state())
-            .oneshot(
+    async fn get_object_route_serves_byte_ranges() {
+        let get = |range: &str| {
+            test_router(state()).oneshot(
                 Request::builder()
-                    .method("DELETE")
                     .uri("/docs/readme.txt")
+                    .header("range", range)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let response = get("bytes=6-").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["content-range"], "bytes 6-10/11");
+        assert_eq!(response.headers()["content-length"], "5");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.as_ref(), b"world");
+
+        let response = get("bytes=0-1,-2").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let content_type = response.headers()["content-type"].to_str().unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .expect("multipart response")
+            .to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            format!(
+                "--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/11\r\n\r\nhe\r\n\
+                 --{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 9-10/11\r\n\r\nld\r\n\
+                 --{boundary}--\r\n"
+            )
+        );
+
+        let response = get("bytes=11-").await.unwrap();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()["content-range"], "bytes */11");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>InvalidRange</Code>"));
+
+        let response = get("bytes=oops").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn conditional_get_and_head_return_304_and_412() {
+        let request = |method: &str, name: &str, value: &str| {
+            test_router(state()).oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri("/docs/readme.txt")
+                    .header(name, value)
                     .body(Body::empty())
                     .unwrap(),
             )
+        };
+
+        let response = request("GET", "if-none-match", "\"etag-1\"").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["etag"], "etag-1");
+        assert_eq!(
+            response.headers()["last-modified"],
+            "Wed, 01 Jan 2025 12:00:00 GMT"
+        );
+
+        let response = request("GET", "if-match", "\"etag-2\"").await.unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>PreconditionFailed</Code>"));
+
+        let response = request("GET", "if-modified-since", "Tue, 31 Dec 2024 00:00:00 GMT")
             .await
             .unwrap();
-        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = request(
+            "HEAD",
+            "if-unmodified-since",
+            "Tue, 31 Dec 2024 00:00:00 GMT",
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
     }
 
     #[tokio::test]
-    async fn restore_post_route_uses_backend() {
+    async fn delete_object_route_uses_backend() {
         let response = test_router(state())
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/docs/readme.txt?restore=true&days=2&tier=Bulk")
+                    .method("DELETE")
+                    .uri("/docs/readme.txt")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn restore_post_route_parses_xml_body() {
+        let backend = Arc::new(MockGatewayBackend::default());
+        let state = Arc::new(GatewayState {
+            backend: backend.clone(),
+            auth: None,
+        });
+        let restore = |body: &'static str| {
+            Request::builder()
+                .method("POST")
+                .uri("/docs/readme.txt?restore")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = test_router(state.clone())
+            .oneshot(restore(
+                "<RestoreRequest><Days>2</Days><GlacierJobParameters><Tier>Bulk</Tier></GlacierJobParameters></RestoreRequest>",
+            ))
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            *backend.restore.lock().unwrap(),
+            Some((2, coldstore_proto::common::RestoreTier::Bulk))
+        );
+
+        let response = test_router(state)
+            .oneshot(restore("<RestoreRequest><Days>0</Days></RestoreRequest>"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<Code>InvalidArgument</Code>"));
     }
 
     #[tokio::test]
@@ -670,9 +1733,61 @@ mod tests {
             .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
         assert_eq!(response.headers()["etag"], "etag-1");
+        assert_eq!(response.headers()["x-amz-storage-class"], "COLD");
+        assert_eq!(response.headers()["x-amz-meta-project"], "apollo");
+        assert_eq!(response.headers()["content-disposition"], "attachment");
+        assert_eq!(response.headers()["x-amz-tagging-count"], "2");
+        assert!(response.headers().get("x-amz-tagging").is_none());
         assert_eq!(
             response.headers()["x-amz-restore"],
             "ongoing-request=\"false\", expiry-date=\"123\""
         );
     }
+
+    #[tokio::test]
+    async fn backend_errors_map_to_s3_error_responses() {
+        let cases = [
+            (
+                "PUT",
+                "/existing",
+                StatusCode::CONFLICT,
+                "BucketAlreadyExists",
+            ),
+            ("DELETE", "/full", StatusCode::CONFLICT, "BucketNotEmpty"),
+            (
+                "GET",
+                "/docs/cold.bin",
+                StatusCode::FORBIDDEN,
+                "InvalidObjectState",
+            ),
+            ("DELETE", "/missing", StatusCode::NOT_FOUND, "NoSuchBucket"),
+        ];
+        for (method, uri, status, code) in cases {
+            let response = test_router(state())
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{method} {uri}");
+            let request_id = response.headers()[crate::request_id::REQUEST_ID_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string();
+            assert_eq!(request_id.len(), 16);
+            let host_id = response.headers()[crate::request_id::HOST_ID_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let text = String::from_utf8(body.to_vec()).unwrap();
+            assert!(text.contains(&format!("<Code>{code}</Code>")), "{text}");
+            assert!(text.contains(&format!("<RequestId>{request_id}</RequestId>")));
+            assert!(text.contains(&format!("<HostId>{host_id}</HostId>")));
+        }
+    }
 }
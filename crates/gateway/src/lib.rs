@@ -1,19 +1,29 @@
+pub mod auth;
 pub mod handler;
+pub mod lifecycle;
+pub mod policy;
 pub mod protocol;
+pub mod request_id;
+pub mod tls;
 
 use anyhow::Result;
+use coldstore_common::client::ChannelPool;
 use coldstore_common::config::GatewayConfig;
 use coldstore_proto::common;
+use coldstore_proto::scheduler::scheduler_internal_service_client::SchedulerInternalServiceClient;
 use coldstore_proto::scheduler::scheduler_service_client::SchedulerServiceClient;
 use coldstore_proto::scheduler::{
-    CreateBucketRequest, DeleteBucketRequest, DeleteObjectRequest, HeadBucketRequest,
-    HeadObjectRequest, HeadObjectResponse, ListBucketsResponse, ListObjectsRequest,
-    ListObjectsResponse, PutObjectMeta, PutObjectRequest, PutObjectResponse, RestoreObjectRequest,
-    RestoreObjectResponse,
+    CreateBucketRequest, DeleteBucketLifecycleRequest, DeleteBucketPolicyRequest,
+    DeleteBucketRequest, DeleteObjectRequest, GetBucketLifecycleRequest, GetBucketPolicyRequest,
+    GetBucketPolicyResponse, HeadBucketRequest, HeadObjectRequest, HeadObjectResponse,
+    ListBucketsResponse, ListObjectsRequest, ListObjectsResponse, LookupAccessKeyRequest,
+    PutBucketLifecycleRequest, PutBucketPolicyRequest, PutObjectMeta, PutObjectRequest,
+    PutObjectResponse, RestoreObjectRequest, RestoreObjectResponse,
 };
+use std::future::Future;
 use std::sync::Arc;
 use tonic::transport::Channel;
-use tracing::info;
+use tracing::{info, warn};
 
 pub struct DownloadedObject {
     pub head: HeadObjectResponse,
@@ -23,7 +33,12 @@ pub struct DownloadedObject {
 #[tonic::async_trait]
 pub trait GatewayBackend: Send + Sync + 'static {
     async fn list_buckets(&self) -> std::result::Result<ListBucketsResponse, tonic::Status>;
-    async fn create_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status>;
+    /// `owner` 为认证通过的调用方；匿名访问时为 `None`
+    async fn create_bucket(
+        &self,
+        bucket: &str,
+        owner: Option<&str>,
+    ) -> std::result::Result<(), tonic::Status>;
     async fn delete_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status>;
     async fn head_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status>;
     async fn list_objects(
@@ -40,11 +55,16 @@ pub trait GatewayBackend: Send + Sync + 'static {
         key: &str,
         body: Vec<u8>,
         content_type: Option<String>,
+        checksum_sha256: Option<String>,
+        attributes: common::ObjectAttributes,
     ) -> std::result::Result<PutObjectResponse, tonic::Status>;
+    /// `ranges` 为空时下载整个对象；否则 `body` 为各区间按顺序拼接的数据，
+    /// `head.content_length` 仍为对象总长度
     async fn get_object(
         &self,
         bucket: &str,
         key: &str,
+        ranges: &[common::ByteRange],
     ) -> std::result::Result<DownloadedObject, tonic::Status>;
     async fn delete_object(
         &self,
@@ -63,59 +83,105 @@ pub trait GatewayBackend: Send + Sync + 'static {
         days: u32,
         tier: common::RestoreTier,
     ) -> std::result::Result<RestoreObjectResponse, tonic::Status>;
+    async fn put_bucket_policy(
+        &self,
+        bucket: &str,
+        policy: String,
+    ) -> std::result::Result<(), tonic::Status>;
+    /// 桶策略与桶所有者；桶不存在时返回 NOT_FOUND
+    async fn get_bucket_policy(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<GetBucketPolicyResponse, tonic::Status>;
+    async fn delete_bucket_policy(&self, bucket: &str) -> std::result::Result<(), tonic::Status>;
+    async fn put_bucket_lifecycle(
+        &self,
+        bucket: &str,
+        rules: Vec<common::LifecycleRule>,
+    ) -> std::result::Result<(), tonic::Status>;
+    /// 桶未配置生命周期时返回 NOT_FOUND 并附带 NoSuchLifecycleConfiguration 原因
+    async fn get_bucket_lifecycle(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<common::BucketLifecycle, tonic::Status>;
+    async fn delete_bucket_lifecycle(&self, bucket: &str)
+        -> std::result::Result<(), tonic::Status>;
 }
 
+/// 通过 gRPC 转发到 Scheduler Worker；请求在健康的 Worker 间轮询，
+/// 连接失败的 Worker 暂时跳过并换节点重试
 pub struct GrpcGatewayBackend {
-    scheduler_addr: String,
+    schedulers: ChannelPool,
 }
 
 impl GrpcGatewayBackend {
-    pub fn new(scheduler_addr: String) -> Self {
-        Self { scheduler_addr }
+    pub fn new(schedulers: ChannelPool) -> Self {
+        Self { schedulers }
+    }
+
+    /// 按 `scheduler_addrs` / `scheduler_tls` / `scheduler_client` 建立连接池
+    pub fn from_config(config: &GatewayConfig) -> Result<Self> {
+        Ok(Self::new(ChannelPool::new(
+            &config.scheduler_addrs,
+            &config.scheduler_tls,
+            config.scheduler_client.clone(),
+        )?))
     }
 
-    async fn connect(&self) -> std::result::Result<SchedulerServiceClient<Channel>, tonic::Status> {
-        SchedulerServiceClient::connect(self.scheduler_addr.clone())
+    async fn call<T, F, Fut>(&self, mut op: F) -> std::result::Result<T, tonic::Status>
+    where
+        F: FnMut(SchedulerServiceClient<Channel>) -> Fut,
+        Fut: Future<Output = std::result::Result<T, tonic::Status>>,
+    {
+        self.schedulers
+            .call(|channel| op(SchedulerServiceClient::new(channel)))
             .await
-            .map_err(|err| tonic::Status::unavailable(err.to_string()))
     }
 }
 
 #[tonic::async_trait]
 impl GatewayBackend for GrpcGatewayBackend {
     async fn list_buckets(&self) -> std::result::Result<ListBucketsResponse, tonic::Status> {
-        let mut client = self.connect().await?;
-        client.list_buckets(()).await.map(|r| r.into_inner())
+        self.call(|mut client| async move { client.list_buckets(()).await.map(|r| r.into_inner()) })
+            .await
     }
 
-    async fn create_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .create_bucket(CreateBucketRequest {
-                bucket: bucket.to_string(),
-            })
-            .await
-            .map(|_| ())
+    async fn create_bucket(
+        &self,
+        bucket: &str,
+        owner: Option<&str>,
+    ) -> std::result::Result<(), tonic::Status> {
+        let request = CreateBucketRequest {
+            bucket: bucket.to_string(),
+            owner: owner.map(str::to_string),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.create_bucket(request).await.map(|_| ()) }
+        })
+        .await
     }
 
     async fn delete_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .delete_bucket(DeleteBucketRequest {
-                bucket: bucket.to_string(),
-            })
-            .await
-            .map(|_| ())
+        let request = DeleteBucketRequest {
+            bucket: bucket.to_string(),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.delete_bucket(request).await.map(|_| ()) }
+        })
+        .await
     }
 
     async fn head_bucket(&self, bucket: &str) -> std::result::Result<(), tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .head_bucket(HeadBucketRequest {
-                bucket: bucket.to_string(),
-            })
-            .await
-            .map(|_| ())
+        let request = HeadBucketRequest {
+            bucket: bucket.to_string(),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.head_bucket(request).await.map(|_| ()) }
+        })
+        .await
     }
 
     async fn list_objects(
@@ -126,17 +192,18 @@ impl GatewayBackend for GrpcGatewayBackend {
         delimiter: Option<&str>,
         max_keys: u32,
     ) -> std::result::Result<ListObjectsResponse, tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .list_objects(ListObjectsRequest {
-                bucket: bucket.to_string(),
-                prefix: prefix.map(str::to_string),
-                marker: marker.map(str::to_string),
-                delimiter: delimiter.map(str::to_string),
-                max_keys,
-            })
-            .await
-            .map(|r| r.into_inner())
+        let request = ListObjectsRequest {
+            bucket: bucket.to_string(),
+            prefix: prefix.map(str::to_string),
+            marker: marker.map(str::to_string),
+            delimiter: delimiter.map(str::to_string),
+            max_keys,
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.list_objects(request).await.map(|r| r.into_inner()) }
+        })
+        .await
     }
 
     async fn put_object(
@@ -145,74 +212,89 @@ impl GatewayBackend for GrpcGatewayBackend {
         key: &str,
         body: Vec<u8>,
         content_type: Option<String>,
+        checksum_sha256: Option<String>,
+        attributes: common::ObjectAttributes,
     ) -> std::result::Result<PutObjectResponse, tonic::Status> {
-        let mut client = self.connect().await?;
-        let stream = tokio_stream::iter(vec![
-            PutObjectRequest {
-                payload: Some(
-                    coldstore_proto::scheduler::put_object_request::Payload::Meta(PutObjectMeta {
-                        bucket: bucket.to_string(),
-                        key: key.to_string(),
-                        content_length: body.len() as u64,
-                        content_type,
-                        checksum_sha256: None,
-                    }),
-                ),
-            },
-            PutObjectRequest {
-                payload: Some(coldstore_proto::scheduler::put_object_request::Payload::Data(body)),
-            },
-        ]);
-        client.put_object(stream).await.map(|r| r.into_inner())
+        let meta = PutObjectMeta {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            content_length: body.len() as u64,
+            content_type,
+            checksum_sha256,
+            attributes: Some(attributes),
+        };
+        self.call(|mut client| {
+            let stream = tokio_stream::iter(vec![
+                PutObjectRequest {
+                    payload: Some(
+                        coldstore_proto::scheduler::put_object_request::Payload::Meta(meta.clone()),
+                    ),
+                },
+                PutObjectRequest {
+                    payload: Some(
+                        coldstore_proto::scheduler::put_object_request::Payload::Data(body.clone()),
+                    ),
+                },
+            ]);
+            async move { client.put_object(stream).await.map(|r| r.into_inner()) }
+        })
+        .await
     }
 
     async fn get_object(
         &self,
         bucket: &str,
         key: &str,
+        ranges: &[common::ByteRange],
     ) -> std::result::Result<DownloadedObject, tonic::Status> {
-        let mut client = self.connect().await?;
-        let mut stream = client
-            .get_object(coldstore_proto::scheduler::GetObjectRequest {
-                bucket: bucket.to_string(),
-                key: key.to_string(),
-                version_id: None,
-            })
-            .await?
-            .into_inner();
-
-        let first = stream
-            .message()
-            .await?
-            .ok_or_else(|| tonic::Status::internal("missing object metadata chunk"))?;
-        let head = match first.payload {
-            Some(coldstore_proto::scheduler::get_object_response::Payload::Meta(meta)) => {
-                HeadObjectResponse {
-                    content_length: meta.content_length,
-                    content_type: meta.content_type,
-                    etag: meta.etag,
-                    storage_class: meta.storage_class,
-                    restore_info: meta.restore_info,
-                    last_modified: meta.last_modified,
-                }
-            }
-            _ => {
-                return Err(tonic::Status::internal(
-                    "first object chunk was not metadata",
-                ))
-            }
+        let request = coldstore_proto::scheduler::GetObjectRequest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: None,
+            ranges: ranges.to_vec(),
         };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move {
+                let mut stream = client.get_object(request).await?.into_inner();
 
-        let mut body = Vec::new();
-        while let Some(chunk) = stream.message().await? {
-            if let Some(coldstore_proto::scheduler::get_object_response::Payload::Data(bytes)) =
-                chunk.payload
-            {
-                body.extend_from_slice(&bytes);
-            }
-        }
+                let first = stream
+                    .message()
+                    .await?
+                    .ok_or_else(|| tonic::Status::internal("missing object metadata chunk"))?;
+                let head = match first.payload {
+                    Some(coldstore_proto::scheduler::get_object_response::Payload::Meta(meta)) => {
+                        HeadObjectResponse {
+                            content_length: meta.content_length,
+                            content_type: meta.content_type,
+                            etag: meta.etag,
+                            storage_class: meta.storage_class,
+                            restore_info: meta.restore_info,
+                            last_modified: meta.last_modified,
+                            attributes: meta.attributes,
+                        }
+                    }
+                    _ => {
+                        return Err(tonic::Status::internal(
+                            "first object chunk was not metadata",
+                        ))
+                    }
+                };
+
+                let mut body = Vec::new();
+                while let Some(chunk) = stream.message().await? {
+                    if let Some(coldstore_proto::scheduler::get_object_response::Payload::Data(
+                        bytes,
+                    )) = chunk.payload
+                    {
+                        body.extend_from_slice(&bytes);
+                    }
+                }
 
-        Ok(DownloadedObject { head, body })
+                Ok(DownloadedObject { head, body })
+            }
+        })
+        .await
     }
 
     async fn delete_object(
@@ -220,15 +302,16 @@ impl GatewayBackend for GrpcGatewayBackend {
         bucket: &str,
         key: &str,
     ) -> std::result::Result<(), tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .delete_object(DeleteObjectRequest {
-                bucket: bucket.to_string(),
-                key: key.to_string(),
-                version_id: None,
-            })
-            .await
-            .map(|_| ())
+        let request = DeleteObjectRequest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: None,
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.delete_object(request).await.map(|_| ()) }
+        })
+        .await
     }
 
     async fn head_object(
@@ -236,15 +319,16 @@ impl GatewayBackend for GrpcGatewayBackend {
         bucket: &str,
         key: &str,
     ) -> std::result::Result<HeadObjectResponse, tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .head_object(HeadObjectRequest {
-                bucket: bucket.to_string(),
-                key: key.to_string(),
-                version_id: None,
-            })
-            .await
-            .map(|r| r.into_inner())
+        let request = HeadObjectRequest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: None,
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.head_object(request).await.map(|r| r.into_inner()) }
+        })
+        .await
     }
 
     async fn restore_object(
@@ -254,35 +338,180 @@ impl GatewayBackend for GrpcGatewayBackend {
         days: u32,
         tier: common::RestoreTier,
     ) -> std::result::Result<RestoreObjectResponse, tonic::Status> {
-        let mut client = self.connect().await?;
-        client
-            .restore_object(RestoreObjectRequest {
-                bucket: bucket.to_string(),
-                key: key.to_string(),
-                version_id: None,
-                days,
-                tier: tier as i32,
+        let request = RestoreObjectRequest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: None,
+            days,
+            tier: tier as i32,
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.restore_object(request).await.map(|r| r.into_inner()) }
+        })
+        .await
+    }
+
+    async fn put_bucket_policy(
+        &self,
+        bucket: &str,
+        policy: String,
+    ) -> std::result::Result<(), tonic::Status> {
+        let request = PutBucketPolicyRequest {
+            bucket: bucket.to_string(),
+            policy,
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.put_bucket_policy(request).await.map(|_| ()) }
+        })
+        .await
+    }
+
+    async fn get_bucket_policy(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<GetBucketPolicyResponse, tonic::Status> {
+        let request = GetBucketPolicyRequest {
+            bucket: bucket.to_string(),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move {
+                client
+                    .get_bucket_policy(request)
+                    .await
+                    .map(|r| r.into_inner())
+            }
+        })
+        .await
+    }
+
+    async fn delete_bucket_policy(&self, bucket: &str) -> std::result::Result<(), tonic::Status> {
+        let request = DeleteBucketPolicyRequest {
+            bucket: bucket.to_string(),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.delete_bucket_policy(request).await.map(|_| ()) }
+        })
+        .await
+    }
+
+    async fn put_bucket_lifecycle(
+        &self,
+        bucket: &str,
+        rules: Vec<common::LifecycleRule>,
+    ) -> std::result::Result<(), tonic::Status> {
+        let request = PutBucketLifecycleRequest {
+            bucket: bucket.to_string(),
+            rules,
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.put_bucket_lifecycle(request).await.map(|_| ()) }
+        })
+        .await
+    }
+
+    async fn get_bucket_lifecycle(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<common::BucketLifecycle, tonic::Status> {
+        let request = GetBucketLifecycleRequest {
+            bucket: bucket.to_string(),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move {
+                client
+                    .get_bucket_lifecycle(request)
+                    .await
+                    .map(|r| r.into_inner())
+            }
+        })
+        .await
+    }
+
+    async fn delete_bucket_lifecycle(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<(), tonic::Status> {
+        let request = DeleteBucketLifecycleRequest {
+            bucket: bucket.to_string(),
+        };
+        self.call(|mut client| {
+            let request = request.clone();
+            async move { client.delete_bucket_lifecycle(request).await.map(|_| ()) }
+        })
+        .await
+    }
+}
+
+/// 元数据中的用户密钥，经 Scheduler 内部服务查询（需 mTLS）；吊销或用户停用的密钥返回 NOT_FOUND
+#[tonic::async_trait]
+impl auth::AccessKeyStore for GrpcGatewayBackend {
+    async fn lookup(
+        &self,
+        access_key_id: &str,
+    ) -> std::result::Result<Option<auth::AccessKey>, tonic::Status> {
+        let request = LookupAccessKeyRequest {
+            access_key_id: access_key_id.to_string(),
+        };
+        let result = self
+            .schedulers
+            .call(|channel| {
+                let request = request.clone();
+                async move {
+                    SchedulerInternalServiceClient::new(channel)
+                        .lookup_access_key(request)
+                        .await
+                }
             })
-            .await
-            .map(|r| r.into_inner())
+            .await;
+        match result {
+            Ok(response) => {
+                let key = response.into_inner();
+                Ok(Some(auth::AccessKey {
+                    access_key_id: key.access_key_id,
+                    secret_access_key: key.secret_access_key,
+                    owner: key.user_id,
+                }))
+            }
+            Err(status) if status.code() == tonic::Code::NotFound => Ok(None),
+            Err(status) => Err(status),
+        }
     }
 }
 
 pub struct GatewayState {
     pub backend: Arc<dyn GatewayBackend>,
+    /// `None` 表示关闭认证 (匿名访问)
+    pub auth: Option<Arc<auth::SigV4Auth>>,
 }
 
 pub async fn run(config: GatewayConfig) -> Result<()> {
-    let scheduler_addr = format!("http://{}", &config.scheduler_addrs[0]);
-    let state = Arc::new(GatewayState {
-        backend: Arc::new(GrpcGatewayBackend::new(scheduler_addr)),
-    });
+    let backend = Arc::new(GrpcGatewayBackend::from_config(&config)?);
+    let auth = auth::SigV4Auth::from_config(&config.auth, Some(backend.clone())).map(Arc::new);
+    if auth.is_none() {
+        warn!("SigV4 认证已关闭，网关接受匿名请求");
+    }
+    let state = Arc::new(GatewayState { backend, auth });
 
-    let app = handler::router(state);
+    // 桶策略的 aws:SourceIp 条件依赖连接地址
+    let app = handler::router(state).into_make_service_with_connect_info::<std::net::SocketAddr>();
 
-    let listener = tokio::net::TcpListener::bind(&config.listen).await?;
-    info!("S3 Gateway 启动在 {}", config.listen);
-    axum::serve(listener, app).await?;
+    if config.tls.enabled {
+        let rustls = tls::load(&config.tls)?;
+        tokio::spawn(tls::watch_certificates(config.tls.clone(), rustls.clone()));
+        let addr: std::net::SocketAddr = config.listen.parse()?;
+        info!("S3 Gateway 启动在 {} (HTTPS)", config.listen);
+        axum_server::bind_rustls(addr, rustls).serve(app).await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(&config.listen).await?;
+        info!("S3 Gateway 启动在 {}", config.listen);
+        axum::serve(listener, app).await?;
+    }
 
     Ok(())
 }
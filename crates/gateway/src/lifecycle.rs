@@ -0,0 +1,332 @@
+//! S3 桶生命周期配置 (`PUT/GET/DELETE /bucket?lifecycle`)
+//!
+//! 解析 `<LifecycleConfiguration>` 请求体并生成 GET 响应。规则保存在元数据中，
+//! 由 Metadata leader 周期评估并删除过期对象。
+//!
+//! 支持的元素:
+//!   - 过滤: `Filter` 下的 `Prefix` / `Tag` / `And`，以及旧式的规则级 `Prefix`
+//!   - 动作: `Expiration` (`Days` 或 `Date`)、`NoncurrentVersionExpiration`、
+//!     `AbortIncompleteMultipartUpload`
+//!
+//! 对象写入即归档到磁带，`Transition` 类动作与按对象大小过滤返回 NotImplemented。
+
+use crate::protocol::S3ErrorCode;
+use chrono::{DateTime, SecondsFormat, Utc};
+use coldstore_proto::common::LifecycleRule;
+use prost_types::Timestamp;
+use quick_xml::escape::escape;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct LifecycleConfigurationXml {
+    #[serde(default, rename = "Rule")]
+    rules: Vec<RuleXml>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RuleXml {
+    #[serde(rename = "ID")]
+    id: Option<String>,
+    status: String,
+    prefix: Option<String>,
+    filter: Option<FilterXml>,
+    expiration: Option<ExpirationXml>,
+    noncurrent_version_expiration: Option<NoncurrentVersionExpirationXml>,
+    abort_incomplete_multipart_upload: Option<AbortIncompleteMultipartUploadXml>,
+    #[serde(default, rename = "Transition")]
+    transitions: Vec<IgnoredAny>,
+    #[serde(default, rename = "NoncurrentVersionTransition")]
+    noncurrent_version_transitions: Vec<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FilterXml {
+    prefix: Option<String>,
+    tag: Option<TagXml>,
+    and: Option<AndXml>,
+    object_size_greater_than: Option<IgnoredAny>,
+    object_size_less_than: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AndXml {
+    prefix: Option<String>,
+    #[serde(default, rename = "Tag")]
+    tags: Vec<TagXml>,
+    object_size_greater_than: Option<IgnoredAny>,
+    object_size_less_than: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TagXml {
+    key: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExpirationXml {
+    days: Option<u32>,
+    date: Option<String>,
+    expired_object_delete_marker: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NoncurrentVersionExpirationXml {
+    noncurrent_days: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AbortIncompleteMultipartUploadXml {
+    days_after_initiation: u32,
+}
+
+/// 解析 `PUT ?lifecycle` 的请求体
+///
+/// 结构错误返回 `MalformedXML`；规则数、ID 唯一性与天数范围由元数据层校验。
+pub fn parse_lifecycle_configuration(
+    body: &[u8],
+) -> Result<Vec<LifecycleRule>, (S3ErrorCode, String)> {
+    let malformed = |message: String| (S3ErrorCode::MalformedXML, message);
+    let not_implemented = |message: &str| (S3ErrorCode::NotImplemented, message.to_string());
+    let document =
+        std::str::from_utf8(body).map_err(|_| malformed("request body must be UTF-8".into()))?;
+    let raw: LifecycleConfigurationXml = quick_xml::de::from_str(document)
+        .map_err(|err| malformed(format!("invalid LifecycleConfiguration: {err}")))?;
+
+    let mut rules = Vec::with_capacity(raw.rules.len());
+    for rule in raw.rules {
+        let enabled = match rule.status.trim() {
+            "Enabled" => true,
+            "Disabled" => false,
+            other => return Err(malformed(format!("unknown rule status {other:?}"))),
+        };
+        if !rule.transitions.is_empty() || !rule.noncurrent_version_transitions.is_empty() {
+            return Err(not_implemented(
+                "objects are archived on write; lifecycle transitions are not supported",
+            ));
+        }
+        let mut parsed = LifecycleRule {
+            id: rule.id.unwrap_or_default(),
+            enabled,
+            prefix: rule.prefix.unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(filter) = rule.filter {
+            let and = filter.and.as_ref();
+            let size_filter = filter.object_size_greater_than.is_some()
+                || filter.object_size_less_than.is_some()
+                || and.is_some_and(|and| {
+                    and.object_size_greater_than.is_some() || and.object_size_less_than.is_some()
+                });
+            if size_filter {
+                return Err(not_implemented(
+                    "lifecycle filters on object size are not supported",
+                ));
+            }
+            let tags = filter
+                .tag
+                .into_iter()
+                .chain(filter.and.into_iter().flat_map(|and| {
+                    parsed.prefix = and.prefix.unwrap_or_default();
+                    and.tags
+                }));
+            parsed.tags = tags.map(|tag| (tag.key, tag.value)).collect();
+            if let Some(prefix) = filter.prefix {
+                parsed.prefix = prefix;
+            }
+        }
+        if let Some(expiration) = rule.expiration {
+            if expiration.expired_object_delete_marker.is_some() {
+                return Err(not_implemented(
+                    "delete markers are not supported; use NoncurrentVersionExpiration",
+                ));
+            }
+            parsed.expiration_days = expiration.days;
+            parsed.expiration_date = expiration
+                .date
+                .as_deref()
+                .map(|date| parse_expiration_date(date.trim()))
+                .transpose()?;
+        }
+        parsed.noncurrent_version_expiration_days = rule
+            .noncurrent_version_expiration
+            .map(|expiration| expiration.noncurrent_days);
+        parsed.abort_incomplete_multipart_upload_days = rule
+            .abort_incomplete_multipart_upload
+            .map(|abort| abort.days_after_initiation);
+        rules.push(parsed);
+    }
+    Ok(rules)
+}
+
+/// `Expiration/Date` 为 ISO 8601 时间，必须是 UTC 零点
+fn parse_expiration_date(date: &str) -> Result<Timestamp, (S3ErrorCode, String)> {
+    let parsed = DateTime::parse_from_rfc3339(date)
+        .map_err(|_| (S3ErrorCode::MalformedXML, format!("invalid date {date:?}")))?
+        .with_timezone(&Utc);
+    if parsed.timestamp() % 86_400 != 0 || parsed.timestamp_subsec_nanos() != 0 {
+        return Err((
+            S3ErrorCode::InvalidArgument,
+            "expiration date must be midnight UTC".into(),
+        ));
+    }
+    Ok(Timestamp {
+        seconds: parsed.timestamp(),
+        nanos: 0,
+    })
+}
+
+/// 生成 `GET ?lifecycle` 的响应体
+pub fn lifecycle_configuration_xml(rules: &[LifecycleRule]) -> String {
+    let rules = rules
+        .iter()
+        .map(|rule| {
+            let mut xml = String::from("<Rule>");
+            if !rule.id.is_empty() {
+                xml.push_str(&format!("<ID>{}</ID>", escape(&rule.id)));
+            }
+            xml.push_str(&filter_xml(rule));
+            xml.push_str(if rule.enabled {
+                "<Status>Enabled</Status>"
+            } else {
+                "<Status>Disabled</Status>"
+            });
+            if let Some(days) = rule.expiration_days {
+                xml.push_str(&format!("<Expiration><Days>{days}</Days></Expiration>"));
+            }
+            if let Some(date) = rule
+                .expiration_date
+                .as_ref()
+                .and_then(|date| DateTime::from_timestamp(date.seconds, 0))
+            {
+                xml.push_str(&format!(
+                    "<Expiration><Date>{}</Date></Expiration>",
+                    date.to_rfc3339_opts(SecondsFormat::Millis, true)
+                ));
+            }
+            if let Some(days) = rule.noncurrent_version_expiration_days {
+                xml.push_str(&format!(
+                    "<NoncurrentVersionExpiration><NoncurrentDays>{days}</NoncurrentDays></NoncurrentVersionExpiration>"
+                ));
+            }
+            if let Some(days) = rule.abort_incomplete_multipart_upload_days {
+                xml.push_str(&format!(
+                    "<AbortIncompleteMultipartUpload><DaysAfterInitiation>{days}</DaysAfterInitiation></AbortIncompleteMultipartUpload>"
+                ));
+            }
+            xml.push_str("</Rule>");
+            xml
+        })
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><LifecycleConfiguration>{rules}</LifecycleConfiguration>"
+    )
+}
+
+fn filter_xml(rule: &LifecycleRule) -> String {
+    let prefix = format!("<Prefix>{}</Prefix>", escape(&rule.prefix));
+    let tags: Vec<String> = rule
+        .tags
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "<Tag><Key>{}</Key><Value>{}</Value></Tag>",
+                escape(key),
+                escape(value)
+            )
+        })
+        .collect();
+    match tags.as_slice() {
+        [] => format!("<Filter>{prefix}</Filter>"),
+        [tag] if rule.prefix.is_empty() => format!("<Filter>{tag}</Filter>"),
+        _ => format!("<Filter><And>{prefix}{}</And></Filter>", tags.concat()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifecycle_configuration_round_trips_through_xml() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <LifecycleConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <Rule>
+                <ID>expire-logs</ID>
+                <Filter><Prefix>logs/</Prefix></Filter>
+                <Status>Enabled</Status>
+                <Expiration><Days>30</Days></Expiration>
+                <AbortIncompleteMultipartUpload><DaysAfterInitiation>7</DaysAfterInitiation></AbortIncompleteMultipartUpload>
+              </Rule>
+              <Rule>
+                <ID>scratch</ID>
+                <Filter><And><Prefix>tmp/</Prefix><Tag><Key>class</Key><Value>temp</Value></Tag></And></Filter>
+                <Status>Disabled</Status>
+                <Expiration><Date>2025-01-01T00:00:00Z</Date></Expiration>
+                <NoncurrentVersionExpiration><NoncurrentDays>5</NoncurrentDays></NoncurrentVersionExpiration>
+              </Rule>
+            </LifecycleConfiguration>"#;
+        let rules = parse_lifecycle_configuration(body).expect("parse");
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].enabled);
+        assert_eq!(rules[0].prefix, "logs/");
+        assert_eq!(rules[0].expiration_days, Some(30));
+        assert_eq!(rules[0].abort_incomplete_multipart_upload_days, Some(7));
+        assert!(!rules[1].enabled);
+        assert_eq!(rules[1].prefix, "tmp/");
+        assert_eq!(rules[1].tags["class"], "temp");
+        assert_eq!(
+            rules[1].expiration_date.map(|date| date.seconds),
+            Some(1_735_689_600)
+        );
+        assert_eq!(rules[1].noncurrent_version_expiration_days, Some(5));
+
+        let xml = lifecycle_configuration_xml(&rules);
+        assert!(xml.contains("<Date>2025-01-01T00:00:00.000Z</Date>"));
+        assert_eq!(
+            parse_lifecycle_configuration(xml.as_bytes()).expect("reparse"),
+            rules
+        );
+    }
+
+    #[test]
+    fn unsupported_or_malformed_rules_are_rejected() {
+        let code = |rule: &str| {
+            let body =
+                format!("<LifecycleConfiguration><Rule>{rule}</Rule></LifecycleConfiguration>");
+            parse_lifecycle_configuration(body.as_bytes())
+                .expect_err("rule should be rejected")
+                .0
+        };
+        assert_eq!(
+            code("<Status>Maybe</Status><Expiration><Days>1</Days></Expiration>"),
+            S3ErrorCode::MalformedXML
+        );
+        assert_eq!(
+            code("<Status>Enabled</Status><Transition><Days>1</Days><StorageClass>GLACIER</StorageClass></Transition>"),
+            S3ErrorCode::NotImplemented
+        );
+        assert_eq!(
+            code("<Status>Enabled</Status><Filter><ObjectSizeGreaterThan>10</ObjectSizeGreaterThan></Filter><Expiration><Days>1</Days></Expiration>"),
+            S3ErrorCode::NotImplemented
+        );
+        assert_eq!(
+            code("<Status>Enabled</Status><Expiration><Date>2025-01-01T12:00:00Z</Date></Expiration>"),
+            S3ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            parse_lifecycle_configuration(b"<LifecycleConfiguration><Rule>")
+                .expect_err("truncated")
+                .0,
+            S3ErrorCode::MalformedXML
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use coldstore_common::config::GatewayConfig;
+use coldstore_common::config::{self, GatewayConfig};
 use tracing::info;
 
 #[tokio::main]
@@ -11,9 +11,11 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    info!("启动 ColdStore S3 Gateway...");
+    let Some(config) = config::load_from_args::<GatewayConfig>()? else {
+        return Ok(());
+    };
 
-    let config = GatewayConfig::default();
+    info!("启动 ColdStore S3 Gateway...");
 
     coldstore_gateway::run(config).await
 }
@@ -0,0 +1,804 @@
+//! 桶策略 (Bucket Policy) 解析与授权
+//!
+//! 支持 IAM 策略语言的子集:
+//!   - `Effect`: `Allow` / `Deny`
+//!   - `Principal`: `"*"` 或 `{"AWS": ["<user_id>", ...]}`，匹配认证后的用户 ID
+//!   - `Action`: `s3:GetObject` `s3:PutObject` `s3:DeleteObject` `s3:RestoreObject`
+//!     `s3:ListBucket` 等，支持 `*` / `?` 通配
+//!   - `Resource`: `arn:aws:s3:::<bucket>` 或 `arn:aws:s3:::<bucket>/<key 模式>`，
+//!     只能引用策略所属的桶
+//!   - `Condition`: `StringEquals` `StringNotEquals` `StringLike` `StringNotLike`
+//!     `IpAddress` `NotIpAddress`，条件键 `aws:SourceIp` `aws:username`
+//!     `aws:userid` `s3:prefix`
+//!
+//! 评估顺序: 显式 Deny > 显式 Allow > 默认规则 (桶所有者允许，其他人拒绝)。
+//! 没有所有者的旧桶默认对所有已认证用户开放。
+//!
+//! 策略在 Gateway 转发请求前执行；RestoreObject 每次都会触发磁带挂载，
+//! 通常应通过策略收紧到少数用户或来源网段。
+
+use crate::auth::Principal;
+use crate::handler::{grpc_status_to_s3_response, s3_error_response};
+use crate::protocol::{is_restore_request, S3ErrorCode};
+use crate::GatewayState;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+const ARN_PREFIX: &str = "arn:aws:s3:::";
+const SUPPORTED_VERSIONS: &[&str] = &["2012-10-17", "2008-10-17"];
+
+/// 需要授权的 S3 操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    GetObject,
+    PutObject,
+    DeleteObject,
+    RestoreObject,
+    ListBucket,
+    DeleteBucket,
+    GetBucketPolicy,
+    PutBucketPolicy,
+    DeleteBucketPolicy,
+    GetLifecycleConfiguration,
+    PutLifecycleConfiguration,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::GetObject => "s3:GetObject",
+            Action::PutObject => "s3:PutObject",
+            Action::DeleteObject => "s3:DeleteObject",
+            Action::RestoreObject => "s3:RestoreObject",
+            Action::ListBucket => "s3:ListBucket",
+            Action::DeleteBucket => "s3:DeleteBucket",
+            Action::GetBucketPolicy => "s3:GetBucketPolicy",
+            Action::PutBucketPolicy => "s3:PutBucketPolicy",
+            Action::DeleteBucketPolicy => "s3:DeleteBucketPolicy",
+            Action::GetLifecycleConfiguration => "s3:GetLifecycleConfiguration",
+            // 与 AWS 一致，删除生命周期配置也归入 s3:PutLifecycleConfiguration
+            Action::PutLifecycleConfiguration => "s3:PutLifecycleConfiguration",
+        }
+    }
+
+    /// 策略管理操作始终允许桶所有者执行，避免写错策略后无法恢复
+    fn manages_policy(&self) -> bool {
+        matches!(
+            self,
+            Action::GetBucketPolicy | Action::PutBucketPolicy | Action::DeleteBucketPolicy
+        )
+    }
+}
+
+/// 单次请求的授权上下文
+#[derive(Debug, Clone)]
+pub struct RequestContext<'a> {
+    pub action: Action,
+    pub bucket: &'a str,
+    pub key: Option<&'a str>,
+    pub principal: Option<&'a Principal>,
+    pub source_ip: Option<IpAddr>,
+    /// ListBucket 的 `prefix` 查询参数 (条件键 `s3:prefix`)
+    pub prefix: Option<&'a str>,
+}
+
+impl RequestContext<'_> {
+    fn resource(&self) -> String {
+        match self.key {
+            Some(key) => format!("{ARN_PREFIX}{}/{key}", self.bucket),
+            None => format!("{ARN_PREFIX}{}", self.bucket),
+        }
+    }
+
+    fn user_id(&self) -> Option<&str> {
+        self.principal.map(|principal| principal.owner.as_str())
+    }
+
+    fn condition_value(&self, key: &str) -> Option<String> {
+        match key {
+            "aws:sourceip" => self.source_ip.map(|ip| ip.to_string()),
+            "aws:username" | "aws:userid" => self.user_id().map(str::to_owned),
+            "s3:prefix" => self.prefix.map(str::to_owned),
+            _ => None,
+        }
+    }
+}
+
+/// 策略评估结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Deny,
+    /// 没有语句匹配，交给默认规则
+    NotApplicable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyError(pub String);
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+fn malformed(message: impl Into<String>) -> PolicyError {
+    PolicyError(message.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConditionOperator {
+    StringEquals,
+    StringNotEquals,
+    StringLike,
+    StringNotLike,
+    IpAddress,
+    NotIpAddress,
+}
+
+impl ConditionOperator {
+    fn parse(name: &str) -> Result<Self, PolicyError> {
+        match name {
+            "StringEquals" => Ok(Self::StringEquals),
+            "StringNotEquals" => Ok(Self::StringNotEquals),
+            "StringLike" => Ok(Self::StringLike),
+            "StringNotLike" => Ok(Self::StringNotLike),
+            "IpAddress" => Ok(Self::IpAddress),
+            "NotIpAddress" => Ok(Self::NotIpAddress),
+            other => Err(malformed(format!(
+                "unsupported condition operator: {other}"
+            ))),
+        }
+    }
+
+    fn negated(&self) -> bool {
+        matches!(
+            self,
+            Self::StringNotEquals | Self::StringNotLike | Self::NotIpAddress
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    operator: ConditionOperator,
+    /// 小写的条件键
+    key: String,
+    values: Vec<String>,
+}
+
+impl Condition {
+    fn matches(&self, ctx: &RequestContext<'_>) -> bool {
+        // 请求中不存在的条件键: 肯定型运算符不匹配，否定型运算符匹配
+        let Some(actual) = ctx.condition_value(&self.key) else {
+            return self.operator.negated();
+        };
+        let any = match self.operator {
+            ConditionOperator::StringEquals | ConditionOperator::StringNotEquals => {
+                self.values.contains(&actual)
+            }
+            ConditionOperator::StringLike | ConditionOperator::StringNotLike => self
+                .values
+                .iter()
+                .any(|pattern| wildcard_match(pattern, &actual)),
+            ConditionOperator::IpAddress | ConditionOperator::NotIpAddress => {
+                match actual.parse::<IpAddr>() {
+                    Ok(ip) => self.values.iter().any(|cidr| cidr_contains(cidr, ip)),
+                    Err(_) => false,
+                }
+            }
+        };
+        any != self.operator.negated()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Statement {
+    effect: Effect,
+    /// 用户 ID 模式，`*` 匹配任意主体
+    principals: Vec<String>,
+    /// 小写的操作模式
+    actions: Vec<String>,
+    resources: Vec<String>,
+    conditions: Vec<Condition>,
+}
+
+impl Statement {
+    fn matches(&self, ctx: &RequestContext<'_>) -> bool {
+        let principal_matches = self.principals.iter().any(|pattern| {
+            pattern == "*"
+                || ctx
+                    .user_id()
+                    .is_some_and(|user_id| wildcard_match(pattern, user_id))
+        });
+        let action = ctx.action.as_str().to_ascii_lowercase();
+        let resource = ctx.resource();
+        principal_matches
+            && self
+                .actions
+                .iter()
+                .any(|pattern| wildcard_match(pattern, &action))
+            && self
+                .resources
+                .iter()
+                .any(|pattern| wildcard_match(pattern, &resource))
+            && self
+                .conditions
+                .iter()
+                .all(|condition| condition.matches(ctx))
+    }
+}
+
+/// 已校验的桶策略
+#[derive(Debug, Clone)]
+pub struct BucketPolicy {
+    statements: Vec<Statement>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPrincipal {
+    Wildcard(String),
+    Map(BTreeMap<String, OneOrMany<String>>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawPolicy {
+    version: Option<String>,
+    #[allow(dead_code)]
+    id: Option<String>,
+    statement: OneOrMany<RawStatement>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawStatement {
+    #[allow(dead_code)]
+    sid: Option<String>,
+    effect: String,
+    principal: Option<RawPrincipal>,
+    action: OneOrMany<String>,
+    resource: OneOrMany<String>,
+    condition: Option<BTreeMap<String, BTreeMap<String, OneOrMany<String>>>>,
+}
+
+impl BucketPolicy {
+    /// 解析并校验 `bucket` 的策略文档；错误映射为 S3 `MalformedPolicy`
+    pub fn parse(bucket: &str, document: &str) -> Result<Self, PolicyError> {
+        let raw: RawPolicy = serde_json::from_str(document)
+            .map_err(|err| malformed(format!("invalid policy document: {err}")))?;
+        if let Some(version) = raw.version.as_deref() {
+            if !SUPPORTED_VERSIONS.contains(&version) {
+                return Err(malformed(format!("unsupported policy version: {version}")));
+            }
+        }
+        let statements = raw
+            .statement
+            .into_vec()
+            .into_iter()
+            .map(|statement| compile_statement(bucket, statement))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { statements })
+    }
+
+    pub fn evaluate(&self, ctx: &RequestContext<'_>) -> Decision {
+        let mut decision = Decision::NotApplicable;
+        for statement in self.statements.iter().filter(|s| s.matches(ctx)) {
+            match statement.effect {
+                Effect::Deny => return Decision::Deny,
+                Effect::Allow => decision = Decision::Allow,
+            }
+        }
+        decision
+    }
+}
+
+fn compile_statement(bucket: &str, raw: RawStatement) -> Result<Statement, PolicyError> {
+    let effect = match raw.effect.as_str() {
+        "Allow" => Effect::Allow,
+        "Deny" => Effect::Deny,
+        other => return Err(malformed(format!("invalid effect: {other}"))),
+    };
+
+    let principals = match raw.principal {
+        None => return Err(malformed("statement is missing Principal")),
+        Some(RawPrincipal::Wildcard(value)) if value == "*" => vec!["*".to_string()],
+        Some(RawPrincipal::Wildcard(value)) => {
+            return Err(malformed(format!("invalid principal: {value}")))
+        }
+        Some(RawPrincipal::Map(map)) => {
+            let mut principals = Vec::new();
+            for (kind, values) in map {
+                if kind != "AWS" {
+                    return Err(malformed(format!("unsupported principal type: {kind}")));
+                }
+                principals.extend(values.into_vec());
+            }
+            principals
+        }
+    };
+    if principals.is_empty() {
+        return Err(malformed("statement has no principals"));
+    }
+
+    let actions = raw
+        .action
+        .into_vec()
+        .into_iter()
+        .map(|action| {
+            if action == "*" || action.to_ascii_lowercase().starts_with("s3:") {
+                Ok(action.to_ascii_lowercase())
+            } else {
+                Err(malformed(format!("unsupported action: {action}")))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let bucket_arn = format!("{ARN_PREFIX}{bucket}");
+    let resources = raw.resource.into_vec();
+    for resource in &resources {
+        let in_bucket = resource == &bucket_arn
+            || resource
+                .strip_prefix(&bucket_arn)
+                .is_some_and(|rest| rest.starts_with('/'));
+        if !in_bucket {
+            return Err(malformed(format!(
+                "policy has invalid resource: {resource}"
+            )));
+        }
+    }
+
+    let mut conditions = Vec::new();
+    for (operator, entries) in raw.condition.unwrap_or_default() {
+        let operator = ConditionOperator::parse(&operator)?;
+        for (key, values) in entries {
+            let values = values.into_vec();
+            if matches!(
+                operator,
+                ConditionOperator::IpAddress | ConditionOperator::NotIpAddress
+            ) {
+                if let Some(bad) = values.iter().find(|value| parse_cidr(value).is_none()) {
+                    return Err(malformed(format!("invalid CIDR block: {bad}")));
+                }
+            }
+            conditions.push(Condition {
+                operator,
+                key: key.to_ascii_lowercase(),
+                values,
+            });
+        }
+    }
+
+    Ok(Statement {
+        effect,
+        principals,
+        actions,
+        resources,
+        conditions,
+    })
+}
+
+/// 结合策略与桶所有者做出最终决定
+pub fn is_authorized(
+    policy: Option<&BucketPolicy>,
+    owner: Option<&str>,
+    ctx: &RequestContext<'_>,
+) -> bool {
+    let is_owner = owner.is_some_and(|owner| ctx.user_id() == Some(owner));
+    if is_owner && ctx.action.manages_policy() {
+        return true;
+    }
+    match policy.map_or(Decision::NotApplicable, |policy| policy.evaluate(ctx)) {
+        Decision::Deny => false,
+        Decision::Allow => true,
+        Decision::NotApplicable => owner.is_none() || is_owner,
+    }
+}
+
+/// IAM 风格通配: `*` 匹配任意长度，`?` 匹配单个字符
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            v = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn parse_cidr(value: &str) -> Option<(IpAddr, u8)> {
+    let (addr, len) = match value.split_once('/') {
+        Some((addr, len)) => (addr.parse::<IpAddr>().ok()?, len.parse::<u8>().ok()?),
+        None => {
+            let addr = value.parse::<IpAddr>().ok()?;
+            (addr, if addr.is_ipv4() { 32 } else { 128 })
+        }
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    (len <= max).then_some((addr, len))
+}
+
+fn cidr_contains(cidr: &str, ip: IpAddr) -> bool {
+    let Some((network, len)) = parse_cidr(cidr) else {
+        return false;
+    };
+    let ip = match (network, ip) {
+        (IpAddr::V4(_), IpAddr::V6(v6)) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => return false,
+        },
+        _ => ip,
+    };
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - len as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - len as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// 由请求方法与路径推导授权目标；返回 `None` 表示无需桶级授权
+/// (健康检查、ListBuckets、CreateBucket)
+fn request_target(method: &Method, path: &str, query: Option<&str>) -> Option<Target> {
+    let path = path.strip_prefix('/')?;
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => (bucket, Some(key)),
+        Some((bucket, _)) => (bucket, None),
+        None => (path, None),
+    };
+    if bucket.is_empty() || (bucket == "health" && key.is_none()) {
+        return None;
+    }
+    let has_param = |name: &str| {
+        query
+            .unwrap_or_default()
+            .split('&')
+            .any(|item| item == name || item.starts_with(&format!("{name}=")))
+    };
+    let action = match (key, method) {
+        (None, _) if has_param("policy") => match *method {
+            Method::GET => Action::GetBucketPolicy,
+            Method::PUT => Action::PutBucketPolicy,
+            Method::DELETE => Action::DeleteBucketPolicy,
+            _ => return None,
+        },
+        (None, _) if has_param("lifecycle") => match *method {
+            Method::GET => Action::GetLifecycleConfiguration,
+            Method::PUT | Method::DELETE => Action::PutLifecycleConfiguration,
+            _ => return None,
+        },
+        (None, &Method::GET) | (None, &Method::HEAD) => Action::ListBucket,
+        (None, &Method::DELETE) => Action::DeleteBucket,
+        (None, _) => return None,
+        (Some(_), &Method::GET) | (Some(_), &Method::HEAD) => Action::GetObject,
+        (Some(_), &Method::PUT) => Action::PutObject,
+        (Some(_), &Method::DELETE) => Action::DeleteObject,
+        (Some(_), &Method::POST) if is_restore_request(query) => Action::RestoreObject,
+        (Some(_), _) => return None,
+    };
+    let prefix = query.unwrap_or_default().split('&').find_map(|item| {
+        item.strip_prefix("prefix=")
+            .map(|value| percent_decode_str(value).decode_utf8_lossy().into_owned())
+    });
+    Some(Target {
+        action,
+        bucket: percent_decode_str(bucket).decode_utf8_lossy().into_owned(),
+        key: key.map(|key| percent_decode_str(key).decode_utf8_lossy().into_owned()),
+        prefix,
+    })
+}
+
+struct Target {
+    action: Action,
+    bucket: String,
+    key: Option<String>,
+    prefix: Option<String>,
+}
+
+/// 授权中间件，位于 `auth::authenticate` 之后
+///
+/// 认证关闭 (匿名模式) 时不执行桶策略。
+pub async fn authorize(
+    State(state): State<Arc<GatewayState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.auth.is_none() {
+        return next.run(request).await;
+    }
+    let Some(target) = request_target(
+        request.method(),
+        request.uri().path(),
+        request.uri().query(),
+    ) else {
+        return next.run(request).await;
+    };
+    let resource = request.uri().path().to_string();
+
+    let access = match state.backend.get_bucket_policy(&target.bucket).await {
+        Ok(access) => access,
+        // 桶不存在时交给 handler 返回 NoSuchBucket
+        Err(status) if status.code() == tonic::Code::NotFound => return next.run(request).await,
+        Err(status) => return grpc_status_to_s3_response(status, &resource),
+    };
+    let policy = match access.policy.as_deref() {
+        Some(document) => match BucketPolicy::parse(&target.bucket, document) {
+            Ok(policy) => Some(policy),
+            Err(err) => {
+                tracing::warn!("桶 {} 的策略无法解析，拒绝访问: {err}", target.bucket);
+                return s3_error_response(S3ErrorCode::AccessDenied, "Access Denied", &resource);
+            }
+        },
+        None => None,
+    };
+
+    let source_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let principal = request.extensions().get::<Principal>();
+    let ctx = RequestContext {
+        action: target.action,
+        bucket: &target.bucket,
+        key: target.key.as_deref(),
+        principal,
+        source_ip,
+        prefix: target.prefix.as_deref(),
+    };
+    if !is_authorized(policy.as_ref(), access.owner.as_deref(), &ctx) {
+        tracing::debug!(
+            "拒绝 {:?} 对 {} 执行 {}",
+            principal.map(|p| &p.owner),
+            ctx.resource(),
+            ctx.action.as_str()
+        );
+        return s3_error_response(S3ErrorCode::AccessDenied, "Access Denied", &resource);
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(user: &str) -> Principal {
+        Principal {
+            access_key_id: format!("AK-{user}"),
+            owner: user.into(),
+        }
+    }
+
+    fn ctx<'a>(action: Action, key: Option<&'a str>, who: &'a Principal) -> RequestContext<'a> {
+        RequestContext {
+            action,
+            bucket: "docs",
+            key,
+            principal: Some(who),
+            source_ip: None,
+            prefix: None,
+        }
+    }
+
+    const RESTORE_POLICY: &str = r#"{
+        "Version": "2012-10-17",
+        "Statement": [
+            {
+                "Sid": "PublicRead",
+                "Effect": "Allow",
+                "Principal": "*",
+                "Action": ["s3:GetObject", "s3:ListBucket"],
+                "Resource": ["arn:aws:s3:::docs", "arn:aws:s3:::docs/public/*"]
+            },
+            {
+                "Sid": "RestoreFromOps",
+                "Effect": "Allow",
+                "Principal": {"AWS": ["bob"]},
+                "Action": "s3:RestoreObject",
+                "Resource": "arn:aws:s3:::docs/*",
+                "Condition": {"IpAddress": {"aws:SourceIp": "10.0.0.0/8"}}
+            },
+            {
+                "Sid": "NoRestoreOutsideOps",
+                "Effect": "Deny",
+                "Principal": "*",
+                "Action": "s3:RestoreObject",
+                "Resource": "arn:aws:s3:::docs/*",
+                "Condition": {"NotIpAddress": {"aws:SourceIp": "10.0.0.0/8"}}
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn explicit_deny_overrides_owner_and_allow() {
+        let policy = BucketPolicy::parse("docs", RESTORE_POLICY).unwrap();
+        let alice = principal("alice");
+        let bob = principal("bob");
+
+        // 所有者在运维网段外也不能 restore
+        let mut request = ctx(Action::RestoreObject, Some("a.bin"), &alice);
+        request.source_ip = Some("192.168.1.5".parse().unwrap());
+        assert!(!is_authorized(Some(&policy), Some("alice"), &request));
+
+        let mut request = ctx(Action::RestoreObject, Some("a.bin"), &bob);
+        request.source_ip = Some("10.1.2.3".parse().unwrap());
+        assert!(is_authorized(Some(&policy), Some("alice"), &request));
+        request.source_ip = None;
+        assert!(!is_authorized(Some(&policy), Some("alice"), &request));
+
+        // 公开前缀可读，其它前缀只有所有者可读
+        let request = ctx(Action::GetObject, Some("public/readme.txt"), &bob);
+        assert!(is_authorized(Some(&policy), Some("alice"), &request));
+        let request = ctx(Action::GetObject, Some("private/key.pem"), &bob);
+        assert!(!is_authorized(Some(&policy), Some("alice"), &request));
+        let request = ctx(Action::GetObject, Some("private/key.pem"), &alice);
+        assert!(is_authorized(Some(&policy), Some("alice"), &request));
+        let request = ctx(Action::PutObject, Some("public/new.txt"), &bob);
+        assert!(!is_authorized(Some(&policy), Some("alice"), &request));
+    }
+
+    #[test]
+    fn default_rules_follow_bucket_owner() {
+        let alice = principal("alice");
+        let bob = principal("bob");
+        let request = ctx(Action::DeleteObject, Some("a"), &bob);
+        assert!(!is_authorized(None, Some("alice"), &request));
+        assert!(is_authorized(None, None, &request));
+
+        // 即使策略拒绝一切，所有者仍可修改策略
+        let deny_all = BucketPolicy::parse(
+            "docs",
+            r#"{"Statement":{"Effect":"Deny","Principal":"*","Action":"s3:*","Resource":["arn:aws:s3:::docs","arn:aws:s3:::docs/*"]}}"#,
+        )
+        .unwrap();
+        let request = ctx(Action::PutBucketPolicy, None, &alice);
+        assert!(is_authorized(Some(&deny_all), Some("alice"), &request));
+        let request = ctx(Action::ListBucket, None, &alice);
+        assert!(!is_authorized(Some(&deny_all), Some("alice"), &request));
+    }
+
+    #[test]
+    fn list_prefix_condition() {
+        let policy = BucketPolicy::parse(
+            "docs",
+            r#"{"Statement":[{"Effect":"Allow","Principal":{"AWS":"bob"},"Action":"s3:ListBucket",
+                "Resource":"arn:aws:s3:::docs","Condition":{"StringLike":{"s3:prefix":"home/bob/*"}}}]}"#,
+        )
+        .unwrap();
+        let bob = principal("bob");
+        let mut request = ctx(Action::ListBucket, None, &bob);
+        request.prefix = Some("home/bob/photos");
+        assert!(is_authorized(Some(&policy), Some("alice"), &request));
+        request.prefix = Some("home/alice/");
+        assert!(!is_authorized(Some(&policy), Some("alice"), &request));
+        request.prefix = None;
+        assert!(!is_authorized(Some(&policy), Some("alice"), &request));
+    }
+
+    #[test]
+    fn malformed_policies_are_rejected() {
+        for document in [
+            "not json",
+            r#"{"Version":"2099-01-01","Statement":[]}"#,
+            r#"{"Statement":[{"Effect":"Maybe","Principal":"*","Action":"s3:GetObject","Resource":"arn:aws:s3:::docs/*"}]}"#,
+            r#"{"Statement":[{"Effect":"Allow","Action":"s3:GetObject","Resource":"arn:aws:s3:::docs/*"}]}"#,
+            r#"{"Statement":[{"Effect":"Allow","Principal":"*","Action":"iam:PassRole","Resource":"arn:aws:s3:::docs/*"}]}"#,
+            r#"{"Statement":[{"Effect":"Allow","Principal":"*","Action":"s3:GetObject","Resource":"arn:aws:s3:::other/*"}]}"#,
+            r#"{"Statement":[{"Effect":"Allow","Principal":"*","Action":"s3:GetObject","Resource":"arn:aws:s3:::docs-2/*"}]}"#,
+            r#"{"Statement":[{"Effect":"Allow","Principal":"*","Action":"s3:GetObject","Resource":"arn:aws:s3:::docs/*","Condition":{"DateGreaterThan":{"aws:CurrentTime":"2020-01-01"}}}]}"#,
+            r#"{"Statement":[{"Effect":"Allow","Principal":"*","Action":"s3:GetObject","Resource":"arn:aws:s3:::docs/*","Condition":{"IpAddress":{"aws:SourceIp":"10.0.0.0/40"}}}]}"#,
+        ] {
+            assert!(
+                BucketPolicy::parse("docs", document).is_err(),
+                "{document} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn wildcard_and_cidr_matching() {
+        assert!(wildcard_match(
+            "arn:aws:s3:::docs/*",
+            "arn:aws:s3:::docs/a/b"
+        ));
+        assert!(wildcard_match("s3:*object", "s3:restoreobject"));
+        assert!(wildcard_match("log-????.txt", "log-2024.txt"));
+        assert!(!wildcard_match("log-????.txt", "log-24.txt"));
+        assert!(wildcard_match("a*b*c", "axxbyyc"));
+        assert!(!wildcard_match("a*b*c", "axxbyy"));
+
+        assert!(cidr_contains("10.0.0.0/8", "10.200.1.1".parse().unwrap()));
+        assert!(!cidr_contains("10.0.0.0/8", "11.0.0.1".parse().unwrap()));
+        assert!(cidr_contains("0.0.0.0/0", "8.8.8.8".parse().unwrap()));
+        assert!(cidr_contains("192.168.1.7", "192.168.1.7".parse().unwrap()));
+        assert!(cidr_contains(
+            "10.0.0.0/8",
+            "::ffff:10.1.1.1".parse().unwrap()
+        ));
+        assert!(cidr_contains(
+            "2001:db8::/32",
+            "2001:db8::1".parse().unwrap()
+        ));
+    }
+
+    #[test]
+    fn request_target_maps_routes_to_actions() {
+        let target = |method: Method, path: &str, query: Option<&str>| {
+            request_target(&method, path, query).map(|t| (t.action, t.bucket, t.key, t.prefix))
+        };
+        assert!(target(Method::GET, "/", None).is_none());
+        assert!(target(Method::GET, "/health", None).is_none());
+        assert!(target(Method::PUT, "/docs", None).is_none());
+        assert_eq!(
+            target(Method::GET, "/docs", Some("prefix=a%2Fb&max-keys=2")),
+            Some((Action::ListBucket, "docs".into(), None, Some("a/b".into())))
+        );
+        assert_eq!(
+            target(Method::PUT, "/docs", Some("policy")).map(|t| t.0),
+            Some(Action::PutBucketPolicy)
+        );
+        assert_eq!(
+            target(Method::DELETE, "/docs", Some("lifecycle")).map(|t| t.0),
+            Some(Action::PutLifecycleConfiguration)
+        );
+        assert_eq!(
+            target(Method::POST, "/docs/a%20b.txt", Some("restore")),
+            Some((
+                Action::RestoreObject,
+                "docs".into(),
+                Some("a b.txt".into()),
+                None
+            ))
+        );
+        assert!(target(Method::POST, "/docs/a", Some("uploads")).is_none());
+    }
+}
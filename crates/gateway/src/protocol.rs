@@ -2,10 +2,26 @@
 //!
 //! 职责:
 //!   - StorageClass 映射: 所有对象写入即 ColdPending
-//!   - RestoreObject 请求解析 (Days, Tier)
+//!   - RestoreObject 请求体解析 (`<RestoreRequest>` XML: Days, GlacierJobParameters/Tier, Description)
 //!   - x-amz-restore 响应头生成
-//!   - 错误码映射 (InvalidObjectState, RestoreAlreadyInProgress 等)
+//!   - 错误码映射: gRPC 状态码 + 结构化错误原因 (`ErrorDetail`) → S3 错误码
 //!   - GET 行为控制 (冷对象需先 Restore)
+//!   - Range 与条件请求头 (If-Match / If-None-Match / If-Modified-Since / If-Unmodified-Since)
+//!   - 客户端摘要校验 (Content-MD5 / x-amz-checksum-sha256)
+//!   - 用户元数据与对象标签的大小限制 (MetadataTooLarge, InvalidTag)
+//!   - 认证失败错误码 (AccessDenied, SignatureDoesNotMatch 等，见 `auth` 模块)
+//!   - 桶策略错误码 (MalformedPolicy, NoSuchBucketPolicy，见 `policy` 模块)
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use coldstore_proto::common::{ByteRange, ErrorReason, ObjectAttributes, RestoreTier};
+use md5::{Digest as _, Md5};
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use sha2::Sha256;
 
 /// S3 错误码
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -16,6 +32,31 @@ pub enum S3ErrorCode {
     NoSuchKey,
     NoSuchBucket,
     NotImplemented,
+    BadDigest,
+    InvalidDigest,
+    AccessDenied,
+    InvalidAccessKeyId,
+    SignatureDoesNotMatch,
+    RequestTimeTooSkewed,
+    XAmzContentSHA256Mismatch,
+    InvalidRequest,
+    MalformedPolicy,
+    NoSuchBucketPolicy,
+    MalformedXML,
+    InvalidArgument,
+    PreconditionFailed,
+    InvalidRange,
+    MetadataTooLarge,
+    InvalidTag,
+    BucketAlreadyExists,
+    BucketNotEmpty,
+    EntityTooLarge,
+    IncompleteBody,
+    OperationAborted,
+    SlowDown,
+    ServiceUnavailable,
+    InternalError,
+    NoSuchLifecycleConfiguration,
 }
 
 impl S3ErrorCode {
@@ -29,6 +70,31 @@ impl S3ErrorCode {
             S3ErrorCode::NoSuchKey => "NoSuchKey",
             S3ErrorCode::NoSuchBucket => "NoSuchBucket",
             S3ErrorCode::NotImplemented => "NotImplemented",
+            S3ErrorCode::BadDigest => "BadDigest",
+            S3ErrorCode::InvalidDigest => "InvalidDigest",
+            S3ErrorCode::AccessDenied => "AccessDenied",
+            S3ErrorCode::InvalidAccessKeyId => "InvalidAccessKeyId",
+            S3ErrorCode::SignatureDoesNotMatch => "SignatureDoesNotMatch",
+            S3ErrorCode::RequestTimeTooSkewed => "RequestTimeTooSkewed",
+            S3ErrorCode::XAmzContentSHA256Mismatch => "XAmzContentSHA256Mismatch",
+            S3ErrorCode::InvalidRequest => "InvalidRequest",
+            S3ErrorCode::MalformedPolicy => "MalformedPolicy",
+            S3ErrorCode::NoSuchBucketPolicy => "NoSuchBucketPolicy",
+            S3ErrorCode::MalformedXML => "MalformedXML",
+            S3ErrorCode::InvalidArgument => "InvalidArgument",
+            S3ErrorCode::PreconditionFailed => "PreconditionFailed",
+            S3ErrorCode::InvalidRange => "InvalidRange",
+            S3ErrorCode::MetadataTooLarge => "MetadataTooLarge",
+            S3ErrorCode::InvalidTag => "InvalidTag",
+            S3ErrorCode::BucketAlreadyExists => "BucketAlreadyExists",
+            S3ErrorCode::BucketNotEmpty => "BucketNotEmpty",
+            S3ErrorCode::EntityTooLarge => "EntityTooLarge",
+            S3ErrorCode::IncompleteBody => "IncompleteBody",
+            S3ErrorCode::OperationAborted => "OperationAborted",
+            S3ErrorCode::SlowDown => "SlowDown",
+            S3ErrorCode::ServiceUnavailable => "ServiceUnavailable",
+            S3ErrorCode::InternalError => "InternalError",
+            S3ErrorCode::NoSuchLifecycleConfiguration => "NoSuchLifecycleConfiguration",
         }
     }
 
@@ -40,6 +106,74 @@ impl S3ErrorCode {
             S3ErrorCode::NoSuchKey => 404,
             S3ErrorCode::NoSuchBucket => 404,
             S3ErrorCode::NotImplemented => 501,
+            S3ErrorCode::BadDigest => 400,
+            S3ErrorCode::InvalidDigest => 400,
+            S3ErrorCode::AccessDenied => 403,
+            S3ErrorCode::InvalidAccessKeyId => 403,
+            S3ErrorCode::SignatureDoesNotMatch => 403,
+            S3ErrorCode::RequestTimeTooSkewed => 403,
+            S3ErrorCode::XAmzContentSHA256Mismatch => 400,
+            S3ErrorCode::InvalidRequest => 400,
+            S3ErrorCode::MalformedPolicy => 400,
+            S3ErrorCode::NoSuchBucketPolicy => 404,
+            S3ErrorCode::MalformedXML => 400,
+            S3ErrorCode::InvalidArgument => 400,
+            S3ErrorCode::PreconditionFailed => 412,
+            S3ErrorCode::InvalidRange => 416,
+            S3ErrorCode::MetadataTooLarge => 400,
+            S3ErrorCode::InvalidTag => 400,
+            S3ErrorCode::BucketAlreadyExists => 409,
+            S3ErrorCode::BucketNotEmpty => 409,
+            S3ErrorCode::EntityTooLarge => 400,
+            S3ErrorCode::IncompleteBody => 400,
+            S3ErrorCode::OperationAborted => 409,
+            S3ErrorCode::SlowDown => 503,
+            S3ErrorCode::ServiceUnavailable => 503,
+            S3ErrorCode::InternalError => 500,
+            S3ErrorCode::NoSuchLifecycleConfiguration => 404,
+        }
+    }
+}
+
+/// 将后端返回的 gRPC Status 映射为 S3 错误码
+///
+/// 优先使用 Status details 中的 [`ErrorReason`]；没有时按状态码与资源路径
+/// (`/bucket` 或 `/bucket/key`) 推断。
+pub fn s3_error_code_for_status(status: &tonic::Status, resource: &str) -> S3ErrorCode {
+    if let Some(reason) = coldstore_common::error::error_reason(status) {
+        match reason {
+            ErrorReason::NoSuchBucket => return S3ErrorCode::NoSuchBucket,
+            ErrorReason::BucketAlreadyExists => return S3ErrorCode::BucketAlreadyExists,
+            ErrorReason::BucketNotEmpty => return S3ErrorCode::BucketNotEmpty,
+            ErrorReason::InvalidObjectState => return S3ErrorCode::InvalidObjectState,
+            ErrorReason::RestoreAlreadyInProgress => return S3ErrorCode::RestoreAlreadyInProgress,
+            ErrorReason::InvalidRange => return S3ErrorCode::InvalidRange,
+            ErrorReason::SlowDown => return S3ErrorCode::SlowDown,
+            ErrorReason::NoSuchLifecycleConfiguration => {
+                return S3ErrorCode::NoSuchLifecycleConfiguration
+            }
+            ErrorReason::Unspecified => {}
+        }
+    }
+    let is_object = resource.trim_start_matches('/').contains('/');
+    match status.code() {
+        tonic::Code::NotFound if is_object => S3ErrorCode::NoSuchKey,
+        tonic::Code::NotFound => S3ErrorCode::NoSuchBucket,
+        tonic::Code::AlreadyExists if is_object => S3ErrorCode::OperationAborted,
+        tonic::Code::AlreadyExists => S3ErrorCode::BucketAlreadyExists,
+        tonic::Code::FailedPrecondition if is_object => S3ErrorCode::InvalidObjectState,
+        tonic::Code::FailedPrecondition | tonic::Code::InvalidArgument => {
+            S3ErrorCode::InvalidRequest
+        }
+        tonic::Code::OutOfRange => S3ErrorCode::InvalidRange,
+        tonic::Code::PermissionDenied | tonic::Code::Unauthenticated => S3ErrorCode::AccessDenied,
+        tonic::Code::Aborted => S3ErrorCode::OperationAborted,
+        tonic::Code::ResourceExhausted => S3ErrorCode::SlowDown,
+        tonic::Code::Unavailable | tonic::Code::DeadlineExceeded => S3ErrorCode::ServiceUnavailable,
+        tonic::Code::Unimplemented => S3ErrorCode::NotImplemented,
+        tonic::Code::DataLoss => S3ErrorCode::BadDigest,
+        tonic::Code::Ok | tonic::Code::Cancelled | tonic::Code::Unknown | tonic::Code::Internal => {
+            S3ErrorCode::InternalError
         }
     }
 }
@@ -49,15 +183,19 @@ pub struct S3ErrorResponse<'a> {
     pub code: S3ErrorCode,
     pub message: &'a str,
     pub resource: &'a str,
+    pub request_id: &'a str,
+    pub host_id: &'a str,
 }
 
 impl<'a> S3ErrorResponse<'a> {
     pub fn to_xml(&self) -> String {
         format!(
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message><Resource>{}</Resource></Error>",
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{}</Code><Message>{}</Message><Resource>{}</Resource><RequestId>{}</RequestId><HostId>{}</HostId></Error>",
             self.code.as_str(),
-            self.message,
-            self.resource,
+            escape(self.message),
+            escape(self.resource),
+            escape(self.request_id),
+            escape(self.host_id),
         )
     }
 }
@@ -80,10 +218,563 @@ pub fn is_restore_request(query: Option<&str>) -> bool {
         .any(|item| item == "restore" || item.starts_with("restore="))
 }
 
+/// 解冻副本保留天数的上限
+pub const MAX_RESTORE_DAYS: u32 = 36_500;
+
+/// 解析后的 `POST ?restore` 请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreRequest {
+    pub days: u32,
+    pub tier: RestoreTier,
+    pub description: Option<String>,
+}
+
+/// RestoreObject 请求体不合法，附带返回给客户端的错误码与说明
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreRequestError {
+    pub code: S3ErrorCode,
+    pub message: String,
+}
+
+impl RestoreRequestError {
+    fn new(code: S3ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RestoreRequestXml {
+    days: Option<u32>,
+    description: Option<String>,
+    glacier_job_parameters: Option<GlacierJobParametersXml>,
+    #[serde(rename = "Type")]
+    kind: Option<String>,
+    tier: Option<String>,
+    output_location: Option<IgnoredAny>,
+    select_parameters: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GlacierJobParametersXml {
+    tier: String,
+}
+
+/// 解析 `POST ?restore` 的 `<RestoreRequest>` 请求体
+///
+/// 只支持原地解冻：`Days` 必填，取值 1..=[`MAX_RESTORE_DAYS`]；Tier 取自
+/// `GlacierJobParameters`，缺省为 Standard。SELECT 类型与 `OutputLocation`
+/// 返回 `NotImplemented`，结构错误返回 `MalformedXML`。
+pub fn parse_restore_request(body: &[u8]) -> Result<RestoreRequest, RestoreRequestError> {
+    let malformed = |message: String| RestoreRequestError::new(S3ErrorCode::MalformedXML, message);
+    let document =
+        std::str::from_utf8(body).map_err(|_| malformed("request body must be UTF-8".into()))?;
+    if root_element(document).as_deref() != Some("RestoreRequest") {
+        return Err(malformed("root element must be RestoreRequest".into()));
+    }
+    let raw: RestoreRequestXml = quick_xml::de::from_str(document)
+        .map_err(|err| malformed(format!("invalid RestoreRequest: {err}")))?;
+
+    if raw.kind.is_some() || raw.select_parameters.is_some() || raw.output_location.is_some() {
+        return Err(RestoreRequestError::new(
+            S3ErrorCode::NotImplemented,
+            "only in-place restores of archived objects are supported",
+        ));
+    }
+    let days = raw
+        .days
+        .ok_or_else(|| malformed("Days is required".into()))?;
+    if !(1..=MAX_RESTORE_DAYS).contains(&days) {
+        return Err(RestoreRequestError::new(
+            S3ErrorCode::InvalidArgument,
+            format!("Days must be between 1 and {MAX_RESTORE_DAYS}"),
+        ));
+    }
+    let tier = match raw
+        .glacier_job_parameters
+        .map(|params| params.tier)
+        .or(raw.tier)
+        .as_deref()
+        .map(str::trim)
+    {
+        None | Some("Standard") => RestoreTier::Standard,
+        Some("Expedited") => RestoreTier::Expedited,
+        Some("Bulk") => RestoreTier::Bulk,
+        Some(other) => return Err(malformed(format!("unknown restore tier {other:?}"))),
+    };
+    Ok(RestoreRequest {
+        days,
+        tier,
+        description: raw.description.filter(|text| !text.is_empty()),
+    })
+}
+
+/// 文档根元素的名称 (忽略 XML 声明与命名空间前缀)
+fn root_element(document: &str) -> Option<String> {
+    let mut reader = quick_xml::Reader::from_str(document);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(element) | Event::Empty(element) => {
+                let name = element.local_name();
+                return Some(String::from_utf8_lossy(name.as_ref()).into_owned());
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// `Range` 请求头解析结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeRequest {
+    /// 没有可用的区间 (语法不合法时按 RFC 7233 忽略该请求头)，返回整个对象
+    Full,
+    /// 可满足的区间，按偏移排序且互不重叠、互不相邻
+    Partial(Vec<ByteRange>),
+    /// 所有区间都超出对象范围，返回 416
+    Unsatisfiable,
+}
+
+/// 单个 `Range` 请求头允许的区间数，超过时按 RFC 7233 忽略该请求头
+pub const MAX_RANGES: usize = 16;
+
+/// 按对象长度 `size` 解析 `Range: bytes=...` 请求头
+///
+/// 支持 `a-b`、`a-` 与后缀形式 `-n`，多个区间以逗号分隔；超出对象范围的区间被丢弃，
+/// 重叠或相邻的区间合并为一个。区间数超过 [`MAX_RANGES`] 时返回整个对象。
+pub fn parse_range(header: &str, size: u64) -> RangeRequest {
+    let Some(specs) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if specs.split(',').count() > MAX_RANGES {
+        return RangeRequest::Full;
+    }
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let Some((first, last)) = spec.trim().split_once('-') else {
+            return RangeRequest::Full;
+        };
+        let range = match (first.trim(), last.trim()) {
+            ("", "") => return RangeRequest::Full,
+            ("", suffix) => {
+                let Ok(suffix) = suffix.parse::<u64>() else {
+                    return RangeRequest::Full;
+                };
+                (suffix > 0 && size > 0).then(|| {
+                    let length = suffix.min(size);
+                    ByteRange {
+                        offset: size - length,
+                        length,
+                    }
+                })
+            }
+            (first, last) => {
+                let Ok(first) = first.parse::<u64>() else {
+                    return RangeRequest::Full;
+                };
+                let last = if last.is_empty() {
+                    u64::MAX
+                } else {
+                    match last.parse::<u64>() {
+                        Ok(last) if last >= first => last,
+                        _ => return RangeRequest::Full,
+                    }
+                };
+                (first < size).then(|| ByteRange {
+                    offset: first,
+                    length: last.min(size - 1) - first + 1,
+                })
+            }
+        };
+        ranges.extend(range);
+    }
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(coalesce_ranges(ranges))
+    }
+}
+
+/// 按偏移排序并合并重叠或相邻的区间
+fn coalesce_ranges(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|range| range.offset);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.offset <= last.offset + last.length => {
+                let end = (range.offset + range.length).max(last.offset + last.length);
+                last.length = end - last.offset;
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// 生成 `Content-Range` 响应头的值
+pub fn format_content_range(range: &ByteRange, size: u64) -> String {
+    format!(
+        "bytes {}-{}/{size}",
+        range.offset,
+        range.offset + range.length - 1
+    )
+}
+
+/// 条件请求头的原始值
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preconditions<'a> {
+    pub if_match: Option<&'a str>,
+    pub if_none_match: Option<&'a str>,
+    pub if_modified_since: Option<&'a str>,
+    pub if_unmodified_since: Option<&'a str>,
+}
+
+/// 条件请求的判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreconditionOutcome {
+    Proceed,
+    /// 304 Not Modified
+    NotModified,
+    /// 412 Precondition Failed
+    Failed,
+}
+
+impl Preconditions<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none()
+            && self.if_none_match.is_none()
+            && self.if_modified_since.is_none()
+            && self.if_unmodified_since.is_none()
+    }
+
+    /// 按 RFC 7232 第 6 节的顺序判定
+    ///
+    /// If-Match 存在时忽略 If-Unmodified-Since，If-None-Match 存在时忽略
+    /// If-Modified-Since；无法解析的日期与未知的 `last_modified` 不参与判定。
+    pub fn evaluate(
+        &self,
+        etag: &str,
+        last_modified: Option<DateTime<Utc>>,
+    ) -> PreconditionOutcome {
+        let modified_after = |header: &str| {
+            let since = parse_http_date(header)?;
+            Some(last_modified?.timestamp() > since.timestamp())
+        };
+        match self.if_match {
+            Some(if_match) if !etag_matches(if_match, etag) => return PreconditionOutcome::Failed,
+            Some(_) => {}
+            None => {
+                if self.if_unmodified_since.and_then(modified_after) == Some(true) {
+                    return PreconditionOutcome::Failed;
+                }
+            }
+        }
+        match self.if_none_match {
+            Some(if_none_match) if etag_matches(if_none_match, etag) => {
+                PreconditionOutcome::NotModified
+            }
+            Some(_) => PreconditionOutcome::Proceed,
+            None if self.if_modified_since.and_then(modified_after) == Some(false) => {
+                PreconditionOutcome::NotModified
+            }
+            None => PreconditionOutcome::Proceed,
+        }
+    }
+}
+
+/// 逗号分隔的 ETag 列表中是否有与 `etag` 相同的值 (`*` 匹配任意对象，忽略弱校验前缀)
+fn etag_matches(list: &str, etag: &str) -> bool {
+    let normalize = |tag: &str| {
+        let tag = tag.trim();
+        tag.strip_prefix("W/")
+            .unwrap_or(tag)
+            .trim_matches('"')
+            .to_string()
+    };
+    let etag = normalize(etag);
+    list.split(',')
+        .any(|candidate| candidate.trim() == "*" || normalize(candidate) == etag)
+}
+
+/// 解析 HTTP 日期 (IMF-fixdate，如 `Sun, 06 Nov 1994 08:49:37 GMT`)
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// 格式化为 HTTP 日期，用于 `Last-Modified` 响应头
+pub fn format_http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// 单次 PUT 的对象大小上限 (与 S3 一致为 5 GiB)，超出返回 EntityTooLarge
+pub const MAX_PUT_OBJECT_SIZE: usize = 5 << 30;
+
+/// `x-amz-meta-*` 名称与值的总字节数上限
+pub const MAX_USER_METADATA_BYTES: usize = 2 * 1024;
+/// 每个对象的标签数上限
+pub const MAX_OBJECT_TAGS: usize = 10;
+const MAX_TAG_KEY_CHARS: usize = 128;
+const MAX_TAG_VALUE_CHARS: usize = 256;
+
+/// 按 S3 的限制校验 PUT 请求携带的用户元数据与标签
+pub fn validate_object_attributes(
+    attributes: &ObjectAttributes,
+) -> Result<(), (S3ErrorCode, String)> {
+    let metadata_bytes: usize = attributes
+        .user_metadata
+        .iter()
+        .map(|(key, value)| key.len() + value.len())
+        .sum();
+    if metadata_bytes > MAX_USER_METADATA_BYTES {
+        return Err((
+            S3ErrorCode::MetadataTooLarge,
+            format!(
+                "user metadata is {metadata_bytes} bytes; the limit is {MAX_USER_METADATA_BYTES}"
+            ),
+        ));
+    }
+    if attributes.tags.len() > MAX_OBJECT_TAGS {
+        return Err((
+            S3ErrorCode::InvalidTag,
+            format!("an object can have at most {MAX_OBJECT_TAGS} tags"),
+        ));
+    }
+    for (key, value) in &attributes.tags {
+        if key.is_empty()
+            || key.chars().count() > MAX_TAG_KEY_CHARS
+            || value.chars().count() > MAX_TAG_VALUE_CHARS
+        {
+            return Err((S3ErrorCode::InvalidTag, format!("invalid tag {key:?}")));
+        }
+    }
+    Ok(())
+}
+
+/// 校验 `Content-MD5` 请求头（base64 编码的 128 位 MD5）
+pub fn verify_content_md5(header: &str, body: &[u8]) -> Result<(), S3ErrorCode> {
+    let expected = BASE64
+        .decode(header.trim())
+        .map_err(|_| S3ErrorCode::InvalidDigest)?;
+    if expected.len() != 16 {
+        return Err(S3ErrorCode::InvalidDigest);
+    }
+    if Md5::digest(body).as_slice() == expected.as_slice() {
+        Ok(())
+    } else {
+        Err(S3ErrorCode::BadDigest)
+    }
+}
+
+/// 校验 `x-amz-checksum-sha256` 请求头（base64 编码的 SHA-256）
+pub fn verify_checksum_sha256(header: &str, body: &[u8]) -> Result<(), S3ErrorCode> {
+    let expected = BASE64
+        .decode(header.trim())
+        .map_err(|_| S3ErrorCode::InvalidDigest)?;
+    if expected.len() != 32 {
+        return Err(S3ErrorCode::InvalidDigest);
+    }
+    if Sha256::digest(body).as_slice() == expected.as_slice() {
+        Ok(())
+    } else {
+        Err(S3ErrorCode::BadDigest)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn range(offset: u64, length: u64) -> ByteRange {
+        ByteRange { offset, length }
+    }
+
+    #[test]
+    fn range_header_forms_are_resolved_against_object_size() {
+        assert_eq!(
+            parse_range("bytes=0-4", 11),
+            RangeRequest::Partial(vec![range(0, 5)])
+        );
+        assert_eq!(
+            parse_range("bytes=6-", 11),
+            RangeRequest::Partial(vec![range(6, 5)])
+        );
+        assert_eq!(
+            parse_range("bytes=-3", 11),
+            RangeRequest::Partial(vec![range(8, 3)])
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 11),
+            RangeRequest::Partial(vec![range(0, 11)])
+        );
+        assert_eq!(
+            parse_range("bytes=8-100", 11),
+            RangeRequest::Partial(vec![range(8, 3)])
+        );
+        assert_eq!(
+            parse_range("bytes=0-0, 20-30, 10-", 11),
+            RangeRequest::Partial(vec![range(0, 1), range(10, 1)])
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_and_malformed_ranges() {
+        assert_eq!(parse_range("bytes=11-", 11), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 11), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=5-2", 11), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=a-b", 11), RangeRequest::Full);
+        assert_eq!(parse_range("items=0-4", 11), RangeRequest::Full);
+        assert_eq!(format_content_range(&range(6, 5), 11), "bytes 6-10/11");
+    }
+
+    #[test]
+    fn overlapping_ranges_are_coalesced_and_range_count_is_capped() {
+        assert_eq!(
+            parse_range("bytes=6-8, 0-2, 3-4, 7-10", 11),
+            RangeRequest::Partial(vec![range(0, 5), range(6, 5)])
+        );
+        assert_eq!(
+            parse_range("bytes=0-0, 0-0, 0-0", 11),
+            RangeRequest::Partial(vec![range(0, 1)])
+        );
+        assert_eq!(
+            parse_range("bytes=-2, 0-1", 11),
+            RangeRequest::Partial(vec![range(0, 2), range(9, 2)])
+        );
+
+        let capped = vec!["0-0"; MAX_RANGES].join(",");
+        assert_eq!(
+            parse_range(&format!("bytes={capped}"), 11),
+            RangeRequest::Partial(vec![range(0, 1)])
+        );
+        let too_many = vec!["0-0"; MAX_RANGES + 1].join(",");
+        assert_eq!(
+            parse_range(&format!("bytes={too_many}"), 11),
+            RangeRequest::Full
+        );
+    }
+
+    #[test]
+    fn preconditions_follow_rfc7232_order() {
+        let modified = parse_http_date("Wed, 01 Jan 2025 12:00:00 GMT");
+        let before = "Tue, 31 Dec 2024 00:00:00 GMT";
+        let after = "Thu, 02 Jan 2025 00:00:00 GMT";
+        let evaluate = |conditions: Preconditions| conditions.evaluate("\"etag-1\"", modified);
+
+        assert_eq!(
+            evaluate(Preconditions::default()),
+            PreconditionOutcome::Proceed
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_match: Some("\"other\", W/\"etag-1\""),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Proceed
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_match: Some("\"other\""),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Failed
+        );
+        // If-Match 成立时忽略 If-Unmodified-Since
+        assert_eq!(
+            evaluate(Preconditions {
+                if_match: Some("*"),
+                if_unmodified_since: Some(before),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Proceed
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_unmodified_since: Some(before),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Failed
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_none_match: Some("etag-1"),
+                ..Default::default()
+            }),
+            PreconditionOutcome::NotModified
+        );
+        // If-None-Match 不成立时忽略 If-Modified-Since
+        assert_eq!(
+            evaluate(Preconditions {
+                if_none_match: Some("\"other\""),
+                if_modified_since: Some(after),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Proceed
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_modified_since: Some(after),
+                ..Default::default()
+            }),
+            PreconditionOutcome::NotModified
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_modified_since: Some(before),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Proceed
+        );
+        assert_eq!(
+            evaluate(Preconditions {
+                if_modified_since: Some("not a date"),
+                ..Default::default()
+            }),
+            PreconditionOutcome::Proceed
+        );
+    }
+
+    #[test]
+    fn oversized_metadata_and_invalid_tags_are_rejected() {
+        let mut attributes = ObjectAttributes::default();
+        attributes
+            .user_metadata
+            .insert("note".into(), "x".repeat(MAX_USER_METADATA_BYTES - 4));
+        assert!(validate_object_attributes(&attributes).is_ok());
+        attributes.user_metadata.insert("more".into(), "y".into());
+        assert_eq!(
+            validate_object_attributes(&attributes).unwrap_err().0,
+            S3ErrorCode::MetadataTooLarge
+        );
+
+        let mut attributes = ObjectAttributes::default();
+        for i in 0..=MAX_OBJECT_TAGS {
+            attributes.tags.insert(format!("k{i}"), "v".into());
+        }
+        assert_eq!(
+            validate_object_attributes(&attributes).unwrap_err().0,
+            S3ErrorCode::InvalidTag
+        );
+        attributes.tags.clear();
+        attributes.tags.insert("k".into(), "v".repeat(257));
+        assert_eq!(
+            validate_object_attributes(&attributes).unwrap_err().0,
+            S3ErrorCode::InvalidTag
+        );
+    }
+
+    #[test]
+    fn http_dates_round_trip() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").expect("valid date");
+        assert_eq!(format_http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
     #[test]
     fn restore_header_formats_completed_state() {
         let value = format_restore_header(false, Some("Fri, 28 Feb 2025 12:00:00 GMT"));
@@ -98,15 +789,202 @@ mod tests {
         assert!(!is_restore_request(Some("foo=bar")));
     }
 
+    #[test]
+    fn restore_request_xml_is_parsed_from_sdk_body() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?>
+<RestoreRequest xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Days>7</Days>
+  <Description>quarterly audit</Description>
+  <GlacierJobParameters><Tier>Bulk</Tier></GlacierJobParameters>
+</RestoreRequest>"#;
+        assert_eq!(
+            parse_restore_request(body),
+            Ok(RestoreRequest {
+                days: 7,
+                tier: RestoreTier::Bulk,
+                description: Some("quarterly audit".into()),
+            })
+        );
+
+        let minimal = parse_restore_request(b"<RestoreRequest><Days>1</Days></RestoreRequest>");
+        assert_eq!(minimal.map(|r| r.tier), Ok(RestoreTier::Standard));
+    }
+
+    #[test]
+    fn invalid_restore_requests_are_rejected() {
+        let code = |body: &str| parse_restore_request(body.as_bytes()).unwrap_err().code;
+        assert_eq!(code(""), S3ErrorCode::MalformedXML);
+        assert_eq!(
+            code("<Restore><Days>1</Days></Restore>"),
+            S3ErrorCode::MalformedXML
+        );
+        assert_eq!(
+            code("<RestoreRequest></RestoreRequest>"),
+            S3ErrorCode::MalformedXML
+        );
+        assert_eq!(
+            code("<RestoreRequest><Days>soon</Days></RestoreRequest>"),
+            S3ErrorCode::MalformedXML
+        );
+        assert_eq!(
+            code("<RestoreRequest><Days>0</Days></RestoreRequest>"),
+            S3ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            code("<RestoreRequest><Days>1</Days><GlacierJobParameters><Tier>Fast</Tier></GlacierJobParameters></RestoreRequest>"),
+            S3ErrorCode::MalformedXML
+        );
+        assert_eq!(
+            code("<RestoreRequest><Type>SELECT</Type><Tier>Standard</Tier></RestoreRequest>"),
+            S3ErrorCode::NotImplemented
+        );
+        assert_eq!(
+            code("<RestoreRequest><Days>1</Days><OutputLocation><S3><BucketName>out</BucketName></S3></OutputLocation></RestoreRequest>"),
+            S3ErrorCode::NotImplemented
+        );
+    }
+
     #[test]
     fn s3_error_xml_contains_code_and_resource() {
         let xml = S3ErrorResponse {
             code: S3ErrorCode::NotImplemented,
             message: "operation is not implemented",
             resource: "/docs/readme.txt",
+            request_id: "4442587FB7D0A2F9",
+            host_id: "host",
         }
         .to_xml();
         assert!(xml.contains("<Code>NotImplemented</Code>"));
         assert!(xml.contains("<Resource>/docs/readme.txt</Resource>"));
+        assert!(xml.contains("<RequestId>4442587FB7D0A2F9</RequestId><HostId>host</HostId>"));
+    }
+
+    #[test]
+    fn s3_error_xml_escapes_message_and_resource() {
+        let xml = S3ErrorResponse {
+            code: S3ErrorCode::NoSuchKey,
+            message: "no object <a&b>",
+            resource: "/docs/a&b<c>.txt",
+            request_id: "",
+            host_id: "",
+        }
+        .to_xml();
+        assert!(xml.contains("<Message>no object &lt;a&amp;b&gt;</Message>"));
+        assert!(xml.contains("<Resource>/docs/a&amp;b&lt;c&gt;.txt</Resource>"));
+    }
+
+    #[test]
+    fn grpc_status_maps_to_s3_error_code() {
+        use coldstore_common::error::status_with_reason;
+
+        let cases = [
+            (
+                status_with_reason(
+                    tonic::Code::AlreadyExists,
+                    "exists",
+                    ErrorReason::BucketAlreadyExists,
+                ),
+                "/docs",
+                S3ErrorCode::BucketAlreadyExists,
+            ),
+            (
+                status_with_reason(
+                    tonic::Code::FailedPrecondition,
+                    "not empty",
+                    ErrorReason::BucketNotEmpty,
+                ),
+                "/docs",
+                S3ErrorCode::BucketNotEmpty,
+            ),
+            (
+                status_with_reason(
+                    tonic::Code::NotFound,
+                    "bucket not found",
+                    ErrorReason::NoSuchBucket,
+                ),
+                "/docs/a.txt",
+                S3ErrorCode::NoSuchBucket,
+            ),
+            (
+                status_with_reason(
+                    tonic::Code::AlreadyExists,
+                    "in progress",
+                    ErrorReason::RestoreAlreadyInProgress,
+                ),
+                "/docs/a.txt",
+                S3ErrorCode::RestoreAlreadyInProgress,
+            ),
+            (
+                tonic::Status::failed_precondition("must be restored"),
+                "/docs/a.txt",
+                S3ErrorCode::InvalidObjectState,
+            ),
+            (
+                tonic::Status::not_found("missing"),
+                "/docs/a.txt",
+                S3ErrorCode::NoSuchKey,
+            ),
+            (
+                tonic::Status::not_found("missing"),
+                "/docs",
+                S3ErrorCode::NoSuchBucket,
+            ),
+            (
+                tonic::Status::out_of_range("range"),
+                "/docs/a.txt",
+                S3ErrorCode::InvalidRange,
+            ),
+            (
+                tonic::Status::resource_exhausted("busy"),
+                "/docs/a.txt",
+                S3ErrorCode::SlowDown,
+            ),
+            (
+                tonic::Status::unavailable("down"),
+                "/docs",
+                S3ErrorCode::ServiceUnavailable,
+            ),
+            (
+                tonic::Status::internal("boom"),
+                "/docs",
+                S3ErrorCode::InternalError,
+            ),
+        ];
+        for (status, resource, expected) in cases {
+            assert_eq!(
+                s3_error_code_for_status(&status, resource),
+                expected,
+                "{status:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn content_md5_is_verified_against_body() {
+        // md5("hello world") = 5eb63bbbe01eeed093cb22bb8f5acdc3
+        let header = BASE64.encode(Md5::digest(b"hello world"));
+        assert_eq!(verify_content_md5(&header, b"hello world"), Ok(()));
+        assert_eq!(
+            verify_content_md5(&header, b"hello there"),
+            Err(S3ErrorCode::BadDigest)
+        );
+        assert_eq!(
+            verify_content_md5("not base64!", b"hello world"),
+            Err(S3ErrorCode::InvalidDigest)
+        );
+        assert_eq!(
+            verify_content_md5(&BASE64.encode(b"short"), b"hello world"),
+            Err(S3ErrorCode::InvalidDigest)
+        );
+    }
+
+    #[test]
+    fn checksum_sha256_is_verified_against_body() {
+        let header = BASE64.encode(Sha256::digest(b"hello world"));
+        assert_eq!(verify_checksum_sha256(&header, b"hello world"), Ok(()));
+        assert_eq!(
+            verify_checksum_sha256(&header, b"hello"),
+            Err(S3ErrorCode::BadDigest)
+        );
     }
 }
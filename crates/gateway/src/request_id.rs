@@ -0,0 +1,56 @@
+//! S3 请求标识
+//!
+//! 每个请求分配一个 `x-amz-request-id`，连同标识本网关实例的 `x-amz-id-2`
+//! (HostId) 写入响应头；错误响应体中的 `<RequestId>` / `<HostId>` 取自同一值，
+//! 便于按日志排查客户端报告的错误。
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::sync::OnceLock;
+
+pub const REQUEST_ID_HEADER: &str = "x-amz-request-id";
+pub const HOST_ID_HEADER: &str = "x-amz-id-2";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 中间件: 在请求处理期间登记请求 ID，并写入响应头
+pub async fn assign(request: Request, next: Next) -> Response {
+    let request_id = new_request_id();
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .await;
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static(REQUEST_ID_HEADER),
+        HeaderValue::from_str(&request_id).unwrap(),
+    );
+    headers.insert(
+        HeaderName::from_static(HOST_ID_HEADER),
+        HeaderValue::from_str(host_id()).unwrap(),
+    );
+    response
+}
+
+/// 当前请求的 ID；不在 [`assign`] 范围内 (如单元测试直接构造响应) 时为空
+pub fn current() -> String {
+    REQUEST_ID
+        .try_with(|request_id| request_id.clone())
+        .unwrap_or_default()
+}
+
+/// 本网关实例的 HostId，进程内固定
+pub fn host_id() -> &'static str {
+    static HOST_ID: OnceLock<String> = OnceLock::new();
+    HOST_ID.get_or_init(|| BASE64.encode(uuid::Uuid::new_v4().as_bytes()))
+}
+
+/// 与 S3 相同的 16 位大写十六进制
+fn new_request_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_ascii_uppercase()
+}
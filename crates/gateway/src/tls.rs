@@ -0,0 +1,113 @@
+//! S3 端点 TLS 终止与证书热加载
+//!
+//! 证书/私钥路径来自 `GatewayConfig.tls`。后台任务按 `reload_interval_secs`
+//! 轮询文件 (修改时间, 大小)，变化后重新加载；新证书无效时保留旧证书继续服务，
+//! 下一轮再试。
+
+use anyhow::Result;
+use axum_server::tls_rustls::RustlsConfig;
+use coldstore_common::tls::TlsConfig;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 按配置构造 rustls 配置
+pub fn load(config: &TlsConfig) -> Result<RustlsConfig> {
+    Ok(RustlsConfig::from_config(Arc::new(
+        config.rustls_server_config()?,
+    )))
+}
+
+/// 重新读取证书文件并替换正在使用的配置
+pub fn reload_certificates(config: &TlsConfig, rustls: &RustlsConfig) -> Result<()> {
+    rustls.reload_from_config(Arc::new(config.rustls_server_config()?));
+    Ok(())
+}
+
+/// 监视证书文件变化并热加载，永不返回
+pub async fn watch_certificates(config: TlsConfig, rustls: RustlsConfig) {
+    let interval = Duration::from_secs(config.reload_interval_secs.max(1));
+    let mut loaded = config.files_fingerprint();
+    loop {
+        tokio::time::sleep(interval).await;
+        let current = config.files_fingerprint();
+        if current == loaded {
+            continue;
+        }
+        match reload_certificates(&config, &rustls) {
+            Ok(()) => {
+                info!("已重新加载 TLS 证书 {}", config.cert_path);
+                loaded = current;
+            }
+            // 证书与私钥可能尚未写完，保留旧证书
+            Err(err) => warn!("重新加载 TLS 证书失败，继续使用旧证书: {err:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coldstore_common::tls::testing::TestPki;
+    use rustls::pki_types::ServerName;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get_health(addr: std::net::SocketAddr, ca_pem: &[u8]) -> std::io::Result<String> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut &ca_pem[..]) {
+            roots.add(cert?).map_err(std::io::Error::other)?;
+        }
+        let client = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .map_err(std::io::Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client));
+        let tcp = tokio::net::TcpStream::connect(addr).await?;
+        let mut stream = connector
+            .connect(ServerName::try_from("localhost").unwrap(), tcp)
+            .await?;
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn https_endpoint_serves_and_reloads_rotated_certificates() {
+        let pki = TestPki::generate();
+        let config = pki.server_config(false);
+        let rustls = load(&config).unwrap();
+        tokio::spawn(watch_certificates(config, rustls.clone()));
+
+        let app = axum::Router::new().route("/health", axum::routing::get(|| async { "OK" }));
+        let handle = axum_server::Handle::new();
+        let server = axum_server::bind_rustls("127.0.0.1:0".parse().unwrap(), rustls)
+            .handle(handle.clone())
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+        let addr = handle.listening().await.unwrap();
+
+        let old_ca = pki.ca_pem();
+        let response = get_health(addr, &old_ca).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        pki.regenerate();
+        let new_ca = pki.ca_pem();
+        let mut reloaded = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if get_health(addr, &new_ca).await.is_ok() {
+                reloaded = true;
+                break;
+            }
+        }
+        assert!(reloaded, "rotated certificate was not picked up");
+        assert!(get_health(addr, &old_ca).await.is_err());
+        handle.shutdown();
+    }
+}
@@ -1,5 +1,7 @@
+use coldstore_common::config::TaskRetryConfig;
 use coldstore_proto::common;
 use coldstore_proto::metadata::*;
+use prost_types::Timestamp;
 
 #[derive(Debug, Clone)]
 pub enum MetadataCommand {
@@ -8,16 +10,78 @@ pub enum MetadataCommand {
     UpdateStorageClass(UpdateStorageClassRequest),
     UpdateArchiveLocation(UpdateArchiveLocationRequest),
     UpdateRestoreStatus(UpdateRestoreStatusRequest),
+    UpdateStagingLocation(UpdateStagingLocationRequest),
     CreateBucket(common::BucketInfo),
     DeleteBucket(DeleteBucketRequest),
+    PutBucketPolicy(common::BucketPolicy),
+    DeleteBucketPolicy(DeleteBucketPolicyRequest),
+    PutBucketLifecycle(common::BucketLifecycle),
+    DeleteBucketLifecycle(DeleteBucketLifecycleRequest),
+    /// 删除生命周期规则在 `now` 判定过期的对象，解冻副本到期的对象转为 RestoreExpired
+    ApplyLifecycle {
+        now: Timestamp,
+    },
     PutArchiveBundle(common::ArchiveBundle),
     UpdateArchiveBundleStatus(UpdateArchiveBundleStatusRequest),
+    UpdateBundleCopy(UpdateBundleCopyRequest),
     PutArchiveTask(common::ArchiveTask),
     UpdateArchiveTask(common::ArchiveTask),
+    /// 任务处于 Pending 或已由 `worker_id` 持有时认领为 InProgress，租约到 `lease_expire_at`；
+    /// 退避中 (`next_attempt_at` 晚于 `now`) 的任务不能认领
+    ClaimArchiveTask {
+        id: String,
+        worker_id: u64,
+        lease_expire_at: Timestamp,
+        now: Timestamp,
+    },
+    /// 持有者回报失败：可重试且未超过 `retry.max_retries` 时退避后重新排队，否则标记为 Failed
+    FailArchiveTask {
+        request: FailTaskRequest,
+        now: Timestamp,
+        retry: TaskRetryConfig,
+    },
     PutRecallTask(common::RecallTask),
     UpdateRecallTask(common::RecallTask),
+    /// 同 [`MetadataCommand::ClaimArchiveTask`]，对象的 restore_status 一并进入 InProgress
+    ClaimRecallTask {
+        id: String,
+        worker_id: u64,
+        lease_expire_at: Timestamp,
+        now: Timestamp,
+    },
+    /// 同 [`MetadataCommand::FailArchiveTask`]，对象的 restore_status 随任务变化
+    FailRecallTask {
+        request: FailTaskRequest,
+        now: Timestamp,
+        retry: TaskRetryConfig,
+    },
+    /// 租约早于 `now` 的进行中任务按 `retry` 退避后重新排队，重试耗尽的标记为 Failed
+    ExpireTaskLeases {
+        now: Timestamp,
+        retry: TaskRetryConfig,
+    },
     PutTape(common::TapeInfo),
     UpdateTape(common::TapeInfo),
+    BeginTapeReclamation(TapeReclamationRequest),
+    /// 磁带已无有效对象：解除其上的归档包副本，磁带清零后转为 Scratch
+    CompleteTapeReclamation(TapeReclamationRequest),
+    LabelTape(LabelTapeRequest),
+    ImportTape(ImportTapeRequest),
+    MoveTapeToPool(MoveTapeToPoolRequest),
+    /// 磁带上已无对象引用的数据：解除归档包副本后转为 Retired
+    RetireTape(RetireTapeRequest),
+    /// 将 Scratch 磁带 `tape_id` 分配到 `request.pool`；由 leader 在提交前选定磁带，
+    /// 应用时磁带已不在 Scratch 则拒绝
+    AllocateScratchTape {
+        request: AllocateScratchTapeRequest,
+        tape_id: String,
+    },
+    /// 磁带移出带库后转为 Offline；只能读该磁带的待处理取回转为 WaitingForMedia
+    MarkTapeOffline(TapeLocationRequest),
+    /// 磁带回到带库后转为 Online；等待该磁带的取回恢复为 Pending
+    MarkTapeOnline(TapeLocationRequest),
+    /// 整盘校验完成：记录校验时间并累加错误数
+    RecordTapeVerification(RecordTapeVerificationRequest),
     RegisterSchedulerWorker(common::SchedulerWorkerInfo),
     DeregisterSchedulerWorker(DeregisterWorkerRequest),
     RegisterCacheWorker(common::CacheWorkerInfo),
@@ -25,5 +89,25 @@ pub enum MetadataCommand {
     RegisterTapeWorker(common::TapeWorkerInfo),
     DeregisterTapeWorker(DeregisterWorkerRequest),
     UpdateWorkerStatus(UpdateWorkerStatusRequest),
-    Heartbeat(HeartbeatRequest),
+    /// 最近心跳早于 `stale_before` (Unix 秒) 的 Worker 标记为 NodeOffline，
+    /// Scheduler Worker 进行中的任务重新排队；提交前心跳已恢复则不生效
+    ExpireWorker {
+        worker_type: common::WorkerType,
+        node_id: u64,
+        stale_before: i64,
+    },
+    /// Scheduler Worker 的心跳同时将其持有的进行中任务续约到 `task_lease_expire_at`
+    Heartbeat {
+        request: HeartbeatRequest,
+        task_lease_expire_at: Timestamp,
+    },
+    CreateUser(common::UserInfo),
+    SetUserEnabled(SetUserEnabledRequest),
+    PutAccessKey(common::AccessKeyInfo),
+    /// 签发 `replacement` 并吊销 `access_key_id`，二者原子生效
+    RotateAccessKey {
+        access_key_id: String,
+        replacement: common::AccessKeyInfo,
+    },
+    RevokeAccessKey(RevokeAccessKeyRequest),
 }
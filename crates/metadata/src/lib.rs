@@ -1,4 +1,6 @@
 pub mod command;
+pub mod lifecycle;
+pub mod secrets;
 pub mod service;
 pub mod state_machine;
 
@@ -9,19 +11,64 @@ pub mod raft_storage;
 
 use anyhow::Result;
 use coldstore_common::config::MetadataConfig;
+use std::sync::Arc;
+use std::time::Duration;
 use tonic::transport::Server;
-use tracing::info;
+use tracing::{info, warn};
 
 pub async fn run(config: MetadataConfig) -> Result<()> {
     let addr = config.listen.parse()?;
 
-    let metadata_service = service::MetadataServiceImpl::new(&config).await?;
+    let metadata_service = Arc::new(service::MetadataServiceImpl::new(&config).await?);
 
     info!("Metadata 节点 {} 启动在 {}", config.node_id, addr);
 
-    Server::builder()
+    // 本节点即 leader (单节点提交路径)，负责过期失联的 Worker 与任务租约
+    let sweeper = metadata_service.clone();
+    let sweep_interval = Duration::from_secs(
+        config
+            .worker_timeout_secs
+            .min(config.task_lease_secs)
+            .div_ceil(2)
+            .max(1),
+    );
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(sweep_interval);
+        loop {
+            ticker.tick().await;
+            if let Err(status) = sweeper.expire_stale_workers().await {
+                warn!("Worker 过期检查失败: {}", status.message());
+            }
+            if let Err(status) = sweeper.expire_task_leases().await {
+                warn!("任务租约过期检查失败: {}", status.message());
+            }
+        }
+    });
+
+    let lifecycle = metadata_service.clone();
+    let lifecycle_interval = Duration::from_secs(config.lifecycle_interval_secs.max(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(lifecycle_interval);
+        loop {
+            ticker.tick().await;
+            if let Err(status) = lifecycle.apply_lifecycle().await {
+                warn!("生命周期评估失败: {}", status.message());
+            }
+        }
+    });
+
+    let mut server = Server::builder();
+    if let Some(tls) = config.tls.server_tls_config()? {
+        server = server.tls_config(tls)?;
+    }
+    server
+        .add_service(
+            coldstore_proto::metadata::metadata_service_server::MetadataServiceServer::from_arc(
+                metadata_service.clone(),
+            ),
+        )
         .add_service(
-            coldstore_proto::metadata::metadata_service_server::MetadataServiceServer::new(
+            coldstore_proto::metadata::metadata_internal_service_server::MetadataInternalServiceServer::from_arc(
                 metadata_service,
             ),
         )
@@ -0,0 +1,387 @@
+//! 桶生命周期规则的校验与评估
+//!
+//! Metadata leader 按 `lifecycle_interval_secs` 周期调用
+//! [`crate::service::MetadataServiceImpl::apply_lifecycle`]；评估结果通过
+//! [`MetadataCommand::ApplyLifecycle`] 提交，状态机以命令携带的 `now` 重新评估，
+//! 各副本因此删除同一批对象。
+//!
+//! 除规则判定过期的对象外，解冻副本到期 (`restore_expire_at`) 的对象在同一轮
+//! 评估中转为 RestoreExpired，不依赖桶是否配置了生命周期。
+//!
+//! [`MetadataCommand::ApplyLifecycle`]: crate::command::MetadataCommand::ApplyLifecycle
+
+use crate::state_machine::{timestamp_sort_key, MetadataState, ObjectKey};
+use coldstore_proto::common;
+use prost_types::Timestamp;
+use std::collections::{BTreeMap, HashSet};
+use tonic::Status;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+/// 每个桶的规则数上限 (与 S3 一致)
+pub(crate) const MAX_LIFECYCLE_RULES: usize = 1_000;
+const MAX_RULE_ID_LEN: usize = 255;
+
+/// 一轮生命周期评估的结果 (均按对象键排序)
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct LifecycleSweep {
+    /// 规则判定过期、需要删除的对象版本
+    pub expired_objects: Vec<ObjectKey>,
+    /// 解冻副本已过期、restore_status 转为 RestoreExpired 的对象
+    pub expired_restores: Vec<ObjectKey>,
+}
+
+impl LifecycleSweep {
+    pub fn is_empty(&self) -> bool {
+        self.expired_objects.is_empty() && self.expired_restores.is_empty()
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub(crate) fn validate(lifecycle: &common::BucketLifecycle) -> Result<(), Status> {
+    if lifecycle.rules.is_empty() {
+        return Err(Status::invalid_argument(
+            "lifecycle configuration must contain at least one rule",
+        ));
+    }
+    if lifecycle.rules.len() > MAX_LIFECYCLE_RULES {
+        return Err(Status::invalid_argument(format!(
+            "lifecycle configuration can have at most {MAX_LIFECYCLE_RULES} rules"
+        )));
+    }
+    let mut ids = HashSet::new();
+    for rule in &lifecycle.rules {
+        if rule.id.len() > MAX_RULE_ID_LEN {
+            return Err(Status::invalid_argument(format!(
+                "lifecycle rule ID is longer than {MAX_RULE_ID_LEN} characters"
+            )));
+        }
+        if !rule.id.is_empty() && !ids.insert(rule.id.as_str()) {
+            return Err(Status::invalid_argument(format!(
+                "duplicate lifecycle rule ID: {}",
+                rule.id
+            )));
+        }
+        if rule.expiration_days.is_none()
+            && rule.expiration_date.is_none()
+            && rule.noncurrent_version_expiration_days.is_none()
+            && rule.abort_incomplete_multipart_upload_days.is_none()
+        {
+            return Err(Status::invalid_argument(format!(
+                "lifecycle rule {:?} has no action",
+                rule.id
+            )));
+        }
+        if rule.expiration_days.is_some() && rule.expiration_date.is_some() {
+            return Err(Status::invalid_argument(format!(
+                "lifecycle rule {:?} sets both expiration days and date",
+                rule.id
+            )));
+        }
+        let zero_days = [
+            rule.expiration_days,
+            rule.noncurrent_version_expiration_days,
+            rule.abort_incomplete_multipart_upload_days,
+        ]
+        .contains(&Some(0));
+        if zero_days {
+            return Err(Status::invalid_argument(format!(
+                "lifecycle rule {:?} must use a positive number of days",
+                rule.id
+            )));
+        }
+        if let Some(date) = &rule.expiration_date {
+            if date.nanos != 0 || date.seconds.rem_euclid(SECONDS_PER_DAY) != 0 {
+                return Err(Status::invalid_argument(format!(
+                    "lifecycle rule {:?} expiration date must be midnight UTC",
+                    rule.id
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 按 `now` 评估全部桶的生命周期规则与解冻副本过期
+pub(crate) fn evaluate(state: &MetadataState, now: &Timestamp) -> LifecycleSweep {
+    let now = timestamp_sort_key(&Some(*now));
+    let mut sweep = LifecycleSweep::default();
+
+    // 同一键的各版本按 updated_at 排序，最后一个为当前版本 (与 find_object 一致)
+    let mut versions: BTreeMap<(&str, &str), Vec<(&ObjectKey, &common::ObjectMetadata)>> =
+        BTreeMap::new();
+    for (key, object) in &state.objects {
+        versions
+            .entry((object.bucket.as_str(), object.key.as_str()))
+            .or_default()
+            .push((key, object));
+    }
+
+    for ((bucket, _), mut versions) in versions {
+        versions.sort_by_key(|(key, object)| (timestamp_sort_key(&object.updated_at), *key));
+        let rules: Vec<&common::LifecycleRule> = state
+            .bucket_lifecycles
+            .get(bucket)
+            .map(|lifecycle| lifecycle.rules.iter().filter(|rule| rule.enabled).collect())
+            .unwrap_or_default();
+
+        let current = versions.len() - 1;
+        for (index, (key, object)) in versions.iter().enumerate() {
+            let matching = rules.iter().filter(|rule| rule_matches(rule, object));
+            let expired = if index == current {
+                matching
+                    .filter_map(|rule| current_version_expiry(rule, object))
+                    .any(|expiry| expiry <= now)
+            } else {
+                // 非当前版本从被下一个版本取代时开始计时
+                let superseded_at = versions[index + 1]
+                    .1
+                    .created_at
+                    .or(versions[index + 1].1.updated_at);
+                matching
+                    .filter_map(|rule| rule.noncurrent_version_expiration_days)
+                    .any(|days| after_days(&superseded_at, days) <= now)
+            };
+            if expired {
+                sweep.expired_objects.push((*key).clone());
+            } else if restore_expired(object, now) {
+                sweep.expired_restores.push((*key).clone());
+            }
+        }
+    }
+    sweep.expired_objects.sort();
+    sweep.expired_restores.sort();
+    sweep
+}
+
+fn rule_matches(rule: &common::LifecycleRule, object: &common::ObjectMetadata) -> bool {
+    let tags = object
+        .attributes
+        .as_ref()
+        .map(|attributes| &attributes.tags);
+    object.key.starts_with(&rule.prefix)
+        && rule.tags.iter().all(|(name, value)| {
+            tags.and_then(|tags| tags.get(name))
+                .is_some_and(|tag| tag == value)
+        })
+}
+
+fn current_version_expiry(
+    rule: &common::LifecycleRule,
+    object: &common::ObjectMetadata,
+) -> Option<(i64, i32)> {
+    if let Some(date) = &rule.expiration_date {
+        return Some(timestamp_sort_key(&Some(*date)));
+    }
+    let created_at = object.created_at.or(object.updated_at);
+    rule.expiration_days
+        .map(|days| after_days(&created_at, days))
+}
+
+/// `since` 加上 `days` 天后取整到下一个 UTC 零点 (S3 的过期时间算法)
+fn after_days(since: &Option<Timestamp>, days: u32) -> (i64, i32) {
+    let (seconds, nanos) = timestamp_sort_key(since);
+    let end = seconds + i64::from(days) * SECONDS_PER_DAY + i64::from(nanos > 0);
+    let midnight = end - end.rem_euclid(SECONDS_PER_DAY);
+    if midnight == end {
+        (end, 0)
+    } else {
+        (midnight + SECONDS_PER_DAY, 0)
+    }
+}
+
+fn restore_expired(object: &common::ObjectMetadata, now: (i64, i32)) -> bool {
+    object.restore_status == Some(common::RestoreStatus::RestoreCompleted as i32)
+        && object
+            .restore_expire_at
+            .as_ref()
+            .is_some_and(|expire_at| timestamp_sort_key(&Some(*expire_at)) <= now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MetadataCommand;
+    use crate::state_machine::MetadataStateMachine;
+
+    const DAY: i64 = SECONDS_PER_DAY;
+    /// 2025-01-01T00:00:00Z
+    const JAN_1: i64 = 1_735_689_600;
+
+    fn ts(seconds: i64) -> Timestamp {
+        Timestamp { seconds, nanos: 0 }
+    }
+
+    fn rule(id: &str, prefix: &str) -> common::LifecycleRule {
+        common::LifecycleRule {
+            id: id.into(),
+            enabled: true,
+            prefix: prefix.into(),
+            ..Default::default()
+        }
+    }
+
+    fn object(key: &str, version_id: Option<&str>, created_at: i64) -> common::ObjectMetadata {
+        common::ObjectMetadata {
+            bucket: "docs".into(),
+            key: key.into(),
+            version_id: version_id.map(str::to_string),
+            created_at: Some(ts(created_at)),
+            ..Default::default()
+        }
+    }
+
+    fn machine(rules: Vec<common::LifecycleRule>) -> MetadataStateMachine {
+        let mut machine = MetadataStateMachine::default();
+        machine
+            .apply(MetadataCommand::CreateBucket(common::BucketInfo {
+                name: "docs".into(),
+                ..Default::default()
+            }))
+            .unwrap();
+        machine
+            .apply(MetadataCommand::PutBucketLifecycle(
+                common::BucketLifecycle {
+                    bucket: "docs".into(),
+                    rules,
+                    updated_at: None,
+                },
+            ))
+            .unwrap();
+        machine
+    }
+
+    /// PutObject 以当前时间覆盖 updated_at，测试直接写入状态以控制版本顺序
+    fn insert(machine: &mut MetadataStateMachine, mut object: common::ObjectMetadata) {
+        object.updated_at = object.created_at;
+        machine
+            .apply(MetadataCommand::PutObject(object.clone()))
+            .unwrap();
+        let key = ObjectKey::new(
+            object.bucket.clone(),
+            object.key.clone(),
+            object.version_id.clone(),
+        );
+        let mut state = std::mem::take(machine).into_state();
+        state.objects.insert(key, object);
+        *machine = MetadataStateMachine::new(state);
+    }
+
+    fn keys(keys: &[ObjectKey]) -> Vec<String> {
+        keys.iter().map(|key| format!("{key:?}")).collect()
+    }
+
+    #[test]
+    fn expiration_days_round_up_to_the_next_midnight() {
+        let mut machine = machine(vec![common::LifecycleRule {
+            expiration_days: Some(1),
+            ..rule("logs", "logs/")
+        }]);
+        // 1 月 1 日 10:00 写入，1 天后取整到 1 月 3 日零点
+        insert(&mut machine, object("logs/a", None, JAN_1 + 10 * 3600));
+        insert(&mut machine, object("keep/a", None, JAN_1));
+
+        assert!(evaluate(machine.state(), &ts(JAN_1 + 2 * DAY - 1)).is_empty());
+        let sweep = evaluate(machine.state(), &ts(JAN_1 + 2 * DAY));
+        assert_eq!(keys(&sweep.expired_objects).len(), 1);
+        assert!(keys(&sweep.expired_objects)[0].contains("logs/a"));
+
+        machine
+            .apply(MetadataCommand::ApplyLifecycle {
+                now: ts(JAN_1 + 2 * DAY),
+            })
+            .unwrap();
+        let remaining: Vec<_> = machine.state().objects().map(|o| o.key.as_str()).collect();
+        assert_eq!(remaining, ["keep/a"]);
+        assert_eq!(machine.state().bucket("docs").unwrap().object_count, 1);
+    }
+
+    #[test]
+    fn tag_filters_and_noncurrent_versions_are_honoured() {
+        let mut machine = machine(vec![
+            common::LifecycleRule {
+                expiration_date: Some(ts(JAN_1 + 10 * DAY)),
+                tags: [("class".to_string(), "temp".to_string())].into(),
+                ..rule("temp", "")
+            },
+            common::LifecycleRule {
+                noncurrent_version_expiration_days: Some(2),
+                ..rule("versions", "")
+            },
+        ]);
+        let mut tagged = object("scratch", None, JAN_1);
+        tagged.attributes = Some(common::ObjectAttributes {
+            tags: [("class".to_string(), "temp".to_string())].into(),
+            ..Default::default()
+        });
+        insert(&mut machine, tagged);
+        insert(&mut machine, object("untagged", None, JAN_1));
+        insert(&mut machine, object("report", Some("v1"), JAN_1));
+        insert(&mut machine, object("report", Some("v2"), JAN_1 + 5 * DAY));
+
+        let sweep = evaluate(machine.state(), &ts(JAN_1 + 7 * DAY));
+        let expired = keys(&sweep.expired_objects);
+        assert_eq!(expired.len(), 1);
+        assert!(expired[0].contains("v1"));
+
+        let sweep = evaluate(machine.state(), &ts(JAN_1 + 10 * DAY));
+        let expired = keys(&sweep.expired_objects);
+        assert_eq!(expired.len(), 2);
+        assert!(expired.iter().any(|key| key.contains("scratch")));
+        assert!(!expired.iter().any(|key| key.contains("v2")));
+    }
+
+    #[test]
+    fn expired_restores_become_restore_expired() {
+        let mut machine = machine(vec![common::LifecycleRule {
+            enabled: false,
+            expiration_days: Some(1),
+            ..rule("disabled", "")
+        }]);
+        let mut restored = object("restored", None, JAN_1);
+        restored.restore_status = Some(common::RestoreStatus::RestoreCompleted as i32);
+        restored.restore_expire_at = Some(ts(JAN_1 + 3 * DAY));
+        insert(&mut machine, restored);
+
+        assert!(evaluate(machine.state(), &ts(JAN_1 + 2 * DAY)).is_empty());
+        let now = ts(JAN_1 + 3 * DAY);
+        let sweep = evaluate(machine.state(), &now);
+        assert!(sweep.expired_objects.is_empty());
+        assert_eq!(sweep.expired_restores.len(), 1);
+
+        machine
+            .apply(MetadataCommand::ApplyLifecycle { now })
+            .unwrap();
+        let object = machine.state().objects().next().unwrap();
+        assert_eq!(
+            object.restore_status,
+            Some(common::RestoreStatus::RestoreExpired as i32)
+        );
+    }
+
+    #[test]
+    fn invalid_configurations_are_rejected() {
+        let lifecycle = |rules| common::BucketLifecycle {
+            bucket: "docs".into(),
+            rules,
+            updated_at: None,
+        };
+        assert!(validate(&lifecycle(vec![])).is_err());
+        assert!(validate(&lifecycle(vec![rule("empty", "")])).is_err());
+        assert!(validate(&lifecycle(vec![common::LifecycleRule {
+            expiration_days: Some(0),
+            ..rule("zero", "")
+        }]))
+        .is_err());
+        assert!(validate(&lifecycle(vec![common::LifecycleRule {
+            expiration_date: Some(ts(JAN_1 + 1)),
+            ..rule("not-midnight", "")
+        }]))
+        .is_err());
+        let expire = common::LifecycleRule {
+            expiration_days: Some(30),
+            ..rule("dup", "")
+        };
+        assert!(validate(&lifecycle(vec![expire.clone(), expire.clone()])).is_err());
+        assert!(validate(&lifecycle(vec![expire])).is_ok());
+    }
+}
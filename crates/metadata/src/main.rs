@@ -1,5 +1,5 @@
 use anyhow::Result;
-use coldstore_common::config::MetadataConfig;
+use coldstore_common::config::{self, MetadataConfig};
 use tracing::info;
 
 #[tokio::main]
@@ -11,10 +11,11 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    info!("启动 ColdStore Metadata 节点...");
+    let Some(config) = config::load_from_args::<MetadataConfig>()? else {
+        return Ok(());
+    };
 
-    // TODO: 从配置文件加载
-    let config = MetadataConfig::default();
+    info!("启动 ColdStore Metadata 节点...");
 
     coldstore_metadata::run(config).await
 }
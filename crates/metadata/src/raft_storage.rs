@@ -159,6 +159,7 @@ mod tests {
             versioning_enabled: false,
             object_count: 0,
             total_size: 0,
+            archive_copies: None,
         }
     }
 
@@ -0,0 +1,105 @@
+//! 访问密钥 secret 的静态加密
+//!
+//! SigV4 校验需要明文 secret，因此 secret 不能只存哈希。Metadata 在提交命令前用
+//! `access_key_encryption_key_path` 中的 AES-256 密钥加密 secret，状态机与快照中只有
+//! `AccessKeyInfo::encrypted_secret`。密文格式为 `nonce (12 字节) || 密文 || tag`，
+//! access_key_id 作为附加数据，密文挪到其它密钥上无法解密。
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use tonic::Status;
+
+pub struct SecretCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl SecretCipher {
+    /// 读取 base64 编码的 32 字节密钥文件
+    pub fn load(path: &str) -> Result<Self> {
+        let encoded = std::fs::read_to_string(path)
+            .with_context(|| format!("read access key encryption key {path}"))?;
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .with_context(|| format!("decode access key encryption key {path}"))?;
+        Self::new(&key).with_context(|| format!("load access key encryption key {path}"))
+    }
+
+    pub fn new(key: &[u8]) -> Result<Self> {
+        if key.len() != AES_256_GCM.key_len() {
+            bail!(
+                "expected a {}-byte key, got {} bytes",
+                AES_256_GCM.key_len(),
+                key.len()
+            );
+        }
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| anyhow::anyhow!("invalid AES-256-GCM key"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// 加密 `access_key_id` 的 secret
+    #[allow(clippy::result_large_err)]
+    pub fn seal(&self, access_key_id: &str, secret: &str) -> Result<Vec<u8>, Status> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| Status::internal("generate access key nonce"))?;
+        let mut sealed = secret.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(access_key_id.as_bytes()),
+                &mut sealed,
+            )
+            .map_err(|_| Status::internal("encrypt access key secret"))?;
+        let mut out = nonce.to_vec();
+        out.append(&mut sealed);
+        Ok(out)
+    }
+
+    /// 解密 `access_key_id` 的 secret；密钥不符或密文被改动时返回 `DataLoss`
+    #[allow(clippy::result_large_err)]
+    pub fn open(&self, access_key_id: &str, sealed: &[u8]) -> Result<String, Status> {
+        let undecryptable = || {
+            Status::data_loss(format!(
+                "secret of access key {access_key_id} cannot be decrypted"
+            ))
+        };
+        if sealed.len() < NONCE_LEN {
+            return Err(undecryptable());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| undecryptable())?;
+        let mut buffer = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(access_key_id.as_bytes()), &mut buffer)
+            .map_err(|_| undecryptable())?;
+        String::from_utf8(plaintext.to_vec()).map_err(|_| undecryptable())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_only_open_under_the_same_key_and_access_key_id() {
+        let cipher = SecretCipher::new(&[7u8; 32]).expect("cipher");
+        let sealed = cipher.seal("AK1", "s3cr3t").expect("seal");
+        assert!(!sealed.windows(6).any(|window| window == b"s3cr3t"));
+        assert_eq!(cipher.open("AK1", &sealed).expect("open"), "s3cr3t");
+
+        let err = cipher.open("AK2", &sealed).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::DataLoss);
+        let other = SecretCipher::new(&[8u8; 32]).expect("cipher");
+        assert!(other.open("AK1", &sealed).is_err());
+        assert!(SecretCipher::new(&[7u8; 16]).is_err());
+    }
+}
@@ -1,22 +1,32 @@
 use crate::command::MetadataCommand;
+use crate::lifecycle;
+use crate::secrets::SecretCipher;
 use crate::state_machine::{
-    find_object, is_active_restore_status, is_pending_restore_status, load_snapshot, now_timestamp,
-    save_snapshot, MetadataState, MetadataStateMachine,
+    find_object, is_active_restore_status, is_pending_restore_status, load_snapshot,
+    media_requests, now_timestamp, save_snapshot, select_scratch_tape, MetadataState,
+    MetadataStateMachine,
 };
 use anyhow::Result;
+use coldstore_common::checksum::sha256_hex;
 use coldstore_common::config::MetadataConfig;
+use coldstore_common::error::status_with_reason;
+use coldstore_common::tls::require_client_certificate;
 use coldstore_proto::common;
+use coldstore_proto::metadata::metadata_internal_service_server::MetadataInternalService;
 use coldstore_proto::metadata::metadata_service_server::MetadataService;
 use coldstore_proto::metadata::*;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tonic::{Request, Response, Status};
+use tracing::{info, warn};
 
 pub struct MetadataServiceImpl {
     config: MetadataConfig,
     state: Arc<RwLock<MetadataState>>,
     snapshot_path: Option<PathBuf>,
+    /// 访问密钥 secret 的加密密钥；未配置时不能签发访问密钥
+    secrets: Option<SecretCipher>,
     #[cfg(feature = "metadata-raft")]
     raft_backend: Option<Arc<crate::raft::RaftMetadataBackend>>,
 }
@@ -27,6 +37,7 @@ impl MetadataServiceImpl {
             config: config.clone(),
             state: Arc::new(RwLock::new(MetadataState::default())),
             snapshot_path: None,
+            secrets: access_key_cipher(config)?,
             #[cfg(feature = "metadata-raft")]
             raft_backend: None,
         })
@@ -46,6 +57,7 @@ impl MetadataServiceImpl {
             config: config.clone(),
             state: Arc::new(RwLock::new(state)),
             snapshot_path: Some(snapshot_path),
+            secrets: access_key_cipher(config)?,
             #[cfg(feature = "metadata-raft")]
             raft_backend: None,
         })
@@ -60,10 +72,27 @@ impl MetadataServiceImpl {
             config: config.clone(),
             state: Arc::new(RwLock::new(MetadataState::default())),
             snapshot_path: None,
+            secrets: access_key_cipher(config)?,
             raft_backend: Some(raft_backend),
         })
     }
 
+    /// 状态机中保存的密钥：secret 只以密文形式存在
+    #[allow(clippy::result_large_err)]
+    fn seal_access_key(
+        &self,
+        key: &common::AccessKeyInfo,
+    ) -> std::result::Result<common::AccessKeyInfo, Status> {
+        let cipher = self.secrets.as_ref().ok_or_else(|| {
+            Status::failed_precondition("access_key_encryption_key_path is not configured")
+        })?;
+        Ok(common::AccessKeyInfo {
+            secret_access_key: String::new(),
+            encrypted_secret: cipher.seal(&key.access_key_id, &key.secret_access_key)?,
+            ..key.clone()
+        })
+    }
+
     async fn persist_locked(&self, state: &MetadataState) -> std::result::Result<(), Status> {
         if let Some(path) = &self.snapshot_path {
             save_snapshot(path, state)
@@ -93,6 +122,163 @@ impl MetadataServiceImpl {
         self.persist_locked(&state).await
     }
 
+    /// 将心跳超过 `worker_timeout_secs` 的 Worker 标记为 NodeOffline，返回本次过期的数量
+    ///
+    /// 由 leader 周期调用；过期命令携带判定时刻，提交前心跳已恢复的 Worker 不受影响。
+    pub async fn expire_stale_workers(&self) -> std::result::Result<usize, Status> {
+        let stale_before = now_timestamp().seconds - self.config.worker_timeout_secs as i64;
+        let stale = {
+            let state = self.state.read().await;
+            let is_stale = |status: i32, last_heartbeat: &Option<prost_types::Timestamp>| {
+                status != common::NodeStatus::NodeOffline as i32
+                    && last_heartbeat
+                        .as_ref()
+                        .is_none_or(|ts| ts.seconds < stale_before)
+            };
+            let mut stale = Vec::new();
+            stale.extend(
+                state
+                    .scheduler_workers
+                    .values()
+                    .filter(|worker| is_stale(worker.status, &worker.last_heartbeat))
+                    .map(|worker| (common::WorkerType::WorkerScheduler, worker.node_id)),
+            );
+            stale.extend(
+                state
+                    .cache_workers
+                    .values()
+                    .filter(|worker| is_stale(worker.status, &worker.last_heartbeat))
+                    .map(|worker| (common::WorkerType::WorkerCache, worker.node_id)),
+            );
+            stale.extend(
+                state
+                    .tape_workers
+                    .values()
+                    .filter(|worker| is_stale(worker.status, &worker.last_heartbeat))
+                    .map(|worker| (common::WorkerType::WorkerTape, worker.node_id)),
+            );
+            stale
+        };
+
+        for (worker_type, node_id) in &stale {
+            warn!(
+                "{} Worker {} 心跳超时，标记为离线",
+                worker_type.as_str_name(),
+                node_id
+            );
+            self.apply_and_persist(MetadataCommand::ExpireWorker {
+                worker_type: *worker_type,
+                node_id: *node_id,
+                stale_before,
+            })
+            .await?;
+        }
+        Ok(stale.len())
+    }
+
+    /// 租约过期的进行中任务按 `task_retry` 退避后重新排队 (重试耗尽的标记为 Failed)，返回本次过期的数量
+    pub async fn expire_task_leases(&self) -> std::result::Result<usize, Status> {
+        let now = now_timestamp();
+        let expired = {
+            let state = self.state.read().await;
+            let expired =
+                |status: i32, in_progress: i32, lease: &Option<prost_types::Timestamp>| {
+                    status == in_progress
+                        && lease.as_ref().is_some_and(|lease| {
+                            (lease.seconds, lease.nanos) < (now.seconds, now.nanos)
+                        })
+                };
+            let archive_in_progress = common::ArchiveTaskStatus::ArchiveTaskInProgress as i32;
+            let recall_in_progress = common::RestoreStatus::RestoreInProgress as i32;
+            state
+                .archive_tasks
+                .values()
+                .filter(|task| expired(task.status, archive_in_progress, &task.lease_expire_at))
+                .count()
+                + state
+                    .recall_tasks
+                    .values()
+                    .filter(|task| expired(task.status, recall_in_progress, &task.lease_expire_at))
+                    .count()
+        };
+        if expired > 0 {
+            warn!("{expired} 个任务的租约已过期，重新排队");
+            self.apply_and_persist(MetadataCommand::ExpireTaskLeases {
+                now,
+                retry: self.config.task_retry.clone(),
+            })
+            .await?;
+        }
+        Ok(expired)
+    }
+
+    /// 执行生命周期规则并将到期的解冻副本标记为 RestoreExpired，返回 (删除的对象数, 过期的解冻数)
+    pub async fn apply_lifecycle(&self) -> std::result::Result<(usize, usize), Status> {
+        let now = now_timestamp();
+        let sweep = lifecycle::evaluate(&*self.state.read().await, &now);
+        if sweep.is_empty() {
+            return Ok((0, 0));
+        }
+        let counts = (sweep.expired_objects.len(), sweep.expired_restores.len());
+        info!(
+            "生命周期: 删除 {} 个过期对象，{} 个解冻副本到期",
+            counts.0, counts.1
+        );
+        self.apply_and_persist(MetadataCommand::ApplyLifecycle { now })
+            .await?;
+        Ok(counts)
+    }
+
+    /// 应用磁带命令后返回磁带的最新状态
+    async fn apply_tape_command(
+        &self,
+        tape_id: &str,
+        command: MetadataCommand,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        self.apply_and_persist(command).await?;
+        let state = self.state.read().await;
+        let tape = state
+            .tapes
+            .get(tape_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("tape not found"))?;
+        Ok(Response::new(tape))
+    }
+
+    /// 应用可能让取回转入 WaitingForMedia 的命令；等待数增加时提示运维放回磁带
+    async fn apply_and_notify_media(
+        &self,
+        command: MetadataCommand,
+    ) -> std::result::Result<(), Status> {
+        let before = waiting_for_media(&*self.state.read().await);
+        self.apply_and_persist(command).await?;
+        let state = self.state.read().await;
+        let after = waiting_for_media(&state);
+        if after > before {
+            let tapes: Vec<String> = media_requests(&state)
+                .iter()
+                .map(|request| {
+                    format!(
+                        "{}@{}",
+                        request.barcode.as_deref().unwrap_or(&request.tape_id),
+                        request.location.as_deref().unwrap_or("unknown")
+                    )
+                })
+                .collect();
+            warn!(
+                "{after} 个取回任务等待离线磁带，请将以下磁带放回带库: {}",
+                tapes.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    fn task_lease_expire_at(&self) -> prost_types::Timestamp {
+        let mut lease = now_timestamp();
+        lease.seconds += self.config.task_lease_secs as i64;
+        lease
+    }
+
     fn metadata_nodes(&self) -> Vec<common::MetadataNodeInfo> {
         self.config
             .cluster
@@ -116,6 +302,43 @@ impl MetadataServiceImpl {
     }
 }
 
+/// 生成新的访问密钥；随机部分在提交命令前确定，保证各副本回放结果一致
+fn generate_access_key(user_id: String) -> common::AccessKeyInfo {
+    let id = uuid::Uuid::new_v4().simple().to_string().to_uppercase();
+    let secret = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        &uuid::Uuid::new_v4().simple().to_string()[..8]
+    );
+    common::AccessKeyInfo {
+        access_key_id: format!("CSAK{}", &id[..16]),
+        user_id,
+        secret_hash: sha256_hex(secret.as_bytes()),
+        secret_access_key: secret,
+        status: common::AccessKeyStatus::AccessKeyActive as i32,
+        created_at: Some(now_timestamp()),
+        revoked_at: None,
+        encrypted_secret: Vec::new(),
+    }
+}
+
+/// 查询接口返回的密钥不含 secret 明文与密文
+fn redact_access_key(key: &common::AccessKeyInfo) -> common::AccessKeyInfo {
+    common::AccessKeyInfo {
+        secret_access_key: String::new(),
+        encrypted_secret: Vec::new(),
+        ..key.clone()
+    }
+}
+
+fn access_key_cipher(config: &MetadataConfig) -> Result<Option<SecretCipher>> {
+    config
+        .access_key_encryption_key_path
+        .as_deref()
+        .map(SecretCipher::load)
+        .transpose()
+}
+
 #[tonic::async_trait]
 impl MetadataService for MetadataServiceImpl {
     async fn put_object(
@@ -247,6 +470,15 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn update_staging_location(
+        &self,
+        request: Request<UpdateStagingLocationRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::UpdateStagingLocation(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
     async fn scan_cold_pending(
         &self,
         request: Request<ScanColdPendingRequest>,
@@ -262,6 +494,7 @@ impl MetadataService for MetadataServiceImpl {
             .objects
             .values()
             .filter(|object| object.storage_class == common::StorageClass::ColdPending as i32)
+            .filter(|object| object.staging_error.is_none())
             .cloned()
             .collect();
         objects.sort_by(|a, b| a.bucket.cmp(&b.bucket).then_with(|| a.key.cmp(&b.key)));
@@ -312,6 +545,84 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(ListBucketsResponse { buckets }))
     }
 
+    async fn put_bucket_policy(
+        &self,
+        request: Request<common::BucketPolicy>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::PutBucketPolicy(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_bucket_policy(
+        &self,
+        request: Request<GetBucketPolicyRequest>,
+    ) -> std::result::Result<Response<common::BucketPolicy>, Status> {
+        let request = request.into_inner();
+        let state = self.state.read().await;
+        let policy = state
+            .bucket_policies
+            .get(&request.bucket)
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!("bucket policy not found: {}", request.bucket))
+            })?;
+        Ok(Response::new(policy))
+    }
+
+    async fn delete_bucket_policy(
+        &self,
+        request: Request<DeleteBucketPolicyRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::DeleteBucketPolicy(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn put_bucket_lifecycle(
+        &self,
+        request: Request<common::BucketLifecycle>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::PutBucketLifecycle(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_bucket_lifecycle(
+        &self,
+        request: Request<GetBucketLifecycleRequest>,
+    ) -> std::result::Result<Response<common::BucketLifecycle>, Status> {
+        let request = request.into_inner();
+        let state = self.state.read().await;
+        if state.bucket(&request.bucket).is_none() {
+            return Err(Status::not_found(format!(
+                "bucket not found: {}",
+                request.bucket
+            )));
+        }
+        let lifecycle = state
+            .bucket_lifecycles
+            .get(&request.bucket)
+            .cloned()
+            .ok_or_else(|| {
+                status_with_reason(
+                    tonic::Code::NotFound,
+                    format!("lifecycle configuration not found: {}", request.bucket),
+                    common::ErrorReason::NoSuchLifecycleConfiguration,
+                )
+            })?;
+        Ok(Response::new(lifecycle))
+    }
+
+    async fn delete_bucket_lifecycle(
+        &self,
+        request: Request<DeleteBucketLifecycleRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::DeleteBucketLifecycle(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
     async fn put_archive_bundle(
         &self,
         request: Request<common::ArchiveBundle>,
@@ -346,6 +657,15 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn update_bundle_copy(
+        &self,
+        request: Request<UpdateBundleCopyRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::UpdateBundleCopy(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
     async fn list_bundles_by_tape(
         &self,
         request: Request<ListBundlesByTapeRequest>,
@@ -355,7 +675,14 @@ impl MetadataService for MetadataServiceImpl {
         let bundle_ids = state
             .archive_bundles
             .values()
-            .filter(|bundle| bundle.tape_id == request.tape_id)
+            .filter(|bundle| {
+                bundle.tape_id == request.tape_id
+                    || bundle.tape_set.contains(&request.tape_id)
+                    || bundle
+                        .copies
+                        .iter()
+                        .any(|copy| copy.tape_id == request.tape_id)
+            })
             .map(|bundle| bundle.id.clone())
             .collect();
         Ok(Response::new(ListBundlesByTapeResponse { bundle_ids }))
@@ -393,6 +720,54 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn claim_archive_task(
+        &self,
+        request: Request<ClaimTaskRequest>,
+    ) -> std::result::Result<Response<common::ArchiveTask>, Status> {
+        let request = request.into_inner();
+        self.apply_and_persist(MetadataCommand::ClaimArchiveTask {
+            id: request.id.clone(),
+            worker_id: request.worker_id,
+            lease_expire_at: self.task_lease_expire_at(),
+            now: now_timestamp(),
+        })
+        .await?;
+        let state = self.state.read().await;
+        let task = state
+            .archive_tasks
+            .get(&request.id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("archive task not found"))?;
+        Ok(Response::new(task))
+    }
+
+    async fn fail_archive_task(
+        &self,
+        request: Request<FailTaskRequest>,
+    ) -> std::result::Result<Response<common::ArchiveTask>, Status> {
+        let request = request.into_inner();
+        let id = request.id.clone();
+        self.apply_and_persist(MetadataCommand::FailArchiveTask {
+            request,
+            now: now_timestamp(),
+            retry: self.config.task_retry.clone(),
+        })
+        .await?;
+        let state = self.state.read().await;
+        let task = state
+            .archive_tasks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("archive task not found"))?;
+        if task.status == common::ArchiveTaskStatus::ArchiveTaskFailed as i32 {
+            warn!(
+                "归档任务 {id} 进入死信: {}",
+                task.error.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(Response::new(task))
+    }
+
     async fn list_pending_archive_tasks(
         &self,
         _request: Request<()>,
@@ -417,7 +792,7 @@ impl MetadataService for MetadataServiceImpl {
         &self,
         request: Request<common::RecallTask>,
     ) -> std::result::Result<Response<()>, Status> {
-        self.apply_and_persist(MetadataCommand::PutRecallTask(request.into_inner()))
+        self.apply_and_notify_media(MetadataCommand::PutRecallTask(request.into_inner()))
             .await?;
         Ok(Response::new(()))
     }
@@ -445,6 +820,54 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn claim_recall_task(
+        &self,
+        request: Request<ClaimTaskRequest>,
+    ) -> std::result::Result<Response<common::RecallTask>, Status> {
+        let request = request.into_inner();
+        self.apply_and_persist(MetadataCommand::ClaimRecallTask {
+            id: request.id.clone(),
+            worker_id: request.worker_id,
+            lease_expire_at: self.task_lease_expire_at(),
+            now: now_timestamp(),
+        })
+        .await?;
+        let state = self.state.read().await;
+        let task = state
+            .recall_tasks
+            .get(&request.id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("recall task not found"))?;
+        Ok(Response::new(task))
+    }
+
+    async fn fail_recall_task(
+        &self,
+        request: Request<FailTaskRequest>,
+    ) -> std::result::Result<Response<common::RecallTask>, Status> {
+        let request = request.into_inner();
+        let id = request.id.clone();
+        self.apply_and_persist(MetadataCommand::FailRecallTask {
+            request,
+            now: now_timestamp(),
+            retry: self.config.task_retry.clone(),
+        })
+        .await?;
+        let state = self.state.read().await;
+        let task = state
+            .recall_tasks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("recall task not found"))?;
+        if task.status == common::RestoreStatus::RestoreFailed as i32 {
+            warn!(
+                "取回任务 {id} 进入死信: {}",
+                task.error.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(Response::new(task))
+    }
+
     async fn list_pending_recall_tasks(
         &self,
         _request: Request<()>,
@@ -548,6 +971,252 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(ListTapesResponse { tapes }))
     }
 
+    async fn begin_tape_reclamation(
+        &self,
+        request: Request<TapeReclamationRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::BeginTapeReclamation(request))
+            .await
+    }
+
+    async fn complete_tape_reclamation(
+        &self,
+        request: Request<TapeReclamationRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::CompleteTapeReclamation(request))
+            .await
+    }
+
+    async fn label_tape(
+        &self,
+        request: Request<LabelTapeRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::LabelTape(request))
+            .await
+    }
+
+    async fn import_tape(
+        &self,
+        request: Request<ImportTapeRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::ImportTape(request))
+            .await
+    }
+
+    async fn move_tape_to_pool(
+        &self,
+        request: Request<MoveTapeToPoolRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::MoveTapeToPool(request))
+            .await
+    }
+
+    async fn retire_tape(
+        &self,
+        request: Request<RetireTapeRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::RetireTape(request))
+            .await
+    }
+
+    async fn allocate_scratch_tape(
+        &self,
+        request: Request<AllocateScratchTapeRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id =
+            select_scratch_tape(&*self.state.read().await, &request).ok_or_else(|| {
+                Status::failed_precondition(format!(
+                    "no scratch tape with {} bytes free for pool {:?}",
+                    request.min_free_bytes, request.pool
+                ))
+            })?;
+        info!("从 Scratch 分配磁带 {tape_id} 到磁带池 {:?}", request.pool);
+        self.apply_tape_command(
+            &tape_id.clone(),
+            MetadataCommand::AllocateScratchTape { request, tape_id },
+        )
+        .await
+    }
+
+    async fn mark_tape_offline(
+        &self,
+        request: Request<TapeLocationRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_and_notify_media(MetadataCommand::MarkTapeOffline(request))
+            .await?;
+        let state = self.state.read().await;
+        let tape = state
+            .tapes
+            .get(&tape_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("tape not found"))?;
+        Ok(Response::new(tape))
+    }
+
+    async fn mark_tape_online(
+        &self,
+        request: Request<TapeLocationRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::MarkTapeOnline(request))
+            .await
+    }
+
+    async fn list_media_requests(
+        &self,
+        _request: Request<()>,
+    ) -> std::result::Result<Response<ListMediaRequestsResponse>, Status> {
+        let state = self.state.read().await;
+        Ok(Response::new(ListMediaRequestsResponse {
+            requests: media_requests(&state),
+        }))
+    }
+
+    async fn record_tape_verification(
+        &self,
+        request: Request<RecordTapeVerificationRequest>,
+    ) -> std::result::Result<Response<common::TapeInfo>, Status> {
+        let request = request.into_inner();
+        if request.errors > 0 {
+            warn!(
+                "磁带 {} 校验发现 {} 个错误条目",
+                request.tape_id, request.errors
+            );
+        }
+        let tape_id = request.tape_id.clone();
+        self.apply_tape_command(&tape_id, MetadataCommand::RecordTapeVerification(request))
+            .await
+    }
+
+    async fn create_user(
+        &self,
+        request: Request<common::UserInfo>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::CreateUser(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_user(
+        &self,
+        request: Request<GetUserRequest>,
+    ) -> std::result::Result<Response<common::UserInfo>, Status> {
+        let request = request.into_inner();
+        let state = self.state.read().await;
+        let user = state
+            .users
+            .get(&request.user_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("user not found"))?;
+        Ok(Response::new(user))
+    }
+
+    async fn list_users(
+        &self,
+        _request: Request<()>,
+    ) -> std::result::Result<Response<ListUsersResponse>, Status> {
+        let state = self.state.read().await;
+        let mut users: Vec<_> = state.users.values().cloned().collect();
+        users.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+        Ok(Response::new(ListUsersResponse { users }))
+    }
+
+    async fn set_user_enabled(
+        &self,
+        request: Request<SetUserEnabledRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::SetUserEnabled(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn create_access_key(
+        &self,
+        request: Request<CreateAccessKeyRequest>,
+    ) -> std::result::Result<Response<common::AccessKeyInfo>, Status> {
+        let key = generate_access_key(request.into_inner().user_id);
+        self.apply_and_persist(MetadataCommand::PutAccessKey(self.seal_access_key(&key)?))
+            .await?;
+        Ok(Response::new(key))
+    }
+
+    async fn rotate_access_key(
+        &self,
+        request: Request<RotateAccessKeyRequest>,
+    ) -> std::result::Result<Response<common::AccessKeyInfo>, Status> {
+        let request = request.into_inner();
+        let user_id = self
+            .state
+            .read()
+            .await
+            .access_keys
+            .get(&request.access_key_id)
+            .map(|key| key.user_id.clone())
+            .ok_or_else(|| Status::not_found("access key not found"))?;
+        let replacement = generate_access_key(user_id);
+        self.apply_and_persist(MetadataCommand::RotateAccessKey {
+            access_key_id: request.access_key_id,
+            replacement: self.seal_access_key(&replacement)?,
+        })
+        .await?;
+        Ok(Response::new(replacement))
+    }
+
+    async fn revoke_access_key(
+        &self,
+        request: Request<RevokeAccessKeyRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.apply_and_persist(MetadataCommand::RevokeAccessKey(request.into_inner()))
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_access_key(
+        &self,
+        request: Request<GetAccessKeyRequest>,
+    ) -> std::result::Result<Response<common::AccessKeyInfo>, Status> {
+        let request = request.into_inner();
+        let state = self.state.read().await;
+        let key = state
+            .access_keys
+            .get(&request.access_key_id)
+            .map(redact_access_key)
+            .ok_or_else(|| Status::not_found("access key not found"))?;
+        Ok(Response::new(key))
+    }
+
+    async fn list_access_keys(
+        &self,
+        request: Request<ListAccessKeysRequest>,
+    ) -> std::result::Result<Response<ListAccessKeysResponse>, Status> {
+        let request = request.into_inner();
+        let state = self.state.read().await;
+        let mut access_keys: Vec<_> = state
+            .access_keys
+            .values()
+            .filter(|key| key.user_id == request.user_id)
+            .map(redact_access_key)
+            .collect();
+        access_keys.sort_by(|a, b| a.access_key_id.cmp(&b.access_key_id));
+        Ok(Response::new(ListAccessKeysResponse { access_keys }))
+    }
+
     async fn get_cluster_info(
         &self,
         _request: Request<()>,
@@ -688,16 +1357,104 @@ impl MetadataService for MetadataServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn list_dead_letter_tasks(
+        &self,
+        _request: Request<()>,
+    ) -> std::result::Result<Response<ListDeadLetterTasksResponse>, Status> {
+        let state = self.state.read().await;
+        let mut archive_tasks: Vec<_> = state
+            .archive_tasks
+            .values()
+            .filter(|task| task.status == common::ArchiveTaskStatus::ArchiveTaskFailed as i32)
+            .cloned()
+            .collect();
+        archive_tasks.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut recall_tasks: Vec<_> = state
+            .recall_tasks
+            .values()
+            .filter(|task| task.status == common::RestoreStatus::RestoreFailed as i32)
+            .cloned()
+            .collect();
+        recall_tasks.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(Response::new(ListDeadLetterTasksResponse {
+            archive_tasks,
+            recall_tasks,
+        }))
+    }
+
     async fn heartbeat(
         &self,
         request: Request<HeartbeatRequest>,
-    ) -> std::result::Result<Response<()>, Status> {
-        self.apply_and_persist(MetadataCommand::Heartbeat(request.into_inner()))
-            .await?;
-        Ok(Response::new(()))
+    ) -> std::result::Result<Response<HeartbeatResponse>, Status> {
+        let request = request.into_inner();
+        let (worker_type, node_id) = (request.worker_type, request.node_id);
+        self.apply_and_persist(MetadataCommand::Heartbeat {
+            request,
+            task_lease_expire_at: self.task_lease_expire_at(),
+        })
+        .await?;
+        let state = self.state.read().await;
+        let status = match common::WorkerType::try_from(worker_type) {
+            Ok(common::WorkerType::WorkerScheduler) => state
+                .scheduler_workers
+                .get(&node_id)
+                .map(|worker| worker.status),
+            Ok(common::WorkerType::WorkerCache) => state
+                .cache_workers
+                .get(&node_id)
+                .map(|worker| worker.status),
+            Ok(common::WorkerType::WorkerTape) => {
+                state.tape_workers.get(&node_id).map(|worker| worker.status)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| Status::not_found("worker not found"))?;
+        Ok(Response::new(HeartbeatResponse { status }))
     }
 }
 
+#[tonic::async_trait]
+impl MetadataInternalService for MetadataServiceImpl {
+    async fn resolve_access_key(
+        &self,
+        request: Request<GetAccessKeyRequest>,
+    ) -> std::result::Result<Response<common::AccessKeyInfo>, Status> {
+        require_client_certificate(&request)?;
+        let request = request.into_inner();
+        let key = self
+            .state
+            .read()
+            .await
+            .access_keys
+            .get(&request.access_key_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found("access key not found"))?;
+        let cipher = self.secrets.as_ref().ok_or_else(|| {
+            Status::failed_precondition("access_key_encryption_key_path is not configured")
+        })?;
+        let secret = cipher.open(&key.access_key_id, &key.encrypted_secret)?;
+        if !sha256_hex(secret.as_bytes()).eq_ignore_ascii_case(&key.secret_hash) {
+            return Err(Status::data_loss(format!(
+                "secret of access key {} does not match its hash",
+                key.access_key_id
+            )));
+        }
+        Ok(Response::new(common::AccessKeyInfo {
+            secret_access_key: secret,
+            encrypted_secret: Vec::new(),
+            ..key
+        }))
+    }
+}
+
+fn waiting_for_media(state: &MetadataState) -> usize {
+    state
+        .recall_tasks
+        .values()
+        .filter(|task| task.status == common::RestoreStatus::RestoreWaitingForMedia as i32)
+        .count()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -716,6 +1473,8 @@ mod tests {
             versioning_enabled: false,
             object_count: 0,
             total_size: 0,
+            archive_copies: None,
+            tape_pools: Vec::new(),
         }
     }
 
@@ -743,6 +1502,9 @@ mod tests {
                 seconds: 1,
                 nanos: 0,
             }),
+            staging_worker_id: None,
+            staging_error: None,
+            attributes: None,
         }
     }
 
@@ -930,6 +1692,7 @@ mod tests {
             pending_recall_tasks: 0,
             active_jobs: 0,
             paired_cache_worker_id: 0,
+            drain: None,
         }))
         .await
         .expect("register worker");
@@ -942,6 +1705,7 @@ mod tests {
                 pending_recall_tasks: 2,
                 active_jobs: 1,
             })),
+            drain: None,
         }))
         .await
         .expect("heartbeat");
@@ -957,6 +1721,305 @@ mod tests {
         assert_eq!(cluster.scheduler_workers[0].active_jobs, 1);
     }
 
+    #[tokio::test]
+    async fn heartbeat_reports_drain_status_and_progress_reaches_cluster_info() {
+        let svc = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("service init");
+        svc.register_tape_worker(Request::new(common::TapeWorkerInfo {
+            node_id: 3,
+            status: common::NodeStatus::NodeOnline as i32,
+            ..Default::default()
+        }))
+        .await
+        .expect("register worker");
+        let heartbeat = |drain| HeartbeatRequest {
+            worker_type: common::WorkerType::WorkerTape as i32,
+            node_id: 3,
+            payload: None,
+            drain,
+        };
+
+        let response = svc
+            .heartbeat(Request::new(heartbeat(None)))
+            .await
+            .expect("heartbeat")
+            .into_inner();
+        assert_eq!(response.status, common::NodeStatus::NodeOnline as i32);
+
+        svc.drain_worker(Request::new(DrainWorkerRequest {
+            worker_type: common::WorkerType::WorkerTape as i32,
+            node_id: 3,
+        }))
+        .await
+        .expect("drain worker");
+        let response = svc
+            .heartbeat(Request::new(heartbeat(Some(common::DrainProgress {
+                remaining_items: 1,
+                remaining_bytes: 0,
+                complete: false,
+                detail: Some("1 tape loaded".into()),
+            }))))
+            .await
+            .expect("heartbeat")
+            .into_inner();
+        assert_eq!(response.status, common::NodeStatus::NodeDraining as i32);
+
+        let cluster = svc
+            .get_cluster_info(Request::new(()))
+            .await
+            .expect("cluster info")
+            .into_inner();
+        let worker = &cluster.tape_workers[0];
+        assert_eq!(worker.status, common::NodeStatus::NodeDraining as i32);
+        assert_eq!(
+            worker.drain.as_ref().map(|drain| drain.remaining_items),
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn workers_without_recent_heartbeat_are_marked_offline() {
+        let svc = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("service init");
+        for node_id in [1, 2] {
+            svc.register_cache_worker(Request::new(common::CacheWorkerInfo {
+                node_id,
+                status: common::NodeStatus::NodeOnline as i32,
+                ..Default::default()
+            }))
+            .await
+            .expect("register cache worker");
+        }
+        assert_eq!(svc.expire_stale_workers().await.expect("sweep"), 0);
+
+        svc.state
+            .write()
+            .await
+            .cache_workers
+            .get_mut(&1)
+            .expect("worker 1")
+            .last_heartbeat = Some(Timestamp {
+            seconds: 1,
+            nanos: 0,
+        });
+        assert_eq!(svc.expire_stale_workers().await.expect("sweep"), 1);
+        // 已离线的 Worker 不再重复过期
+        assert_eq!(svc.expire_stale_workers().await.expect("sweep"), 0);
+
+        let online = svc
+            .list_online_cache_workers(Request::new(()))
+            .await
+            .expect("list online")
+            .into_inner()
+            .workers;
+        assert_eq!(
+            online
+                .iter()
+                .map(|worker| worker.node_id)
+                .collect::<Vec<_>>(),
+            [2]
+        );
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_rejects_clients_without_certificates() {
+        use coldstore_common::tls::testing::TestPki;
+        use coldstore_proto::metadata::metadata_service_client::MetadataServiceClient;
+        use coldstore_proto::metadata::metadata_service_server::MetadataServiceServer;
+        use tonic::transport::Server;
+
+        let pki = TestPki::generate();
+        let svc = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("service init");
+        svc.create_bucket(Request::new(test_bucket("docs")))
+            .await
+            .expect("create bucket");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        drop(listener);
+        let server_tls = pki
+            .server_config(true)
+            .server_tls_config()
+            .expect("server tls")
+            .expect("tls enabled");
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            Server::builder()
+                .tls_config(server_tls)
+                .expect("server tls config")
+                .add_service(MetadataServiceServer::new(svc))
+                .serve_with_shutdown(addr, async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+                .expect("metadata server should run");
+        });
+
+        let addr = addr.to_string();
+        let mut channel = None;
+        for _ in 0..20 {
+            match pki.client_config(true).connect(&addr).await {
+                Ok(connected) => {
+                    channel = Some(connected);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(25)).await,
+            }
+        }
+        let mut client = MetadataServiceClient::new(channel.expect("mTLS client connects"));
+        let buckets = client
+            .list_buckets(Request::new(()))
+            .await
+            .expect("list buckets over mTLS")
+            .into_inner();
+        assert_eq!(buckets.buckets.len(), 1);
+
+        // 不出示客户端证书时握手失败
+        let anonymous = match pki.client_config(false).connect(&addr).await {
+            Ok(channel) => MetadataServiceClient::new(channel)
+                .list_buckets(Request::new(()))
+                .await
+                .map(|_| ()),
+            Err(_) => Err(Status::unavailable("handshake failed")),
+        };
+        assert!(anonymous.is_err());
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn access_key_secrets_are_stored_encrypted_and_resolved_only_over_mtls() {
+        use base64::Engine;
+        use coldstore_common::tls::testing::TestPki;
+        use coldstore_proto::metadata::metadata_internal_service_client::MetadataInternalServiceClient;
+        use coldstore_proto::metadata::metadata_internal_service_server::MetadataInternalServiceServer;
+        use tonic::transport::Server;
+
+        let unconfigured = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("service init");
+        unconfigured
+            .create_user(Request::new(common::UserInfo {
+                user_id: "alice".into(),
+                display_name: "Alice".into(),
+                enabled: true,
+                created_at: None,
+            }))
+            .await
+            .expect("create user");
+        let err = unconfigured
+            .create_access_key(Request::new(CreateAccessKeyRequest {
+                user_id: "alice".into(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        let pki = TestPki::generate();
+        let key_path = pki.dir.join("access-keys.key");
+        std::fs::write(
+            &key_path,
+            base64::engine::general_purpose::STANDARD.encode([9u8; 32]),
+        )
+        .expect("write encryption key");
+        let config = MetadataConfig {
+            access_key_encryption_key_path: Some(key_path.display().to_string()),
+            ..MetadataConfig::default()
+        };
+        let svc = Arc::new(
+            MetadataServiceImpl::new(&config)
+                .await
+                .expect("service init"),
+        );
+        svc.create_user(Request::new(common::UserInfo {
+            user_id: "alice".into(),
+            display_name: "Alice".into(),
+            enabled: true,
+            created_at: None,
+        }))
+        .await
+        .expect("create user");
+        let created = svc
+            .create_access_key(Request::new(CreateAccessKeyRequest {
+                user_id: "alice".into(),
+            }))
+            .await
+            .expect("create access key")
+            .into_inner();
+        assert!(!created.secret_access_key.is_empty());
+
+        let stored = svc.state.read().await.access_keys[&created.access_key_id].clone();
+        assert!(stored.secret_access_key.is_empty());
+        assert!(!stored
+            .encrypted_secret
+            .windows(created.secret_access_key.len())
+            .any(|window| window == created.secret_access_key.as_bytes()));
+        let fetched = svc
+            .get_access_key(Request::new(GetAccessKeyRequest {
+                access_key_id: created.access_key_id.clone(),
+            }))
+            .await
+            .expect("get access key")
+            .into_inner();
+        assert!(fetched.secret_access_key.is_empty());
+        assert!(fetched.encrypted_secret.is_empty());
+
+        // 进程内直接调用没有客户端证书
+        let err = svc
+            .resolve_access_key(Request::new(GetAccessKeyRequest {
+                access_key_id: created.access_key_id.clone(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        drop(listener);
+        let server_tls = pki
+            .server_config(true)
+            .server_tls_config()
+            .expect("server tls")
+            .expect("tls enabled");
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let served = svc.clone();
+        tokio::spawn(async move {
+            Server::builder()
+                .tls_config(server_tls)
+                .expect("server tls config")
+                .add_service(MetadataInternalServiceServer::from_arc(served))
+                .serve_with_shutdown(addr, async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+                .expect("metadata server should run");
+        });
+        let addr = addr.to_string();
+        let mut channel = None;
+        for _ in 0..20 {
+            match pki.client_config(true).connect(&addr).await {
+                Ok(connected) => {
+                    channel = Some(connected);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(25)).await,
+            }
+        }
+        let resolved = MetadataInternalServiceClient::new(channel.expect("mTLS client connects"))
+            .resolve_access_key(Request::new(GetAccessKeyRequest {
+                access_key_id: created.access_key_id.clone(),
+            }))
+            .await
+            .expect("resolve over mTLS")
+            .into_inner();
+        assert_eq!(resolved.secret_access_key, created.secret_access_key);
+        assert!(resolved.encrypted_secret.is_empty());
+        let _ = shutdown_tx.send(());
+    }
+
     #[cfg(feature = "metadata-raft")]
     #[tokio::test]
     async fn metadata_service_raft_mode_routes_writes_through_propose_backend() {
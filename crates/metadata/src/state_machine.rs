@@ -1,4 +1,7 @@
 use anyhow::Result;
+use coldstore_common::config::TaskRetryConfig;
+use coldstore_common::error::status_with_reason;
+use coldstore_common::media::{tape_appendable, tape_format_capacity_bytes, tape_readable};
 use coldstore_proto::common;
 use coldstore_proto::metadata::*;
 use prost::Message;
@@ -8,6 +11,7 @@ use std::path::Path;
 use tonic::Status;
 
 use crate::command::MetadataCommand;
+use crate::lifecycle;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub(crate) struct ObjectKey {
@@ -17,7 +21,7 @@ pub(crate) struct ObjectKey {
 }
 
 impl ObjectKey {
-    fn new(bucket: String, key: String, version_id: Option<String>) -> Self {
+    pub(crate) fn new(bucket: String, key: String, version_id: Option<String>) -> Self {
         Self {
             bucket,
             key,
@@ -37,6 +41,10 @@ pub struct MetadataState {
     pub(crate) scheduler_workers: HashMap<u64, common::SchedulerWorkerInfo>,
     pub(crate) cache_workers: HashMap<u64, common::CacheWorkerInfo>,
     pub(crate) tape_workers: HashMap<u64, common::TapeWorkerInfo>,
+    pub(crate) users: HashMap<String, common::UserInfo>,
+    pub(crate) access_keys: HashMap<String, common::AccessKeyInfo>,
+    pub(crate) bucket_policies: HashMap<String, common::BucketPolicy>,
+    pub(crate) bucket_lifecycles: HashMap<String, common::BucketLifecycle>,
 }
 
 impl MetadataState {
@@ -48,6 +56,14 @@ impl MetadataState {
         self.objects.values()
     }
 
+    pub fn archive_bundle(&self, id: &str) -> Option<&common::ArchiveBundle> {
+        self.archive_bundles.get(id)
+    }
+
+    pub fn tape(&self, id: &str) -> Option<&common::TapeInfo> {
+        self.tapes.get(id)
+    }
+
     pub fn bucket_count(&self) -> usize {
         self.buckets.len()
     }
@@ -97,10 +113,11 @@ pub(crate) fn apply_command(
     match command {
         MetadataCommand::PutObject(mut object) => {
             if !state.buckets.contains_key(&object.bucket) {
-                return Err(Status::not_found(format!(
-                    "bucket not found: {}",
-                    object.bucket
-                )));
+                return Err(status_with_reason(
+                    tonic::Code::NotFound,
+                    format!("bucket not found: {}", object.bucket),
+                    common::ErrorReason::NoSuchBucket,
+                ));
             }
             let now = now_timestamp();
             if object.created_at.is_none() {
@@ -112,46 +129,98 @@ pub(crate) fn apply_command(
                 object.key.clone(),
                 object.version_id.clone(),
             );
-            state.objects.insert(key, object.clone());
+            let previous = state.objects.insert(key, object.clone());
+            if previous.as_ref().map(|previous| &previous.archive_id) != Some(&object.archive_id) {
+                if let Some(previous) = previous {
+                    account_archived_bytes(state, &previous, false);
+                }
+                account_archived_bytes(state, &object, true);
+            }
             refresh_bucket_stats(state, &object.bucket);
         }
         MetadataCommand::DeleteObject(request) => {
-            let removed = state.objects.remove(&ObjectKey::new(
-                request.bucket.clone(),
-                request.key.clone(),
-                None,
-            ));
-            if removed.is_none() {
-                return Err(Status::not_found("object not found"));
-            }
+            let removed = state
+                .objects
+                .remove(&ObjectKey::new(
+                    request.bucket.clone(),
+                    request.key.clone(),
+                    request.version_id.clone(),
+                ))
+                .ok_or_else(|| Status::not_found("object not found"))?;
+            account_archived_bytes(state, &removed, false);
             refresh_bucket_stats(state, &request.bucket);
         }
         MetadataCommand::UpdateStorageClass(request) => {
+            if request.storage_class == common::StorageClass::Cold as i32 {
+                let object = find_object(state, &request.bucket, &request.key, None)?;
+                if let Some(bundle) = object
+                    .archive_id
+                    .as_ref()
+                    .and_then(|id| state.archive_bundles.get(id))
+                {
+                    if !bundle.copies.is_empty()
+                        && durable_copies(bundle).count() < required_copies(bundle)
+                    {
+                        return Err(Status::failed_precondition(
+                            "required archive copies are not yet durable",
+                        ));
+                    }
+                }
+            }
             let object = find_object_mut(state, &request.bucket, &request.key, None)?;
             object.storage_class = request.storage_class;
             object.updated_at = Some(now_timestamp());
         }
         MetadataCommand::UpdateArchiveLocation(request) => {
+            let previous = find_object(state, &request.bucket, &request.key, None)?;
+            account_archived_bytes(state, &previous, false);
             let object = find_object_mut(state, &request.bucket, &request.key, None)?;
             object.archive_id = Some(request.archive_id);
             object.tape_id = Some(request.tape_id);
             object.tape_set = request.tape_set;
             object.tape_block_offset = Some(request.tape_block_offset);
             object.updated_at = Some(now_timestamp());
+            let object = object.clone();
+            account_archived_bytes(state, &object, true);
         }
         MetadataCommand::UpdateRestoreStatus(request) => {
             let object = find_object_mut(state, &request.bucket, &request.key, None)?;
             validate_restore_transition(object.restore_status, request.status)?;
+            let reopened = request.status == common::RestoreStatus::RestorePending as i32
+                && matches!(
+                    object
+                        .restore_status
+                        .and_then(|s| common::RestoreStatus::try_from(s).ok()),
+                    Some(
+                        common::RestoreStatus::RestoreFailed
+                            | common::RestoreStatus::RestoreExpired
+                    )
+                );
             object.restore_status = Some(request.status);
             object.restore_expire_at = request.expire_at;
             object.updated_at = Some(now_timestamp());
+            if reopened {
+                reopen_failed_recalls(state, &request.bucket, &request.key, request.expire_at);
+            }
+        }
+        MetadataCommand::UpdateStagingLocation(request) => {
+            let object = find_object_mut(
+                state,
+                &request.bucket,
+                &request.key,
+                request.version_id.as_deref(),
+            )?;
+            object.staging_worker_id = request.staging_worker_id;
+            object.staging_error = request.error;
+            object.updated_at = Some(now_timestamp());
         }
         MetadataCommand::CreateBucket(mut bucket) => {
             if state.buckets.contains_key(&bucket.name) {
-                return Err(Status::already_exists(format!(
-                    "bucket already exists: {}",
-                    bucket.name
-                )));
+                return Err(status_with_reason(
+                    tonic::Code::AlreadyExists,
+                    format!("bucket already exists: {}", bucket.name),
+                    common::ErrorReason::BucketAlreadyExists,
+                ));
             }
             if bucket.created_at.is_none() {
                 bucket.created_at = Some(now_timestamp());
@@ -164,14 +233,88 @@ pub(crate) fn apply_command(
                 .values()
                 .any(|object| object.bucket == request.name);
             if has_objects {
-                return Err(Status::failed_precondition("bucket is not empty"));
+                return Err(status_with_reason(
+                    tonic::Code::FailedPrecondition,
+                    "bucket is not empty",
+                    common::ErrorReason::BucketNotEmpty,
+                ));
             }
             state
                 .buckets
                 .remove(&request.name)
                 .ok_or_else(|| Status::not_found(format!("bucket not found: {}", request.name)))?;
+            state.bucket_policies.remove(&request.name);
+            state.bucket_lifecycles.remove(&request.name);
+        }
+        MetadataCommand::PutBucketPolicy(mut policy) => {
+            if !state.buckets.contains_key(&policy.bucket) {
+                return Err(Status::not_found(format!(
+                    "bucket not found: {}",
+                    policy.bucket
+                )));
+            }
+            if policy.policy.trim().is_empty() {
+                return Err(Status::invalid_argument("bucket policy must not be empty"));
+            }
+            if policy.updated_at.is_none() {
+                policy.updated_at = Some(now_timestamp());
+            }
+            state.bucket_policies.insert(policy.bucket.clone(), policy);
+        }
+        MetadataCommand::DeleteBucketPolicy(request) => {
+            if !state.buckets.contains_key(&request.bucket) {
+                return Err(Status::not_found(format!(
+                    "bucket not found: {}",
+                    request.bucket
+                )));
+            }
+            state.bucket_policies.remove(&request.bucket);
+        }
+        MetadataCommand::PutBucketLifecycle(mut lifecycle) => {
+            if !state.buckets.contains_key(&lifecycle.bucket) {
+                return Err(status_with_reason(
+                    tonic::Code::NotFound,
+                    format!("bucket not found: {}", lifecycle.bucket),
+                    common::ErrorReason::NoSuchBucket,
+                ));
+            }
+            lifecycle::validate(&lifecycle)?;
+            if lifecycle.updated_at.is_none() {
+                lifecycle.updated_at = Some(now_timestamp());
+            }
+            state
+                .bucket_lifecycles
+                .insert(lifecycle.bucket.clone(), lifecycle);
+        }
+        MetadataCommand::DeleteBucketLifecycle(request) => {
+            if !state.buckets.contains_key(&request.bucket) {
+                return Err(Status::not_found(format!(
+                    "bucket not found: {}",
+                    request.bucket
+                )));
+            }
+            state.bucket_lifecycles.remove(&request.bucket);
+        }
+        MetadataCommand::ApplyLifecycle { now } => {
+            let sweep = lifecycle::evaluate(state, &now);
+            let mut buckets = std::collections::BTreeSet::new();
+            for key in sweep.expired_objects {
+                if let Some(object) = state.objects.remove(&key) {
+                    account_archived_bytes(state, &object, false);
+                    buckets.insert(object.bucket);
+                }
+            }
+            for bucket in buckets {
+                refresh_bucket_stats(state, &bucket);
+            }
+            for key in sweep.expired_restores {
+                if let Some(object) = state.objects.get_mut(&key) {
+                    object.restore_status = Some(common::RestoreStatus::RestoreExpired as i32);
+                }
+            }
         }
         MetadataCommand::PutArchiveBundle(mut bundle) => {
+            validate_bundle_copies(state, &bundle)?;
             if bundle.created_at.is_none() {
                 bundle.created_at = Some(now_timestamp());
             }
@@ -188,42 +331,266 @@ pub(crate) fn apply_command(
                 bundle.completed_at = Some(now_timestamp());
             }
         }
+        MetadataCommand::UpdateBundleCopy(request) => {
+            apply_bundle_copy_update(state, request)?;
+        }
         MetadataCommand::PutArchiveTask(mut task) => {
             if task.created_at.is_none() {
                 task.created_at = Some(now_timestamp());
             }
             state.archive_tasks.insert(task.id.clone(), task);
         }
-        MetadataCommand::UpdateArchiveTask(task) => {
+        MetadataCommand::UpdateArchiveTask(mut task) => {
             let current = state
                 .archive_tasks
                 .get(&task.id)
                 .ok_or_else(|| Status::not_found("archive task not found"))?;
-            validate_archive_task_transition(current.status, task.status)?;
+            let in_progress = common::ArchiveTaskStatus::ArchiveTaskInProgress as i32;
+            if current.status == in_progress && task.worker_id != current.worker_id {
+                return Err(lease_conflict("archive", &task.id, current.worker_id));
+            }
+            if current.status != task.status {
+                if task.status == in_progress {
+                    return Err(Status::failed_precondition(
+                        "archive tasks enter InProgress only through ClaimArchiveTask",
+                    ));
+                }
+                validate_archive_task_transition(current.status, task.status)?;
+            }
+            // 租约只由认领、心跳与过期处理维护
+            task.lease_expire_at = if task.status == in_progress {
+                current.lease_expire_at
+            } else {
+                None
+            };
             state.archive_tasks.insert(task.id.clone(), task);
         }
+        MetadataCommand::ClaimArchiveTask {
+            id,
+            worker_id,
+            lease_expire_at,
+            now,
+        } => {
+            let task = state
+                .archive_tasks
+                .get_mut(&id)
+                .ok_or_else(|| Status::not_found("archive task not found"))?;
+            let in_progress = common::ArchiveTaskStatus::ArchiveTaskInProgress as i32;
+            if task.status == in_progress && task.worker_id != Some(worker_id) {
+                return Err(lease_conflict("archive", &id, task.worker_id));
+            }
+            if task.status != in_progress {
+                validate_archive_task_transition(task.status, in_progress)?;
+                check_backoff("archive", &id, &task.next_attempt_at, &now)?;
+                task.status = in_progress;
+                task.worker_id = Some(worker_id);
+                task.started_at = Some(now);
+                task.next_attempt_at = None;
+            }
+            task.lease_expire_at = Some(lease_expire_at);
+        }
+        MetadataCommand::FailArchiveTask {
+            request,
+            now,
+            retry,
+        } => {
+            let task = state
+                .archive_tasks
+                .get_mut(&request.id)
+                .ok_or_else(|| Status::not_found("archive task not found"))?;
+            if task.status != common::ArchiveTaskStatus::ArchiveTaskInProgress as i32 {
+                return Err(Status::failed_precondition(format!(
+                    "archive task {} is not in progress",
+                    request.id
+                )));
+            }
+            if task.worker_id != Some(request.worker_id) {
+                return Err(lease_conflict("archive", &request.id, task.worker_id));
+            }
+            task.worker_id = None;
+            task.lease_expire_at = None;
+            task.started_at = None;
+            task.error = Some(request.error);
+            match schedule_retry(&mut task.retry_count, retriable(request.kind), &retry, now) {
+                Some(next_attempt_at) => {
+                    task.status = common::ArchiveTaskStatus::ArchiveTaskPending as i32;
+                    task.next_attempt_at = Some(next_attempt_at);
+                }
+                None => {
+                    task.status = common::ArchiveTaskStatus::ArchiveTaskFailed as i32;
+                    task.completed_at = Some(now);
+                }
+            }
+        }
         MetadataCommand::PutRecallTask(mut task) => {
             if task.created_at.is_none() {
                 task.created_at = Some(now_timestamp());
             }
             state.recall_tasks.insert(task.id.clone(), task);
+            refresh_media_waits(state);
         }
-        MetadataCommand::UpdateRecallTask(task) => {
+        MetadataCommand::UpdateRecallTask(mut task) => {
             let current = state
                 .recall_tasks
                 .get(&task.id)
                 .ok_or_else(|| Status::not_found("recall task not found"))?;
-            validate_restore_transition(Some(current.status), task.status)?;
+            let in_progress = common::RestoreStatus::RestoreInProgress as i32;
+            if current.status == in_progress && task.worker_id != current.worker_id {
+                return Err(lease_conflict("recall", &task.id, current.worker_id));
+            }
+            if current.status != task.status {
+                if task.status == in_progress {
+                    return Err(Status::failed_precondition(
+                        "recall tasks enter InProgress only through ClaimRecallTask",
+                    ));
+                }
+                validate_restore_transition(Some(current.status), task.status)?;
+            }
+            task.lease_expire_at = if task.status == in_progress {
+                current.lease_expire_at
+            } else {
+                None
+            };
             state.recall_tasks.insert(task.id.clone(), task);
         }
+        MetadataCommand::ClaimRecallTask {
+            id,
+            worker_id,
+            lease_expire_at,
+            now,
+        } => {
+            let task = state
+                .recall_tasks
+                .get_mut(&id)
+                .ok_or_else(|| Status::not_found("recall task not found"))?;
+            let in_progress = common::RestoreStatus::RestoreInProgress as i32;
+            if task.status == in_progress && task.worker_id != Some(worker_id) {
+                return Err(lease_conflict("recall", &id, task.worker_id));
+            }
+            if task.status != in_progress {
+                validate_restore_transition(Some(task.status), in_progress)?;
+                check_backoff("recall", &id, &task.next_attempt_at, &now)?;
+            }
+            task.lease_expire_at = Some(lease_expire_at);
+            if task.status != in_progress {
+                task.status = in_progress;
+                task.worker_id = Some(worker_id);
+                task.started_at = Some(now);
+                task.next_attempt_at = None;
+                let (bucket, key, version_id) = (
+                    task.bucket.clone(),
+                    task.key.clone(),
+                    task.version_id.clone(),
+                );
+                if let Ok(object) = find_object_mut(state, &bucket, &key, version_id.as_deref()) {
+                    if object.restore_status == Some(common::RestoreStatus::RestorePending as i32) {
+                        object.restore_status = Some(in_progress);
+                        object.updated_at = Some(now_timestamp());
+                    }
+                }
+            }
+        }
+        MetadataCommand::FailRecallTask {
+            request,
+            now,
+            retry,
+        } => {
+            let task = state
+                .recall_tasks
+                .get_mut(&request.id)
+                .ok_or_else(|| Status::not_found("recall task not found"))?;
+            let in_progress = common::RestoreStatus::RestoreInProgress as i32;
+            if task.status != in_progress {
+                return Err(Status::failed_precondition(format!(
+                    "recall task {} is not in progress",
+                    request.id
+                )));
+            }
+            if task.worker_id != Some(request.worker_id) {
+                return Err(lease_conflict("recall", &request.id, task.worker_id));
+            }
+            task.worker_id = None;
+            task.lease_expire_at = None;
+            task.started_at = None;
+            task.error = Some(request.error);
+            match schedule_retry(&mut task.retry_count, retriable(request.kind), &retry, now) {
+                Some(next_attempt_at) => {
+                    task.status = common::RestoreStatus::RestorePending as i32;
+                    task.next_attempt_at = Some(next_attempt_at);
+                }
+                None => {
+                    task.status = common::RestoreStatus::RestoreFailed as i32;
+                    task.completed_at = Some(now);
+                }
+            }
+            let (bucket, key, version_id, status) = (
+                task.bucket.clone(),
+                task.key.clone(),
+                task.version_id.clone(),
+                task.status,
+            );
+            if let Ok(object) = find_object_mut(state, &bucket, &key, version_id.as_deref()) {
+                if object.restore_status == Some(in_progress) {
+                    object.restore_status = Some(status);
+                    object.updated_at = Some(now);
+                }
+            }
+        }
+        MetadataCommand::ExpireTaskLeases { now, retry } => {
+            expire_task_leases(state, now, &retry);
+        }
         MetadataCommand::PutTape(mut tape) => {
             if tape.registered_at.is_none() {
                 tape.registered_at = Some(now_timestamp());
             }
             state.tapes.insert(tape.id.clone(), tape);
+            refresh_media_waits(state);
         }
         MetadataCommand::UpdateTape(tape) => {
             state.tapes.insert(tape.id.clone(), tape);
+            refresh_media_waits(state);
+        }
+        MetadataCommand::BeginTapeReclamation(request) => {
+            let tape = state
+                .tapes
+                .get_mut(&request.tape_id)
+                .ok_or_else(|| Status::not_found("tape not found"))?;
+            match common::TapeStatus::try_from(tape.status) {
+                Ok(common::TapeStatus::TapeOnline) => {
+                    tape.status = common::TapeStatus::TapeReclaiming as i32;
+                }
+                Ok(common::TapeStatus::TapeReclaiming) => {}
+                _ => {
+                    return Err(Status::failed_precondition(format!(
+                        "tape {} is not online and cannot be reclaimed",
+                        request.tape_id
+                    )));
+                }
+            }
+        }
+        MetadataCommand::CompleteTapeReclamation(request) => {
+            complete_tape_reclamation(state, &request.tape_id)?;
+        }
+        MetadataCommand::LabelTape(request) => label_tape(state, request)?,
+        MetadataCommand::ImportTape(request) => import_tape(state, request)?,
+        MetadataCommand::MoveTapeToPool(request) => move_tape_to_pool(state, request)?,
+        MetadataCommand::RetireTape(request) => retire_tape(state, &request.tape_id)?,
+        MetadataCommand::AllocateScratchTape { request, tape_id } => {
+            allocate_scratch_tape(state, request, &tape_id)?;
+        }
+        MetadataCommand::MarkTapeOffline(request) => mark_tape_offline(state, request)?,
+        MetadataCommand::MarkTapeOnline(request) => mark_tape_online(state, request)?,
+        MetadataCommand::RecordTapeVerification(request) => {
+            let tape = state
+                .tapes
+                .get_mut(&request.tape_id)
+                .ok_or_else(|| Status::not_found("tape not found"))?;
+            tape.last_verified_at = Some(now_timestamp());
+            tape.error_count = tape.error_count.saturating_add(request.errors);
+            // 出现损坏的磁带不再追加写入，剩余数据由 Scheduler 从其它副本重新写出
+            if request.errors > 0 {
+                tape.media_state = common::TapeMediaState::TapeMediaReadOnly as i32;
+            }
         }
         MetadataCommand::RegisterSchedulerWorker(mut worker) => {
             worker.last_heartbeat = Some(now_timestamp());
@@ -231,6 +598,7 @@ pub(crate) fn apply_command(
         }
         MetadataCommand::DeregisterSchedulerWorker(request) => {
             state.scheduler_workers.remove(&request.node_id);
+            requeue_worker_tasks(state, request.node_id);
         }
         MetadataCommand::RegisterCacheWorker(mut worker) => {
             worker.last_heartbeat = Some(now_timestamp());
@@ -264,7 +632,40 @@ pub(crate) fn apply_command(
             }
             .ok_or_else(|| Status::not_found("worker not found"))?;
         }
-        MetadataCommand::Heartbeat(request) => {
+        MetadataCommand::ExpireWorker {
+            worker_type,
+            node_id,
+            stale_before,
+        } => {
+            let (status, last_heartbeat) = match worker_type {
+                common::WorkerType::WorkerScheduler => state
+                    .scheduler_workers
+                    .get_mut(&node_id)
+                    .map(|worker| (&mut worker.status, worker.last_heartbeat)),
+                common::WorkerType::WorkerCache => state
+                    .cache_workers
+                    .get_mut(&node_id)
+                    .map(|worker| (&mut worker.status, worker.last_heartbeat)),
+                common::WorkerType::WorkerTape => state
+                    .tape_workers
+                    .get_mut(&node_id)
+                    .map(|worker| (&mut worker.status, worker.last_heartbeat)),
+                common::WorkerType::Unspecified => None,
+            }
+            .ok_or_else(|| Status::not_found("worker not found"))?;
+            let offline = common::NodeStatus::NodeOffline as i32;
+            if *status == offline || timestamp_sort_key(&last_heartbeat).0 >= stale_before {
+                return Ok(());
+            }
+            *status = offline;
+            if worker_type == common::WorkerType::WorkerScheduler {
+                requeue_worker_tasks(state, node_id);
+            }
+        }
+        MetadataCommand::Heartbeat {
+            request,
+            task_lease_expire_at,
+        } => {
             let now = Some(now_timestamp());
             match common::WorkerType::try_from(request.worker_type) {
                 Ok(common::WorkerType::WorkerScheduler) => {
@@ -273,11 +674,14 @@ pub(crate) fn apply_command(
                         .get_mut(&request.node_id)
                         .ok_or_else(|| Status::not_found("scheduler worker not found"))?;
                     worker.last_heartbeat = now;
+                    revive_worker(&mut worker.status);
+                    worker.drain = request.drain;
                     if let Some(heartbeat_request::Payload::Scheduler(payload)) = request.payload {
                         worker.pending_archive_tasks = payload.pending_archive_tasks;
                         worker.pending_recall_tasks = payload.pending_recall_tasks;
                         worker.active_jobs = payload.active_jobs;
                     }
+                    renew_task_leases(state, request.node_id, task_lease_expire_at);
                 }
                 Ok(common::WorkerType::WorkerCache) => {
                     let worker = state
@@ -285,6 +689,8 @@ pub(crate) fn apply_command(
                         .get_mut(&request.node_id)
                         .ok_or_else(|| Status::not_found("cache worker not found"))?;
                     worker.last_heartbeat = now;
+                    revive_worker(&mut worker.status);
+                    worker.drain = request.drain;
                     if let Some(heartbeat_request::Payload::Cache(payload)) = request.payload {
                         worker.used_capacity = payload.used_capacity;
                         worker.blob_count = payload.blob_count;
@@ -296,17 +702,325 @@ pub(crate) fn apply_command(
                         .get_mut(&request.node_id)
                         .ok_or_else(|| Status::not_found("tape worker not found"))?;
                     worker.last_heartbeat = now;
+                    revive_worker(&mut worker.status);
+                    worker.drain = request.drain;
                     if let Some(heartbeat_request::Payload::Tape(payload)) = request.payload {
                         worker.drives = payload.drives;
+                        record_tape_errors(state, payload.tape_errors);
                     }
                 }
                 _ => return Err(Status::invalid_argument("unknown worker type")),
             }
         }
+        MetadataCommand::CreateUser(mut user) => {
+            if user.user_id.is_empty() {
+                return Err(Status::invalid_argument("user_id is required"));
+            }
+            if state.users.contains_key(&user.user_id) {
+                return Err(Status::already_exists(format!(
+                    "user already exists: {}",
+                    user.user_id
+                )));
+            }
+            if user.created_at.is_none() {
+                user.created_at = Some(now_timestamp());
+            }
+            state.users.insert(user.user_id.clone(), user);
+        }
+        MetadataCommand::SetUserEnabled(request) => {
+            state
+                .users
+                .get_mut(&request.user_id)
+                .ok_or_else(|| Status::not_found(format!("user not found: {}", request.user_id)))?
+                .enabled = request.enabled;
+        }
+        MetadataCommand::PutAccessKey(key) => {
+            insert_access_key(state, key)?;
+        }
+        MetadataCommand::RotateAccessKey {
+            access_key_id,
+            replacement,
+        } => {
+            let current = state
+                .access_keys
+                .get(&access_key_id)
+                .ok_or_else(|| Status::not_found("access key not found"))?;
+            if current.status != common::AccessKeyStatus::AccessKeyActive as i32 {
+                return Err(Status::failed_precondition("access key is already revoked"));
+            }
+            if current.user_id != replacement.user_id {
+                return Err(Status::invalid_argument(
+                    "replacement key must belong to the same user",
+                ));
+            }
+            insert_access_key(state, replacement)?;
+            revoke_access_key(state, &access_key_id)?;
+        }
+        MetadataCommand::RevokeAccessKey(request) => {
+            revoke_access_key(state, &request.access_key_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 因心跳超时被标记为 NodeOffline 的 Worker 恢复心跳后重新上线；
+/// Draining / Maintenance 由运维显式设置，心跳不改变
+fn revive_worker(status: &mut i32) {
+    if *status == common::NodeStatus::NodeOffline as i32 {
+        *status = common::NodeStatus::NodeOnline as i32;
+    }
+}
+
+fn lease_conflict(kind: &str, id: &str, holder: Option<u64>) -> Status {
+    match holder {
+        Some(holder) => Status::aborted(format!(
+            "{kind} task {id} is claimed by scheduler worker {holder}"
+        )),
+        None => Status::aborted(format!("{kind} task {id} is already in progress")),
+    }
+}
+
+/// `node_id` 持有的进行中任务续约
+fn renew_task_leases(state: &mut MetadataState, node_id: u64, lease_expire_at: Timestamp) {
+    let archive_in_progress = common::ArchiveTaskStatus::ArchiveTaskInProgress as i32;
+    for task in state.archive_tasks.values_mut() {
+        if task.status == archive_in_progress && task.worker_id == Some(node_id) {
+            task.lease_expire_at = Some(lease_expire_at);
+        }
+    }
+    let recall_in_progress = common::RestoreStatus::RestoreInProgress as i32;
+    for task in state.recall_tasks.values_mut() {
+        if task.status == recall_in_progress && task.worker_id == Some(node_id) {
+            task.lease_expire_at = Some(lease_expire_at);
+        }
+    }
+}
+
+fn lease_expired(lease: &Option<Timestamp>, now: &Timestamp) -> bool {
+    lease.is_some() && timestamp_sort_key(lease) < (now.seconds, now.nanos)
+}
+
+/// 未指定分类的失败按可重试处理
+fn retriable(kind: i32) -> bool {
+    kind != common::TaskErrorKind::TaskErrorPermanent as i32
+}
+
+/// 允许重试时累加 `retry_count` 并返回下次可认领的时间；重试耗尽或不可重试时返回 None
+fn schedule_retry(
+    retry_count: &mut u32,
+    retriable: bool,
+    retry: &TaskRetryConfig,
+    now: Timestamp,
+) -> Option<Timestamp> {
+    if !retriable || *retry_count >= retry.max_retries {
+        return None;
+    }
+    *retry_count += 1;
+    let mut next_attempt_at = now;
+    next_attempt_at.seconds += retry.backoff(*retry_count).as_secs() as i64;
+    Some(next_attempt_at)
+}
+
+#[allow(clippy::result_large_err)]
+fn check_backoff(
+    kind: &str,
+    id: &str,
+    next_attempt_at: &Option<Timestamp>,
+    now: &Timestamp,
+) -> Result<(), Status> {
+    match next_attempt_at {
+        Some(next) if (next.seconds, next.nanos) > (now.seconds, now.nanos) => {
+            Err(Status::failed_precondition(format!(
+                "{kind} task {id} is backing off until {}",
+                next.seconds
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 租约过期的进行中任务按退避策略重新排队；重试耗尽的任务标记为 Failed
+///
+/// 取回任务的对象 restore_status 随任务退回 Pending 或标记为 Failed。
+fn expire_task_leases(state: &mut MetadataState, now: Timestamp, retry: &TaskRetryConfig) {
+    let archive_in_progress = common::ArchiveTaskStatus::ArchiveTaskInProgress as i32;
+    for task in state.archive_tasks.values_mut() {
+        if task.status != archive_in_progress || !lease_expired(&task.lease_expire_at, &now) {
+            continue;
+        }
+        task.worker_id = None;
+        task.lease_expire_at = None;
+        match schedule_retry(&mut task.retry_count, true, retry, now) {
+            Some(next_attempt_at) => {
+                task.status = common::ArchiveTaskStatus::ArchiveTaskPending as i32;
+                task.started_at = None;
+                task.next_attempt_at = Some(next_attempt_at);
+            }
+            None => {
+                task.status = common::ArchiveTaskStatus::ArchiveTaskFailed as i32;
+                task.completed_at = Some(now);
+                task.error = Some(format!("lease expired after {} retries", task.retry_count));
+            }
+        }
+    }
+
+    let recall_in_progress = common::RestoreStatus::RestoreInProgress as i32;
+    let mut objects = Vec::new();
+    for task in state.recall_tasks.values_mut() {
+        if task.status != recall_in_progress || !lease_expired(&task.lease_expire_at, &now) {
+            continue;
+        }
+        task.worker_id = None;
+        task.lease_expire_at = None;
+        match schedule_retry(&mut task.retry_count, true, retry, now) {
+            Some(next_attempt_at) => {
+                task.status = common::RestoreStatus::RestorePending as i32;
+                task.started_at = None;
+                task.next_attempt_at = Some(next_attempt_at);
+            }
+            None => {
+                task.status = common::RestoreStatus::RestoreFailed as i32;
+                task.completed_at = Some(now);
+                task.error = Some(format!("lease expired after {} retries", task.retry_count));
+            }
+        }
+        objects.push((task.bucket.clone(), task.key.clone(), task.status));
     }
+    for (bucket, key, status) in objects {
+        if let Ok(object) = find_object_mut(state, &bucket, &key, None) {
+            if object.restore_status == Some(recall_in_progress) {
+                object.restore_status = Some(status);
+                object.updated_at = Some(now);
+            }
+        }
+    }
+}
+
+/// 对象重新发起解冻时，其已失败的取回任务清零重试次数后重新排队
+fn reopen_failed_recalls(
+    state: &mut MetadataState,
+    bucket: &str,
+    key: &str,
+    expire_at: Option<Timestamp>,
+) {
+    let failed = common::RestoreStatus::RestoreFailed as i32;
+    for task in state.recall_tasks.values_mut() {
+        if task.bucket != bucket || task.key != key || task.status != failed {
+            continue;
+        }
+        task.status = common::RestoreStatus::RestorePending as i32;
+        task.retry_count = 0;
+        task.error = None;
+        task.next_attempt_at = None;
+        task.started_at = None;
+        task.completed_at = None;
+        if let Some(expire_at) = expire_at {
+            task.expire_at = Some(expire_at);
+        }
+    }
+}
+
+/// Scheduler Worker 离线或注销后，将其进行中的归档/取回任务退回待调度
+///
+/// 取回任务连同对象的 restore_status 从 InProgress 退回 Pending，由其它
+/// Scheduler 重新从磁带读取；等待介质的任务只解除归属。
+fn requeue_worker_tasks(state: &mut MetadataState, node_id: u64) {
+    for task in state.archive_tasks.values_mut() {
+        if task.worker_id != Some(node_id) {
+            continue;
+        }
+        task.worker_id = None;
+        task.lease_expire_at = None;
+        if task.status == common::ArchiveTaskStatus::ArchiveTaskInProgress as i32 {
+            task.status = common::ArchiveTaskStatus::ArchiveTaskPending as i32;
+            task.started_at = None;
+        }
+    }
+
+    let mut requeued_objects = Vec::new();
+    for task in state.recall_tasks.values_mut() {
+        if task.worker_id != Some(node_id) || !is_pending_restore_status(task.status) {
+            continue;
+        }
+        task.worker_id = None;
+        task.lease_expire_at = None;
+        if task.status == common::RestoreStatus::RestoreInProgress as i32 {
+            task.status = common::RestoreStatus::RestorePending as i32;
+            task.started_at = None;
+            requeued_objects.push((task.bucket.clone(), task.key.clone()));
+        }
+    }
+    for (bucket, key) in requeued_objects {
+        if let Ok(object) = find_object_mut(state, &bucket, &key, None) {
+            if object.restore_status == Some(common::RestoreStatus::RestoreInProgress as i32) {
+                object.restore_status = Some(common::RestoreStatus::RestorePending as i32);
+                object.updated_at = Some(now_timestamp());
+            }
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn insert_access_key(state: &mut MetadataState, key: common::AccessKeyInfo) -> Result<(), Status> {
+    if !state.users.contains_key(&key.user_id) {
+        return Err(Status::not_found(format!(
+            "user not found: {}",
+            key.user_id
+        )));
+    }
+    if key.access_key_id.is_empty() || key.encrypted_secret.is_empty() {
+        return Err(Status::invalid_argument(
+            "access_key_id and encrypted_secret are required",
+        ));
+    }
+    // secret 明文不进入状态机与快照
+    if !key.secret_access_key.is_empty() {
+        return Err(Status::invalid_argument(
+            "access key secrets must be encrypted before they are stored",
+        ));
+    }
+    if state.access_keys.contains_key(&key.access_key_id) {
+        return Err(Status::already_exists("access key already exists"));
+    }
+    state.access_keys.insert(key.access_key_id.clone(), key);
+    Ok(())
+}
 
+#[allow(clippy::result_large_err)]
+fn revoke_access_key(state: &mut MetadataState, access_key_id: &str) -> Result<(), Status> {
+    let key = state
+        .access_keys
+        .get_mut(access_key_id)
+        .ok_or_else(|| Status::not_found("access key not found"))?;
+    if key.status != common::AccessKeyStatus::AccessKeyRevoked as i32 {
+        key.status = common::AccessKeyStatus::AccessKeyRevoked as i32;
+        key.revoked_at = Some(now_timestamp());
+    }
     Ok(())
 }
+/// 每盘磁带保留的最近错误条数，与 Tape Worker 的驱动错误历史一致
+const TAPE_ERROR_HISTORY_LEN: usize = 32;
+
+/// 将 Tape Worker 上报的介质错误计入磁带的错误计数与错误历史
+fn record_tape_errors(state: &mut MetadataState, events: Vec<common::DriveErrorEvent>) {
+    for event in events {
+        let Some(tape) = event
+            .tape_id
+            .as_ref()
+            .and_then(|tape_id| state.tapes.get_mut(tape_id))
+        else {
+            continue;
+        };
+        tape.error_count += 1;
+        tape.error_history.push(event);
+        if tape.error_history.len() > TAPE_ERROR_HISTORY_LEN {
+            let excess = tape.error_history.len() - TAPE_ERROR_HISTORY_LEN;
+            tape.error_history.drain(..excess);
+        }
+    }
+}
+
 const SNAPSHOT_MAGIC: &[u8] = b"COLDMETA2\n";
 const MAX_SNAPSHOT_MESSAGES_PER_SECTION: u64 = 1_000_000;
 const MAX_SNAPSHOT_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
@@ -316,7 +1030,7 @@ pub(crate) async fn load_snapshot(path: &Path) -> Result<MetadataState> {
     decode_snapshot(&bytes)
 }
 
-pub(crate) async fn save_snapshot(path: &Path, state: &MetadataState) -> Result<()> {
+pub async fn save_snapshot(path: &Path, state: &MetadataState) -> Result<()> {
     if let Some(parent) = path.parent() {
         tokio::fs::create_dir_all(parent).await?;
     }
@@ -337,6 +1051,10 @@ pub(crate) fn encode_snapshot(state: &MetadataState) -> Vec<u8> {
     write_messages(&mut out, state.scheduler_workers.values());
     write_messages(&mut out, state.cache_workers.values());
     write_messages(&mut out, state.tape_workers.values());
+    write_messages(&mut out, state.users.values());
+    write_messages(&mut out, state.access_keys.values());
+    write_messages(&mut out, state.bucket_policies.values());
+    write_messages(&mut out, state.bucket_lifecycles.values());
     out
 }
 
@@ -355,9 +1073,14 @@ pub(crate) fn decode_snapshot(bytes: &[u8]) -> Result<MetadataState> {
     let scheduler_workers = read_messages::<common::SchedulerWorkerInfo>(&mut cursor)?;
     let cache_workers = read_messages::<common::CacheWorkerInfo>(&mut cursor)?;
     let tape_workers = read_messages::<common::TapeWorkerInfo>(&mut cursor)?;
+    // 以下为后续版本追加的分节，旧快照中不存在
+    let users = read_optional_messages::<common::UserInfo>(&mut cursor)?;
+    let access_keys = read_optional_messages::<common::AccessKeyInfo>(&mut cursor)?;
+    let bucket_policies = read_optional_messages::<common::BucketPolicy>(&mut cursor)?;
+    let bucket_lifecycles = read_optional_messages::<common::BucketLifecycle>(&mut cursor)?;
     anyhow::ensure!(cursor.is_empty(), "trailing bytes in metadata snapshot");
 
-    Ok(MetadataState {
+    let mut state = MetadataState {
         objects: objects
             .into_iter()
             .map(|object| {
@@ -403,7 +1126,26 @@ pub(crate) fn decode_snapshot(bytes: &[u8]) -> Result<MetadataState> {
             .into_iter()
             .map(|worker| (worker.node_id, worker))
             .collect(),
-    })
+        users: users
+            .into_iter()
+            .map(|user| (user.user_id.clone(), user))
+            .collect(),
+        access_keys: access_keys
+            .into_iter()
+            .map(|key| (key.access_key_id.clone(), key))
+            .collect(),
+        bucket_policies: bucket_policies
+            .into_iter()
+            .map(|policy| (policy.bucket.clone(), policy))
+            .collect(),
+        bucket_lifecycles: bucket_lifecycles
+            .into_iter()
+            .map(|lifecycle| (lifecycle.bucket.clone(), lifecycle))
+            .collect(),
+    };
+    // 旧快照没有有效/失效字节统计
+    rebuild_space_accounting(&mut state);
+    Ok(state)
 }
 
 fn write_messages<'a, M, I>(out: &mut Vec<u8>, messages: I)
@@ -446,6 +1188,17 @@ where
     Ok(messages)
 }
 
+fn read_optional_messages<M>(cursor: &mut &[u8]) -> Result<Vec<M>>
+where
+    M: Message + Default,
+{
+    if cursor.is_empty() {
+        Ok(Vec::new())
+    } else {
+        read_messages(cursor)
+    }
+}
+
 fn read_u64(cursor: &mut &[u8]) -> Result<u64> {
     anyhow::ensure!(cursor.len() >= 8, "truncated metadata snapshot header");
     let (bytes, rest) = cursor.split_at(8);
@@ -480,7 +1233,7 @@ fn refresh_bucket_stats(state: &mut MetadataState, bucket_name: &str) {
 }
 
 #[allow(clippy::result_large_err)]
-pub(crate) fn find_object(
+pub fn find_object(
     state: &MetadataState,
     bucket: &str,
     key: &str,
@@ -552,7 +1305,7 @@ fn find_object_mut<'a>(
     }
 }
 
-fn timestamp_sort_key(ts: &Option<Timestamp>) -> (i64, i32) {
+pub(crate) fn timestamp_sort_key(ts: &Option<Timestamp>) -> (i64, i32) {
     ts.as_ref()
         .map(|ts| (ts.seconds, ts.nanos))
         .unwrap_or_default()
@@ -581,10 +1334,15 @@ fn validate_restore_transition(current: Option<i32>, next: i32) -> Result<(), St
                 common::RestoreStatus::RestoreCompleted | common::RestoreStatus::RestoreFailed
             )
         }
-        Some(common::RestoreStatus::RestoreCompleted) => {
-            matches!(next, common::RestoreStatus::RestoreExpired)
+        // Completed → Completed 延长解冻副本的过期时间
+        Some(common::RestoreStatus::RestoreCompleted) => matches!(
+            next,
+            common::RestoreStatus::RestoreCompleted | common::RestoreStatus::RestoreExpired
+        ),
+        // 重新发起解冻
+        Some(common::RestoreStatus::RestoreExpired | common::RestoreStatus::RestoreFailed) => {
+            matches!(next, common::RestoreStatus::RestorePending)
         }
-        Some(common::RestoreStatus::RestoreExpired | common::RestoreStatus::RestoreFailed) => false,
         Some(common::RestoreStatus::Unspecified) => true,
     };
 
@@ -597,38 +1355,721 @@ fn validate_restore_transition(current: Option<i32>, next: i32) -> Result<(), St
     }
 }
 
+/// 各副本必须位于不同磁带；已登记位置的磁带还要求位置互不相同
 #[allow(clippy::result_large_err)]
-fn validate_archive_bundle_transition(current: i32, next: i32) -> Result<(), Status> {
-    let current = common::ArchiveBundleStatus::try_from(current)
-        .map_err(|_| Status::invalid_argument("invalid archive bundle status"))?;
-    let next = common::ArchiveBundleStatus::try_from(next)
-        .map_err(|_| Status::invalid_argument("invalid archive bundle status"))?;
-    let valid = match current {
-        common::ArchiveBundleStatus::BundlePending => {
-            matches!(next, common::ArchiveBundleStatus::BundleWriting)
+fn validate_bundle_copies(
+    state: &MetadataState,
+    bundle: &common::ArchiveBundle,
+) -> Result<(), Status> {
+    if bundle.copies.is_empty() {
+        return Ok(());
+    }
+    if bundle.copies.len() < required_copies(bundle) {
+        return Err(Status::invalid_argument(
+            "archive bundle has fewer copies than required_copies",
+        ));
+    }
+    let mut tapes = std::collections::HashSet::new();
+    let mut locations = std::collections::HashSet::new();
+    for copy in &bundle.copies {
+        if !tapes.insert(copy.tape_id.as_str()) {
+            return Err(Status::invalid_argument(format!(
+                "archive bundle copies share tape {}",
+                copy.tape_id
+            )));
         }
-        common::ArchiveBundleStatus::BundleWriting => matches!(
-            next,
-            common::ArchiveBundleStatus::BundleCompleted
-                | common::ArchiveBundleStatus::BundleFailed
-        ),
-        common::ArchiveBundleStatus::BundleFailed => {
-            matches!(next, common::ArchiveBundleStatus::BundlePending)
+        let location = copy.location.clone().or_else(|| {
+            state
+                .tapes
+                .get(&copy.tape_id)
+                .and_then(|tape| tape.location.clone())
+        });
+        if let Some(location) = location {
+            if !locations.insert(location.clone()) {
+                return Err(Status::invalid_argument(format!(
+                    "archive bundle copies share location {location}"
+                )));
+            }
         }
-        common::ArchiveBundleStatus::BundleCompleted => false,
-        common::ArchiveBundleStatus::Unspecified => true,
-    };
-    if valid {
-        Ok(())
-    } else {
-        Err(Status::failed_precondition(
-            "invalid archive bundle state transition",
-        ))
     }
+    Ok(())
+}
+
+fn required_copies(bundle: &common::ArchiveBundle) -> usize {
+    bundle.required_copies.max(1) as usize
+}
+
+fn durable_copies(bundle: &common::ArchiveBundle) -> impl Iterator<Item = &common::BundleCopy> {
+    bundle
+        .copies
+        .iter()
+        .filter(|copy| copy.status == common::ArchiveBundleStatus::BundleCompleted as i32)
+}
+
+/// 对象在某一副本上的逻辑块地址
+fn copy_block_offset(copy: &common::BundleCopy, entry: &common::BundleEntry) -> u64 {
+    copy.start_block + entry.offset_in_bundle / u64::from(copy.block_size.max(1))
 }
 
+/// 更新单个副本状态；副本数达到 `required_copies` 时归档包完成，包内对象转为 COLD
 #[allow(clippy::result_large_err)]
-fn validate_archive_task_transition(current: i32, next: i32) -> Result<(), Status> {
+fn apply_bundle_copy_update(
+    state: &mut MetadataState,
+    request: UpdateBundleCopyRequest,
+) -> Result<(), Status> {
+    let bundle = state
+        .archive_bundles
+        .get_mut(&request.bundle_id)
+        .ok_or_else(|| Status::not_found("archive bundle not found"))?;
+    let copy = bundle
+        .copies
+        .iter_mut()
+        .find(|copy| copy.tape_id == request.tape_id)
+        .ok_or_else(|| Status::not_found("archive bundle copy not found"))?;
+    // 副本可以不经 Writing 直接回报写入结果
+    let reported = [
+        common::ArchiveBundleStatus::BundleCompleted as i32,
+        common::ArchiveBundleStatus::BundleFailed as i32,
+    ];
+    if !(copy.status == common::ArchiveBundleStatus::BundlePending as i32
+        && reported.contains(&request.status))
+    {
+        validate_archive_bundle_transition(copy.status, request.status)?;
+    }
+    copy.status = request.status;
+    copy.error = request.error;
+    if request.status == common::ArchiveBundleStatus::BundleCompleted as i32 {
+        copy.filemark_start = request.filemark_start;
+        copy.filemark_end = request.filemark_end;
+        copy.start_block = request.start_block;
+        copy.block_size = request.block_size;
+        copy.completed_at = Some(now_timestamp());
+    }
+
+    let durable: Vec<common::BundleCopy> = durable_copies(bundle).cloned().collect();
+    if durable.len() < required_copies(bundle) {
+        return Ok(());
+    }
+
+    // 归档包已完成后再落盘的副本只扩充 tape_set，主副本保持不变
+    let tape_set: Vec<String> = durable.iter().map(|copy| copy.tape_id.clone()).collect();
+    let completing = bundle.status != common::ArchiveBundleStatus::BundleCompleted as i32;
+    if completing {
+        let primary = &durable[0];
+        bundle.status = common::ArchiveBundleStatus::BundleCompleted as i32;
+        bundle.completed_at = Some(now_timestamp());
+        bundle.tape_id = primary.tape_id.clone();
+        bundle.filemark_start = primary.filemark_start;
+        bundle.filemark_end = primary.filemark_end;
+        for entry in bundle.entries.iter_mut() {
+            entry.tape_block_offset = copy_block_offset(primary, entry);
+        }
+    }
+    bundle.tape_set = tape_set.clone();
+
+    let bundle_id = bundle.id.clone();
+    let entries = bundle.entries.clone();
+    let primary_tape = bundle.tape_id.clone();
+    let (mut live_bytes, mut dead_bytes) = (0, 0);
+    for entry in entries {
+        let key = ObjectKey::new(
+            entry.bucket.clone(),
+            entry.key.clone(),
+            entry.version_id.clone(),
+        );
+        let Some(object) = state.objects.get(&key) else {
+            dead_bytes += entry.size;
+            continue;
+        };
+        // 写入期间被覆盖的对象不指向本归档包；回收写入的新归档包接管对象时释放旧归档包中的字节
+        let repoint = if completing {
+            object.checksum.eq_ignore_ascii_case(&entry.checksum)
+        } else {
+            object.archive_id.as_deref() == Some(bundle_id.as_str())
+        };
+        if !repoint {
+            dead_bytes += entry.size;
+            continue;
+        }
+        live_bytes += entry.size;
+        if completing && object.archive_id.as_deref() != Some(bundle_id.as_str()) {
+            let previous = object.clone();
+            account_archived_bytes(state, &previous, false);
+        }
+        let object = state.objects.get_mut(&key).expect("object checked above");
+        object.storage_class = common::StorageClass::Cold as i32;
+        object.archive_id = Some(bundle_id.clone());
+        object.tape_id = Some(primary_tape.clone());
+        object.tape_set = tape_set.clone();
+        object.tape_block_offset = Some(entry.tape_block_offset);
+        object.updated_at = Some(now_timestamp());
+        repoint_pending_recalls(state, &key, &bundle_id, &primary_tape, &tape_set, &entry);
+    }
+
+    let bundle = state
+        .archive_bundles
+        .get_mut(&bundle_id)
+        .expect("bundle looked up above");
+    if completing {
+        bundle.live_bytes = live_bytes;
+        bundle.dead_bytes = dead_bytes;
+    }
+    let (live_bytes, dead_bytes, total_size) =
+        (bundle.live_bytes, bundle.dead_bytes, bundle.total_size);
+    for tape_id in &tape_set {
+        if let Some(tape) = state.tapes.get_mut(tape_id) {
+            if !tape.archive_bundle_ids.contains(&bundle_id) {
+                tape.archive_bundle_ids.push(bundle_id.clone());
+                tape.live_bytes += live_bytes;
+                tape.dead_bytes += dead_bytes;
+                tape.used_bytes += total_size;
+                tape.remaining_bytes = tape.capacity_bytes.saturating_sub(tape.used_bytes);
+                if tape.remaining_bytes == 0 {
+                    tape.media_state = common::TapeMediaState::TapeMediaFull as i32;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 对象开始 (`live`) 或不再引用其归档包条目时，调整归档包及其所在磁带的有效/失效字节
+fn account_archived_bytes(state: &mut MetadataState, object: &common::ObjectMetadata, live: bool) {
+    let Some(bundle) = object
+        .archive_id
+        .as_ref()
+        .and_then(|id| state.archive_bundles.get_mut(id))
+    else {
+        return;
+    };
+    let Some(size) = bundle
+        .entries
+        .iter()
+        .find(|entry| {
+            entry.bucket == object.bucket
+                && entry.key == object.key
+                && entry.version_id == object.version_id
+        })
+        .map(|entry| entry.size)
+    else {
+        return;
+    };
+    let shift = |live_bytes: &mut u64, dead_bytes: &mut u64| {
+        if live {
+            *live_bytes += size;
+            *dead_bytes = dead_bytes.saturating_sub(size);
+        } else {
+            *live_bytes = live_bytes.saturating_sub(size);
+            *dead_bytes += size;
+        }
+    };
+    shift(&mut bundle.live_bytes, &mut bundle.dead_bytes);
+    for tape_id in &bundle.tape_set {
+        if let Some(tape) = state.tapes.get_mut(tape_id) {
+            shift(&mut tape.live_bytes, &mut tape.dead_bytes);
+        }
+    }
+}
+
+/// 尚未认领的取回任务跟随对象迁移到新归档包，进行中的任务继续读取原位置
+fn repoint_pending_recalls(
+    state: &mut MetadataState,
+    key: &ObjectKey,
+    bundle_id: &str,
+    primary_tape: &str,
+    tape_set: &[String],
+    entry: &common::BundleEntry,
+) {
+    let pending = [
+        common::RestoreStatus::RestorePending as i32,
+        common::RestoreStatus::RestoreWaitingForMedia as i32,
+    ];
+    for task in state.recall_tasks.values_mut() {
+        if task.bucket == key.bucket
+            && task.key == key.key
+            && task.version_id == key.version_id
+            && pending.contains(&task.status)
+            && task.archive_id != bundle_id
+        {
+            task.archive_id = bundle_id.to_string();
+            task.tape_id = primary_tape.to_string();
+            task.tape_set = tape_set.to_vec();
+            task.tape_block_offset = entry.tape_block_offset;
+        }
+    }
+}
+
+/// 回收完成：磁带上不能再有对象引用或进行中的取回，解除全部归档包副本后清零并转为 Scratch
+#[allow(clippy::result_large_err)]
+fn complete_tape_reclamation(state: &mut MetadataState, tape_id: &str) -> Result<(), Status> {
+    let tape = state
+        .tapes
+        .get(tape_id)
+        .ok_or_else(|| Status::not_found("tape not found"))?;
+    if tape.status != common::TapeStatus::TapeReclaiming as i32 {
+        return Err(Status::failed_precondition(format!(
+            "tape {tape_id} is not being reclaimed"
+        )));
+    }
+    ensure_tape_unreferenced(state, tape_id)?;
+    detach_tape(state, tape_id);
+    let tape = state.tapes.get_mut(tape_id).expect("tape checked above");
+    tape.status = common::TapeStatus::TapeScratch as i32;
+    tape.media_state = common::TapeMediaState::TapeMediaAppendable as i32;
+    tape.pool.clear();
+    tape.used_bytes = 0;
+    tape.remaining_bytes = tape.capacity_bytes;
+    tape.live_bytes = 0;
+    tape.dead_bytes = 0;
+    tape.last_verified_at = None;
+    Ok(())
+}
+
+/// 磁带上没有对象引用的数据、写入中的副本和进行中的取回
+#[allow(clippy::result_large_err)]
+fn ensure_tape_unreferenced(state: &MetadataState, tape_id: &str) -> Result<(), Status> {
+    if let Some(object) = state.objects.values().find(|object| {
+        object.tape_id.as_deref() == Some(tape_id) || object.tape_set.iter().any(|id| id == tape_id)
+    }) {
+        return Err(Status::failed_precondition(format!(
+            "object {}/{} is still archived on tape {tape_id}",
+            object.bucket, object.key
+        )));
+    }
+    let written = [
+        common::ArchiveBundleStatus::BundleCompleted as i32,
+        common::ArchiveBundleStatus::BundleFailed as i32,
+    ];
+    if let Some(bundle) = state.archive_bundles.values().find(|bundle| {
+        bundle
+            .copies
+            .iter()
+            .any(|copy| copy.tape_id == tape_id && !written.contains(&copy.status))
+    }) {
+        return Err(Status::failed_precondition(format!(
+            "archive bundle {} is still being written to tape {tape_id}",
+            bundle.id
+        )));
+    }
+    if let Some(task) = state.recall_tasks.values().find(|task| {
+        task.tape_id == tape_id && task.status == common::RestoreStatus::RestoreInProgress as i32
+    }) {
+        return Err(Status::failed_precondition(format!(
+            "recall task {} is reading tape {tape_id}",
+            task.id
+        )));
+    }
+    Ok(())
+}
+
+/// 从归档包中移除该磁带上的副本
+fn detach_tape(state: &mut MetadataState, tape_id: &str) {
+    for bundle in state.archive_bundles.values_mut() {
+        bundle.copies.retain(|copy| copy.tape_id != tape_id);
+        bundle.tape_set.retain(|id| id != tape_id);
+        if bundle.tape_id == tape_id {
+            bundle.tape_id = bundle.tape_set.first().cloned().unwrap_or_default();
+        }
+    }
+    // 只存在于本盘且已无有效对象的归档包整体删除
+    state.archive_bundles.retain(|_, bundle| {
+        !(bundle.tape_set.is_empty()
+            && bundle.copies.is_empty()
+            && bundle.live_bytes == 0
+            && bundle.status == common::ArchiveBundleStatus::BundleCompleted as i32)
+    });
+    if let Some(tape) = state.tapes.get_mut(tape_id) {
+        tape.archive_bundle_ids.clear();
+    }
+}
+
+/// 未指定容量时按磁带格式取默认原生容量
+#[allow(clippy::result_large_err)]
+fn tape_capacity(format: &str, capacity_bytes: u64) -> Result<u64, Status> {
+    if capacity_bytes > 0 {
+        return Ok(capacity_bytes);
+    }
+    tape_format_capacity_bytes(format).ok_or_else(|| {
+        Status::invalid_argument(format!(
+            "unknown tape format {format:?}; capacity_bytes is required"
+        ))
+    })
+}
+
+#[allow(clippy::result_large_err)]
+fn label_tape(state: &mut MetadataState, request: LabelTapeRequest) -> Result<(), Status> {
+    if request.tape_id.is_empty() {
+        return Err(Status::invalid_argument("tape_id is required"));
+    }
+    if state.tapes.contains_key(&request.tape_id) {
+        return Err(Status::already_exists(format!(
+            "tape {} is already registered",
+            request.tape_id
+        )));
+    }
+    let capacity_bytes = tape_capacity(&request.format, request.capacity_bytes)?;
+    let status = match request.pool {
+        Some(_) => common::TapeStatus::TapeOnline,
+        None => common::TapeStatus::TapeScratch,
+    };
+    state.tapes.insert(
+        request.tape_id.clone(),
+        common::TapeInfo {
+            id: request.tape_id,
+            barcode: request.barcode,
+            format: request.format,
+            status: status as i32,
+            location: request.location,
+            capacity_bytes,
+            remaining_bytes: capacity_bytes,
+            registered_at: Some(now_timestamp()),
+            pool: request.pool.unwrap_or_default(),
+            media_state: common::TapeMediaState::TapeMediaAppendable as i32,
+            ..Default::default()
+        },
+    );
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+fn import_tape(state: &mut MetadataState, request: ImportTapeRequest) -> Result<(), Status> {
+    if request.tape_id.is_empty() {
+        return Err(Status::invalid_argument("tape_id is required"));
+    }
+    if state.tapes.contains_key(&request.tape_id) {
+        return Err(Status::already_exists(format!(
+            "tape {} is already registered",
+            request.tape_id
+        )));
+    }
+    let capacity_bytes = tape_capacity(&request.format, request.capacity_bytes)?;
+    if request.used_bytes > capacity_bytes {
+        return Err(Status::invalid_argument(format!(
+            "used_bytes {} exceeds capacity {capacity_bytes}",
+            request.used_bytes
+        )));
+    }
+    state.tapes.insert(
+        request.tape_id.clone(),
+        common::TapeInfo {
+            id: request.tape_id,
+            barcode: request.barcode,
+            format: request.format,
+            status: common::TapeStatus::TapeOnline as i32,
+            location: request.location,
+            capacity_bytes,
+            used_bytes: request.used_bytes,
+            remaining_bytes: capacity_bytes - request.used_bytes,
+            registered_at: Some(now_timestamp()),
+            pool: request.pool,
+            media_state: common::TapeMediaState::TapeMediaReadOnly as i32,
+            ..Default::default()
+        },
+    );
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+fn move_tape_to_pool(
+    state: &mut MetadataState,
+    request: MoveTapeToPoolRequest,
+) -> Result<(), Status> {
+    let tape = state
+        .tapes
+        .get_mut(&request.tape_id)
+        .ok_or_else(|| Status::not_found("tape not found"))?;
+    if tape.status == common::TapeStatus::TapeRetired as i32 {
+        return Err(Status::failed_precondition(format!(
+            "tape {} is retired",
+            request.tape_id
+        )));
+    }
+    if tape.status == common::TapeStatus::TapeScratch as i32 {
+        tape.status = common::TapeStatus::TapeOnline as i32;
+        tape.media_state = common::TapeMediaState::TapeMediaAppendable as i32;
+    }
+    tape.pool = request.pool;
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+fn retire_tape(state: &mut MetadataState, tape_id: &str) -> Result<(), Status> {
+    let tape = state
+        .tapes
+        .get(tape_id)
+        .ok_or_else(|| Status::not_found("tape not found"))?;
+    if tape.status == common::TapeStatus::TapeRetired as i32 {
+        return Ok(());
+    }
+    ensure_tape_unreferenced(state, tape_id)?;
+    detach_tape(state, tape_id);
+    let tape = state.tapes.get_mut(tape_id).expect("tape checked above");
+    tape.status = common::TapeStatus::TapeRetired as i32;
+    tape.media_state = common::TapeMediaState::TapeMediaReadOnly as i32;
+    tape.live_bytes = 0;
+    tape.dead_bytes = 0;
+    Ok(())
+}
+
+/// 可分配给 `request.pool` 的 Scratch 磁带，按磁带 ID 取第一盘
+pub(crate) fn select_scratch_tape(
+    state: &MetadataState,
+    request: &AllocateScratchTapeRequest,
+) -> Option<String> {
+    state
+        .tapes
+        .values()
+        .filter(|tape| scratch_tape_fits(tape, request))
+        .map(|tape| tape.id.clone())
+        .min()
+}
+
+fn scratch_tape_fits(tape: &common::TapeInfo, request: &AllocateScratchTapeRequest) -> bool {
+    tape.status == common::TapeStatus::TapeScratch as i32
+        && tape.remaining_bytes >= request.min_free_bytes
+        && tape
+            .location
+            .as_ref()
+            .is_none_or(|location| !request.exclude_locations.contains(location))
+}
+
+#[allow(clippy::result_large_err)]
+fn allocate_scratch_tape(
+    state: &mut MetadataState,
+    request: AllocateScratchTapeRequest,
+    tape_id: &str,
+) -> Result<(), Status> {
+    let tape = state
+        .tapes
+        .get(tape_id)
+        .ok_or_else(|| Status::not_found("tape not found"))?;
+    if !scratch_tape_fits(tape, &request) {
+        return Err(Status::failed_precondition(format!(
+            "tape {tape_id} is no longer available in the scratch pool"
+        )));
+    }
+    // 池内写不下本次归档包的磁带不再参与选盘
+    for tape in state.tapes.values_mut() {
+        if tape.pool == request.pool
+            && tape_appendable(tape)
+            && tape.remaining_bytes < request.min_free_bytes
+        {
+            tape.media_state = common::TapeMediaState::TapeMediaFull as i32;
+        }
+    }
+    let tape = state.tapes.get_mut(tape_id).expect("tape checked above");
+    tape.status = common::TapeStatus::TapeOnline as i32;
+    tape.media_state = common::TapeMediaState::TapeMediaAppendable as i32;
+    tape.pool = request.pool;
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+fn mark_tape_offline(
+    state: &mut MetadataState,
+    request: TapeLocationRequest,
+) -> Result<(), Status> {
+    let tape = state
+        .tapes
+        .get(&request.tape_id)
+        .ok_or_else(|| Status::not_found("tape not found"))?;
+    if !matches!(
+        common::TapeStatus::try_from(tape.status),
+        Ok(common::TapeStatus::TapeOnline
+            | common::TapeStatus::TapeError
+            | common::TapeStatus::TapeOffline)
+    ) {
+        return Err(Status::failed_precondition(format!(
+            "tape {} cannot leave the library in its current state",
+            request.tape_id
+        )));
+    }
+    if let Some(task) = state.recall_tasks.values().find(|task| {
+        task.tape_id == request.tape_id
+            && task.status == common::RestoreStatus::RestoreInProgress as i32
+    }) {
+        return Err(Status::failed_precondition(format!(
+            "recall task {} is reading tape {}",
+            task.id, request.tape_id
+        )));
+    }
+    let tape = state
+        .tapes
+        .get_mut(&request.tape_id)
+        .expect("tape checked above");
+    tape.status = common::TapeStatus::TapeOffline as i32;
+    tape.location = request.location;
+    refresh_media_waits(state);
+    Ok(())
+}
+
+#[allow(clippy::result_large_err)]
+fn mark_tape_online(state: &mut MetadataState, request: TapeLocationRequest) -> Result<(), Status> {
+    let tape = state
+        .tapes
+        .get_mut(&request.tape_id)
+        .ok_or_else(|| Status::not_found("tape not found"))?;
+    if tape.status != common::TapeStatus::TapeOffline as i32 {
+        return Err(Status::failed_precondition(format!(
+            "tape {} is not offline",
+            request.tape_id
+        )));
+    }
+    tape.status = common::TapeStatus::TapeOnline as i32;
+    tape.location = request.location;
+    refresh_media_waits(state);
+    Ok(())
+}
+
+/// 取回任务可读取的磁带：主副本在前，其余 tape_set 次之
+fn recall_tapes(task: &common::RecallTask) -> impl Iterator<Item = &String> {
+    std::iter::once(&task.tape_id).chain(task.tape_set.iter().filter(|id| **id != task.tape_id))
+}
+
+/// 重新判定待处理取回是否在等待介质
+///
+/// 所有副本都不可读且至少一份只是移出了带库 (Offline) 时转为 WaitingForMedia，任一副本
+/// 重新可读时恢复为 Pending；对象的解冻状态随之变化。副本故障或退役不属于等待介质，
+/// 任务保持 Pending 由 Scheduler 认领后按失败处理。
+fn refresh_media_waits(state: &mut MetadataState) {
+    let pending = common::RestoreStatus::RestorePending as i32;
+    let waiting = common::RestoreStatus::RestoreWaitingForMedia as i32;
+    let mut changed = Vec::new();
+    for task in state.recall_tasks.values_mut() {
+        if task.status != pending && task.status != waiting {
+            continue;
+        }
+        let tapes: Vec<&common::TapeInfo> = recall_tapes(task)
+            .filter_map(|id| state.tapes.get(id))
+            .collect();
+        let next = if tapes.iter().any(|tape| tape_readable(tape)) {
+            pending
+        } else if tapes
+            .iter()
+            .any(|tape| tape.status == common::TapeStatus::TapeOffline as i32)
+        {
+            waiting
+        } else {
+            continue;
+        };
+        if task.status != next {
+            task.status = next;
+            changed.push((
+                task.bucket.clone(),
+                task.key.clone(),
+                task.version_id.clone(),
+                next,
+            ));
+        }
+    }
+    for (bucket, key, version_id, next) in changed {
+        if let Ok(object) = find_object_mut(state, &bucket, &key, version_id.as_deref()) {
+            if object.restore_status == Some(pending) || object.restore_status == Some(waiting) {
+                object.restore_status = Some(next);
+                object.updated_at = Some(now_timestamp());
+            }
+        }
+    }
+}
+
+/// 等待介质的取回所需的离线磁带，按最早等待的任务创建时间排序
+pub(crate) fn media_requests(state: &MetadataState) -> Vec<MediaRequest> {
+    let mut requests: HashMap<&str, MediaRequest> = HashMap::new();
+    let waiting = common::RestoreStatus::RestoreWaitingForMedia as i32;
+    for task in state
+        .recall_tasks
+        .values()
+        .filter(|task| task.status == waiting)
+    {
+        for tape in recall_tapes(task).filter_map(|id| state.tapes.get(id)) {
+            if tape.status != common::TapeStatus::TapeOffline as i32 {
+                continue;
+            }
+            let request = requests.entry(&tape.id).or_insert_with(|| MediaRequest {
+                tape_id: tape.id.clone(),
+                barcode: tape.barcode.clone(),
+                location: tape.location.clone(),
+                waiting_recalls: 0,
+                waiting_since: task.created_at,
+            });
+            request.waiting_recalls += 1;
+            if timestamp_sort_key(&task.created_at) < timestamp_sort_key(&request.waiting_since) {
+                request.waiting_since = task.created_at;
+            }
+        }
+    }
+    let mut requests: Vec<MediaRequest> = requests.into_values().collect();
+    requests.sort_by(|a, b| {
+        timestamp_sort_key(&a.waiting_since)
+            .cmp(&timestamp_sort_key(&b.waiting_since))
+            .then_with(|| a.tape_id.cmp(&b.tape_id))
+    });
+    requests
+}
+
+/// 由对象引用关系重算各归档包与磁带的有效/失效字节，用于加载快照与离线重建后的元数据
+pub fn rebuild_space_accounting(state: &mut MetadataState) {
+    for bundle in state.archive_bundles.values_mut() {
+        let (mut live_bytes, mut dead_bytes) = (0, 0);
+        for entry in &bundle.entries {
+            let key = ObjectKey::new(
+                entry.bucket.clone(),
+                entry.key.clone(),
+                entry.version_id.clone(),
+            );
+            match state.objects.get(&key) {
+                Some(object) if object.archive_id.as_deref() == Some(bundle.id.as_str()) => {
+                    live_bytes += entry.size
+                }
+                _ => dead_bytes += entry.size,
+            }
+        }
+        bundle.live_bytes = live_bytes;
+        bundle.dead_bytes = dead_bytes;
+    }
+    for tape in state.tapes.values_mut() {
+        let bundles: Vec<&common::ArchiveBundle> = tape
+            .archive_bundle_ids
+            .iter()
+            .filter_map(|id| state.archive_bundles.get(id))
+            .collect();
+        tape.live_bytes = bundles.iter().map(|bundle| bundle.live_bytes).sum();
+        tape.dead_bytes = bundles.iter().map(|bundle| bundle.dead_bytes).sum();
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn validate_archive_bundle_transition(current: i32, next: i32) -> Result<(), Status> {
+    let current = common::ArchiveBundleStatus::try_from(current)
+        .map_err(|_| Status::invalid_argument("invalid archive bundle status"))?;
+    let next = common::ArchiveBundleStatus::try_from(next)
+        .map_err(|_| Status::invalid_argument("invalid archive bundle status"))?;
+    let valid = match current {
+        common::ArchiveBundleStatus::BundlePending => {
+            matches!(next, common::ArchiveBundleStatus::BundleWriting)
+        }
+        common::ArchiveBundleStatus::BundleWriting => matches!(
+            next,
+            common::ArchiveBundleStatus::BundleCompleted
+                | common::ArchiveBundleStatus::BundleFailed
+        ),
+        common::ArchiveBundleStatus::BundleFailed => {
+            matches!(next, common::ArchiveBundleStatus::BundlePending)
+        }
+        common::ArchiveBundleStatus::BundleCompleted => false,
+        common::ArchiveBundleStatus::Unspecified => true,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Status::failed_precondition(
+            "invalid archive bundle state transition",
+        ))
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn validate_archive_task_transition(current: i32, next: i32) -> Result<(), Status> {
     let current = common::ArchiveTaskStatus::try_from(current)
         .map_err(|_| Status::invalid_argument("invalid archive task status"))?;
     let next = common::ArchiveTaskStatus::try_from(next)
@@ -680,6 +2121,1092 @@ pub(crate) fn is_active_restore_status(status: i32) -> bool {
 mod tests {
     use super::*;
 
+    fn copy_bundle_state() -> MetadataStateMachine {
+        let mut machine = MetadataStateMachine::default();
+        machine
+            .apply(MetadataCommand::CreateBucket(common::BucketInfo {
+                name: "docs".into(),
+                created_at: None,
+                owner: None,
+                versioning_enabled: false,
+                object_count: 0,
+                total_size: 0,
+                archive_copies: Some(2),
+                tape_pools: Vec::new(),
+            }))
+            .unwrap();
+        machine
+            .apply(MetadataCommand::PutObject(common::ObjectMetadata {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                size: 5,
+                checksum: "abc".into(),
+                content_type: None,
+                etag: None,
+                storage_class: common::StorageClass::ColdPending as i32,
+                archive_id: Some("bundle-1".into()),
+                tape_id: None,
+                tape_set: vec![],
+                tape_block_offset: None,
+                restore_status: None,
+                restore_expire_at: None,
+                created_at: None,
+                updated_at: None,
+                staging_worker_id: None,
+                staging_error: None,
+                attributes: None,
+            }))
+            .unwrap();
+        let copy = |tape_id: &str, location: &str| common::BundleCopy {
+            tape_id: tape_id.into(),
+            location: Some(location.into()),
+            status: common::ArchiveBundleStatus::BundlePending as i32,
+            filemark_start: 0,
+            filemark_end: 0,
+            start_block: 0,
+            block_size: 0,
+            error: None,
+            completed_at: None,
+        };
+        machine
+            .apply(MetadataCommand::PutArchiveBundle(common::ArchiveBundle {
+                id: "bundle-1".into(),
+                tape_id: String::new(),
+                tape_set: vec![],
+                entries: vec![common::BundleEntry {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    version_id: None,
+                    size: 5,
+                    offset_in_bundle: 1024,
+                    tape_block_offset: 0,
+                    checksum: "abc".into(),
+                }],
+                total_size: 2048,
+                filemark_start: 0,
+                filemark_end: 0,
+                checksum: None,
+                status: common::ArchiveBundleStatus::BundlePending as i32,
+                created_at: None,
+                completed_at: None,
+                copies: vec![copy("TAPE01", "rack-a"), copy("TAPE02", "vault")],
+                required_copies: 2,
+                live_bytes: 0,
+                dead_bytes: 0,
+            }))
+            .unwrap();
+        machine
+    }
+
+    fn copy_written(tape_id: &str, start_block: u64) -> MetadataCommand {
+        MetadataCommand::UpdateBundleCopy(UpdateBundleCopyRequest {
+            bundle_id: "bundle-1".into(),
+            tape_id: tape_id.into(),
+            status: common::ArchiveBundleStatus::BundleCompleted as i32,
+            filemark_start: 3,
+            filemark_end: 4,
+            start_block,
+            block_size: 512,
+            error: None,
+        })
+    }
+
+    fn storage_class(machine: &MetadataStateMachine) -> i32 {
+        find_object(machine.state(), "docs", "guide.txt", None)
+            .unwrap()
+            .storage_class
+    }
+
+    #[test]
+    fn object_turns_cold_only_after_required_copies_are_durable() {
+        let mut machine = copy_bundle_state();
+        let cold = MetadataCommand::UpdateStorageClass(UpdateStorageClassRequest {
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            storage_class: common::StorageClass::Cold as i32,
+        });
+
+        machine.apply(copy_written("TAPE02", 100)).unwrap();
+        assert_eq!(
+            storage_class(&machine),
+            common::StorageClass::ColdPending as i32
+        );
+        let err = machine.apply(cold.clone()).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        machine.apply(copy_written("TAPE01", 10)).unwrap();
+        let object = find_object(machine.state(), "docs", "guide.txt", None).unwrap();
+        assert_eq!(object.storage_class, common::StorageClass::Cold as i32);
+        assert_eq!(object.tape_id.as_deref(), Some("TAPE01"));
+        assert_eq!(object.tape_set, ["TAPE01", "TAPE02"]);
+        assert_eq!(object.tape_block_offset, Some(12));
+        let bundle = &machine.state().archive_bundles["bundle-1"];
+        assert_eq!(
+            bundle.status,
+            common::ArchiveBundleStatus::BundleCompleted as i32
+        );
+        machine.apply(cold).unwrap();
+    }
+
+    #[test]
+    fn failed_copy_can_be_retried() {
+        let mut machine = copy_bundle_state();
+        machine
+            .apply(MetadataCommand::UpdateBundleCopy(UpdateBundleCopyRequest {
+                bundle_id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                status: common::ArchiveBundleStatus::BundleFailed as i32,
+                filemark_start: 0,
+                filemark_end: 0,
+                start_block: 0,
+                block_size: 0,
+                error: Some("media error".into()),
+            }))
+            .unwrap();
+        let err = machine.apply(copy_written("TAPE01", 10)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        machine
+            .apply(MetadataCommand::UpdateBundleCopy(UpdateBundleCopyRequest {
+                bundle_id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                status: common::ArchiveBundleStatus::BundlePending as i32,
+                filemark_start: 0,
+                filemark_end: 0,
+                start_block: 0,
+                block_size: 0,
+                error: None,
+            }))
+            .unwrap();
+        machine.apply(copy_written("TAPE01", 10)).unwrap();
+        machine.apply(copy_written("TAPE02", 100)).unwrap();
+        assert_eq!(storage_class(&machine), common::StorageClass::Cold as i32);
+    }
+
+    #[test]
+    fn bundle_copies_must_use_distinct_locations() {
+        let mut machine = copy_bundle_state();
+        let mut bundle = machine.state().archive_bundles["bundle-1"].clone();
+        bundle.id = "bundle-2".into();
+        bundle.copies[1].location = Some("rack-a".into());
+        let err = machine
+            .apply(MetadataCommand::PutArchiveBundle(bundle))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn space_accounting_tracks_deleted_objects_and_reclaimed_tapes() {
+        let mut machine = copy_bundle_state();
+        for id in ["TAPE01", "TAPE02"] {
+            machine
+                .apply(MetadataCommand::PutTape(common::TapeInfo {
+                    id: id.into(),
+                    format: "LTO-9".into(),
+                    status: common::TapeStatus::TapeOnline as i32,
+                    capacity_bytes: 1 << 20,
+                    remaining_bytes: 1 << 20,
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        machine.apply(copy_written("TAPE01", 10)).unwrap();
+        machine.apply(copy_written("TAPE02", 100)).unwrap();
+        let tape = &machine.state().tapes["TAPE01"];
+        assert_eq!((tape.live_bytes, tape.dead_bytes), (5, 0));
+        assert_eq!(tape.used_bytes, 2048);
+
+        let reclaim = |tape_id: &str| TapeReclamationRequest {
+            tape_id: tape_id.into(),
+        };
+        machine
+            .apply(MetadataCommand::BeginTapeReclamation(reclaim("TAPE02")))
+            .unwrap();
+        let err = machine
+            .apply(MetadataCommand::CompleteTapeReclamation(reclaim("TAPE02")))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        machine
+            .apply(MetadataCommand::DeleteObject(DeleteObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+            }))
+            .unwrap();
+        let bundle = &machine.state().archive_bundles["bundle-1"];
+        assert_eq!((bundle.live_bytes, bundle.dead_bytes), (0, 5));
+        assert_eq!(machine.state().tapes["TAPE01"].live_bytes, 0);
+
+        machine
+            .apply(MetadataCommand::CompleteTapeReclamation(reclaim("TAPE02")))
+            .unwrap();
+        let tape = &machine.state().tapes["TAPE02"];
+        assert_eq!(tape.status, common::TapeStatus::TapeScratch as i32);
+        assert_eq!((tape.used_bytes, tape.remaining_bytes), (0, 1 << 20));
+        assert_eq!((tape.live_bytes, tape.dead_bytes), (0, 0));
+        let bundle = &machine.state().archive_bundles["bundle-1"];
+        assert_eq!(bundle.tape_set, ["TAPE01"]);
+
+        let err = machine
+            .apply(MetadataCommand::BeginTapeReclamation(reclaim("TAPE02")))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn tapes_are_labelled_imported_moved_and_retired() {
+        let mut machine = copy_bundle_state();
+        machine
+            .apply(MetadataCommand::LabelTape(LabelTapeRequest {
+                tape_id: "TAPE01".into(),
+                format: "LTO-10".into(),
+                pool: Some("onsite".into()),
+                location: Some("rack-a".into()),
+                ..Default::default()
+            }))
+            .unwrap();
+        let tape = &machine.state().tapes["TAPE01"];
+        assert_eq!(tape.status, common::TapeStatus::TapeOnline as i32);
+        assert_eq!(tape.capacity_bytes, 30_000_000_000_000);
+        assert_eq!(tape.remaining_bytes, tape.capacity_bytes);
+
+        let label = |tape_id: &str, format: &str| LabelTapeRequest {
+            tape_id: tape_id.into(),
+            format: format.into(),
+            ..Default::default()
+        };
+        let err = machine
+            .apply(MetadataCommand::LabelTape(label("TAPE01", "LTO-9")))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+        let err = machine
+            .apply(MetadataCommand::LabelTape(label("TAPE09", "DLT-S4")))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        machine
+            .apply(MetadataCommand::LabelTape(label("TAPE02", "LTO-9")))
+            .unwrap();
+        assert_eq!(
+            machine.state().tapes["TAPE02"].status,
+            common::TapeStatus::TapeScratch as i32
+        );
+
+        machine
+            .apply(MetadataCommand::ImportTape(ImportTapeRequest {
+                tape_id: "TAPE03".into(),
+                format: "LTO-9".into(),
+                used_bytes: 1 << 40,
+                pool: "legacy".into(),
+                ..Default::default()
+            }))
+            .unwrap();
+        let imported = &machine.state().tapes["TAPE03"];
+        assert_eq!(
+            imported.media_state,
+            common::TapeMediaState::TapeMediaReadOnly as i32
+        );
+        assert_eq!(imported.remaining_bytes, 18_000_000_000_000 - (1 << 40));
+
+        machine
+            .apply(MetadataCommand::MoveTapeToPool(MoveTapeToPoolRequest {
+                tape_id: "TAPE02".into(),
+                pool: "vault".into(),
+            }))
+            .unwrap();
+        let moved = &machine.state().tapes["TAPE02"];
+        assert_eq!(moved.status, common::TapeStatus::TapeOnline as i32);
+        assert_eq!(moved.pool, "vault");
+
+        // guide.txt 写在 TAPE01 / TAPE02 上，仍有对象引用时不能退役
+        machine.apply(copy_written("TAPE01", 10)).unwrap();
+        machine.apply(copy_written("TAPE02", 100)).unwrap();
+        let retire = |tape_id: &str| {
+            MetadataCommand::RetireTape(RetireTapeRequest {
+                tape_id: tape_id.into(),
+            })
+        };
+        let err = machine.apply(retire("TAPE02")).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        machine
+            .apply(MetadataCommand::DeleteObject(DeleteObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+            }))
+            .unwrap();
+        machine.apply(retire("TAPE02")).unwrap();
+        let retired = &machine.state().tapes["TAPE02"];
+        assert_eq!(retired.status, common::TapeStatus::TapeRetired as i32);
+        assert!(retired.archive_bundle_ids.is_empty());
+        assert_eq!(
+            machine.state().archive_bundles["bundle-1"].tape_set,
+            ["TAPE01"]
+        );
+        let err = machine
+            .apply(MetadataCommand::MoveTapeToPool(MoveTapeToPoolRequest {
+                tape_id: "TAPE02".into(),
+                pool: "onsite".into(),
+            }))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    fn access_key(id: &str, user_id: &str) -> common::AccessKeyInfo {
+        common::AccessKeyInfo {
+            access_key_id: id.into(),
+            user_id: user_id.into(),
+            secret_access_key: String::new(),
+            secret_hash: String::new(),
+            status: common::AccessKeyStatus::AccessKeyActive as i32,
+            created_at: None,
+            revoked_at: None,
+            encrypted_secret: format!("sealed-{id}").into_bytes(),
+        }
+    }
+
+    #[test]
+    fn access_keys_rotate_and_revoke_atomically() {
+        let mut machine = MetadataStateMachine::default();
+        let err = machine
+            .apply(MetadataCommand::PutAccessKey(access_key("AK1", "alice")))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        machine
+            .apply(MetadataCommand::CreateUser(common::UserInfo {
+                user_id: "alice".into(),
+                display_name: "Alice".into(),
+                enabled: true,
+                created_at: None,
+            }))
+            .unwrap();
+        let plaintext = common::AccessKeyInfo {
+            secret_access_key: "secret-AK1".into(),
+            ..access_key("AK1", "alice")
+        };
+        let err = machine
+            .apply(MetadataCommand::PutAccessKey(plaintext))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        machine
+            .apply(MetadataCommand::PutAccessKey(access_key("AK1", "alice")))
+            .unwrap();
+
+        let err = machine
+            .apply(MetadataCommand::RotateAccessKey {
+                access_key_id: "AK1".into(),
+                replacement: access_key("AK1", "alice"),
+            })
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+        assert_eq!(
+            machine.state().access_keys["AK1"].status,
+            common::AccessKeyStatus::AccessKeyActive as i32
+        );
+
+        machine
+            .apply(MetadataCommand::RotateAccessKey {
+                access_key_id: "AK1".into(),
+                replacement: access_key("AK2", "alice"),
+            })
+            .unwrap();
+        let keys = &machine.state().access_keys;
+        assert_eq!(
+            keys["AK1"].status,
+            common::AccessKeyStatus::AccessKeyRevoked as i32
+        );
+        assert!(keys["AK1"].revoked_at.is_some());
+        assert_eq!(
+            keys["AK2"].status,
+            common::AccessKeyStatus::AccessKeyActive as i32
+        );
+
+        let err = machine
+            .apply(MetadataCommand::RotateAccessKey {
+                access_key_id: "AK1".into(),
+                replacement: access_key("AK3", "alice"),
+            })
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn snapshot_round_trips_identity_and_accepts_older_layout() {
+        let mut machine = MetadataStateMachine::default();
+        machine
+            .apply(MetadataCommand::CreateUser(common::UserInfo {
+                user_id: "alice".into(),
+                display_name: "Alice".into(),
+                enabled: true,
+                created_at: None,
+            }))
+            .unwrap();
+        machine
+            .apply(MetadataCommand::PutAccessKey(access_key("AK1", "alice")))
+            .unwrap();
+
+        let bytes = machine.encode_snapshot();
+        let restored = MetadataStateMachine::decode_snapshot(&bytes).unwrap();
+        assert_eq!(restored.state().users["alice"].display_name, "Alice");
+        assert_eq!(
+            restored.state().access_keys["AK1"].encrypted_secret,
+            b"sealed-AK1"
+        );
+
+        // 不含用户/密钥分节的旧快照
+        let legacy = MetadataStateMachine::default().encode_snapshot();
+        let legacy = &legacy[..legacy.len() - 16];
+        let restored = MetadataStateMachine::decode_snapshot(legacy).unwrap();
+        assert!(restored.state().users.is_empty());
+    }
+
+    #[test]
+    fn bucket_policy_follows_bucket_lifecycle() {
+        let mut machine = MetadataStateMachine::default();
+        let policy = common::BucketPolicy {
+            bucket: "docs".into(),
+            policy: r#"{"Version":"2012-10-17","Statement":[]}"#.into(),
+            updated_at: None,
+        };
+        let err = machine
+            .apply(MetadataCommand::PutBucketPolicy(policy.clone()))
+            .expect_err("policy requires bucket");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        machine
+            .apply(MetadataCommand::CreateBucket(common::BucketInfo {
+                name: "docs".into(),
+                created_at: None,
+                owner: Some("alice".into()),
+                versioning_enabled: false,
+                object_count: 0,
+                total_size: 0,
+                archive_copies: None,
+                tape_pools: Vec::new(),
+            }))
+            .unwrap();
+        machine
+            .apply(MetadataCommand::PutBucketPolicy(policy.clone()))
+            .unwrap();
+
+        let restored = MetadataStateMachine::decode_snapshot(&machine.encode_snapshot()).unwrap();
+        assert_eq!(
+            restored.state().bucket_policies["docs"].policy,
+            policy.policy
+        );
+
+        machine
+            .apply(MetadataCommand::DeleteBucket(DeleteBucketRequest {
+                name: "docs".into(),
+            }))
+            .unwrap();
+        assert!(machine.state().bucket_policies.is_empty());
+    }
+
+    fn recall_task(id: &str, worker_id: u64) -> common::RecallTask {
+        common::RecallTask {
+            id: id.into(),
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            archive_id: "bundle-1".into(),
+            tape_id: "TAPE01".into(),
+            status: common::RestoreStatus::RestoreInProgress as i32,
+            started_at: Some(now_timestamp()),
+            worker_id: Some(worker_id),
+            ..Default::default()
+        }
+    }
+
+    fn expire_scheduler(node_id: u64, stale_before: i64) -> MetadataCommand {
+        MetadataCommand::ExpireWorker {
+            worker_type: common::WorkerType::WorkerScheduler,
+            node_id,
+            stale_before,
+        }
+    }
+
+    #[test]
+    fn expired_scheduler_worker_goes_offline_and_its_tasks_are_requeued() {
+        let mut machine = copy_bundle_state();
+        for node_id in [7, 8] {
+            machine
+                .apply(MetadataCommand::RegisterSchedulerWorker(
+                    common::SchedulerWorkerInfo {
+                        node_id,
+                        status: common::NodeStatus::NodeOnline as i32,
+                        ..Default::default()
+                    },
+                ))
+                .unwrap();
+        }
+        machine
+            .apply(MetadataCommand::UpdateRestoreStatus(
+                UpdateRestoreStatusRequest {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    status: common::RestoreStatus::RestoreInProgress as i32,
+                    expire_at: None,
+                },
+            ))
+            .unwrap();
+        for task in [recall_task("recall-7", 7), recall_task("recall-8", 8)] {
+            machine.apply(MetadataCommand::PutRecallTask(task)).unwrap();
+        }
+        machine
+            .apply(MetadataCommand::PutArchiveTask(common::ArchiveTask {
+                id: "archive-7".into(),
+                bundle_id: "bundle-1".into(),
+                status: common::ArchiveTaskStatus::ArchiveTaskInProgress as i32,
+                started_at: Some(now_timestamp()),
+                worker_id: Some(7),
+                ..Default::default()
+            }))
+            .unwrap();
+
+        // 判定时刻之后收到过心跳：不过期
+        machine.apply(expire_scheduler(7, 0)).unwrap();
+        assert_eq!(
+            machine.state().scheduler_workers[&7].status,
+            common::NodeStatus::NodeOnline as i32
+        );
+
+        machine.apply(expire_scheduler(7, i64::MAX)).unwrap();
+        let state = machine.state();
+        assert_eq!(
+            state.scheduler_workers[&7].status,
+            common::NodeStatus::NodeOffline as i32
+        );
+        let requeued = &state.recall_tasks["recall-7"];
+        assert_eq!(
+            requeued.status,
+            common::RestoreStatus::RestorePending as i32
+        );
+        assert_eq!(requeued.worker_id, None);
+        assert!(requeued.started_at.is_none());
+        let untouched = &state.recall_tasks["recall-8"];
+        assert_eq!(untouched.worker_id, Some(8));
+        assert_eq!(
+            untouched.status,
+            common::RestoreStatus::RestoreInProgress as i32
+        );
+        let archive = &state.archive_tasks["archive-7"];
+        assert_eq!(
+            archive.status,
+            common::ArchiveTaskStatus::ArchiveTaskPending as i32
+        );
+        assert_eq!(archive.worker_id, None);
+        assert_eq!(
+            find_object(state, "docs", "guide.txt", None)
+                .unwrap()
+                .restore_status,
+            Some(common::RestoreStatus::RestorePending as i32)
+        );
+
+        // 恢复心跳后重新上线
+        machine
+            .apply(MetadataCommand::Heartbeat {
+                request: HeartbeatRequest {
+                    worker_type: common::WorkerType::WorkerScheduler as i32,
+                    node_id: 7,
+                    payload: None,
+                    drain: None,
+                },
+                task_lease_expire_at: now_timestamp(),
+            })
+            .unwrap();
+        assert_eq!(
+            machine.state().scheduler_workers[&7].status,
+            common::NodeStatus::NodeOnline as i32
+        );
+    }
+
+    fn at(seconds: i64) -> Timestamp {
+        Timestamp { seconds, nanos: 0 }
+    }
+
+    fn claim_archive(worker_id: u64, now: i64) -> MetadataCommand {
+        MetadataCommand::ClaimArchiveTask {
+            id: "archive-1".into(),
+            worker_id,
+            lease_expire_at: at(now + 100),
+            now: at(now),
+        }
+    }
+
+    fn retry_policy(max_retries: u32) -> TaskRetryConfig {
+        TaskRetryConfig {
+            max_retries,
+            initial_backoff_secs: 10,
+            max_backoff_secs: 40,
+        }
+    }
+
+    #[test]
+    fn task_claims_are_exclusive_renewed_by_heartbeat_and_requeued_until_retries_run_out() {
+        let mut machine = copy_bundle_state();
+        machine
+            .apply(MetadataCommand::RegisterSchedulerWorker(
+                common::SchedulerWorkerInfo {
+                    node_id: 7,
+                    ..Default::default()
+                },
+            ))
+            .unwrap();
+        machine
+            .apply(MetadataCommand::PutArchiveTask(common::ArchiveTask {
+                id: "archive-1".into(),
+                status: common::ArchiveTaskStatus::ArchiveTaskPending as i32,
+                ..Default::default()
+            }))
+            .unwrap();
+
+        machine.apply(claim_archive(7, 0)).unwrap();
+        let err = machine.apply(claim_archive(8, 0)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Aborted);
+        let mut stolen = machine.state().archive_tasks["archive-1"].clone();
+        stolen.worker_id = Some(8);
+        stolen.status = common::ArchiveTaskStatus::ArchiveTaskCompleted as i32;
+        let err = machine
+            .apply(MetadataCommand::UpdateArchiveTask(stolen))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Aborted);
+
+        machine
+            .apply(MetadataCommand::Heartbeat {
+                request: HeartbeatRequest {
+                    worker_type: common::WorkerType::WorkerScheduler as i32,
+                    node_id: 7,
+                    payload: None,
+                    drain: None,
+                },
+                task_lease_expire_at: at(300),
+            })
+            .unwrap();
+        let task = &machine.state().archive_tasks["archive-1"];
+        assert_eq!(task.worker_id, Some(7));
+        assert_eq!(task.lease_expire_at, Some(at(300)));
+
+        let expire = |now| MetadataCommand::ExpireTaskLeases {
+            now: at(now),
+            retry: retry_policy(1),
+        };
+        machine.apply(expire(250)).unwrap();
+        assert_eq!(
+            machine.state().archive_tasks["archive-1"].status,
+            common::ArchiveTaskStatus::ArchiveTaskInProgress as i32
+        );
+
+        machine.apply(expire(400)).unwrap();
+        let task = &machine.state().archive_tasks["archive-1"];
+        assert_eq!(
+            task.status,
+            common::ArchiveTaskStatus::ArchiveTaskPending as i32
+        );
+        assert_eq!((task.worker_id, task.retry_count), (None, 1));
+        assert_eq!(task.next_attempt_at, Some(at(410)));
+
+        let err = machine.apply(claim_archive(8, 405)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        machine.apply(claim_archive(8, 450)).unwrap();
+        assert_eq!(
+            machine.state().archive_tasks["archive-1"].next_attempt_at,
+            None
+        );
+        machine.apply(expire(600)).unwrap();
+        let task = &machine.state().archive_tasks["archive-1"];
+        assert_eq!(
+            task.status,
+            common::ArchiveTaskStatus::ArchiveTaskFailed as i32
+        );
+        assert!(task.error.as_deref().unwrap().contains("lease expired"));
+    }
+
+    #[test]
+    fn claimed_recall_moves_object_to_in_progress_and_expiry_requeues_both() {
+        let mut machine = copy_bundle_state();
+        machine
+            .apply(MetadataCommand::UpdateRestoreStatus(
+                UpdateRestoreStatusRequest {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    status: common::RestoreStatus::RestorePending as i32,
+                    expire_at: None,
+                },
+            ))
+            .unwrap();
+        let mut task = recall_task("recall-1", 7);
+        task.status = common::RestoreStatus::RestorePending as i32;
+        task.worker_id = None;
+        machine.apply(MetadataCommand::PutRecallTask(task)).unwrap();
+
+        machine
+            .apply(MetadataCommand::ClaimRecallTask {
+                id: "recall-1".into(),
+                worker_id: 7,
+                lease_expire_at: at(100),
+                now: at(0),
+            })
+            .unwrap();
+        let restore_status = |machine: &MetadataStateMachine| {
+            find_object(machine.state(), "docs", "guide.txt", None)
+                .unwrap()
+                .restore_status
+        };
+        assert_eq!(
+            restore_status(&machine),
+            Some(common::RestoreStatus::RestoreInProgress as i32)
+        );
+
+        machine
+            .apply(MetadataCommand::ExpireTaskLeases {
+                now: at(200),
+                retry: retry_policy(3),
+            })
+            .unwrap();
+        let task = &machine.state().recall_tasks["recall-1"];
+        assert_eq!(task.status, common::RestoreStatus::RestorePending as i32);
+        assert_eq!(task.retry_count, 1);
+        assert_eq!(
+            restore_status(&machine),
+            Some(common::RestoreStatus::RestorePending as i32)
+        );
+    }
+
+    #[test]
+    fn recalls_wait_for_offline_media_and_resume_when_it_returns() {
+        let mut machine = copy_bundle_state();
+        for tape_id in ["TAPE01", "TAPE02"] {
+            machine
+                .apply(MetadataCommand::LabelTape(LabelTapeRequest {
+                    tape_id: tape_id.into(),
+                    barcode: Some(format!("{tape_id}L9")),
+                    format: "LTO-9".into(),
+                    pool: Some(String::new()),
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        machine
+            .apply(MetadataCommand::UpdateRestoreStatus(
+                UpdateRestoreStatusRequest {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    status: common::RestoreStatus::RestorePending as i32,
+                    expire_at: None,
+                },
+            ))
+            .unwrap();
+        let mut task = recall_task("recall-1", 7);
+        task.status = common::RestoreStatus::RestorePending as i32;
+        task.worker_id = None;
+        task.tape_set = vec!["TAPE01".into(), "TAPE02".into()];
+        task.created_at = Some(at(5));
+        machine.apply(MetadataCommand::PutRecallTask(task)).unwrap();
+
+        let offline = |tape_id: &str| {
+            MetadataCommand::MarkTapeOffline(TapeLocationRequest {
+                tape_id: tape_id.into(),
+                location: Some("vault-b".into()),
+            })
+        };
+        let task_status = |machine: &MetadataStateMachine| {
+            let restore_status = find_object(machine.state(), "docs", "guide.txt", None)
+                .unwrap()
+                .restore_status;
+            let task = &machine.state().recall_tasks["recall-1"];
+            assert_eq!(restore_status, Some(task.status));
+            task.status
+        };
+
+        // 仍有一份副本在带库内时照常排队
+        machine.apply(offline("TAPE01")).unwrap();
+        assert_eq!(
+            task_status(&machine),
+            common::RestoreStatus::RestorePending as i32
+        );
+        assert!(media_requests(machine.state()).is_empty());
+
+        machine.apply(offline("TAPE02")).unwrap();
+        assert_eq!(
+            task_status(&machine),
+            common::RestoreStatus::RestoreWaitingForMedia as i32
+        );
+        let requests = media_requests(machine.state());
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].tape_id, "TAPE01");
+        assert_eq!(requests[0].barcode.as_deref(), Some("TAPE01L9"));
+        assert_eq!(requests[0].location.as_deref(), Some("vault-b"));
+        assert_eq!(requests[0].waiting_recalls, 1);
+        assert_eq!(requests[0].waiting_since, Some(at(5)));
+        assert!(machine
+            .apply(MetadataCommand::ClaimRecallTask {
+                id: "recall-1".into(),
+                worker_id: 7,
+                lease_expire_at: at(100),
+                now: at(0),
+            })
+            .is_err());
+
+        machine
+            .apply(MetadataCommand::MarkTapeOnline(TapeLocationRequest {
+                tape_id: "TAPE02".into(),
+                location: Some("library-1".into()),
+            }))
+            .unwrap();
+        assert_eq!(
+            task_status(&machine),
+            common::RestoreStatus::RestorePending as i32
+        );
+        assert_eq!(
+            machine.state().tapes["TAPE02"].location.as_deref(),
+            Some("library-1")
+        );
+
+        // 正在读取的磁带不能移出带库
+        machine
+            .apply(MetadataCommand::ClaimRecallTask {
+                id: "recall-1".into(),
+                worker_id: 7,
+                lease_expire_at: at(100),
+                now: at(0),
+            })
+            .unwrap();
+        let mut task = machine.state().recall_tasks["recall-1"].clone();
+        task.tape_id = "TAPE02".into();
+        machine
+            .apply(MetadataCommand::UpdateRecallTask(task))
+            .unwrap();
+        let err = machine.apply(offline("TAPE02")).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    fn fail_task(id: &str, worker_id: u64, kind: common::TaskErrorKind) -> FailTaskRequest {
+        FailTaskRequest {
+            id: id.into(),
+            worker_id,
+            error: format!("{} failure", kind.as_str_name()),
+            kind: kind as i32,
+        }
+    }
+
+    #[test]
+    fn failed_archive_tasks_back_off_exponentially_then_dead_letter() {
+        let mut machine = copy_bundle_state();
+        for id in ["archive-1", "archive-2"] {
+            machine
+                .apply(MetadataCommand::PutArchiveTask(common::ArchiveTask {
+                    id: id.into(),
+                    status: common::ArchiveTaskStatus::ArchiveTaskPending as i32,
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        let fail = |id: &str, kind, now| MetadataCommand::FailArchiveTask {
+            request: fail_task(id, 7, kind),
+            now: at(now),
+            retry: retry_policy(2),
+        };
+
+        // 只有持有者能回报失败
+        let err = machine
+            .apply(fail(
+                "archive-1",
+                common::TaskErrorKind::TaskErrorRetriable,
+                0,
+            ))
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        machine.apply(claim_archive(7, 0)).unwrap();
+        machine
+            .apply(fail(
+                "archive-1",
+                common::TaskErrorKind::TaskErrorRetriable,
+                0,
+            ))
+            .unwrap();
+        let task = &machine.state().archive_tasks["archive-1"];
+        assert_eq!(
+            task.status,
+            common::ArchiveTaskStatus::ArchiveTaskPending as i32
+        );
+        assert_eq!((task.retry_count, task.next_attempt_at), (1, Some(at(10))));
+
+        machine.apply(claim_archive(7, 10)).unwrap();
+        machine
+            .apply(fail(
+                "archive-1",
+                common::TaskErrorKind::TaskErrorRetriable,
+                10,
+            ))
+            .unwrap();
+        let task = &machine.state().archive_tasks["archive-1"];
+        assert_eq!((task.retry_count, task.next_attempt_at), (2, Some(at(30))));
+
+        machine.apply(claim_archive(7, 30)).unwrap();
+        machine
+            .apply(fail(
+                "archive-1",
+                common::TaskErrorKind::TaskErrorRetriable,
+                30,
+            ))
+            .unwrap();
+        let task = &machine.state().archive_tasks["archive-1"];
+        assert_eq!(
+            task.status,
+            common::ArchiveTaskStatus::ArchiveTaskFailed as i32
+        );
+        assert_eq!(task.completed_at, Some(at(30)));
+
+        // 不可重试的失败直接进入死信
+        machine
+            .apply(MetadataCommand::ClaimArchiveTask {
+                id: "archive-2".into(),
+                worker_id: 7,
+                lease_expire_at: at(100),
+                now: at(0),
+            })
+            .unwrap();
+        machine
+            .apply(fail(
+                "archive-2",
+                common::TaskErrorKind::TaskErrorPermanent,
+                0,
+            ))
+            .unwrap();
+        let task = &machine.state().archive_tasks["archive-2"];
+        assert_eq!(
+            task.status,
+            common::ArchiveTaskStatus::ArchiveTaskFailed as i32
+        );
+        assert_eq!(task.retry_count, 0);
+        assert!(task.error.as_deref().unwrap().contains("PERMANENT"));
+    }
+
+    #[test]
+    fn permanently_failed_recall_is_reopened_by_a_new_restore() {
+        let mut machine = copy_bundle_state();
+        let restore = |status: common::RestoreStatus, expire_at| {
+            MetadataCommand::UpdateRestoreStatus(UpdateRestoreStatusRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                status: status as i32,
+                expire_at,
+            })
+        };
+        machine
+            .apply(restore(common::RestoreStatus::RestorePending, None))
+            .unwrap();
+        let mut task = recall_task("recall-1", 7);
+        task.status = common::RestoreStatus::RestorePending as i32;
+        task.worker_id = None;
+        machine.apply(MetadataCommand::PutRecallTask(task)).unwrap();
+        machine
+            .apply(MetadataCommand::ClaimRecallTask {
+                id: "recall-1".into(),
+                worker_id: 7,
+                lease_expire_at: at(100),
+                now: at(0),
+            })
+            .unwrap();
+        machine
+            .apply(MetadataCommand::FailRecallTask {
+                request: fail_task("recall-1", 7, common::TaskErrorKind::TaskErrorPermanent),
+                now: at(0),
+                retry: retry_policy(3),
+            })
+            .unwrap();
+        let restore_status = |machine: &MetadataStateMachine| {
+            find_object(machine.state(), "docs", "guide.txt", None)
+                .unwrap()
+                .restore_status
+        };
+        assert_eq!(
+            restore_status(&machine),
+            Some(common::RestoreStatus::RestoreFailed as i32)
+        );
+        assert_eq!(
+            machine.state().recall_tasks["recall-1"].status,
+            common::RestoreStatus::RestoreFailed as i32
+        );
+
+        machine
+            .apply(restore(
+                common::RestoreStatus::RestorePending,
+                Some(at(86_400)),
+            ))
+            .unwrap();
+        assert_eq!(
+            restore_status(&machine),
+            Some(common::RestoreStatus::RestorePending as i32)
+        );
+        let task = &machine.state().recall_tasks["recall-1"];
+        assert_eq!(task.status, common::RestoreStatus::RestorePending as i32);
+        assert_eq!((task.retry_count, task.error.as_deref()), (0, None));
+        assert_eq!(task.expire_at, Some(at(86_400)));
+    }
+
+    #[test]
+    fn tape_heartbeats_record_media_errors_in_tape_history() {
+        let mut machine = MetadataStateMachine::default();
+        machine
+            .apply(MetadataCommand::RegisterTapeWorker(
+                common::TapeWorkerInfo {
+                    node_id: 3,
+                    ..Default::default()
+                },
+            ))
+            .unwrap();
+        machine
+            .apply(MetadataCommand::PutTape(common::TapeInfo {
+                id: "TAPE01".into(),
+                format: "LTO-9".into(),
+                status: common::TapeStatus::TapeOnline as i32,
+                capacity_bytes: 1 << 20,
+                remaining_bytes: 1 << 20,
+                ..Default::default()
+            }))
+            .unwrap();
+        let event = |tape_id: &str, flag| common::DriveErrorEvent {
+            at: Some(at(flag as i64)),
+            drive_id: "drive-0".into(),
+            tape_id: Some(tape_id.into()),
+            tape_alert_flag: flag,
+            description: "Media".into(),
+            hard: true,
+        };
+        let heartbeat = |tape_errors| MetadataCommand::Heartbeat {
+            request: HeartbeatRequest {
+                worker_type: common::WorkerType::WorkerTape as i32,
+                node_id: 3,
+                payload: Some(heartbeat_request::Payload::Tape(TapeHeartbeat {
+                    drives: Vec::new(),
+                    tape_errors,
+                })),
+                drain: None,
+            },
+            task_lease_expire_at: now_timestamp(),
+        };
+
+        machine
+            .apply(heartbeat(vec![event("TAPE01", 4), event("UNKNOWN", 4)]))
+            .unwrap();
+        let tape = &machine.state().tapes["TAPE01"];
+        assert_eq!(tape.error_count, 1);
+        assert_eq!(tape.error_history.len(), 1);
+
+        let burst = (0..TAPE_ERROR_HISTORY_LEN as u32)
+            .map(|flag| event("TAPE01", 100 + flag))
+            .collect();
+        machine.apply(heartbeat(burst)).unwrap();
+        let tape = &machine.state().tapes["TAPE01"];
+        assert_eq!(tape.error_count, 1 + TAPE_ERROR_HISTORY_LEN as u32);
+        assert_eq!(tape.error_history.len(), TAPE_ERROR_HISTORY_LEN);
+        assert_eq!(tape.error_history[0].tape_alert_flag, 100);
+    }
+
     #[test]
     fn decode_snapshot_rejects_excessive_section_count_before_allocating() {
         let mut bytes = SNAPSHOT_MAGIC.to_vec();
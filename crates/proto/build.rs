@@ -8,6 +8,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     ];
 
     tonic_build::configure()
+        // map 字段按键排序编码，快照与 Raft 日志的字节内容保持确定
+        .btree_map(["."])
         .build_server(true)
         .build_client(true)
         .compile_protos(proto_files, &["proto/"])?;
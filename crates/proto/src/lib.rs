@@ -8,12 +8,14 @@ pub mod metadata {
     tonic::include_proto!("coldstore.metadata");
 }
 
-#[allow(clippy::doc_lazy_continuation)]
+// 流式请求的 meta 变体携带对象属性，明显大于 data 变体
+#[allow(clippy::doc_lazy_continuation, clippy::large_enum_variant)]
 pub mod scheduler {
     tonic::include_proto!("coldstore.scheduler");
 }
 
-#[allow(clippy::doc_lazy_continuation)]
+// 流式请求的 meta 变体携带对象属性，明显大于 data 变体
+#[allow(clippy::doc_lazy_continuation, clippy::large_enum_variant)]
 pub mod cache {
     tonic::include_proto!("coldstore.cache");
 }
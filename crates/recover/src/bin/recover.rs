@@ -0,0 +1,84 @@
+//! 从磁带重建元数据快照
+//!
+//! 用法:
+//!   coldstore-metadata-recover --tape-addr 127.0.0.1:24001 --output /var/lib/coldstore/metadata/recovered.snapshot
+//!     [--drive drive-0] [--format LTO-9] [--ca ca.pem --cert node.pem --key node.key]
+
+use anyhow::{Context, Result};
+use coldstore_common::tls::TlsConfig;
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_recover::{scan_library, write_snapshot};
+use std::path::PathBuf;
+use tracing::info;
+
+struct Args {
+    tape_addr: String,
+    drive_id: String,
+    format: String,
+    output: PathBuf,
+    tls: TlsConfig,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut tape_addr = None;
+    let mut drive_id = "drive-0".to_string();
+    let mut format = "LTO-9".to_string();
+    let mut output = None;
+    let mut tls = TlsConfig::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("missing value for {flag}"))
+        };
+        match flag.as_str() {
+            "--tape-addr" => tape_addr = Some(value()?),
+            "--drive" => drive_id = value()?,
+            "--format" => format = value()?,
+            "--output" => output = Some(PathBuf::from(value()?)),
+            "--ca" => {
+                tls.enabled = true;
+                tls.ca_path = Some(value()?);
+            }
+            "--cert" => tls.cert_path = value()?,
+            "--key" => tls.key_path = value()?,
+            other => anyhow::bail!("unknown argument: {other}"),
+        }
+    }
+
+    Ok(Args {
+        tape_addr: tape_addr.context("--tape-addr is required")?,
+        drive_id,
+        format,
+        output: output.context("--output is required")?,
+        tls,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "coldstore_recover=info".into()),
+        )
+        .init();
+
+    let args = parse_args()?;
+    info!("从 Tape Worker {} 扫描磁带重建元数据...", args.tape_addr);
+
+    let mut tape = TapeServiceClient::new(args.tls.connect(&args.tape_addr).await?);
+    let (state, report) = scan_library(&mut tape, &args.drive_id, &args.format).await?;
+    write_snapshot(&args.output, &state).await?;
+
+    info!(
+        "重建完成: {} 个对象, {} 个冲突, {} 个被取代的副本, 快照已写入 {}",
+        report.objects_recovered,
+        report.conflicts.len(),
+        report.superseded.len(),
+        args.output.display()
+    );
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
@@ -0,0 +1,823 @@
+//! 灾难恢复：从磁带重建元数据
+//!
+//! 元数据集群整体丢失时，逐盘挂载磁带，沿 filemark 逐个读取归档包并解析
+//! 自描述头 (`coldstore_tape::bundle`)，重建 `ObjectMetadata`、
+//! `ArchiveBundle`、`TapeInfo` 及所属桶到一个全新的 `MetadataState`。
+//!
+//! 同一对象 (bucket/key/version) 出现在多个归档包中时保留归档包写入时间最新的一份：
+//! - 同一归档包在其它磁带上的副本并入 `tape_set`；
+//! - 其它归档包中 checksum 相同的旧副本记入 [`RecoveryReport::superseded`]，
+//!   其块地址属于另一个归档包，不能作为取回的备选副本；
+//! - checksum 不同：记为冲突。
+//!
+//! 同一归档包出现在多盘磁带上时每盘记一个 `BundleCopy` (起始块、块大小)，
+//! `required_copies` 取实际找到的副本数；有效字节由 `finish` 按重建的对象重新计算。
+
+use anyhow::{Context, Result};
+use coldstore_common::attributes;
+use coldstore_common::checksum::sha256_hex;
+use coldstore_metadata::command::MetadataCommand;
+use coldstore_metadata::state_machine::{
+    rebuild_space_accounting, save_snapshot, MetadataState, MetadataStateMachine,
+};
+use coldstore_proto::common;
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_proto::tape::{
+    read_bundle_request, read_bundle_response, GetTapeMediaStatusRequest, LoadTapeRequest,
+    ReadBundleRequest, SeekToFilemarkRequest, TapeMediaStatus, UnloadTapeRequest,
+};
+use coldstore_tape::bundle::{decode_bundle, DecodedBundle};
+use prost_types::Timestamp;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tonic::transport::Channel;
+use tonic::Request;
+use tracing::{info, warn};
+
+/// 对象的一处磁带位置
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RecoveredLocation {
+    pub tape_id: String,
+    pub bundle_id: String,
+    pub checksum: String,
+    /// 归档包写入时间 (Unix 秒)
+    pub created_at: i64,
+}
+
+/// 同一对象在不同归档包中 checksum 不一致
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryConflict {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    pub kept: RecoveredLocation,
+    pub discarded: Vec<RecoveredLocation>,
+}
+
+/// 对象在较旧归档包中内容相同的副本，已被保留的归档包取代
+#[derive(Debug, Clone, Serialize)]
+pub struct SupersededCopies {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    pub kept: RecoveredLocation,
+    pub superseded: Vec<RecoveredLocation>,
+}
+
+/// 无法解析的归档包
+#[derive(Debug, Clone, Serialize)]
+pub struct UnreadableBundle {
+    pub tape_id: String,
+    pub filemark: u32,
+    pub error: String,
+}
+
+/// 无法挂载或扫描的磁带
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedTape {
+    pub tape_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RecoveryReport {
+    pub tapes_scanned: usize,
+    pub bundles_recovered: usize,
+    pub objects_recovered: usize,
+    pub conflicts: Vec<RecoveryConflict>,
+    pub superseded: Vec<SupersededCopies>,
+    pub unreadable: Vec<UnreadableBundle>,
+    pub skipped_tapes: Vec<SkippedTape>,
+}
+
+type ObjectId = (String, String, Option<String>);
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    object: common::ObjectMetadata,
+    location: RecoveredLocation,
+    created_at: (i64, i32),
+}
+
+/// 累积扫描结果，最后一次性生成 `MetadataState`
+#[derive(Debug, Default)]
+pub struct RecoveryBuilder {
+    tapes: BTreeMap<String, common::TapeInfo>,
+    bundles: BTreeMap<String, common::ArchiveBundle>,
+    candidates: BTreeMap<ObjectId, Vec<Candidate>>,
+    unreadable: Vec<UnreadableBundle>,
+    skipped: Vec<SkippedTape>,
+}
+
+impl RecoveryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一盘已扫描的磁带
+    pub fn add_tape(&mut self, media: &TapeMediaStatus, format: &str) {
+        let Some(tape_id) = media.tape_id.clone() else {
+            return;
+        };
+        self.tapes.insert(
+            tape_id.clone(),
+            common::TapeInfo {
+                id: tape_id.clone(),
+                barcode: Some(tape_id),
+                format: format.to_string(),
+                status: common::TapeStatus::TapeOnline as i32,
+                location: None,
+                capacity_bytes: media.capacity_bytes,
+                used_bytes: media.used_bytes,
+                remaining_bytes: media.remaining_bytes,
+                archive_bundle_ids: Vec::new(),
+                last_verified_at: None,
+                error_count: 0,
+                registered_at: None,
+                live_bytes: 0,
+                dead_bytes: 0,
+                pool: String::new(),
+                error_history: Vec::new(),
+                media_state: common::TapeMediaState::TapeMediaAppendable as i32,
+            },
+        );
+    }
+
+    /// 记录一盘无法扫描的磁带，已读出的归档包照常保留
+    pub fn skip_tape(&mut self, tape_id: &str, error: String) {
+        self.skipped.push(SkippedTape {
+            tape_id: tape_id.to_string(),
+            error,
+        });
+    }
+
+    /// 解析位于 `tape_id` 第 `filemark` 个文件、起始块为 `start_block` 的归档包
+    pub fn add_bundle(&mut self, tape_id: &str, filemark: u32, start_block: u64, bytes: &[u8]) {
+        match decode_bundle(bytes) {
+            Ok(decoded) => self.add_decoded(tape_id, filemark, start_block, bytes, decoded),
+            Err(err) => {
+                warn!("跳过无法解析的归档包 {tape_id}#{filemark}: {err}");
+                self.unreadable.push(UnreadableBundle {
+                    tape_id: tape_id.to_string(),
+                    filemark,
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+
+    fn add_decoded(
+        &mut self,
+        tape_id: &str,
+        filemark: u32,
+        start_block: u64,
+        bytes: &[u8],
+        decoded: DecodedBundle,
+    ) {
+        let header = decoded.header;
+        let block_size = header.block_size as u64;
+        let created_at = Timestamp {
+            seconds: header.created_at_secs,
+            nanos: header.created_at_nanos as i32,
+        };
+
+        let mut payloads = Vec::new();
+        let mut entries = Vec::with_capacity(decoded.objects.len());
+        for (entry, data) in &decoded.objects {
+            payloads.extend_from_slice(data);
+            let object = &entry.header;
+            let tape_block_offset = start_block + entry.payload_offset / block_size;
+            entries.push(common::BundleEntry {
+                bucket: object.bucket.clone(),
+                key: object.key.clone(),
+                version_id: object.version_id.clone(),
+                size: object.size,
+                offset_in_bundle: entry.payload_offset,
+                tape_block_offset,
+                checksum: object.checksum.clone(),
+            });
+
+            let id = (
+                object.bucket.clone(),
+                object.key.clone(),
+                object.version_id.clone(),
+            );
+            self.candidates.entry(id).or_default().push(Candidate {
+                object: common::ObjectMetadata {
+                    bucket: object.bucket.clone(),
+                    key: object.key.clone(),
+                    version_id: object.version_id.clone(),
+                    size: object.size,
+                    checksum: object.checksum.clone(),
+                    content_type: object.content_type.clone(),
+                    etag: Some(object.checksum.clone()),
+                    storage_class: common::StorageClass::Cold as i32,
+                    archive_id: Some(header.bundle_id.clone()),
+                    tape_id: Some(tape_id.to_string()),
+                    tape_set: vec![tape_id.to_string()],
+                    tape_block_offset: Some(tape_block_offset),
+                    restore_status: None,
+                    restore_expire_at: None,
+                    created_at: Some(created_at),
+                    updated_at: Some(created_at),
+                    staging_worker_id: None,
+                    staging_error: None,
+                    attributes: Some(attributes::from_headers(
+                        object
+                            .attributes
+                            .iter()
+                            .map(|(name, value)| (name.as_str(), value.as_str())),
+                    )),
+                },
+                location: RecoveredLocation {
+                    tape_id: tape_id.to_string(),
+                    bundle_id: header.bundle_id.clone(),
+                    checksum: object.checksum.clone(),
+                    created_at: header.created_at_secs,
+                },
+                created_at: (created_at.seconds, created_at.nanos),
+            });
+        }
+
+        if let Some(tape) = self.tapes.get_mut(tape_id) {
+            if !tape.archive_bundle_ids.contains(&header.bundle_id) {
+                tape.archive_bundle_ids.push(header.bundle_id.clone());
+            }
+        }
+
+        let bundle = self
+            .bundles
+            .entry(header.bundle_id.clone())
+            .or_insert_with(|| common::ArchiveBundle {
+                id: header.bundle_id.clone(),
+                tape_id: tape_id.to_string(),
+                tape_set: Vec::new(),
+                entries,
+                total_size: bytes.len() as u64,
+                filemark_start: filemark,
+                filemark_end: filemark + 1,
+                checksum: Some(sha256_hex(&payloads)),
+                status: common::ArchiveBundleStatus::BundleCompleted as i32,
+                created_at: Some(created_at),
+                completed_at: Some(created_at),
+                copies: Vec::new(),
+                required_copies: 0,
+                live_bytes: 0,
+                dead_bytes: 0,
+            });
+        if !bundle.tape_set.iter().any(|id| id == tape_id) {
+            bundle.tape_set.push(tape_id.to_string());
+        }
+        if !bundle.copies.iter().any(|copy| copy.tape_id == tape_id) {
+            bundle.copies.push(common::BundleCopy {
+                tape_id: tape_id.to_string(),
+                location: None,
+                status: common::ArchiveBundleStatus::BundleCompleted as i32,
+                filemark_start: filemark,
+                filemark_end: filemark + 1,
+                start_block,
+                block_size: header.block_size,
+                error: None,
+                completed_at: Some(created_at),
+            });
+            bundle.required_copies = bundle.copies.len() as u32;
+        }
+    }
+
+    /// 裁决冲突并生成元数据状态
+    pub fn finish(self) -> Result<(MetadataState, RecoveryReport)> {
+        let mut report = RecoveryReport {
+            tapes_scanned: self.tapes.len(),
+            bundles_recovered: self.bundles.len(),
+            unreadable: self.unreadable,
+            skipped_tapes: self.skipped,
+            ..RecoveryReport::default()
+        };
+
+        let mut objects = Vec::with_capacity(self.candidates.len());
+        for ((bucket, key, version_id), mut candidates) in self.candidates {
+            candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.created_at));
+            let newest = candidates.remove(0);
+            let mut object = newest.object;
+            let mut discarded = Vec::new();
+            let mut superseded = Vec::new();
+            for candidate in candidates {
+                if candidate.location.bundle_id == newest.location.bundle_id {
+                    if !object.tape_set.contains(&candidate.location.tape_id) {
+                        object.tape_set.push(candidate.location.tape_id);
+                    }
+                } else if candidate
+                    .location
+                    .checksum
+                    .eq_ignore_ascii_case(&object.checksum)
+                {
+                    superseded.push(candidate.location);
+                } else {
+                    discarded.push(candidate.location);
+                }
+            }
+            if !superseded.is_empty() {
+                report.superseded.push(SupersededCopies {
+                    bucket: bucket.clone(),
+                    key: key.clone(),
+                    version_id: version_id.clone(),
+                    kept: newest.location.clone(),
+                    superseded,
+                });
+            }
+            if !discarded.is_empty() {
+                warn!(
+                    "对象 {bucket}/{key} 在 {} 个归档包中 checksum 不一致，保留 {}",
+                    discarded.len() + 1,
+                    newest.location.bundle_id
+                );
+                report.conflicts.push(RecoveryConflict {
+                    bucket,
+                    key,
+                    version_id,
+                    kept: newest.location,
+                    discarded,
+                });
+            }
+            objects.push(object);
+        }
+        report.objects_recovered = objects.len();
+
+        let mut buckets: BTreeMap<String, Timestamp> = BTreeMap::new();
+        for object in &objects {
+            let created_at = object.created_at.unwrap_or_default();
+            buckets
+                .entry(object.bucket.clone())
+                .and_modify(|earliest| {
+                    if (created_at.seconds, created_at.nanos) < (earliest.seconds, earliest.nanos) {
+                        *earliest = created_at;
+                    }
+                })
+                .or_insert(created_at);
+        }
+
+        let mut machine = MetadataStateMachine::default();
+        for (name, created_at) in buckets {
+            machine
+                .apply(MetadataCommand::CreateBucket(common::BucketInfo {
+                    name,
+                    created_at: Some(created_at),
+                    owner: None,
+                    versioning_enabled: false,
+                    object_count: 0,
+                    total_size: 0,
+                    archive_copies: None,
+                    tape_pools: Vec::new(),
+                }))
+                .map_err(|status| anyhow::anyhow!("rebuild bucket: {}", status.message()))?;
+        }
+        let commands = objects
+            .into_iter()
+            .map(MetadataCommand::PutObject)
+            .chain(
+                self.bundles
+                    .into_values()
+                    .map(MetadataCommand::PutArchiveBundle),
+            )
+            .chain(self.tapes.into_values().map(MetadataCommand::PutTape));
+        for command in commands {
+            machine
+                .apply(command)
+                .map_err(|status| anyhow::anyhow!("rebuild metadata: {}", status.message()))?;
+        }
+
+        let mut state = machine.into_state();
+        rebuild_space_accounting(&mut state);
+        Ok((state, report))
+    }
+}
+
+/// 通过 Tape Worker 逐盘扫描带库中的全部磁带
+///
+/// `drive_id` 指定用于扫描的驱动，扫描前会卸载其中已有的磁带；清洁带与 import/export
+/// 槽位中的磁带不扫描。无法挂载或读取的磁带记入 [`RecoveryReport::skipped_tapes`]
+/// 后继续扫描其余磁带。
+/// `format` 写入重建的 `TapeInfo::format` (磁带头中不记录介质型号)。
+pub async fn scan_library(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    format: &str,
+) -> Result<(MetadataState, RecoveryReport)> {
+    let inventory = tape
+        .inventory(Request::new(()))
+        .await
+        .context("tape inventory")?
+        .into_inner();
+    let tape_ids: BTreeSet<String> = inventory
+        .slots
+        .into_iter()
+        .filter(|slot| !slot.is_cleaning && !slot.is_import_export)
+        .filter_map(|slot| slot.tape_id)
+        .collect();
+
+    let mut builder = RecoveryBuilder::new();
+    for tape_id in tape_ids {
+        info!("扫描磁带 {tape_id}");
+        if let Err(err) = scan_tape(tape, drive_id, &tape_id, format, &mut builder).await {
+            warn!("跳过无法扫描的磁带 {tape_id}: {err:#}");
+            builder.skip_tape(&tape_id, format!("{err:#}"));
+        }
+    }
+    builder.finish()
+}
+
+async fn scan_tape(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    tape_id: &str,
+    format: &str,
+    builder: &mut RecoveryBuilder,
+) -> Result<()> {
+    tape.unload_tape(Request::new(UnloadTapeRequest {
+        drive_id: drive_id.to_string(),
+        target_slot_id: None,
+    }))
+    .await?;
+    tape.load_tape(Request::new(LoadTapeRequest {
+        tape_id: tape_id.to_string(),
+        drive_id: drive_id.to_string(),
+        slot_id: None,
+    }))
+    .await?;
+
+    let media = media_status(tape, drive_id).await?;
+    builder.add_tape(&media, format);
+
+    let mut filemark = 0u32;
+    loop {
+        if let Err(status) = tape
+            .seek_to_filemark(Request::new(SeekToFilemarkRequest {
+                drive_id: drive_id.to_string(),
+                filemark,
+            }))
+            .await
+        {
+            if status.code() == tonic::Code::OutOfRange {
+                break;
+            }
+            return Err(status.into());
+        }
+        let start_block = media_status(tape, drive_id).await?.current_position;
+        match read_file(tape, drive_id, filemark).await {
+            Ok(bytes) => builder.add_bundle(tape_id, filemark, start_block, &bytes),
+            Err(status) if status.code() == tonic::Code::OutOfRange => break,
+            Err(status) => return Err(status.into()),
+        }
+        filemark += 1;
+    }
+
+    tape.unload_tape(Request::new(UnloadTapeRequest {
+        drive_id: drive_id.to_string(),
+        target_slot_id: None,
+    }))
+    .await?;
+    Ok(())
+}
+
+async fn media_status(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+) -> Result<TapeMediaStatus> {
+    Ok(tape
+        .get_tape_media_status(Request::new(GetTapeMediaStatusRequest {
+            drive_id: drive_id.to_string(),
+        }))
+        .await?
+        .into_inner())
+}
+
+async fn read_file(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    filemark: u32,
+) -> std::result::Result<Vec<u8>, tonic::Status> {
+    let mut stream = tape
+        .read_bundle(Request::new(ReadBundleRequest {
+            drive_id: drive_id.to_string(),
+            length: 0,
+            location: Some(read_bundle_request::Location::Filemark(filemark)),
+        }))
+        .await?
+        .into_inner();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = stream.message().await? {
+        if let Some(read_bundle_response::Payload::Data(data)) = chunk.payload {
+            bytes.extend_from_slice(&data);
+        }
+    }
+    Ok(bytes)
+}
+
+/// 将重建结果写为元数据快照，供 `MetadataServiceImpl::new_with_snapshot` 加载
+pub async fn write_snapshot(path: &Path, state: &MetadataState) -> Result<()> {
+    save_snapshot(path, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coldstore_common::config::{ScsiConfig, TapeConfig, VirtualLibraryConfig};
+    use coldstore_metadata::state_machine::find_object;
+    use coldstore_proto::tape::tape_service_server::{TapeService, TapeServiceServer};
+    use coldstore_proto::tape::{BundleObjectHeader, ExportTapeRequest, WriteBundleMeta};
+    use coldstore_tape::bundle::{encode_bundle, BundleHeader, ObjectHeader};
+    use coldstore_tape::service::TapeServiceImpl;
+    use tokio::time::{sleep, Duration};
+    use tonic::transport::Server;
+
+    fn object(key: &str, data: &[u8]) -> (ObjectHeader, Vec<u8>) {
+        (
+            ObjectHeader {
+                bucket: "docs".into(),
+                key: key.into(),
+                version_id: None,
+                size: data.len() as u64,
+                checksum: sha256_hex(data),
+                content_type: Some("text/plain".into()),
+                attributes: vec![("x-amz-meta-owner".into(), "ops".into())],
+            },
+            data.to_vec(),
+        )
+    }
+
+    fn bundle(id: &str, created_at_secs: i64, objects: &[(ObjectHeader, Vec<u8>)]) -> Vec<u8> {
+        encode_bundle(
+            &BundleHeader {
+                version: 1,
+                bundle_id: id.into(),
+                object_count: objects.len() as u32,
+                block_size: 512,
+                created_at_secs,
+                created_at_nanos: 0,
+            },
+            objects,
+        )
+        .expect("encode bundle")
+        .bytes
+    }
+
+    fn media(tape_id: &str) -> TapeMediaStatus {
+        TapeMediaStatus {
+            drive_id: "drive-0".into(),
+            tape_id: Some(tape_id.into()),
+            tape_status: common::TapeStatus::TapeOnline as i32,
+            capacity_bytes: 1 << 20,
+            used_bytes: 4096,
+            remaining_bytes: (1 << 20) - 4096,
+            current_position: 0,
+            current_filemark: 0,
+            is_write_protected: false,
+        }
+    }
+
+    #[test]
+    fn conflicting_copies_resolve_to_newest_bundle() {
+        let mut builder = RecoveryBuilder::new();
+        builder.add_tape(&media("TAPE01"), "LTO-9");
+        builder.add_tape(&media("TAPE02"), "LTO-9");
+        builder.add_bundle(
+            "TAPE01",
+            0,
+            0,
+            &bundle(
+                "old",
+                100,
+                &[object("a.txt", b"v1"), object("b.txt", b"same")],
+            ),
+        );
+        builder.add_bundle(
+            "TAPE02",
+            0,
+            0,
+            &bundle(
+                "new",
+                200,
+                &[object("a.txt", b"v2"), object("b.txt", b"same")],
+            ),
+        );
+
+        let (state, report) = builder.finish().expect("finish recovery");
+        assert_eq!(report.tapes_scanned, 2);
+        assert_eq!(report.bundles_recovered, 2);
+        assert_eq!(report.objects_recovered, 2);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].key, "a.txt");
+        assert_eq!(report.conflicts[0].kept.bundle_id, "new");
+        assert_eq!(report.conflicts[0].discarded[0].bundle_id, "old");
+
+        let a = find_object(&state, "docs", "a.txt", None).unwrap();
+        assert_eq!(a.checksum, sha256_hex(b"v2"));
+        assert_eq!(a.archive_id.as_deref(), Some("new"));
+        assert_eq!(
+            a.attributes
+                .as_ref()
+                .map(|attributes| &attributes.user_metadata["owner"][..]),
+            Some("ops")
+        );
+        // 旧归档包中的相同内容不是 "new" 的副本，块地址不同，不能并入 tape_set
+        let b = find_object(&state, "docs", "b.txt", None).unwrap();
+        assert_eq!(b.archive_id.as_deref(), Some("new"));
+        assert_eq!(b.tape_set, vec!["TAPE02"]);
+        assert_eq!(report.superseded.len(), 1);
+        assert_eq!(report.superseded[0].key, "b.txt");
+        assert_eq!(report.superseded[0].kept.bundle_id, "new");
+        assert_eq!(report.superseded[0].superseded[0].bundle_id, "old");
+        assert_eq!(report.superseded[0].superseded[0].tape_id, "TAPE01");
+        assert_eq!(state.bucket("docs").unwrap().object_count, 2);
+        assert_eq!(
+            state.tape("TAPE01").unwrap().archive_bundle_ids,
+            vec!["old"]
+        );
+    }
+
+    #[test]
+    fn bundle_found_on_several_tapes_gets_a_copy_per_tape() {
+        let mut builder = RecoveryBuilder::new();
+        builder.add_tape(&media("TAPE01"), "LTO-9");
+        builder.add_tape(&media("TAPE02"), "LTO-9");
+        let bytes = bundle("b1", 100, &[object("a.txt", b"hello")]);
+        builder.add_bundle("TAPE01", 0, 0, &bytes);
+        builder.add_bundle("TAPE02", 2, 9, &bytes);
+
+        let (state, _) = builder.finish().expect("finish recovery");
+        let bundle = state.archive_bundle("b1").unwrap();
+        assert_eq!(bundle.required_copies, 2);
+        let copies: Vec<_> = bundle
+            .copies
+            .iter()
+            .map(|copy| {
+                (
+                    copy.tape_id.as_str(),
+                    copy.filemark_start,
+                    copy.start_block,
+                    copy.block_size,
+                )
+            })
+            .collect();
+        assert_eq!(copies, [("TAPE01", 0, 0, 512), ("TAPE02", 2, 9, 512)]);
+        assert!(bundle
+            .copies
+            .iter()
+            .all(|copy| copy.status == common::ArchiveBundleStatus::BundleCompleted as i32));
+        assert_eq!((bundle.live_bytes, bundle.dead_bytes), (5, 0));
+        assert_eq!(state.tape("TAPE02").unwrap().live_bytes, 5);
+        let a = find_object(&state, "docs", "a.txt", None).unwrap();
+        assert_eq!(a.tape_set, vec!["TAPE01", "TAPE02"]);
+    }
+
+    #[test]
+    fn corrupted_bundle_is_reported_not_recovered() {
+        let mut builder = RecoveryBuilder::new();
+        let mut bytes = bundle("b1", 100, &[object("a.txt", b"hello")]);
+        bytes[1024] ^= 0xff;
+        builder.add_bundle("TAPE01", 3, 0, &bytes);
+        let (state, report) = builder.finish().expect("finish recovery");
+        assert_eq!(state.object_count(), 0);
+        assert_eq!(report.unreadable.len(), 1);
+        assert_eq!(report.unreadable[0].filemark, 3);
+    }
+
+    fn header(key: &str, data: &[u8]) -> BundleObjectHeader {
+        BundleObjectHeader {
+            bucket: "docs".into(),
+            key: key.into(),
+            version_id: None,
+            size: data.len() as u64,
+            checksum: sha256_hex(data),
+            content_type: None,
+            attributes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_library_rebuilds_objects_from_virtual_tapes() {
+        let tape = TapeServiceImpl::new(&TapeConfig {
+            sdk_backend: "virtual".into(),
+            scsi: ScsiConfig {
+                devices: vec!["/dev/nst0".into(), "/dev/nst1".into()],
+                block_size: 512,
+                buffer_size_mb: 1,
+            },
+            virtual_library: Some(VirtualLibraryConfig {
+                tapes: vec![
+                    "TAPE01".into(),
+                    "TAPE02".into(),
+                    "TAPE03".into(),
+                    "TAPE04".into(),
+                ],
+                capacity_bytes: 1 << 20,
+                import_export_slots: 2,
+                cleaning_tapes: vec!["CLN001".into()],
+            }),
+            ..TapeConfig::default()
+        })
+        .expect("tape service init");
+
+        let mut written = Vec::new();
+        for (tape_id, bundle_id, key, data) in [
+            ("TAPE01", "bundle-1", "a.txt", b"alpha".as_slice()),
+            ("TAPE01", "bundle-2", "b.txt", b"bravo".as_slice()),
+            ("TAPE02", "bundle-3", "c.txt", b"charlie".as_slice()),
+        ] {
+            tape.unload_tape(Request::new(UnloadTapeRequest {
+                drive_id: "drive-0".into(),
+                target_slot_id: None,
+            }))
+            .await
+            .expect("unload tape");
+            tape.load_tape(Request::new(LoadTapeRequest {
+                tape_id: tape_id.into(),
+                drive_id: "drive-0".into(),
+                slot_id: None,
+            }))
+            .await
+            .expect("load tape");
+            let response = tape
+                .write_objects(
+                    WriteBundleMeta {
+                        drive_id: "drive-0".into(),
+                        bundle_id: bundle_id.into(),
+                        total_size: data.len() as u64,
+                        object_count: 1,
+                        block_size: 0,
+                        objects: vec![header(key, data)],
+                    },
+                    data.to_vec(),
+                )
+                .await
+                .expect("write bundle");
+            written.push(response);
+        }
+        // TAPE03 已导出到 import/export 槽位；TAPE04 占用另一台驱动，无法在 drive-0 扫描
+        tape.export_tape(Request::new(ExportTapeRequest {
+            tape_id: "TAPE03".into(),
+        }))
+        .await
+        .expect("export tape");
+        tape.load_tape(Request::new(LoadTapeRequest {
+            tape_id: "TAPE04".into(),
+            drive_id: "drive-1".into(),
+            slot_id: None,
+        }))
+        .await
+        .expect("load tape into drive-1");
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        drop(listener);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(async move {
+            Server::builder()
+                .add_service(TapeServiceServer::new(tape))
+                .serve_with_shutdown(addr, async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+                .expect("tape server should run");
+        });
+        let mut client = None;
+        for _ in 0..20 {
+            match TapeServiceClient::connect(format!("http://{addr}")).await {
+                Ok(connected) => {
+                    client = Some(connected);
+                    break;
+                }
+                Err(_) => sleep(Duration::from_millis(25)).await,
+            }
+        }
+        let mut client = client.expect("connect tape client");
+
+        let (state, report) = scan_library(&mut client, "drive-0", "LTO-9")
+            .await
+            .expect("scan library");
+        assert_eq!(report.tapes_scanned, 2);
+        assert!(state.tape("CLN001").is_none());
+        assert!(state.tape("TAPE03").is_none());
+        assert_eq!(report.skipped_tapes.len(), 1);
+        assert_eq!(report.skipped_tapes[0].tape_id, "TAPE04");
+        assert_eq!(report.bundles_recovered, 3);
+        assert!(report.conflicts.is_empty());
+
+        let b = find_object(&state, "docs", "b.txt", None).unwrap();
+        assert_eq!(b.archive_id.as_deref(), Some("bundle-2"));
+        assert_eq!(b.tape_id.as_deref(), Some("TAPE01"));
+        assert_eq!(
+            b.tape_block_offset,
+            Some(written[1].entries[0].tape_block_offset)
+        );
+        assert_eq!(state.tape("TAPE02").unwrap().format, "LTO-9");
+        let bundle = state.archive_bundle("bundle-2").unwrap();
+        assert_eq!(bundle.filemark_start, 1);
+        assert_eq!(bundle.copies.len(), 1);
+        assert_eq!(bundle.copies[0].start_block, written[1].start_block);
+        assert_eq!(bundle.live_bytes, 5);
+
+        shutdown_tx.send(()).ok();
+    }
+}
@@ -0,0 +1,105 @@
+//! 运维 RPC
+//!
+//! 磁带异地导出/导入需要同时更新 Metadata 并操作带库，由 Scheduler 编排；流程见
+//! [`crate::vault`]。直接调用 Tape Worker 的 `ExportTape`/`ImportTapes` 只移动磁带，
+//! Metadata 中的状态不会变化。
+
+use crate::{vault, SchedulerState};
+use coldstore_proto::common;
+use coldstore_proto::scheduler::scheduler_admin_service_server::SchedulerAdminService;
+use coldstore_proto::scheduler::{ExportTapeRequest, ImportTapesRequest, ImportTapesResponse};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+pub struct SchedulerAdminServiceImpl {
+    state: Arc<SchedulerState>,
+}
+
+impl SchedulerAdminServiceImpl {
+    pub fn new(state: Arc<SchedulerState>) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl SchedulerAdminService for SchedulerAdminServiceImpl {
+    async fn export_tape(
+        &self,
+        req: Request<ExportTapeRequest>,
+    ) -> Result<Response<common::TapeInfo>, Status> {
+        let req = req.into_inner();
+        if req.tape_id.is_empty() {
+            return Err(Status::invalid_argument("tape_id is required"));
+        }
+        let mut tape = self.state.tape_client().await?;
+        let exported =
+            vault::export_tape(&self.state, &mut tape, &req.tape_id, req.location).await?;
+        Ok(Response::new(exported))
+    }
+
+    async fn import_tapes(
+        &self,
+        req: Request<ImportTapesRequest>,
+    ) -> Result<Response<ImportTapesResponse>, Status> {
+        let mut tape = self.state.tape_client().await?;
+        let tapes = vault::import_tapes(&self.state, &mut tape, req.into_inner().location).await?;
+        Ok(Response::new(ImportTapesResponse { tapes }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scheduler_state, spawn_metadata_server, spawn_tape_worker};
+
+    #[tokio::test]
+    async fn export_and_import_use_the_registered_tape_worker() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let admin = SchedulerAdminServiceImpl::new(state.clone());
+        let err = admin
+            .import_tapes(Request::new(ImportTapesRequest { location: None }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        let (_, tape_shutdown) = spawn_tape_worker(&state.metadata, 5, &["TAPE01"]).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .put_tape(Request::new(common::TapeInfo {
+                id: "TAPE01".into(),
+                format: "LTO-9".into(),
+                status: common::TapeStatus::TapeOnline as i32,
+                capacity_bytes: 1 << 20,
+                remaining_bytes: 1 << 20,
+                ..Default::default()
+            }))
+            .await
+            .expect("register tape");
+
+        let exported = admin
+            .export_tape(Request::new(ExportTapeRequest {
+                tape_id: "TAPE01".into(),
+                location: Some("vault-b".into()),
+            }))
+            .await
+            .expect("export tape")
+            .into_inner();
+        assert_eq!(exported.status, common::TapeStatus::TapeOffline as i32);
+        assert_eq!(exported.location.as_deref(), Some("vault-b"));
+
+        let imported = admin
+            .import_tapes(Request::new(ImportTapesRequest {
+                location: Some("library-1".into()),
+            }))
+            .await
+            .expect("import tapes")
+            .into_inner()
+            .tapes;
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].status, common::TapeStatus::TapeOnline as i32);
+
+        let _ = tape_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+}
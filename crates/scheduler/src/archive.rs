@@ -0,0 +1,902 @@
+//! 归档路径 (Cache 暂存 → 磁带归档包) 的数据完整性校验
+//!
+//! Scheduler 从 Cache 读出暂存数据组装归档包之前，先按元数据记录的
+//! checksum 重新计算 SHA-256；Tape Worker 写完后回报的 checksum 也需要
+//! 与发送的数据流一致，否则该归档任务失败。
+//!
+//! 每个归档包按桶 (或全局) 配置写入多份副本，副本分布在不同位置的磁带上；
+//! 每份副本写入桶为该副本指定的磁带池，池内没有可追加的磁带时从 Scratch 分配。
+//! 每份副本单独回报状态，所需副本全部落盘后元数据才将对象转为 COLD。
+//!
+//! 暂存数据从对象记录的 Cache Worker 读取 (见 [`crate::pairing`])，而不是本节点
+//! 配对的 Cache Worker。
+//!
+//! Scheduler 周期调用 [`run_archive`]：先由 [`plan_archive_tasks`] 将 ColdPending 对象
+//! 按桶聚合成归档包并创建归档任务，再占用一个驱动认领并执行待执行的任务。
+
+use crate::recall::now_timestamp;
+use crate::reclaim::{acquire_drive, current_object, release_drive, write_bundle};
+use crate::SchedulerState;
+use coldstore_common::checksum::verify_sha256;
+use coldstore_common::media::tape_appendable;
+use coldstore_proto::cache::cache_service_client::CacheServiceClient;
+use coldstore_proto::cache::{get_staging_response, GetStagingRequest};
+use coldstore_proto::common;
+use coldstore_proto::metadata::metadata_service_client::MetadataServiceClient;
+use coldstore_proto::metadata::{
+    AllocateScratchTapeRequest, GetArchiveBundleRequest, GetBucketRequest, ScanColdPendingRequest,
+    UpdateBundleCopyRequest,
+};
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_proto::tape::WriteBundleResponse;
+use std::collections::{BTreeMap, HashSet};
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+use tracing::{info, warn};
+
+/// 一轮归档：规划新的归档任务后占用一个驱动依次认领并执行可认领的任务
+///
+/// 单个任务失败时经 [`SchedulerState::fail_archive_task`] 回报后继续下一个；排空期间
+/// 不再认领。返回本轮完成的任务。
+pub async fn run_archive(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+) -> Result<Vec<common::ArchiveTask>, Status> {
+    plan_archive_tasks(state).await?;
+    let tasks = claimable_archive_tasks(state).await?;
+    if tasks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(drive_id) = acquire_drive(tape).await? else {
+        info!("没有空闲驱动，跳过本轮归档");
+        return Ok(Vec::new());
+    };
+    let mut completed = Vec::new();
+    for task in tasks {
+        if state.claim_worker_id().is_err() {
+            break;
+        }
+        let claimed = match state.claim_archive_task(&task.id).await {
+            Ok(claimed) => claimed,
+            // 其它 Worker 已认领或仍在退避
+            Err(status)
+                if matches!(
+                    status.code(),
+                    tonic::Code::Aborted | tonic::Code::FailedPrecondition
+                ) =>
+            {
+                continue
+            }
+            Err(status) => {
+                warn!("认领归档任务 {} 失败: {}", task.id, status.message());
+                continue;
+            }
+        };
+        match execute_archive_task(state, tape, &drive_id, &claimed).await {
+            Ok(task) => completed.push(task),
+            Err(status) => {
+                warn!("归档任务 {} 失败: {}", claimed.id, status.message());
+                if let Err(err) = state.fail_archive_task(&claimed.id, &status).await {
+                    warn!("回报归档任务 {} 失败出错: {}", claimed.id, err.message());
+                }
+            }
+        }
+    }
+    release_drive(tape, &drive_id).await;
+    Ok(completed)
+}
+
+/// 将尚未排入归档任务的 ColdPending 对象按桶聚合成归档包，每个归档包创建一个待执行的归档任务
+///
+/// 每轮最多扫描 `archive.batch_size` 个对象，单个归档包不超过 `archive.max_archive_size_mb`。
+/// 不足 `archive.min_archive_size_mb` 的尾批等到其中最早的对象超过
+/// `archive.aggregation_window_secs` 后才排入。已进入死信的任务中的对象不再重新规划。
+pub async fn plan_archive_tasks(
+    state: &SchedulerState,
+) -> Result<Vec<common::ArchiveTask>, Status> {
+    let config = &state.config.archive;
+    let request = ScanColdPendingRequest {
+        limit: u32::try_from(config.batch_size).unwrap_or(u32::MAX),
+    };
+    let objects = state
+        .metadata
+        .call(|mut client| async move { client.scan_cold_pending(request).await })
+        .await?
+        .objects;
+    if objects.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let queued = queued_objects(state).await?;
+    let mut by_bucket: BTreeMap<String, Vec<common::ObjectMetadata>> = BTreeMap::new();
+    for object in objects {
+        if !queued.contains(&object_id(&object)) {
+            by_bucket
+                .entry(object.bucket.clone())
+                .or_default()
+                .push(object);
+        }
+    }
+
+    let max_bundle_size = (config.max_archive_size_mb << 20).max(1);
+    let min_bundle_size = config.min_archive_size_mb << 20;
+    let window_start = now_timestamp().seconds - config.aggregation_window_secs as i64;
+    let mut tasks = Vec::new();
+    for objects in by_bucket.into_values() {
+        let mut batch: Vec<common::ObjectMetadata> = Vec::new();
+        let mut batch_size = 0;
+        for object in objects {
+            if !batch.is_empty() && batch_size + object.size > max_bundle_size {
+                tasks.push(create_archive_task(state, &std::mem::take(&mut batch)).await?);
+                batch_size = 0;
+            }
+            batch_size += object.size;
+            batch.push(object);
+        }
+        let window_elapsed = batch.iter().any(|object| {
+            object
+                .created_at
+                .is_none_or(|created| created.seconds <= window_start)
+        });
+        if !batch.is_empty() && (batch_size >= min_bundle_size || window_elapsed) {
+            tasks.push(create_archive_task(state, &batch).await?);
+        }
+    }
+    Ok(tasks)
+}
+
+type ObjectId = (String, String, Option<String>);
+
+fn object_id(object: &common::ObjectMetadata) -> ObjectId {
+    (
+        object.bucket.clone(),
+        object.key.clone(),
+        object.version_id.clone(),
+    )
+}
+
+/// 已排入待执行、执行中或死信归档任务的对象
+async fn queued_objects(state: &SchedulerState) -> Result<HashSet<ObjectId>, Status> {
+    let mut tasks = state
+        .metadata
+        .call(|mut client| async move { client.list_pending_archive_tasks(()).await })
+        .await?
+        .tasks;
+    tasks.extend(
+        state
+            .metadata
+            .call(|mut client| async move { client.list_dead_letter_tasks(()).await })
+            .await?
+            .archive_tasks,
+    );
+
+    let mut queued = HashSet::new();
+    for task in tasks {
+        let request = GetArchiveBundleRequest { id: task.bundle_id };
+        let bundle = match state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_archive_bundle(request).await }
+            })
+            .await
+        {
+            Ok(bundle) => bundle,
+            Err(status) if status.code() == tonic::Code::NotFound => continue,
+            Err(status) => return Err(status),
+        };
+        queued.extend(
+            bundle
+                .entries
+                .into_iter()
+                .map(|entry| (entry.bucket, entry.key, entry.version_id)),
+        );
+    }
+    Ok(queued)
+}
+
+/// 登记待写入的归档包 (只有条目、尚无副本) 及其归档任务
+async fn create_archive_task(
+    state: &SchedulerState,
+    objects: &[common::ObjectMetadata],
+) -> Result<common::ArchiveTask, Status> {
+    let id = uuid::Uuid::new_v4();
+    let total_size = objects.iter().map(|object| object.size).sum();
+    let bundle = common::ArchiveBundle {
+        id: format!("bundle-{id}"),
+        entries: objects
+            .iter()
+            .map(|object| common::BundleEntry {
+                bucket: object.bucket.clone(),
+                key: object.key.clone(),
+                version_id: object.version_id.clone(),
+                size: object.size,
+                offset_in_bundle: 0,
+                tape_block_offset: 0,
+                checksum: object.checksum.clone(),
+            })
+            .collect(),
+        total_size,
+        status: common::ArchiveBundleStatus::BundlePending as i32,
+        ..Default::default()
+    };
+    let task = common::ArchiveTask {
+        id: format!("archive-{id}"),
+        bundle_id: bundle.id.clone(),
+        object_count: objects.len() as u32,
+        total_size,
+        status: common::ArchiveTaskStatus::ArchiveTaskPending as i32,
+        ..Default::default()
+    };
+    state
+        .metadata
+        .call(|mut client| {
+            let bundle = bundle.clone();
+            async move { client.put_archive_bundle(bundle).await }
+        })
+        .await?;
+    state
+        .metadata
+        .call(|mut client| {
+            let task = task.clone();
+            async move { client.put_archive_task(task).await }
+        })
+        .await?;
+    info!(
+        "创建归档任务 {}: {} 个对象 ({total_size} 字节)",
+        task.id, task.object_count
+    );
+    Ok(task)
+}
+
+/// 待执行且已过退避时间的归档任务，按创建时间排序
+async fn claimable_archive_tasks(
+    state: &SchedulerState,
+) -> Result<Vec<common::ArchiveTask>, Status> {
+    let now = now_timestamp();
+    let mut tasks: Vec<_> = state
+        .metadata
+        .call(|mut client| async move { client.list_pending_archive_tasks(()).await })
+        .await?
+        .tasks
+        .into_iter()
+        .filter(|task| task.status == common::ArchiveTaskStatus::ArchiveTaskPending as i32)
+        .filter(|task| {
+            task.next_attempt_at
+                .is_none_or(|next| (next.seconds, next.nanos) <= (now.seconds, now.nanos))
+        })
+        .collect();
+    tasks.sort_by_key(|task| {
+        let created = task.created_at.unwrap_or_default();
+        (created.seconds, created.nanos, task.id.clone())
+    });
+    Ok(tasks)
+}
+
+/// 执行已认领的归档任务：读出并校验暂存数据，按桶的副本数与磁带池写入归档包后完成任务
+///
+/// 已删除、已被覆盖或已归档的对象跳过；暂存数据与元数据不一致时任务失败。
+async fn execute_archive_task(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    task: &common::ArchiveTask,
+) -> Result<common::ArchiveTask, Status> {
+    let request = GetArchiveBundleRequest {
+        id: task.bundle_id.clone(),
+    };
+    let bundle = state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.get_archive_bundle(request).await }
+        })
+        .await?;
+
+    let mut objects = Vec::with_capacity(bundle.entries.len());
+    for entry in &bundle.entries {
+        let Some(object) = current_object(state, entry).await? else {
+            continue;
+        };
+        if object.storage_class != common::StorageClass::ColdPending as i32
+            || !object.checksum.eq_ignore_ascii_case(&entry.checksum)
+        {
+            continue;
+        }
+        let data = read_staging(state, &object).await?;
+        objects.push((object, data));
+    }
+
+    if let Some((first, _)) = objects.first() {
+        let request = GetBucketRequest {
+            name: first.bucket.clone(),
+        };
+        let bucket = state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_bucket(request).await }
+            })
+            .await?;
+        let config = &state.config.archive;
+        let copies = required_copies(config.copies, &bucket);
+        let pools = copy_pools(&config.default_tape_pool, copies, &bucket);
+        write_bundle(state, tape, drive_id, &bundle.id, &pools, &objects).await?;
+    }
+
+    let mut completed = task.clone();
+    completed.status = common::ArchiveTaskStatus::ArchiveTaskCompleted as i32;
+    completed.drive_id = Some(drive_id.to_string());
+    completed.bytes_written = objects.iter().map(|(object, _)| object.size).sum();
+    completed.completed_at = Some(now_timestamp());
+    state
+        .metadata
+        .call(|mut client| {
+            let completed = completed.clone();
+            async move { client.update_archive_task(completed).await }
+        })
+        .await?;
+    info!(
+        "归档任务 {} 完成: 归档包 {} 写入 {} 个对象",
+        completed.id,
+        bundle.id,
+        objects.len()
+    );
+    Ok(completed)
+}
+
+/// 从持有暂存副本的 Cache Worker 读取对象并校验
+pub async fn read_staging(
+    state: &SchedulerState,
+    object: &common::ObjectMetadata,
+) -> Result<Vec<u8>, Status> {
+    let mut cache = state.staging_cache(object).await?;
+    read_staging_verified(&mut cache, object).await
+}
+
+/// 从 Cache 读取暂存对象，并校验大小和 SHA-256 与元数据一致
+pub async fn read_staging_verified(
+    cache: &mut CacheServiceClient<Channel>,
+    object: &common::ObjectMetadata,
+) -> Result<Vec<u8>, Status> {
+    let mut stream = cache
+        .get_staging(Request::new(GetStagingRequest {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            version_id: object.version_id.clone(),
+        }))
+        .await?
+        .into_inner();
+
+    let mut staged_checksum = None;
+    let mut data = Vec::with_capacity(object.size as usize);
+    while let Some(chunk) = stream.message().await? {
+        match chunk.payload {
+            Some(get_staging_response::Payload::Meta(meta)) => staged_checksum = meta.checksum,
+            Some(get_staging_response::Payload::Data(bytes)) => data.extend_from_slice(&bytes),
+            None => return Err(Status::internal("empty get_staging chunk")),
+        }
+    }
+
+    if data.len() as u64 != object.size {
+        return Err(Status::data_loss(format!(
+            "staging object {}/{} size mismatch: expected {}, actual {}",
+            object.bucket,
+            object.key,
+            object.size,
+            data.len()
+        )));
+    }
+    if let Some(staged) = staged_checksum.filter(|value| !value.is_empty()) {
+        if !staged.eq_ignore_ascii_case(&object.checksum) {
+            return Err(Status::data_loss(format!(
+                "staging object {}/{} checksum differs from metadata: expected {}, staged {}",
+                object.bucket, object.key, object.checksum, staged
+            )));
+        }
+    }
+    verify_sha256(&data, &object.checksum, "scheduler.archive.read_staging")?;
+    Ok(data)
+}
+
+/// 校验 Tape Worker 回报的归档包 checksum 与本地组装结果一致
+///
+/// `expected` 为 Scheduler 发送的对象数据流 (按顺序拼接) 的 SHA-256；Tape Worker 未回报
+/// checksum 时视为无法确认写入完整性，同样返回错误。
+#[allow(clippy::result_large_err)]
+pub fn verify_bundle_write(response: &WriteBundleResponse, expected: &str) -> Result<(), Status> {
+    if !response.success {
+        return Err(Status::internal(format!(
+            "write_bundle {} failed: {}",
+            response.bundle_id,
+            response.error.as_deref().unwrap_or("unknown error")
+        )));
+    }
+    match response.checksum.as_deref() {
+        Some(actual) if actual.eq_ignore_ascii_case(expected) => Ok(()),
+        Some(actual) => Err(Status::data_loss(format!(
+            "write_bundle {} checksum mismatch: expected {}, actual {}",
+            response.bundle_id, expected, actual
+        ))),
+        None => Err(Status::data_loss(format!(
+            "write_bundle {} returned no checksum",
+            response.bundle_id
+        ))),
+    }
+}
+
+/// 桶级副本数优先，未配置时使用全局默认值；至少为 1
+pub fn required_copies(default_copies: u32, bucket: &common::BucketInfo) -> u32 {
+    bucket.archive_copies.unwrap_or(default_copies).max(1)
+}
+
+/// 各副本写入的磁带池：桶的 `tape_pools` 按副本顺序覆盖 `default_pool`
+pub fn copy_pools(default_pool: &str, copies: u32, bucket: &common::BucketInfo) -> Vec<String> {
+    (0..copies as usize)
+        .map(|index| {
+            bucket
+                .tape_pools
+                .get(index)
+                .filter(|pool| !pool.is_empty())
+                .map_or_else(|| default_pool.to_string(), Clone::clone)
+        })
+        .collect()
+}
+
+/// 无法为第 `index` 份副本选到磁带；`locations` 为前面副本已占用的位置
+struct MissingCopy {
+    index: usize,
+    locations: Vec<String>,
+}
+
+fn plan_copy_tapes(
+    tapes: &[common::TapeInfo],
+    pools: &[String],
+    bundle_size: u64,
+) -> Result<Vec<common::BundleCopy>, MissingCopy> {
+    let mut candidates: Vec<&common::TapeInfo> = tapes
+        .iter()
+        .filter(|tape| tape_appendable(tape) && tape.remaining_bytes >= bundle_size)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.remaining_bytes
+            .cmp(&a.remaining_bytes)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    let location =
+        |tape: &common::TapeInfo| tape.location.clone().unwrap_or_else(|| tape.id.clone());
+    let mut locations = Vec::with_capacity(pools.len());
+    let mut selected = Vec::with_capacity(pools.len());
+    for (index, pool) in pools.iter().enumerate() {
+        let Some(tape) = candidates
+            .iter()
+            .find(|tape| tape.pool == *pool && !locations.contains(&location(tape)))
+        else {
+            return Err(MissingCopy { index, locations });
+        };
+        locations.push(location(tape));
+        selected.push(common::BundleCopy {
+            tape_id: tape.id.clone(),
+            location: tape.location.clone(),
+            status: common::ArchiveBundleStatus::BundlePending as i32,
+            filemark_start: 0,
+            filemark_end: 0,
+            start_block: 0,
+            block_size: 0,
+            error: None,
+            completed_at: None,
+        });
+    }
+    Ok(selected)
+}
+
+/// 为归档包的每份副本在对应磁带池中挑选不同位置的可追加磁带
+///
+/// 未登记位置的磁带各自视为独立位置。候选不足时返回 `FailedPrecondition`。
+#[allow(clippy::result_large_err)]
+pub fn select_copy_tapes(
+    tapes: &[common::TapeInfo],
+    pools: &[String],
+    bundle_size: u64,
+) -> Result<Vec<common::BundleCopy>, Status> {
+    plan_copy_tapes(tapes, pools, bundle_size).map_err(|missing| {
+        Status::failed_precondition(format!(
+            "copy {} needs a tape in pool {:?} with {bundle_size} bytes free outside locations {:?}",
+            missing.index + 1,
+            pools[missing.index],
+            missing.locations
+        ))
+    })
+}
+
+/// 同 [`select_copy_tapes`]，池内没有合适磁带时从 Scratch 分配一盘后重新挑选
+///
+/// 每次分配都会消耗一盘 Scratch 磁带，Scratch 耗尽时返回 Metadata 的 `FailedPrecondition`。
+pub async fn allocate_copy_tapes(
+    metadata: &mut MetadataServiceClient<Channel>,
+    pools: &[String],
+    bundle_size: u64,
+) -> Result<Vec<common::BundleCopy>, Status> {
+    loop {
+        let tapes = metadata
+            .list_tapes(Request::new(()))
+            .await?
+            .into_inner()
+            .tapes;
+        let missing = match plan_copy_tapes(&tapes, pools, bundle_size) {
+            Ok(copies) => return Ok(copies),
+            Err(missing) => missing,
+        };
+        let tape = metadata
+            .allocate_scratch_tape(Request::new(AllocateScratchTapeRequest {
+                pool: pools[missing.index].clone(),
+                min_free_bytes: bundle_size,
+                exclude_locations: missing.locations,
+            }))
+            .await?
+            .into_inner();
+        info!(
+            "磁带池 {:?} 已无可写磁带，分配 Scratch 磁带 {}",
+            tape.pool, tape.id
+        );
+    }
+}
+
+/// 回报单份副本的写入结果
+///
+/// 写入成功时记录 filemark 与起始块，失败时记录错误；元数据在所需副本数
+/// 达成后完成归档包并将对象转为 COLD。
+pub async fn record_copy_written(
+    metadata: &mut MetadataServiceClient<Channel>,
+    tape_id: &str,
+    response: &WriteBundleResponse,
+    result: Result<(), &Status>,
+) -> Result<(), Status> {
+    let (status, error) = match result {
+        Ok(()) => (common::ArchiveBundleStatus::BundleCompleted, None),
+        Err(err) => (
+            common::ArchiveBundleStatus::BundleFailed,
+            Some(err.message().to_string()),
+        ),
+    };
+    metadata
+        .update_bundle_copy(Request::new(UpdateBundleCopyRequest {
+            bundle_id: response.bundle_id.clone(),
+            tape_id: tape_id.to_string(),
+            status: status as i32,
+            filemark_start: response.filemark_start,
+            filemark_end: response.filemark_end,
+            start_block: response.start_block,
+            block_size: response.block_size,
+            error,
+        }))
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        scheduler_state, spawn_cache, spawn_cache_worker, spawn_metadata_server,
+    };
+    use coldstore_common::checksum::sha256_hex;
+    use coldstore_common::membership::deregister;
+    use coldstore_proto::cache::{put_staging_request, PutStagingMeta, PutStagingRequest};
+    use coldstore_proto::metadata::{LabelTapeRequest, ScanColdPendingRequest};
+
+    fn staging_request(data: &[u8], checksum: Option<String>) -> Vec<PutStagingRequest> {
+        vec![
+            PutStagingRequest {
+                payload: Some(put_staging_request::Payload::Meta(PutStagingMeta {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    version_id: None,
+                    size: data.len() as u64,
+                    checksum,
+                    content_type: None,
+                    etag: None,
+                })),
+            },
+            PutStagingRequest {
+                payload: Some(put_staging_request::Payload::Data(data.to_vec())),
+            },
+        ]
+    }
+
+    fn object(checksum: String) -> common::ObjectMetadata {
+        common::ObjectMetadata {
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            version_id: None,
+            size: 5,
+            checksum,
+            content_type: None,
+            etag: None,
+            storage_class: common::StorageClass::ColdPending as i32,
+            archive_id: None,
+            tape_id: None,
+            tape_set: vec![],
+            tape_block_offset: None,
+            restore_status: None,
+            restore_expire_at: None,
+            created_at: None,
+            updated_at: None,
+            staging_worker_id: None,
+            staging_error: None,
+            attributes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_rejects_staging_with_wrong_checksum() {
+        let (mut cache, shutdown_tx) = spawn_cache().await;
+        let err = cache
+            .put_staging(tokio_stream::iter(staging_request(
+                b"hello",
+                Some(sha256_hex(b"other")),
+            )))
+            .await
+            .expect_err("mismatched staging checksum should be rejected");
+        assert_eq!(err.code(), tonic::Code::DataLoss);
+        assert!(err.message().contains("cache.put_staging"));
+        shutdown_tx.send(()).ok();
+    }
+
+    #[tokio::test]
+    async fn staging_read_is_verified_against_metadata_checksum() {
+        let (mut cache, shutdown_tx) = spawn_cache().await;
+        cache
+            .put_staging(tokio_stream::iter(staging_request(b"hello", None)))
+            .await
+            .expect("staging without checksum is accepted");
+
+        let data = read_staging_verified(&mut cache, &object(sha256_hex(b"hello")))
+            .await
+            .expect("matching checksum should pass");
+        assert_eq!(data, b"hello");
+
+        let err = read_staging_verified(&mut cache, &object(sha256_hex(b"hellp")))
+            .await
+            .expect_err("metadata checksum mismatch should fail");
+        assert_eq!(err.code(), tonic::Code::DataLoss);
+        shutdown_tx.send(()).ok();
+    }
+
+    #[tokio::test]
+    async fn staging_is_read_from_the_worker_that_holds_it_and_lost_copies_need_reupload() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let writer = scheduler_state(addr, Some(1));
+        let archiver = scheduler_state(addr, Some(2));
+        let first_cache = spawn_cache_worker(&writer.metadata, 1).await;
+        let second_cache = spawn_cache_worker(&writer.metadata, 2).await;
+        assert_eq!(archiver.pair_cache().await.expect("pair").node_id, 2);
+
+        let mut staged = object(sha256_hex(b"hello"));
+        staged.staging_worker_id = Some(
+            writer
+                .write_staging(&staged, b"hello".to_vec())
+                .await
+                .expect("stage on paired worker"),
+        );
+        assert_eq!(staged.staging_worker_id, Some(1));
+        let mut metadata = writer.metadata.client().await.expect("metadata leader");
+        metadata
+            .create_bucket(Request::new(common::BucketInfo {
+                name: "docs".into(),
+                ..Default::default()
+            }))
+            .await
+            .expect("create bucket");
+        metadata
+            .put_object(Request::new(staged.clone()))
+            .await
+            .expect("put object");
+
+        let data = read_staging(&archiver, &staged)
+            .await
+            .expect("read from the worker holding the copy");
+        assert_eq!(data, b"hello");
+
+        deregister(&writer.metadata, common::WorkerType::WorkerCache, 1)
+            .await
+            .expect("deregister first cache");
+        let err = read_staging(&archiver, &staged)
+            .await
+            .expect_err("holder left the cluster");
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        assert!(err.message().contains("re-uploaded"), "{}", err.message());
+
+        let pending = metadata
+            .scan_cold_pending(Request::new(ScanColdPendingRequest { limit: 0 }))
+            .await
+            .expect("scan")
+            .into_inner()
+            .objects;
+        assert!(pending.is_empty(), "lost staging copies are not archived");
+
+        let _ = first_cache.send(());
+        let _ = second_cache.send(());
+        let _ = metadata_shutdown.send(());
+    }
+
+    #[test]
+    fn bundle_write_checksum_must_match() {
+        let mut response = WriteBundleResponse {
+            drive_id: "drive-0".into(),
+            bundle_id: "bundle-1".into(),
+            bytes_written: 5,
+            filemark_start: 0,
+            filemark_end: 1,
+            checksum: Some(sha256_hex(b"hello").to_uppercase()),
+            success: true,
+            error: None,
+            entries: vec![],
+            start_block: 0,
+            block_size: 512,
+        };
+        assert!(verify_bundle_write(&response, &sha256_hex(b"hello")).is_ok());
+
+        response.checksum = Some(sha256_hex(b"other"));
+        let err = verify_bundle_write(&response, &sha256_hex(b"hello")).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::DataLoss);
+
+        response.checksum = None;
+        assert!(verify_bundle_write(&response, &sha256_hex(b"hello")).is_err());
+    }
+
+    fn tape(id: &str, location: Option<&str>, status: common::TapeStatus) -> common::TapeInfo {
+        common::TapeInfo {
+            id: id.into(),
+            barcode: None,
+            format: "LTO-9".into(),
+            status: status as i32,
+            location: location.map(Into::into),
+            capacity_bytes: 1 << 30,
+            used_bytes: 0,
+            remaining_bytes: 1 << 30,
+            archive_bundle_ids: vec![],
+            last_verified_at: None,
+            error_count: 0,
+            registered_at: None,
+            live_bytes: 0,
+            dead_bytes: 0,
+            pool: String::new(),
+            error_history: Vec::new(),
+            media_state: common::TapeMediaState::TapeMediaAppendable as i32,
+        }
+    }
+
+    #[test]
+    fn copy_tapes_are_spread_across_locations() {
+        let tapes = vec![
+            tape("TAPE01", Some("rack-a"), common::TapeStatus::TapeOnline),
+            tape("TAPE02", Some("rack-a"), common::TapeStatus::TapeOnline),
+            tape("TAPE03", Some("rack-b"), common::TapeStatus::TapeError),
+            tape("TAPE04", Some("vault"), common::TapeStatus::TapeOnline),
+        ];
+        let pools = vec![String::new(); 3];
+        let copies = select_copy_tapes(&tapes, &pools[..2], 1024).expect("two locations available");
+        let ids: Vec<_> = copies.iter().map(|copy| copy.tape_id.as_str()).collect();
+        assert_eq!(ids, ["TAPE01", "TAPE04"]);
+        assert!(copies
+            .iter()
+            .all(|copy| copy.status == common::ArchiveBundleStatus::BundlePending as i32));
+
+        let err = select_copy_tapes(&tapes, &pools, 1024).expect_err("only two usable locations");
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn each_copy_is_written_to_its_own_pool() {
+        let mut tapes = vec![
+            tape("TAPE01", Some("rack-a"), common::TapeStatus::TapeOnline),
+            tape("TAPE02", Some("rack-b"), common::TapeStatus::TapeOnline),
+            tape("TAPE03", Some("vault"), common::TapeStatus::TapeOnline),
+        ];
+        tapes[0].pool = "onsite".into();
+        tapes[0].remaining_bytes = 1 << 31;
+        tapes[1].pool = "onsite".into();
+        tapes[1].media_state = common::TapeMediaState::TapeMediaFull as i32;
+        tapes[2].pool = "vault".into();
+
+        let pools = vec!["onsite".to_string(), "vault".to_string()];
+        let copies = select_copy_tapes(&tapes, &pools, 1024).expect("one tape per pool");
+        let ids: Vec<_> = copies.iter().map(|copy| copy.tape_id.as_str()).collect();
+        assert_eq!(ids, ["TAPE01", "TAPE03"]);
+
+        let err = select_copy_tapes(&tapes, &vec!["onsite".to_string(); 2], 1024)
+            .expect_err("the other onsite tape is full");
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[test]
+    fn bucket_pools_override_default_per_copy() {
+        let bucket = common::BucketInfo {
+            name: "docs".into(),
+            tape_pools: vec!["onsite".into(), String::new()],
+            ..Default::default()
+        };
+        assert_eq!(
+            copy_pools("general", 3, &bucket),
+            ["onsite", "general", "general"]
+        );
+    }
+
+    #[tokio::test]
+    async fn full_pool_draws_a_tape_from_scratch() {
+        let (addr, shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        let mut nearly_full = tape("TAPE01", Some("rack-a"), common::TapeStatus::TapeOnline);
+        nearly_full.pool = "onsite".into();
+        nearly_full.remaining_bytes = 512;
+        metadata
+            .put_tape(Request::new(nearly_full))
+            .await
+            .expect("put tape");
+        for (id, location) in [("TAPE02", "rack-a"), ("TAPE03", "rack-b")] {
+            metadata
+                .label_tape(Request::new(LabelTapeRequest {
+                    tape_id: id.into(),
+                    format: "LTO-9".into(),
+                    location: Some(location.into()),
+                    ..Default::default()
+                }))
+                .await
+                .expect("label scratch tape");
+        }
+
+        let pools = vec!["onsite".to_string(), "onsite".to_string()];
+        let copies = allocate_copy_tapes(&mut metadata, &pools, 1024)
+            .await
+            .expect("scratch tapes allocated");
+        let ids: Vec<_> = copies.iter().map(|copy| copy.tape_id.as_str()).collect();
+        assert_eq!(ids, ["TAPE02", "TAPE03"]);
+
+        let tapes = metadata
+            .list_tapes(Request::new(()))
+            .await
+            .expect("list tapes")
+            .into_inner()
+            .tapes;
+        for tape in &tapes {
+            assert_eq!(tape.pool, "onsite");
+            assert_eq!(tape.status, common::TapeStatus::TapeOnline as i32);
+        }
+        let full = tapes.iter().find(|tape| tape.id == "TAPE01").unwrap();
+        assert_eq!(
+            full.media_state,
+            common::TapeMediaState::TapeMediaFull as i32
+        );
+        assert_eq!(full.capacity_bytes, 1 << 30);
+        let labelled = tapes.iter().find(|tape| tape.id == "TAPE02").unwrap();
+        assert_eq!(labelled.capacity_bytes, 18_000_000_000_000);
+
+        let err = allocate_copy_tapes(&mut metadata, &["offsite".to_string()], 1024)
+            .await
+            .expect_err("scratch pool is empty");
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        let _ = shutdown.send(());
+    }
+
+    #[test]
+    fn bucket_copy_count_overrides_default() {
+        let mut bucket = common::BucketInfo {
+            name: "docs".into(),
+            created_at: None,
+            owner: None,
+            versioning_enabled: false,
+            object_count: 0,
+            total_size: 0,
+            archive_copies: None,
+            tape_pools: Vec::new(),
+        };
+        assert_eq!(required_copies(2, &bucket), 2);
+        bucket.archive_copies = Some(3);
+        assert_eq!(required_copies(2, &bucket), 3);
+        bucket.archive_copies = Some(0);
+        assert_eq!(required_copies(2, &bucket), 1);
+    }
+}
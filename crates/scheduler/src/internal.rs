@@ -0,0 +1,261 @@
+//! 内部 RPC
+//!
+//! 仅供集群内组件调用，必须通过 mTLS 访问：Gateway 校验 SigV4 签名时经此解析
+//! access key 明文 secret。Metadata 只保存加密后的 secret，由
+//! `MetadataInternalService` 解密后返回。
+
+use crate::SchedulerState;
+use coldstore_common::tls::require_client_certificate;
+use coldstore_proto::common;
+use coldstore_proto::metadata::metadata_internal_service_client::MetadataInternalServiceClient;
+use coldstore_proto::metadata::{GetAccessKeyRequest, GetUserRequest};
+use coldstore_proto::scheduler::scheduler_internal_service_server::SchedulerInternalService;
+use coldstore_proto::scheduler::LookupAccessKeyRequest;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+pub struct SchedulerInternalServiceImpl {
+    state: Arc<SchedulerState>,
+}
+
+impl SchedulerInternalServiceImpl {
+    pub fn new(state: Arc<SchedulerState>) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl SchedulerInternalService for SchedulerInternalServiceImpl {
+    /// 查找可用于签名校验的 access key；吊销的 key 或停用用户的 key 视为不存在
+    async fn lookup_access_key(
+        &self,
+        req: Request<LookupAccessKeyRequest>,
+    ) -> Result<Response<common::AccessKeyInfo>, Status> {
+        require_client_certificate(&req)?;
+        let access_key_id = req.into_inner().access_key_id;
+        let request = GetAccessKeyRequest {
+            access_key_id: access_key_id.clone(),
+        };
+        let key = self
+            .state
+            .metadata
+            .call_channel(|channel| {
+                let request = request.clone();
+                async move {
+                    MetadataInternalServiceClient::new(channel)
+                        .resolve_access_key(request)
+                        .await
+                }
+            })
+            .await?;
+        if key.status != common::AccessKeyStatus::AccessKeyActive as i32 {
+            return Err(Status::not_found(format!(
+                "access key {access_key_id} not found"
+            )));
+        }
+        let request = GetUserRequest {
+            user_id: key.user_id.clone(),
+        };
+        let user = self
+            .state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_user(request).await }
+            })
+            .await?;
+        if !user.enabled {
+            return Err(Status::not_found(format!(
+                "access key {access_key_id} not found"
+            )));
+        }
+        Ok(Response::new(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use coldstore_common::client::{ClientConfig, MetadataClientPool};
+    use coldstore_common::config::{MetadataConfig, SchedulerConfig};
+    use coldstore_common::tls::testing::TestPki;
+    use coldstore_metadata::service::MetadataServiceImpl;
+    use coldstore_proto::metadata::metadata_internal_service_server::MetadataInternalServiceServer;
+    use coldstore_proto::metadata::metadata_service_server::MetadataServiceServer;
+    use coldstore_proto::metadata::{
+        CreateAccessKeyRequest, RevokeAccessKeyRequest, SetUserEnabledRequest,
+    };
+    use coldstore_proto::scheduler::scheduler_internal_service_client::SchedulerInternalServiceClient;
+    use coldstore_proto::scheduler::scheduler_internal_service_server::SchedulerInternalServiceServer;
+    use std::net::SocketAddr;
+    use std::sync::atomic::AtomicBool;
+    use tokio::sync::oneshot;
+    use tonic::transport::{Channel, Server};
+
+    fn free_addr() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind test listener");
+        listener.local_addr().expect("listener addr")
+    }
+
+    async fn connect(pki: &TestPki, addr: SocketAddr) -> Channel {
+        for _ in 0..20 {
+            if let Ok(channel) = pki.client_config(true).connect(&addr.to_string()).await {
+                return channel;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        }
+        panic!("could not connect to test server at {addr}");
+    }
+
+    #[tokio::test]
+    async fn lookup_access_key_requires_mtls_and_hides_revoked_keys_and_disabled_users() {
+        let pki = TestPki::generate();
+        let key_path = pki.dir.join("access-keys.key");
+        std::fs::write(
+            &key_path,
+            base64::engine::general_purpose::STANDARD.encode([7u8; 32]),
+        )
+        .expect("write encryption key");
+        let metadata = Arc::new(
+            MetadataServiceImpl::new(&MetadataConfig {
+                access_key_encryption_key_path: Some(key_path.display().to_string()),
+                ..MetadataConfig::default()
+            })
+            .await
+            .expect("metadata service init"),
+        );
+        let metadata_addr = free_addr();
+        let (metadata_shutdown, metadata_shutdown_rx) = oneshot::channel::<()>();
+        let server_tls = pki
+            .server_config(true)
+            .server_tls_config()
+            .expect("server tls")
+            .expect("tls enabled");
+        let served = metadata.clone();
+        tokio::spawn(async move {
+            Server::builder()
+                .tls_config(server_tls)
+                .expect("server tls config")
+                .add_service(MetadataServiceServer::from_arc(served.clone()))
+                .add_service(MetadataInternalServiceServer::from_arc(served))
+                .serve_with_shutdown(metadata_addr, async {
+                    let _ = metadata_shutdown_rx.await;
+                })
+                .await
+                .expect("metadata server should run");
+        });
+
+        let state = Arc::new(SchedulerState {
+            metadata: Arc::new(
+                MetadataClientPool::new(
+                    &[metadata_addr.to_string()],
+                    &pki.client_config(true),
+                    ClientConfig::default(),
+                )
+                .expect("metadata pool"),
+            ),
+            cache: Default::default(),
+            tape: None,
+            config: SchedulerConfig::default(),
+            draining: AtomicBool::new(false),
+        });
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .create_user(Request::new(common::UserInfo {
+                user_id: "alice".into(),
+                display_name: "Alice".into(),
+                enabled: true,
+                created_at: None,
+            }))
+            .await
+            .expect("create user");
+        let key = metadata
+            .create_access_key(Request::new(CreateAccessKeyRequest {
+                user_id: "alice".into(),
+            }))
+            .await
+            .expect("create access key")
+            .into_inner();
+
+        // 进程内直接调用没有客户端证书
+        let internal = SchedulerInternalServiceImpl::new(state.clone());
+        let err = internal
+            .lookup_access_key(Request::new(LookupAccessKeyRequest {
+                access_key_id: key.access_key_id.clone(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+
+        let scheduler_addr = free_addr();
+        let (scheduler_shutdown, scheduler_shutdown_rx) = oneshot::channel::<()>();
+        let server_tls = pki
+            .server_config(true)
+            .server_tls_config()
+            .expect("server tls")
+            .expect("tls enabled");
+        tokio::spawn(async move {
+            Server::builder()
+                .tls_config(server_tls)
+                .expect("server tls config")
+                .add_service(SchedulerInternalServiceServer::new(internal))
+                .serve_with_shutdown(scheduler_addr, async {
+                    let _ = scheduler_shutdown_rx.await;
+                })
+                .await
+                .expect("scheduler server should run");
+        });
+        let mut client = SchedulerInternalServiceClient::new(connect(&pki, scheduler_addr).await);
+
+        let found = client
+            .lookup_access_key(Request::new(LookupAccessKeyRequest {
+                access_key_id: key.access_key_id.clone(),
+            }))
+            .await
+            .expect("active key resolves")
+            .into_inner();
+        assert_eq!(found.user_id, "alice");
+        assert_eq!(found.secret_access_key, key.secret_access_key);
+        assert!(found.encrypted_secret.is_empty());
+
+        metadata
+            .set_user_enabled(Request::new(SetUserEnabledRequest {
+                user_id: "alice".into(),
+                enabled: false,
+            }))
+            .await
+            .expect("disable user");
+        let err = client
+            .lookup_access_key(Request::new(LookupAccessKeyRequest {
+                access_key_id: key.access_key_id.clone(),
+            }))
+            .await
+            .expect_err("disabled user key is hidden");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        metadata
+            .set_user_enabled(Request::new(SetUserEnabledRequest {
+                user_id: "alice".into(),
+                enabled: true,
+            }))
+            .await
+            .expect("enable user");
+        metadata
+            .revoke_access_key(Request::new(RevokeAccessKeyRequest {
+                access_key_id: key.access_key_id.clone(),
+            }))
+            .await
+            .expect("revoke key");
+        let err = client
+            .lookup_access_key(Request::new(LookupAccessKeyRequest {
+                access_key_id: key.access_key_id,
+            }))
+            .await
+            .expect_err("revoked key is hidden");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let _ = scheduler_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+}
@@ -1,45 +1,769 @@
+pub mod admin;
+pub mod archive;
+pub mod internal;
+pub mod pairing;
+pub mod recall;
+pub mod reclaim;
+pub mod retry;
+pub mod scrub;
 pub mod service;
+#[cfg(test)]
+mod test_support;
+pub mod vault;
 
 use anyhow::Result;
+use coldstore_common::client::MetadataClientPool;
 use coldstore_common::config::SchedulerConfig;
-use coldstore_proto::cache::cache_service_client::CacheServiceClient;
-use coldstore_proto::metadata::metadata_service_client::MetadataServiceClient;
+use coldstore_common::membership::{shutdown_signal, Membership, Registration, WorkerMembership};
+use coldstore_proto::common;
+use coldstore_proto::metadata::{
+    heartbeat_request, ClaimTaskRequest, FailTaskRequest, SchedulerHeartbeat,
+};
 use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use pairing::PairedCache;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
 use tonic::transport::{Channel, Server};
-use tracing::info;
+use tracing::{info, warn};
 
 pub struct SchedulerState {
-    pub metadata: MetadataServiceClient<Channel>,
-    pub cache: Option<CacheServiceClient<Channel>>,
+    /// 连接全部 Metadata 节点，请求路由到 leader
+    pub metadata: Arc<MetadataClientPool>,
+    /// 配对的 Cache Worker，新写入对象的暂存副本落在这里；见 [`pairing`]
+    pub cache: RwLock<Option<PairedCache>>,
+    /// 指定的 Tape Worker；为 `None` 时按需从 Metadata 发现，见 [`SchedulerState::tape_client`]
     pub tape: Option<TapeServiceClient<Channel>>,
     pub config: SchedulerConfig,
+    /// Metadata 将本节点置为 NodeDraining 后不再认领新任务
+    pub draining: AtomicBool,
+}
+
+impl SchedulerState {
+    /// 认领归档/取回任务时使用的 Worker ID；排空期间拒绝认领新任务
+    #[allow(clippy::result_large_err)]
+    pub fn claim_worker_id(&self) -> std::result::Result<u64, tonic::Status> {
+        if self.draining.load(Ordering::Acquire) {
+            return Err(tonic::Status::unavailable(format!(
+                "scheduler worker {} is draining",
+                self.config.membership.node_id
+            )));
+        }
+        Ok(self.config.membership.node_id)
+    }
+
+    /// 以本节点身份认领归档任务并获得租约；其它 Worker 已持有时返回 `Aborted`
+    pub async fn claim_archive_task(
+        &self,
+        id: &str,
+    ) -> std::result::Result<common::ArchiveTask, tonic::Status> {
+        let request = ClaimTaskRequest {
+            id: id.to_string(),
+            worker_id: self.claim_worker_id()?,
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.claim_archive_task(request).await }
+            })
+            .await
+    }
+
+    /// 回报本节点持有的归档任务失败；按 [`retry::classify`] 决定退避重试或进入死信
+    pub async fn fail_archive_task(
+        &self,
+        id: &str,
+        error: &tonic::Status,
+    ) -> std::result::Result<common::ArchiveTask, tonic::Status> {
+        let request = FailTaskRequest {
+            id: id.to_string(),
+            worker_id: self.config.membership.node_id,
+            error: error.message().to_string(),
+            kind: retry::classify(error) as i32,
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.fail_archive_task(request).await }
+            })
+            .await
+    }
+
+    /// Tape Worker 客户端；未指定时连接 Metadata 中节点 ID 最小的在线 Tape Worker
+    pub async fn tape_client(
+        &self,
+    ) -> std::result::Result<TapeServiceClient<Channel>, tonic::Status> {
+        if let Some(tape) = &self.tape {
+            return Ok(tape.clone());
+        }
+        let workers = self
+            .metadata
+            .call(|mut client| async move { client.list_online_tape_workers(()).await })
+            .await?
+            .workers;
+        let worker = workers
+            .iter()
+            .filter(|worker| worker.status == common::NodeStatus::NodeOnline as i32)
+            .min_by_key(|worker| worker.node_id)
+            .ok_or_else(|| tonic::Status::unavailable("no online tape worker"))?;
+        let endpoint = self
+            .config
+            .tls
+            .endpoint(&worker.addr)
+            .map_err(tonic::Status::from)?;
+        Ok(TapeServiceClient::new(endpoint.connect_lazy()))
+    }
+
+    /// 本节点负责的待执行任务数与执行中任务数
+    async fn owned_task_counts(&self) -> std::result::Result<SchedulerHeartbeat, tonic::Status> {
+        let node_id = Some(self.config.membership.node_id);
+        let archive = self
+            .metadata
+            .call(|mut client| async move { client.list_pending_archive_tasks(()).await })
+            .await?
+            .tasks;
+        let recall = self
+            .metadata
+            .call(|mut client| async move { client.list_pending_recall_tasks(()).await })
+            .await?
+            .tasks;
+        let archive_in_progress = common::ArchiveTaskStatus::ArchiveTaskInProgress as i32;
+        let recall_in_progress = common::RestoreStatus::RestoreInProgress as i32;
+        let owned_archive: Vec<_> = archive.iter().filter(|t| t.worker_id == node_id).collect();
+        let owned_recall: Vec<_> = recall.iter().filter(|t| t.worker_id == node_id).collect();
+        let active_jobs = owned_archive
+            .iter()
+            .filter(|task| task.status == archive_in_progress)
+            .count()
+            + owned_recall
+                .iter()
+                .filter(|task| task.status == recall_in_progress)
+                .count();
+        Ok(SchedulerHeartbeat {
+            pending_archive_tasks: owned_archive.len() as u64,
+            pending_recall_tasks: owned_recall.len() as u64,
+            active_jobs: active_jobs as u64,
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl WorkerMembership for SchedulerState {
+    async fn registration(&self) -> Registration {
+        Registration::Scheduler(common::SchedulerWorkerInfo {
+            node_id: self.config.membership.node_id,
+            addr: self.config.membership.addr(&self.config.listen).to_string(),
+            status: common::NodeStatus::NodeOnline as i32,
+            last_heartbeat: None,
+            is_active: true,
+            pending_archive_tasks: 0,
+            pending_recall_tasks: 0,
+            active_jobs: 0,
+            paired_cache_worker_id: self
+                .cache
+                .read()
+                .await
+                .as_ref()
+                .map_or(0, |paired| paired.node_id),
+            drain: None,
+        })
+    }
+
+    async fn heartbeat(&self) -> heartbeat_request::Payload {
+        let counts = self.owned_task_counts().await.unwrap_or_else(|status| {
+            warn!("统计本节点任务失败: {}", status.message());
+            SchedulerHeartbeat::default()
+        });
+        heartbeat_request::Payload::Scheduler(counts)
+    }
+
+    async fn status_changed(&self, status: common::NodeStatus) {
+        self.draining.store(
+            status == common::NodeStatus::NodeDraining,
+            Ordering::Release,
+        );
+    }
+
+    /// 已认领的任务继续执行，全部结束后排空完成
+    async fn drain(&self, _metadata: &MetadataClientPool) -> common::DrainProgress {
+        match self.owned_task_counts().await {
+            Ok(counts) => common::DrainProgress {
+                remaining_items: counts.active_jobs,
+                remaining_bytes: 0,
+                complete: counts.active_jobs == 0,
+                detail: Some(format!("{} tasks in progress", counts.active_jobs)),
+            },
+            Err(status) => common::DrainProgress {
+                remaining_items: 0,
+                remaining_bytes: 0,
+                complete: false,
+                detail: Some(format!("count owned tasks: {}", status.message())),
+            },
+        }
+    }
+}
+
+/// 执行一轮取回与归档任务，取回先于归档以缩短解冻等待
+///
+/// 排空期间不再认领新任务，已认领的任务由各自的执行路径完成或回报失败。
+pub async fn run_pending_tasks(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+) -> std::result::Result<(), tonic::Status> {
+    if state.draining.load(Ordering::Acquire) {
+        return Ok(());
+    }
+    recall::run_recall(state, tape).await?;
+    archive::run_archive(state, tape).await?;
+    Ok(())
 }
 
 pub async fn run(config: SchedulerConfig) -> Result<()> {
     let addr = config.listen.parse()?;
 
-    let metadata_addr = format!("http://{}", &config.metadata_addrs[0]);
-    let metadata = MetadataServiceClient::connect(metadata_addr).await?;
+    let metadata = Arc::new(MetadataClientPool::new(
+        &config.metadata_addrs,
+        &config.tls,
+        config.metadata_client.clone(),
+    )?);
 
-    let state = std::sync::Arc::new(SchedulerState {
+    let state = Arc::new(SchedulerState {
         metadata,
-        cache: None,
+        cache: RwLock::new(None),
         tape: None,
         config: config.clone(),
+        draining: AtomicBool::new(false),
     });
+    if let Err(status) = state.pair_cache().await {
+        warn!(
+            "启动时未能配对 Cache Worker，首次写入时重试: {}",
+            status.message()
+        );
+    }
 
-    let scheduler_service = service::SchedulerServiceImpl::new(state);
+    let membership =
+        Membership::start(state.metadata.clone(), state.clone(), &config.membership).await;
+    let scheduler_service = service::SchedulerServiceImpl::new(state.clone());
+    let admin_service = admin::SchedulerAdminServiceImpl::new(state.clone());
+    let internal_service = internal::SchedulerInternalServiceImpl::new(state.clone());
+
+    let executor = state.clone();
+    let task_interval = Duration::from_secs(config.archive.scan_interval_secs.max(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(task_interval);
+        loop {
+            ticker.tick().await;
+            if executor.draining.load(Ordering::Acquire) {
+                continue;
+            }
+            let result = match executor.tape_client().await {
+                Ok(mut tape) => run_pending_tasks(&executor, &mut tape).await,
+                Err(status) => Err(status),
+            };
+            if let Err(status) = result {
+                warn!("执行归档/取回任务失败: {}", status.message());
+            }
+        }
+    });
+
+    if config.reclamation.enabled {
+        let reclaimer = state.clone();
+        let reclaim_interval = Duration::from_secs(config.reclamation.scan_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(reclaim_interval);
+            loop {
+                ticker.tick().await;
+                if reclaimer.draining.load(Ordering::Acquire) {
+                    continue;
+                }
+                let result = match reclaimer.tape_client().await {
+                    Ok(mut tape) => reclaim::run_reclamation(&reclaimer, &mut tape).await,
+                    Err(status) => Err(status),
+                };
+                if let Err(status) = result {
+                    warn!("磁带回收失败: {}", status.message());
+                }
+            }
+        });
+    }
+
+    let scrubber = state;
+    let scrub_interval = Duration::from_secs(config.scrub.scan_interval_secs.max(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(scrub_interval);
+        loop {
+            ticker.tick().await;
+            if scrubber.draining.load(Ordering::Acquire) {
+                continue;
+            }
+            let result = match scrubber.tape_client().await {
+                Ok(mut tape) => scrub::run_scrub(&scrubber, &mut tape).await,
+                Err(status) => Err(status),
+            };
+            if let Err(status) = result {
+                warn!("磁带巡检失败: {}", status.message());
+            }
+        }
+    });
 
     info!("Scheduler Worker 启动在 {}", config.listen);
 
-    Server::builder()
+    let mut server = Server::builder();
+    if let Some(tls) = config.tls.server_tls_config()? {
+        server = server.tls_config(tls)?;
+    }
+    server
         .add_service(
             coldstore_proto::scheduler::scheduler_service_server::SchedulerServiceServer::new(
                 scheduler_service,
             ),
         )
-        .serve(addr)
+        .add_service(
+            coldstore_proto::scheduler::scheduler_admin_service_server::SchedulerAdminServiceServer::new(
+                admin_service,
+            ),
+        )
+        .add_service(
+            coldstore_proto::scheduler::scheduler_internal_service_server::SchedulerInternalServiceServer::new(
+                internal_service,
+            ),
+        )
+        .serve_with_shutdown(addr, shutdown_signal())
         .await?;
 
+    if let Err(status) = membership.stop().await {
+        warn!("从 Metadata 注销失败: {}", status.message());
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        register_tapes, scheduler_state, scheduler_state_with_config, spawn_cache_worker,
+        spawn_metadata_server, spawn_tape,
+    };
+    use coldstore_common::checksum::sha256_hex;
+    use coldstore_common::config::MembershipConfig;
+    use coldstore_common::tls::TlsConfig;
+    use coldstore_proto::cache::{get_response, GetRequest};
+    use coldstore_proto::metadata::{
+        GetArchiveBundleRequest, GetObjectRequest, GetRecallTaskRequest,
+    };
+    use coldstore_proto::scheduler::scheduler_service_server::SchedulerService;
+    use coldstore_proto::scheduler::RestoreObjectRequest;
+    use coldstore_proto::tape::ExportTapeRequest;
+    use tonic::Request;
+
+    async fn scheduler_workers(metadata: &MetadataClientPool) -> Vec<common::SchedulerWorkerInfo> {
+        metadata
+            .call(|mut client| async move { client.get_cluster_info(()).await })
+            .await
+            .expect("cluster info")
+            .scheduler_workers
+    }
+
+    #[tokio::test]
+    async fn scheduler_registers_heartbeats_and_deregisters_on_stop() {
+        let (addr, shutdown_tx) = spawn_metadata_server().await;
+        let metadata = Arc::new(
+            MetadataClientPool::new(
+                &[addr.to_string()],
+                &TlsConfig::default(),
+                Default::default(),
+            )
+            .expect("metadata pool"),
+        );
+        let mut config = SchedulerConfig {
+            listen: "127.0.0.1:22001".into(),
+            ..SchedulerConfig::default()
+        };
+        config.membership = MembershipConfig {
+            node_id: 7,
+            advertise_addr: String::new(),
+            heartbeat_interval_secs: 1,
+        };
+        let state = Arc::new(SchedulerState {
+            metadata: metadata.clone(),
+            cache: RwLock::new(None),
+            tape: None,
+            config: config.clone(),
+            draining: AtomicBool::new(false),
+        });
+
+        let membership = Membership::start(metadata.clone(), state, &config.membership).await;
+        let mut workers = Vec::new();
+        for _ in 0..40 {
+            workers = scheduler_workers(&metadata).await;
+            if !workers.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        assert_eq!(workers.len(), 1);
+        assert_eq!(workers[0].node_id, 7);
+        assert_eq!(workers[0].addr, "127.0.0.1:22001");
+        assert_eq!(workers[0].status, common::NodeStatus::NodeOnline as i32);
+
+        membership.stop().await.expect("deregister");
+        assert!(scheduler_workers(&metadata).await.is_empty());
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn only_one_scheduler_wins_a_concurrent_archive_task_claim() {
+        let (addr, shutdown_tx) = spawn_metadata_server().await;
+        let first = scheduler_state(addr, None);
+        let mut second_config = first.config.clone();
+        second_config.membership.node_id = 2;
+        let second = SchedulerState {
+            metadata: first.metadata.clone(),
+            cache: RwLock::new(None),
+            tape: None,
+            config: second_config,
+            draining: AtomicBool::new(false),
+        };
+        first
+            .metadata
+            .call(|mut client| async move {
+                client
+                    .put_archive_task(common::ArchiveTask {
+                        id: "archive-1".into(),
+                        status: common::ArchiveTaskStatus::ArchiveTaskPending as i32,
+                        ..Default::default()
+                    })
+                    .await
+            })
+            .await
+            .expect("put archive task");
+
+        let (a, b) = tokio::join!(
+            first.claim_archive_task("archive-1"),
+            second.claim_archive_task("archive-1")
+        );
+        let (winner, loser) = match (a, b) {
+            (Ok(task), Err(err)) | (Err(err), Ok(task)) => (task, err),
+            other => panic!("exactly one claim should win: {other:?}"),
+        };
+        assert_eq!(loser.code(), tonic::Code::Aborted);
+        assert!(winner.lease_expire_at.is_some());
+        assert_eq!(
+            winner.status,
+            common::ArchiveTaskStatus::ArchiveTaskInProgress as i32
+        );
+        let _ = shutdown_tx.send(());
+    }
+
+    /// 归档包达到任意大小即可写入、每份归档 `copies` 份副本，配对 Cache Worker 1
+    fn archive_config(copies: u32) -> SchedulerConfig {
+        let mut config = SchedulerConfig {
+            cache_worker_id: Some(1),
+            ..SchedulerConfig::default()
+        };
+        config.archive.min_archive_size_mb = 0;
+        config.archive.copies = copies;
+        config
+    }
+
+    /// 建桶并登记一个 ColdPending 对象，其暂存副本 (`staged`) 写入配对 Cache Worker
+    async fn stage_object(state: &SchedulerState, data: &[u8], staged: &[u8]) {
+        let mut object = common::ObjectMetadata {
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            size: data.len() as u64,
+            checksum: sha256_hex(staged),
+            storage_class: common::StorageClass::ColdPending as i32,
+            ..Default::default()
+        };
+        object.staging_worker_id = Some(
+            state
+                .write_staging(&object, staged.to_vec())
+                .await
+                .expect("write staging"),
+        );
+        object.checksum = sha256_hex(data);
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .create_bucket(Request::new(common::BucketInfo {
+                name: "docs".into(),
+                ..Default::default()
+            }))
+            .await
+            .expect("create bucket");
+        metadata
+            .put_object(Request::new(object))
+            .await
+            .expect("put object");
+    }
+
+    async fn object(state: &SchedulerState) -> common::ObjectMetadata {
+        let request = GetObjectRequest {
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+        };
+        state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_object(request).await }
+            })
+            .await
+            .expect("get object")
+    }
+
+    async fn restore(state: &Arc<SchedulerState>) {
+        let response = service::SchedulerServiceImpl::new(state.clone())
+            .restore_object(Request::new(RestoreObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                days: 1,
+                tier: common::RestoreTier::Standard as i32,
+            }))
+            .await
+            .expect("restore object")
+            .into_inner();
+        assert_eq!(response.status_code, 202);
+    }
+
+    /// 配对 Cache Worker 解冻区中的对象数据
+    async fn restored_body(state: &SchedulerState) -> Vec<u8> {
+        let mut cache = state.paired_cache().await.expect("paired cache").client;
+        let mut stream = cache
+            .get(Request::new(GetRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                ..Default::default()
+            }))
+            .await
+            .expect("cache get")
+            .into_inner();
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.message().await.expect("stream chunk") {
+            if let Some(get_response::Payload::Data(bytes)) = chunk.payload {
+                body.extend_from_slice(&bytes);
+            }
+        }
+        body
+    }
+
+    async fn pending_archive_tasks(state: &SchedulerState) -> Vec<common::ArchiveTask> {
+        state
+            .metadata
+            .call(|mut client| async move { client.list_pending_archive_tasks(()).await })
+            .await
+            .expect("list archive tasks")
+            .tasks
+    }
+
+    #[tokio::test]
+    async fn pending_tasks_loop_archives_staged_objects_and_serves_recalls() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state_with_config(addr, archive_config(1));
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 1).await;
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01"]).await;
+        register_tapes(&state.metadata, &["TAPE01"]).await;
+        stage_object(&state, b"hello", b"hello").await;
+
+        // 排空期间既不规划也不认领任务
+        state.draining.store(true, Ordering::Release);
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("draining round");
+        assert!(pending_archive_tasks(&state).await.is_empty());
+        assert_eq!(
+            object(&state).await.storage_class,
+            common::StorageClass::ColdPending as i32
+        );
+        state.draining.store(false, Ordering::Release);
+
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("archive round");
+        let archived = object(&state).await;
+        assert_eq!(archived.storage_class, common::StorageClass::Cold as i32);
+        assert_eq!(archived.tape_set, ["TAPE01"]);
+        assert!(pending_archive_tasks(&state).await.is_empty());
+
+        restore(&state).await;
+        let recalls = state
+            .metadata
+            .call(|mut client| async move { client.list_pending_recall_tasks(()).await })
+            .await
+            .expect("list recall tasks")
+            .tasks;
+        assert_eq!(recalls.len(), 1);
+        assert_eq!(recalls[0].archive_id, archived.archive_id.unwrap());
+
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("recall round");
+        assert_eq!(
+            object(&state).await.restore_status,
+            Some(common::RestoreStatus::RestoreCompleted as i32)
+        );
+        assert_eq!(restored_body(&state).await, b"hello");
+
+        let _ = tape_shutdown.send(());
+        let _ = cache_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+
+    #[tokio::test]
+    async fn pending_tasks_loop_dead_letters_archives_of_corrupted_staging() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state_with_config(addr, archive_config(1));
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 1).await;
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01"]).await;
+        register_tapes(&state.metadata, &["TAPE01"]).await;
+        stage_object(&state, b"hello", b"hellp").await;
+
+        for _ in 0..2 {
+            run_pending_tasks(&state, &mut tape)
+                .await
+                .expect("archive round");
+        }
+        let dead_letters = state
+            .metadata
+            .call(|mut client| async move { client.list_dead_letter_tasks(()).await })
+            .await
+            .expect("list dead letters")
+            .archive_tasks;
+        assert_eq!(
+            dead_letters.len(),
+            1,
+            "corrupted objects are not planned again"
+        );
+        assert!(dead_letters[0]
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("checksum differs from metadata")));
+        assert!(pending_archive_tasks(&state).await.is_empty());
+        assert_eq!(
+            object(&state).await.storage_class,
+            common::StorageClass::ColdPending as i32
+        );
+
+        let _ = tape_shutdown.send(());
+        let _ = cache_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+
+    #[tokio::test]
+    async fn pending_tasks_loop_fails_recalls_whose_tape_data_does_not_match() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state_with_config(addr, archive_config(1));
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 1).await;
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01"]).await;
+        register_tapes(&state.metadata, &["TAPE01"]).await;
+        stage_object(&state, b"hello", b"hello").await;
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("archive round");
+
+        // 取回任务记录的 checksum 与磁带上的数据不一致
+        restore(&state).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        let mut task = metadata
+            .list_pending_recall_tasks(Request::new(()))
+            .await
+            .expect("list recall tasks")
+            .into_inner()
+            .tasks
+            .remove(0);
+        task.checksum = sha256_hex(b"hellp");
+        metadata
+            .update_recall_task(Request::new(task.clone()))
+            .await
+            .expect("update recall task");
+
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("recall round");
+        let failed = metadata
+            .get_recall_task(Request::new(GetRecallTaskRequest { id: task.id }))
+            .await
+            .expect("get recall task")
+            .into_inner();
+        assert_eq!(failed.status, common::RestoreStatus::RestoreFailed as i32);
+        assert!(failed
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("scheduler.recall.read_tape")));
+        assert_eq!(
+            object(&state).await.restore_status,
+            Some(common::RestoreStatus::RestoreFailed as i32)
+        );
+        let mut cache = state.paired_cache().await.expect("paired cache").client;
+        let err = cache
+            .get(Request::new(GetRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                ..Default::default()
+            }))
+            .await
+            .expect_err("corrupted data is not restored");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let _ = tape_shutdown.send(());
+        let _ = cache_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+
+    #[tokio::test]
+    async fn pending_tasks_loop_writes_every_copy_and_recalls_from_a_surviving_one() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state_with_config(addr, archive_config(2));
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 1).await;
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01", "TAPE02"]).await;
+        register_tapes(&state.metadata, &["TAPE01", "TAPE02"]).await;
+        stage_object(&state, b"hello", b"hello").await;
+
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("archive round");
+        let archived = object(&state).await;
+        assert_eq!(archived.storage_class, common::StorageClass::Cold as i32);
+        assert_eq!(archived.tape_set, ["TAPE01", "TAPE02"]);
+        let request = GetArchiveBundleRequest {
+            id: archived.archive_id.clone().unwrap(),
+        };
+        let bundle = state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_archive_bundle(request).await }
+            })
+            .await
+            .expect("get bundle");
+        assert_eq!(bundle.required_copies, 2);
+        assert!(bundle
+            .copies
+            .iter()
+            .all(|copy| copy.status == common::ArchiveBundleStatus::BundleCompleted as i32));
+
+        // 主副本磁带已不在带库中，Metadata 尚未得知
+        tape.export_tape(Request::new(ExportTapeRequest {
+            tape_id: archived.tape_id.clone().unwrap(),
+        }))
+        .await
+        .expect("export primary tape");
+        restore(&state).await;
+        run_pending_tasks(&state, &mut tape)
+            .await
+            .expect("recall round");
+        assert_eq!(
+            object(&state).await.restore_status,
+            Some(common::RestoreStatus::RestoreCompleted as i32)
+        );
+        assert_eq!(restored_body(&state).await, b"hello");
+
+        let _ = tape_shutdown.send(());
+        let _ = cache_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use coldstore_common::config::SchedulerConfig;
+use coldstore_common::config::{self, SchedulerConfig};
 use tracing::info;
 
 #[tokio::main]
@@ -11,9 +11,11 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    info!("启动 ColdStore Scheduler Worker...");
+    let Some(config) = config::load_from_args::<SchedulerConfig>()? else {
+        return Ok(());
+    };
 
-    let config = SchedulerConfig::default();
+    info!("启动 ColdStore Scheduler Worker...");
 
     coldstore_scheduler::run(config).await
 }
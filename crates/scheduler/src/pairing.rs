@@ -0,0 +1,250 @@
+//! Scheduler 与 Cache Worker 的配对及暂存副本定位
+//!
+//! Scheduler 从 Metadata 的在线 Cache Worker 中选出一个配对节点，新写入对象的暂存
+//! 副本落在该节点，并记录在 `ObjectMetadata.staging_worker_id`。归档时按该字段从
+//! 持有副本的 Cache Worker 读取，对象可能由其他 Scheduler 写入。持有副本的节点已
+//! 离开集群 (注销或心跳超时) 时，对象标记为需要重新上传，不再参与归档。
+
+use crate::SchedulerState;
+use coldstore_common::config::SchedulerConfig;
+use coldstore_proto::cache::cache_service_client::CacheServiceClient;
+use coldstore_proto::cache::{put_staging_request, PutStagingMeta, PutStagingRequest};
+use coldstore_proto::common;
+use coldstore_proto::metadata::UpdateStagingLocationRequest;
+use std::cmp::Reverse;
+use tonic::transport::Channel;
+use tonic::{Code, Status};
+use tracing::{info, warn};
+
+/// 与本 Scheduler 配对的 Cache Worker
+#[derive(Clone)]
+pub struct PairedCache {
+    pub node_id: u64,
+    pub client: CacheServiceClient<Channel>,
+}
+
+/// 选择配对的 Cache Worker：显式配置的节点 > 同主机节点 > 剩余空间最多的节点
+pub fn choose_cache_worker<'a>(
+    workers: &'a [common::CacheWorkerInfo],
+    config: &SchedulerConfig,
+) -> Option<&'a common::CacheWorkerInfo> {
+    let mut online = workers
+        .iter()
+        .filter(|worker| worker.status == common::NodeStatus::NodeOnline as i32);
+    if let Some(node_id) = config.cache_worker_id {
+        return online.find(|worker| worker.node_id == node_id);
+    }
+    let host = host_of(config.membership.addr(&config.listen));
+    online.max_by_key(|worker| {
+        (
+            host_of(&worker.addr) == host,
+            worker.total_capacity.saturating_sub(worker.used_capacity),
+            Reverse(worker.node_id),
+        )
+    })
+}
+
+fn host_of(addr: &str) -> &str {
+    addr.rsplit_once(':').map_or(addr, |(host, _)| host)
+}
+
+fn reupload_required(object: &common::ObjectMetadata, reason: &str) -> Status {
+    Status::failed_precondition(format!(
+        "staging copy of {}/{} is unavailable ({reason}); the object must be re-uploaded",
+        object.bucket, object.key
+    ))
+}
+
+impl SchedulerState {
+    /// 当前配对的 Cache Worker；尚未配对时从 Metadata 发现
+    pub async fn paired_cache(&self) -> Result<PairedCache, Status> {
+        if let Some(paired) = self.cache.read().await.clone() {
+            return Ok(paired);
+        }
+        self.pair_cache().await
+    }
+
+    /// 从 Metadata 的在线 Cache Worker 中选择配对节点
+    pub async fn pair_cache(&self) -> Result<PairedCache, Status> {
+        let workers = self
+            .metadata
+            .call(|mut client| async move { client.list_online_cache_workers(()).await })
+            .await?
+            .workers;
+        let worker = choose_cache_worker(&workers, &self.config).ok_or_else(|| {
+            match self.config.cache_worker_id {
+                Some(node_id) => {
+                    Status::unavailable(format!("configured cache worker {node_id} is not online"))
+                }
+                None => Status::unavailable("no online cache worker to pair with"),
+            }
+        })?;
+        let paired = PairedCache {
+            node_id: worker.node_id,
+            client: self.connect_cache(&worker.addr)?,
+        };
+        info!("与 Cache Worker {} ({}) 配对", worker.node_id, worker.addr);
+        *self.cache.write().await = Some(paired.clone());
+        Ok(paired)
+    }
+
+    /// 配对节点不可用时解除配对，下次写入重新发现
+    pub async fn unpair_cache(&self, node_id: u64) {
+        let mut cache = self.cache.write().await;
+        if cache
+            .as_ref()
+            .is_some_and(|paired| paired.node_id == node_id)
+        {
+            warn!("Cache Worker {node_id} 不可用，解除配对");
+            *cache = None;
+        }
+    }
+
+    /// 写入配对 Cache Worker 的暂存区，返回持有副本的节点 ID
+    pub async fn write_staging(
+        &self,
+        object: &common::ObjectMetadata,
+        body: Vec<u8>,
+    ) -> Result<u64, Status> {
+        let mut paired = self.paired_cache().await?;
+        let meta = PutStagingMeta {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            version_id: object.version_id.clone(),
+            size: body.len() as u64,
+            checksum: Some(object.checksum.clone()),
+            content_type: object.content_type.clone(),
+            etag: object.etag.clone(),
+        };
+        let chunks = vec![
+            PutStagingRequest {
+                payload: Some(put_staging_request::Payload::Meta(meta)),
+            },
+            PutStagingRequest {
+                payload: Some(put_staging_request::Payload::Data(body)),
+            },
+        ];
+        match paired.client.put_staging(tokio_stream::iter(chunks)).await {
+            Ok(_) => Ok(paired.node_id),
+            Err(status) => {
+                if status.code() == Code::Unavailable {
+                    self.unpair_cache(paired.node_id).await;
+                }
+                Err(status)
+            }
+        }
+    }
+
+    /// 持有对象暂存副本的 Cache Worker
+    ///
+    /// 节点已离开集群时将对象标记为需要重新上传，并返回 `FailedPrecondition`。
+    pub async fn staging_cache(
+        &self,
+        object: &common::ObjectMetadata,
+    ) -> Result<CacheServiceClient<Channel>, Status> {
+        if let Some(error) = &object.staging_error {
+            return Err(reupload_required(object, error));
+        }
+        let Some(node_id) = object.staging_worker_id else {
+            return Err(reupload_required(object, "no staging copy was recorded"));
+        };
+        if let Some(paired) = self.cache.read().await.as_ref() {
+            if paired.node_id == node_id {
+                return Ok(paired.client.clone());
+            }
+        }
+
+        let holder = self
+            .metadata
+            .call(|mut client| async move { client.get_cluster_info(()).await })
+            .await?
+            .cache_workers
+            .into_iter()
+            .find(|worker| {
+                worker.node_id == node_id
+                    && (worker.status == common::NodeStatus::NodeOnline as i32
+                        || worker.status == common::NodeStatus::NodeDraining as i32)
+            });
+        if let Some(worker) = holder {
+            return self.connect_cache(&worker.addr);
+        }
+
+        let error = format!("cache worker {node_id} holding it is no longer in the cluster");
+        let request = UpdateStagingLocationRequest {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            version_id: object.version_id.clone(),
+            staging_worker_id: None,
+            error: Some(error.clone()),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.update_staging_location(request).await }
+            })
+            .await?;
+        warn!(
+            "对象 {}/{} 的暂存副本已丢失 (Cache Worker {node_id})，需要重新上传",
+            object.bucket, object.key
+        );
+        Err(reupload_required(object, &error))
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn connect_cache(&self, addr: &str) -> Result<CacheServiceClient<Channel>, Status> {
+        let endpoint = self.config.tls.endpoint(addr).map_err(Status::from)?;
+        Ok(CacheServiceClient::new(endpoint.connect_lazy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(node_id: u64, addr: &str, free: u64) -> common::CacheWorkerInfo {
+        common::CacheWorkerInfo {
+            node_id,
+            addr: addr.into(),
+            status: common::NodeStatus::NodeOnline as i32,
+            total_capacity: 100,
+            used_capacity: 100 - free,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pairing_prefers_configured_then_same_host_then_most_free_space() {
+        let mut config = SchedulerConfig {
+            listen: "10.0.0.2:22001".into(),
+            ..SchedulerConfig::default()
+        };
+        let mut draining = worker(4, "10.0.0.2:23001", 90);
+        draining.status = common::NodeStatus::NodeDraining as i32;
+        let workers = vec![
+            worker(1, "10.0.0.1:23001", 80),
+            worker(2, "10.0.0.2:23001", 10),
+            worker(3, "10.0.0.3:23001", 50),
+            draining,
+        ];
+
+        assert_eq!(
+            choose_cache_worker(&workers, &config).map(|w| w.node_id),
+            Some(2)
+        );
+
+        config.listen = "10.0.0.9:22001".into();
+        assert_eq!(
+            choose_cache_worker(&workers, &config).map(|w| w.node_id),
+            Some(1)
+        );
+
+        config.cache_worker_id = Some(3);
+        assert_eq!(
+            choose_cache_worker(&workers, &config).map(|w| w.node_id),
+            Some(3)
+        );
+
+        config.cache_worker_id = Some(4);
+        assert!(choose_cache_worker(&workers, &config).is_none());
+    }
+}
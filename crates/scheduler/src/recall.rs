@@ -0,0 +1,718 @@
+//! 取回路径 (磁带 → Cache 解冻数据) 的数据完整性校验
+//!
+//! 从磁带读回的对象数据在写入 Cache 之前按 RecallTask 记录的 checksum
+//! 重新计算 SHA-256。校验失败时不写入缓存，RecallTask 与对象的
+//! restore_status 均标记为 Failed，避免把损坏数据暴露给 GET。写入 Cache
+//! 的其它失败按 [`crate::retry::classify`] 退避后重新排队。
+//!
+//! 归档包有多份副本时，若 RecallTask 指向的磁带处于 Error/Offline/Retired
+//! 状态，取回自动切换到 tape_set 中另一份可用副本。
+//! 全部副本都不可读且至少一份已移出带库时，Metadata 将任务置为 WaitingForMedia
+//! (不可认领)，磁带重新导入后自动恢复为 Pending，见 [`crate::vault`]。
+//!
+//! Scheduler 周期调用 [`run_recall`] 认领并执行待执行的 RecallTask：从选定副本读出
+//! 对象后经 [`restore_to_cache`] 校验并写入配对 Cache Worker 的解冻区；读取失败时
+//! 改用 tape_set 中的下一份可读副本。
+
+use crate::reclaim::{acquire_drive, mount, read_blocks, release_drive};
+use crate::SchedulerState;
+use coldstore_common::checksum::verify_sha256;
+use coldstore_common::media::tape_readable;
+use coldstore_proto::cache::cache_service_client::CacheServiceClient;
+use coldstore_proto::cache::{put_restored_request, PutRestoredMeta, PutRestoredRequest};
+use coldstore_proto::common;
+use coldstore_proto::metadata::metadata_service_client::MetadataServiceClient;
+use coldstore_proto::metadata::{
+    ClaimTaskRequest, FailTaskRequest, GetArchiveBundleRequest, GetObjectRequest, GetTapeRequest,
+    UpdateRestoreStatusRequest,
+};
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use prost_types::Timestamp;
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+use tracing::{info, warn};
+
+/// 一轮取回：占用一个驱动依次认领并执行可认领的 RecallTask，Expedited 优先
+///
+/// 单个任务失败时回报后继续下一个；排空期间不再认领。返回本轮完成的任务。
+pub async fn run_recall(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+) -> Result<Vec<common::RecallTask>, Status> {
+    let tasks = claimable_recall_tasks(state).await?;
+    if tasks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(drive_id) = acquire_drive(tape).await? else {
+        info!("没有空闲驱动，跳过本轮取回");
+        return Ok(Vec::new());
+    };
+    let mut completed = Vec::new();
+    for task in tasks {
+        if state.claim_worker_id().is_err() {
+            break;
+        }
+        let claimed = match begin_recall(state, &task).await {
+            Ok(claimed) => claimed,
+            // 其它 Worker 已认领或仍在退避
+            Err(status)
+                if matches!(
+                    status.code(),
+                    tonic::Code::Aborted | tonic::Code::FailedPrecondition
+                ) =>
+            {
+                continue
+            }
+            Err(status) => {
+                warn!("认领取回任务 {} 失败: {}", task.id, status.message());
+                continue;
+            }
+        };
+        match execute_recall(state, tape, &drive_id, &claimed).await {
+            Ok(task) => completed.push(task),
+            Err(status) => warn!("取回任务 {} 失败: {}", claimed.id, status.message()),
+        }
+    }
+    release_drive(tape, &drive_id).await;
+    Ok(completed)
+}
+
+/// 待执行且已过退避时间的 RecallTask，按取回等级与创建时间排序
+async fn claimable_recall_tasks(state: &SchedulerState) -> Result<Vec<common::RecallTask>, Status> {
+    let now = now_timestamp();
+    let mut tasks: Vec<_> = state
+        .metadata
+        .call(|mut client| async move { client.list_pending_recall_tasks(()).await })
+        .await?
+        .tasks
+        .into_iter()
+        .filter(|task| task.status == common::RestoreStatus::RestorePending as i32)
+        .filter(|task| {
+            task.next_attempt_at
+                .is_none_or(|next| (next.seconds, next.nanos) <= (now.seconds, now.nanos))
+        })
+        .collect();
+    tasks.sort_by_key(|task| {
+        let created = task.created_at.unwrap_or_default();
+        (task.tier, created.seconds, created.nanos, task.id.clone())
+    });
+    Ok(tasks)
+}
+
+/// 从已认领任务选定的副本读出对象并写入配对 Cache Worker 的解冻区
+///
+/// 磁带装载或读取失败时依次改用 tape_set 中其它可读副本；全部失败时回报任务失败。
+async fn execute_recall(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    task: &common::RecallTask,
+) -> Result<common::RecallTask, Status> {
+    let mut metadata = state.metadata.client().await?;
+    let mut last_error = None;
+    for copy in readable_copies(&mut metadata, task).await? {
+        let read = match mount(tape, drive_id, &copy.tape_id).await {
+            Ok(()) => read_blocks(tape, drive_id, copy.tape_block_offset, copy.object_size).await,
+            Err(status) => Err(status),
+        };
+        let data = match read {
+            Ok(data) => data,
+            Err(status) => {
+                warn!(
+                    "从磁带 {} 读取 {}/{} 失败，尝试下一份副本: {}",
+                    copy.tape_id,
+                    copy.bucket,
+                    copy.key,
+                    status.message()
+                );
+                last_error = Some(status);
+                continue;
+            }
+        };
+        if copy.tape_id != task.tape_id {
+            metadata
+                .update_recall_task(Request::new(copy.clone()))
+                .await?;
+        }
+        let mut cache = match state.paired_cache().await {
+            Ok(paired) => paired.client,
+            Err(status) => {
+                fail_recall(&mut metadata, &copy, &status).await?;
+                return Err(status);
+            }
+        };
+        restore_to_cache(&mut metadata, &mut cache, &copy, data).await?;
+        info!(
+            "取回任务 {} 完成: {}/{} 自磁带 {}",
+            copy.id, copy.bucket, copy.key, copy.tape_id
+        );
+        return Ok(copy);
+    }
+
+    let status = last_error.unwrap_or_else(|| no_readable_copy(task));
+    fail_recall(&mut metadata, task, &status).await?;
+    Err(status)
+}
+
+/// 以本节点身份认领 RecallTask 并将其与对象的解冻状态推进到 InProgress（磁带读取开始前调用）
+///
+/// 认领是 compare-and-set：任务已由其它 Worker 持有时返回 `Aborted`，排空期间返回
+/// `Unavailable`。返回的任务已切换到可读的副本磁带，调用方应按其 `tape_id` 与
+/// `tape_block_offset` 读取；没有可读副本时任务按失败回报。租约随本节点的心跳续约，
+/// Worker 失联后任务由 Metadata 重新排队。
+pub async fn begin_recall(
+    state: &SchedulerState,
+    task: &common::RecallTask,
+) -> Result<common::RecallTask, Status> {
+    let request = ClaimTaskRequest {
+        id: task.id.clone(),
+        worker_id: state.claim_worker_id()?,
+    };
+    let claimed = state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.claim_recall_task(request).await }
+        })
+        .await?;
+    let mut metadata = state.metadata.client().await?;
+    let selected = match select_recall_copy(&mut metadata, &claimed).await {
+        Ok(selected) => selected,
+        Err(status) => {
+            fail_recall(&mut metadata, &claimed, &status).await?;
+            return Err(status);
+        }
+    };
+    if selected.tape_id != claimed.tape_id {
+        let update = selected.clone();
+        state
+            .metadata
+            .call(|mut client| {
+                let update = update.clone();
+                async move { client.update_recall_task(update).await }
+            })
+            .await?;
+    }
+    Ok(selected)
+}
+
+/// 按 `tape_id` 优先、其余 tape_set 次之的顺序挑选可读副本
+///
+/// 磁带处于 Error/Offline/Retired/Scratch 或未登记时跳过；没有可用副本返回 `Unavailable`。
+pub async fn select_recall_copy(
+    metadata: &mut MetadataServiceClient<Channel>,
+    task: &common::RecallTask,
+) -> Result<common::RecallTask, Status> {
+    readable_copies(metadata, task)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| no_readable_copy(task))
+}
+
+fn no_readable_copy(task: &common::RecallTask) -> Status {
+    Status::unavailable(format!(
+        "no readable copy of {}/{} in tape set {:?}",
+        task.bucket, task.key, task.tape_set
+    ))
+}
+
+/// 任务在各可读副本上的读取位置，`tape_id` 优先、其余 tape_set 次之
+async fn readable_copies(
+    metadata: &mut MetadataServiceClient<Channel>,
+    task: &common::RecallTask,
+) -> Result<Vec<common::RecallTask>, Status> {
+    let mut candidates = vec![task.tape_id.clone()];
+    for tape_id in &task.tape_set {
+        if !candidates.contains(tape_id) {
+            candidates.push(tape_id.clone());
+        }
+    }
+
+    let mut copies = Vec::new();
+    for tape_id in candidates {
+        let tape = match metadata
+            .get_tape(Request::new(GetTapeRequest {
+                tape_id: tape_id.clone(),
+            }))
+            .await
+        {
+            Ok(response) => response.into_inner(),
+            Err(status) if status.code() == tonic::Code::NotFound => continue,
+            Err(status) => return Err(status),
+        };
+        if !tape_readable(&tape) {
+            continue;
+        }
+
+        let mut selected = task.clone();
+        if tape_id != task.tape_id {
+            selected.tape_block_offset = copy_block_offset(metadata, task, &tape_id).await?;
+            selected.tape_id = tape_id;
+        }
+        copies.push(selected);
+    }
+    Ok(copies)
+}
+
+/// 对象在指定副本磁带上的块地址；旧归档包没有副本记录时沿用主副本地址
+async fn copy_block_offset(
+    metadata: &mut MetadataServiceClient<Channel>,
+    task: &common::RecallTask,
+    tape_id: &str,
+) -> Result<u64, Status> {
+    let bundle = metadata
+        .get_archive_bundle(Request::new(GetArchiveBundleRequest {
+            id: task.archive_id.clone(),
+        }))
+        .await?
+        .into_inner();
+    let Some(copy) = bundle.copies.iter().find(|copy| copy.tape_id == tape_id) else {
+        return Ok(task.tape_block_offset);
+    };
+    let entry = bundle
+        .entries
+        .iter()
+        .find(|entry| {
+            entry.bucket == task.bucket
+                && entry.key == task.key
+                && entry.version_id == task.version_id
+        })
+        .ok_or_else(|| {
+            Status::not_found(format!(
+                "object {}/{} not in bundle {}",
+                task.bucket, task.key, bundle.id
+            ))
+        })?;
+    Ok(copy.start_block + entry.offset_in_bundle / u64::from(copy.block_size.max(1)))
+}
+
+/// 校验从磁带读回的数据并写入 Cache 解冻区
+///
+/// `task` 需已处于 InProgress 状态。成功时 RecallTask 与对象状态推进到
+/// Completed；checksum 不一致时二者标记为 Failed 并返回 `DataLoss`。
+pub async fn restore_to_cache(
+    metadata: &mut MetadataServiceClient<Channel>,
+    cache: &mut CacheServiceClient<Channel>,
+    task: &common::RecallTask,
+    data: Vec<u8>,
+) -> Result<(), Status> {
+    if let Err(err) = verify_sha256(&data, &task.checksum, "scheduler.recall.read_tape") {
+        let status = Status::from(err);
+        fail_recall(metadata, task, &status).await?;
+        return Err(status);
+    }
+
+    let object = metadata
+        .get_object(Request::new(GetObjectRequest {
+            bucket: task.bucket.clone(),
+            key: task.key.clone(),
+        }))
+        .await?
+        .into_inner();
+
+    let meta = PutRestoredMeta {
+        bucket: task.bucket.clone(),
+        key: task.key.clone(),
+        version_id: task.version_id.clone(),
+        size: data.len() as u64,
+        checksum: Some(task.checksum.clone()),
+        content_type: object.content_type,
+        etag: object.etag,
+        expire_at: task.expire_at,
+        attributes: object.attributes,
+    };
+    let chunks = vec![
+        PutRestoredRequest {
+            payload: Some(put_restored_request::Payload::Meta(meta)),
+        },
+        PutRestoredRequest {
+            payload: Some(put_restored_request::Payload::Data(data)),
+        },
+    ];
+    if let Err(status) = cache.put_restored(tokio_stream::iter(chunks)).await {
+        fail_recall(metadata, task, &status).await?;
+        return Err(status);
+    }
+
+    let mut completed = task.clone();
+    completed.status = common::RestoreStatus::RestoreCompleted as i32;
+    completed.completed_at = Some(now_timestamp());
+    metadata
+        .update_recall_task(Request::new(completed.clone()))
+        .await?;
+    update_object_restore_status(
+        metadata,
+        &completed,
+        common::RestoreStatus::RestoreCompleted,
+        completed.expire_at,
+    )
+    .await
+}
+
+/// 回报进行中的 RecallTask 失败，返回 Metadata 处理后的任务
+///
+/// 可重试的失败退避后与对象一起退回 Pending，否则二者标记为 Failed (死信)。
+pub async fn fail_recall(
+    metadata: &mut MetadataServiceClient<Channel>,
+    task: &common::RecallTask,
+    error: &Status,
+) -> Result<common::RecallTask, Status> {
+    Ok(metadata
+        .fail_recall_task(Request::new(FailTaskRequest {
+            id: task.id.clone(),
+            worker_id: task.worker_id.unwrap_or_default(),
+            error: error.message().to_string(),
+            kind: crate::retry::classify(error) as i32,
+        }))
+        .await?
+        .into_inner())
+}
+
+async fn update_object_restore_status(
+    metadata: &mut MetadataServiceClient<Channel>,
+    task: &common::RecallTask,
+    status: common::RestoreStatus,
+    expire_at: Option<Timestamp>,
+) -> Result<(), Status> {
+    metadata
+        .update_restore_status(Request::new(UpdateRestoreStatusRequest {
+            bucket: task.bucket.clone(),
+            key: task.key.clone(),
+            status: status as i32,
+            expire_at,
+        }))
+        .await?;
+    Ok(())
+}
+
+pub(crate) fn now_timestamp() -> Timestamp {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Timestamp {
+        seconds: now.as_secs() as i64,
+        nanos: now.subsec_nanos() as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scheduler_state, spawn_cache, spawn_metadata_server};
+    use coldstore_common::checksum::sha256_hex;
+    use coldstore_proto::cache::{get_response, GetRequest};
+    use coldstore_proto::metadata::GetRecallTaskRequest;
+
+    fn tape(id: &str, status: common::TapeStatus) -> common::TapeInfo {
+        common::TapeInfo {
+            id: id.into(),
+            barcode: None,
+            format: "LTO-9".into(),
+            status: status as i32,
+            location: None,
+            capacity_bytes: 1 << 30,
+            used_bytes: 0,
+            remaining_bytes: 1 << 30,
+            archive_bundle_ids: vec![],
+            last_verified_at: None,
+            error_count: 0,
+            registered_at: None,
+            live_bytes: 0,
+            dead_bytes: 0,
+            pool: String::new(),
+            error_history: Vec::new(),
+            media_state: common::TapeMediaState::TapeMediaAppendable as i32,
+        }
+    }
+
+    fn copy(tape_id: &str, start_block: u64) -> common::BundleCopy {
+        common::BundleCopy {
+            tape_id: tape_id.into(),
+            location: None,
+            status: common::ArchiveBundleStatus::BundleCompleted as i32,
+            filemark_start: 0,
+            filemark_end: 1,
+            start_block,
+            block_size: 512,
+            error: None,
+            completed_at: None,
+        }
+    }
+
+    fn owner_attributes() -> common::ObjectAttributes {
+        coldstore_common::attributes::from_headers([("x-amz-meta-owner", "ops")])
+    }
+
+    async fn seed(
+        metadata: &mut MetadataServiceClient<Channel>,
+        data: &[u8],
+    ) -> common::RecallTask {
+        let now = now_timestamp();
+        for id in ["TAPE01", "TAPE02"] {
+            metadata
+                .put_tape(Request::new(tape(id, common::TapeStatus::TapeOnline)))
+                .await
+                .expect("seed tape");
+        }
+        metadata
+            .put_archive_bundle(Request::new(common::ArchiveBundle {
+                id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                tape_set: vec!["TAPE01".into(), "TAPE02".into()],
+                entries: vec![common::BundleEntry {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    version_id: None,
+                    size: data.len() as u64,
+                    offset_in_bundle: 1024,
+                    tape_block_offset: 2,
+                    checksum: sha256_hex(data),
+                }],
+                total_size: 2048,
+                filemark_start: 0,
+                filemark_end: 1,
+                checksum: None,
+                status: common::ArchiveBundleStatus::BundleCompleted as i32,
+                created_at: Some(now),
+                completed_at: Some(now),
+                copies: vec![copy("TAPE01", 0), copy("TAPE02", 40)],
+                required_copies: 2,
+                live_bytes: 0,
+                dead_bytes: 0,
+            }))
+            .await
+            .expect("seed bundle");
+        metadata
+            .create_bucket(Request::new(common::BucketInfo {
+                name: "docs".into(),
+                created_at: Some(now),
+                owner: None,
+                versioning_enabled: false,
+                object_count: 0,
+                total_size: 0,
+                archive_copies: None,
+                tape_pools: Vec::new(),
+            }))
+            .await
+            .expect("seed bucket");
+        metadata
+            .put_object(Request::new(common::ObjectMetadata {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                size: data.len() as u64,
+                checksum: sha256_hex(data),
+                content_type: Some("text/plain".into()),
+                etag: Some(sha256_hex(data)),
+                storage_class: common::StorageClass::Cold as i32,
+                archive_id: Some("bundle-1".into()),
+                tape_id: Some("TAPE01".into()),
+                tape_set: vec!["TAPE01".into()],
+                tape_block_offset: Some(0),
+                restore_status: Some(common::RestoreStatus::RestorePending as i32),
+                restore_expire_at: None,
+                created_at: Some(now),
+                updated_at: Some(now),
+                staging_worker_id: None,
+                staging_error: None,
+                attributes: Some(owner_attributes()),
+            }))
+            .await
+            .expect("seed object");
+        let task = common::RecallTask {
+            id: "recall-1".into(),
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            version_id: None,
+            archive_id: "bundle-1".into(),
+            tape_id: "TAPE01".into(),
+            tape_set: vec!["TAPE01".into(), "TAPE02".into()],
+            tape_block_offset: 2,
+            object_size: data.len() as u64,
+            checksum: sha256_hex(data),
+            tier: common::RestoreTier::Standard as i32,
+            days: 1,
+            expire_at: Some(Timestamp {
+                seconds: now.seconds + 86_400,
+                nanos: 0,
+            }),
+            status: common::RestoreStatus::RestorePending as i32,
+            drive_id: None,
+            retry_count: 0,
+            created_at: Some(now),
+            started_at: None,
+            completed_at: None,
+            error: None,
+            worker_id: None,
+            lease_expire_at: None,
+            next_attempt_at: None,
+        };
+        metadata
+            .put_recall_task(Request::new(task.clone()))
+            .await
+            .expect("seed recall task");
+        task
+    }
+
+    async fn object_restore_status(metadata: &mut MetadataServiceClient<Channel>) -> Option<i32> {
+        metadata
+            .get_object(Request::new(GetObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+            }))
+            .await
+            .expect("get object")
+            .into_inner()
+            .restore_status
+    }
+
+    #[tokio::test]
+    async fn verified_recall_lands_in_cache_and_completes() {
+        let (addr, meta_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        let (mut cache, cache_shutdown) = spawn_cache().await;
+        let task = seed(&mut metadata, b"hello").await;
+        let task = begin_recall(&state, &task).await.expect("begin recall");
+
+        restore_to_cache(&mut metadata, &mut cache, &task, b"hello".to_vec())
+            .await
+            .expect("restore should succeed");
+
+        let stored = metadata
+            .get_recall_task(Request::new(GetRecallTaskRequest {
+                id: task.id.clone(),
+            }))
+            .await
+            .expect("get recall task")
+            .into_inner();
+        assert_eq!(
+            stored.status,
+            common::RestoreStatus::RestoreCompleted as i32
+        );
+        assert_eq!(
+            object_restore_status(&mut metadata).await,
+            Some(common::RestoreStatus::RestoreCompleted as i32)
+        );
+
+        let mut stream = cache
+            .get(Request::new(GetRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                ..Default::default()
+            }))
+            .await
+            .expect("cache get")
+            .into_inner();
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.message().await.expect("stream chunk") {
+            match chunk.payload {
+                Some(get_response::Payload::Meta(meta)) => {
+                    assert_eq!(meta.checksum.as_deref(), Some(task.checksum.as_str()));
+                    assert_eq!(meta.attributes, Some(owner_attributes()));
+                }
+                Some(get_response::Payload::Data(bytes)) => body.extend_from_slice(&bytes),
+                None => panic!("empty chunk"),
+            }
+        }
+        assert_eq!(body, b"hello");
+
+        meta_shutdown.send(()).ok();
+        cache_shutdown.send(()).ok();
+    }
+
+    #[tokio::test]
+    async fn corrupted_recall_marks_task_and_object_failed() {
+        let (addr, meta_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        let (mut cache, cache_shutdown) = spawn_cache().await;
+        let task = seed(&mut metadata, b"hello").await;
+        let task = begin_recall(&state, &task).await.expect("begin recall");
+
+        let err = restore_to_cache(&mut metadata, &mut cache, &task, b"hellp".to_vec())
+            .await
+            .expect_err("corrupted data must not be restored");
+        assert_eq!(err.code(), tonic::Code::DataLoss);
+
+        let stored = metadata
+            .get_recall_task(Request::new(GetRecallTaskRequest {
+                id: task.id.clone(),
+            }))
+            .await
+            .expect("get recall task")
+            .into_inner();
+        assert_eq!(stored.status, common::RestoreStatus::RestoreFailed as i32);
+        assert!(stored
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("scheduler.recall.read_tape")));
+        assert_eq!(
+            object_restore_status(&mut metadata).await,
+            Some(common::RestoreStatus::RestoreFailed as i32)
+        );
+        // checksum 不一致不可重试，直接进入死信
+        assert_eq!(stored.retry_count, 0);
+        let dead_letters = metadata
+            .list_dead_letter_tasks(Request::new(()))
+            .await
+            .expect("list dead letters")
+            .into_inner();
+        assert_eq!(dead_letters.recall_tasks.len(), 1);
+        assert_eq!(dead_letters.recall_tasks[0].id, task.id);
+
+        let err = cache
+            .get(Request::new(GetRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                ..Default::default()
+            }))
+            .await
+            .expect_err("nothing should be cached");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        meta_shutdown.send(()).ok();
+        cache_shutdown.send(()).ok();
+    }
+
+    #[tokio::test]
+    async fn recall_falls_back_to_another_copy_when_tape_fails() {
+        let (addr, meta_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        let task = seed(&mut metadata, b"hello").await;
+
+        let primary = begin_recall(&state, &task)
+            .await
+            .expect("primary copy readable");
+        assert_eq!(primary.tape_id, "TAPE01");
+        assert_eq!(primary.tape_block_offset, 2);
+
+        metadata
+            .update_tape(Request::new(tape("TAPE01", common::TapeStatus::TapeError)))
+            .await
+            .expect("mark primary failed");
+        let fallback = select_recall_copy(&mut metadata, &task)
+            .await
+            .expect("secondary copy readable");
+        assert_eq!(fallback.tape_id, "TAPE02");
+        assert_eq!(fallback.tape_block_offset, 42);
+
+        metadata
+            .update_tape(Request::new(tape(
+                "TAPE02",
+                common::TapeStatus::TapeOffline,
+            )))
+            .await
+            .expect("mark secondary offline");
+        let err = select_recall_copy(&mut metadata, &task)
+            .await
+            .expect_err("no copy left");
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        meta_shutdown.send(()).ok();
+    }
+}
@@ -0,0 +1,741 @@
+//! 磁带空间回收
+//!
+//! 删除或覆盖对象只让元数据中的归档包条目失效，数据仍占用磁带。Metadata 按归档包与
+//! 磁带统计有效/失效字节，有效字节占比低于 `reclamation.live_ratio_threshold` 的磁带
+//! 按以下步骤回收：
+//!
+//! 1. `BeginTapeReclamation` 将磁带转为 Reclaiming，不再分配新归档包
+//! 2. 从源磁带读出仍被对象引用的条目，按元数据 checksum 校验
+//! 3. 按 `archive.max_archive_size_mb` 分批写入其它磁带上的新归档包，副本数与磁带池沿用源归档包
+//! 4. 新归档包达到所需副本数时，Metadata 在同一命令中把对象指向新位置并释放旧条目
+//! 5. `CompleteTapeReclamation` 确认磁带上已无有效对象后将其清零并转为 Scratch
+//!
+//! 任一步失败时磁带保持 Reclaiming，再次执行时从剩余的有效对象继续。`reclamation.enabled`
+//! 时 Scheduler 按 `reclamation.scan_interval_secs` 周期调用 [`run_reclamation`]。
+
+use crate::archive::{allocate_copy_tapes, record_copy_written, verify_bundle_write};
+use crate::SchedulerState;
+use coldstore_common::checksum::{sha256_hex, verify_sha256};
+use coldstore_proto::common;
+use coldstore_proto::metadata::{
+    GetArchiveBundleRequest, GetObjectRequest, GetObjectVersionRequest, ListBundlesByTapeRequest,
+    TapeReclamationRequest,
+};
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_proto::tape::{
+    read_bundle_request, read_bundle_response, write_bundle_request, AcquireDriveRequest,
+    BundleObjectHeader, LoadTapeRequest, ReadBundleRequest, ReleaseDriveRequest, UnloadTapeRequest,
+    WriteBundleMeta, WriteBundleRequest, WriteBundleResponse,
+};
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+use tracing::info;
+
+/// 一次磁带回收的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReclaimReport {
+    pub tape_id: String,
+    pub objects_relocated: usize,
+    pub bytes_relocated: u64,
+    /// 承接迁出对象的新归档包
+    pub bundles_written: Vec<String>,
+}
+
+/// 仍被对象引用、需要迁出的归档包条目
+#[derive(Debug, Clone)]
+pub(crate) struct LiveEntry {
+    pub(crate) object: common::ObjectMetadata,
+    /// 对象在源磁带上的逻辑块地址
+    pub(crate) block_offset: u64,
+    /// 源归档包各副本所在的磁带池，新归档包按同样的池写入
+    pub(crate) pools: Vec<String>,
+}
+
+/// 有效字节占比；尚未写入任何数据的磁带视为 1
+pub fn live_ratio(tape: &common::TapeInfo) -> f64 {
+    let total = tape.live_bytes + tape.dead_bytes;
+    if total == 0 {
+        return 1.0;
+    }
+    tape.live_bytes as f64 / total as f64
+}
+
+/// 需要回收的磁带，按有效字节占比从低到高排列
+///
+/// 中断的回收 (Reclaiming) 总是排在最前，其余为有失效字节且占比低于阈值的在线磁带。
+pub fn reclamation_candidates(
+    tapes: &[common::TapeInfo],
+    threshold: f64,
+) -> Vec<&common::TapeInfo> {
+    let reclaiming = common::TapeStatus::TapeReclaiming as i32;
+    let mut candidates: Vec<&common::TapeInfo> = tapes
+        .iter()
+        .filter(|tape| {
+            tape.status == reclaiming
+                || (tape.status == common::TapeStatus::TapeOnline as i32
+                    && tape.dead_bytes > 0
+                    && live_ratio(tape) < threshold)
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        (b.status == reclaiming)
+            .cmp(&(a.status == reclaiming))
+            .then_with(|| live_ratio(a).total_cmp(&live_ratio(b)))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    candidates
+}
+
+/// 占用一个驱动回收全部候选磁带，结束后归还驱动；没有空闲驱动时不执行
+pub async fn run_reclamation(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+) -> Result<Vec<ReclaimReport>, Status> {
+    let Some(drive_id) = acquire_drive(tape).await? else {
+        info!("没有空闲驱动，跳过本轮磁带回收");
+        return Ok(Vec::new());
+    };
+    let result = reclaim_candidates(state, tape, &drive_id).await;
+    release_drive(tape, &drive_id).await;
+    result
+}
+
+/// 回收全部候选磁带；单盘失败时记录后继续处理下一盘
+async fn reclaim_candidates(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+) -> Result<Vec<ReclaimReport>, Status> {
+    let tapes = state
+        .metadata
+        .call(|mut client| async move { client.list_tapes(()).await })
+        .await?
+        .tapes;
+    let threshold = state.config.reclamation.live_ratio_threshold;
+    let mut reports = Vec::new();
+    for candidate in reclamation_candidates(&tapes, threshold) {
+        match reclaim_tape(state, tape, drive_id, &candidate.id).await {
+            Ok(report) => reports.push(report),
+            Err(status) => tracing::warn!("回收磁带 {} 失败: {}", candidate.id, status.message()),
+        }
+    }
+    Ok(reports)
+}
+
+/// 将 `tape_id` 上的有效对象迁移到其它磁带，完成后磁带转为 Scratch
+pub async fn reclaim_tape(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    tape_id: &str,
+) -> Result<ReclaimReport, Status> {
+    let request = TapeReclamationRequest {
+        tape_id: tape_id.to_string(),
+    };
+    state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.begin_tape_reclamation(request).await }
+        })
+        .await?;
+
+    let live = live_entries(state, tape_id).await?;
+    let max_bundle_size = (state.config.archive.max_archive_size_mb << 20).max(1);
+    let mut report = ReclaimReport {
+        tape_id: tape_id.to_string(),
+        ..ReclaimReport::default()
+    };
+    let mut batch: Vec<LiveEntry> = Vec::new();
+    let mut batch_size = 0;
+    for entry in live {
+        if !batch.is_empty()
+            && (batch_size + entry.object.size > max_bundle_size || batch[0].pools != entry.pools)
+        {
+            relocate(
+                state,
+                tape,
+                drive_id,
+                tape_id,
+                &std::mem::take(&mut batch),
+                &mut report,
+            )
+            .await?;
+            batch_size = 0;
+        }
+        batch_size += entry.object.size;
+        batch.push(entry);
+    }
+    if !batch.is_empty() {
+        relocate(state, tape, drive_id, tape_id, &batch, &mut report).await?;
+    }
+    tape.unload_tape(Request::new(UnloadTapeRequest {
+        drive_id: drive_id.to_string(),
+        target_slot_id: None,
+    }))
+    .await?;
+
+    state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.complete_tape_reclamation(request).await }
+        })
+        .await?;
+    info!(
+        "磁带 {tape_id} 回收完成: 迁出 {} 个对象 ({} 字节)，转为 Scratch",
+        report.objects_relocated, report.bytes_relocated
+    );
+    Ok(report)
+}
+
+/// 源磁带上仍被对象引用的条目，按目标磁带池分组、组内按块地址排序以便顺序读取
+async fn live_entries(state: &SchedulerState, tape_id: &str) -> Result<Vec<LiveEntry>, Status> {
+    let request = ListBundlesByTapeRequest {
+        tape_id: tape_id.to_string(),
+    };
+    let bundle_ids = state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.list_bundles_by_tape(request).await }
+        })
+        .await?
+        .bundle_ids;
+
+    let tapes = state
+        .metadata
+        .call(|mut client| async move { client.list_tapes(()).await })
+        .await?
+        .tapes;
+
+    let mut live = Vec::new();
+    for bundle_id in bundle_ids {
+        let request = GetArchiveBundleRequest { id: bundle_id };
+        let bundle = state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_archive_bundle(request).await }
+            })
+            .await?;
+        live.extend(bundle_live_entries(state, &bundle, tape_id, &tapes).await?);
+    }
+    live.sort_by(|a, b| {
+        a.pools
+            .cmp(&b.pools)
+            .then_with(|| a.block_offset.cmp(&b.block_offset))
+    });
+    Ok(live)
+}
+
+/// 归档包中仍被对象引用的条目，块地址按 `tape_id` 上的副本计算
+pub(crate) async fn bundle_live_entries(
+    state: &SchedulerState,
+    bundle: &common::ArchiveBundle,
+    tape_id: &str,
+    tapes: &[common::TapeInfo],
+) -> Result<Vec<LiveEntry>, Status> {
+    if bundle.live_bytes == 0 {
+        return Ok(Vec::new());
+    }
+    let default_pool = &state.config.archive.default_tape_pool;
+    let copy = bundle.copies.iter().find(|copy| copy.tape_id == tape_id);
+    let mut pools: Vec<String> = bundle
+        .copies
+        .iter()
+        .map(|copy| {
+            tapes
+                .iter()
+                .find(|tape| tape.id == copy.tape_id)
+                .map_or_else(|| default_pool.clone(), |tape| tape.pool.clone())
+        })
+        .collect();
+    pools.resize(bundle.required_copies.max(1) as usize, default_pool.clone());
+
+    let mut live = Vec::new();
+    for entry in &bundle.entries {
+        let Some(object) = current_object(state, entry).await? else {
+            continue;
+        };
+        if object.archive_id.as_deref() != Some(bundle.id.as_str())
+            || !object.checksum.eq_ignore_ascii_case(&entry.checksum)
+        {
+            continue;
+        }
+        let block_offset = match copy {
+            Some(copy) => entry_block_offset(copy, entry),
+            None => entry.tape_block_offset,
+        };
+        live.push(LiveEntry {
+            object,
+            block_offset,
+            pools: pools.clone(),
+        });
+    }
+    Ok(live)
+}
+
+/// 条目在某份副本上的逻辑块地址
+pub(crate) fn entry_block_offset(copy: &common::BundleCopy, entry: &common::BundleEntry) -> u64 {
+    copy.start_block + entry.offset_in_bundle / u64::from(copy.block_size.max(1))
+}
+
+/// 条目对应的对象版本；已删除时返回 `None`
+pub(crate) async fn current_object(
+    state: &SchedulerState,
+    entry: &common::BundleEntry,
+) -> Result<Option<common::ObjectMetadata>, Status> {
+    let result = match &entry.version_id {
+        Some(version_id) => {
+            let request = GetObjectVersionRequest {
+                bucket: entry.bucket.clone(),
+                key: entry.key.clone(),
+                version_id: version_id.clone(),
+            };
+            state
+                .metadata
+                .call(|mut client| {
+                    let request = request.clone();
+                    async move { client.get_object_version(request).await }
+                })
+                .await
+        }
+        None => {
+            let request = GetObjectRequest {
+                bucket: entry.bucket.clone(),
+                key: entry.key.clone(),
+            };
+            state
+                .metadata
+                .call(|mut client| {
+                    let request = request.clone();
+                    async move { client.get_object(request).await }
+                })
+                .await
+        }
+    };
+    match result {
+        Ok(object) if object.version_id == entry.version_id => Ok(Some(object)),
+        Ok(_) => Ok(None),
+        Err(status) if status.code() == tonic::Code::NotFound => Ok(None),
+        Err(status) => Err(status),
+    }
+}
+
+/// 读出一批有效对象并写入新归档包
+async fn relocate(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    source_tape: &str,
+    batch: &[LiveEntry],
+    report: &mut ReclaimReport,
+) -> Result<(), Status> {
+    mount(tape, drive_id, source_tape).await?;
+    let mut objects = Vec::with_capacity(batch.len());
+    for entry in batch {
+        let data = read_object(tape, drive_id, entry).await?;
+        objects.push((entry.object.clone(), data));
+    }
+    let bundle_id = format!("reclaim-{}", uuid::Uuid::new_v4());
+    write_bundle(state, tape, drive_id, &bundle_id, &batch[0].pools, &objects).await?;
+
+    report.objects_relocated += objects.len();
+    report.bytes_relocated += objects.iter().map(|(object, _)| object.size).sum::<u64>();
+    report.bundles_written.push(bundle_id);
+    Ok(())
+}
+
+async fn read_object(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    entry: &LiveEntry,
+) -> Result<Vec<u8>, Status> {
+    let data = read_blocks(tape, drive_id, entry.block_offset, entry.object.size).await?;
+    verify_sha256(&data, &entry.object.checksum, "scheduler.reclaim.read_tape")?;
+    Ok(data)
+}
+
+/// 从驱动中磁带的逻辑块地址起读取 `length` 字节，不做校验
+pub(crate) async fn read_blocks(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    block_offset: u64,
+    length: u64,
+) -> Result<Vec<u8>, Status> {
+    let mut stream = tape
+        .read_bundle(Request::new(ReadBundleRequest {
+            drive_id: drive_id.to_string(),
+            location: Some(read_bundle_request::Location::BlockOffset(block_offset)),
+            length,
+        }))
+        .await?
+        .into_inner();
+    let mut data = Vec::with_capacity(length as usize);
+    while let Some(chunk) = stream.message().await? {
+        if let Some(read_bundle_response::Payload::Data(bytes)) = chunk.payload {
+            data.extend_from_slice(&bytes);
+        }
+    }
+    Ok(data)
+}
+
+/// 将对象写成新归档包，第 i 份副本写入 `pools[i]`，并登记到元数据
+///
+/// 各副本写入并校验后才登记归档包；所需副本回报完成时 Metadata 将对象指向该归档包。
+pub(crate) async fn write_bundle(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    bundle_id: &str,
+    pools: &[String],
+    objects: &[(common::ObjectMetadata, Vec<u8>)],
+) -> Result<(), Status> {
+    let data: Vec<u8> = objects
+        .iter()
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect();
+    let expected = sha256_hex(&data);
+    let mut metadata = state.metadata.client().await?;
+    let targets = allocate_copy_tapes(&mut metadata, pools, data.len() as u64).await?;
+    let meta = WriteBundleMeta {
+        drive_id: drive_id.to_string(),
+        bundle_id: bundle_id.to_string(),
+        total_size: data.len() as u64,
+        object_count: objects.len() as u32,
+        block_size: 0,
+        objects: objects
+            .iter()
+            .map(|(object, _)| BundleObjectHeader {
+                bucket: object.bucket.clone(),
+                key: object.key.clone(),
+                version_id: object.version_id.clone(),
+                size: object.size,
+                checksum: object.checksum.clone(),
+                content_type: object.content_type.clone(),
+                attributes: object.attributes.clone(),
+            })
+            .collect(),
+    };
+
+    let mut written: Vec<(String, WriteBundleResponse)> = Vec::with_capacity(targets.len());
+    for target in &targets {
+        mount(tape, drive_id, &target.tape_id).await?;
+        let chunks = vec![
+            WriteBundleRequest {
+                payload: Some(write_bundle_request::Payload::Meta(meta.clone())),
+            },
+            WriteBundleRequest {
+                payload: Some(write_bundle_request::Payload::Data(data.clone())),
+            },
+        ];
+        let response = tape
+            .write_bundle(tokio_stream::iter(chunks))
+            .await?
+            .into_inner();
+        verify_bundle_write(&response, &expected)?;
+        written.push((target.tape_id.clone(), response));
+    }
+
+    let bundle = common::ArchiveBundle {
+        id: bundle_id.to_string(),
+        entries: written
+            .first()
+            .map(|(_, response)| response.entries.clone())
+            .unwrap_or_default(),
+        total_size: data.len() as u64,
+        checksum: Some(expected),
+        status: common::ArchiveBundleStatus::BundlePending as i32,
+        copies: targets,
+        required_copies: pools.len() as u32,
+        ..Default::default()
+    };
+    state
+        .metadata
+        .call(|mut client| {
+            let bundle = bundle.clone();
+            async move { client.put_archive_bundle(bundle).await }
+        })
+        .await?;
+    for (tape_id, response) in &written {
+        record_copy_written(&mut metadata, tape_id, response, Ok(())).await?;
+    }
+    Ok(())
+}
+
+/// 向 Tape Worker 申请独占一个驱动；全部驱动都在使用时返回 `None`
+pub(crate) async fn acquire_drive(
+    tape: &mut TapeServiceClient<Channel>,
+) -> Result<Option<String>, Status> {
+    let request = AcquireDriveRequest {
+        preferred_drive_id: None,
+        required_tape_id: None,
+        priority: 0,
+        timeout_secs: 0,
+    };
+    match tape.acquire_drive(Request::new(request)).await {
+        Ok(response) => Ok(Some(response.into_inner().drive_id)),
+        Err(status) if status.code() == tonic::Code::ResourceExhausted => Ok(None),
+        Err(status) => Err(status),
+    }
+}
+
+/// 卸下磁带后归还 `acquire_drive` 占用的驱动，出错中止的作业不会把磁带留在驱动里；
+/// 失败只记录日志，驱动可由 `ResetDrive` 收回
+pub(crate) async fn release_drive(tape: &mut TapeServiceClient<Channel>, drive_id: &str) {
+    let unload = UnloadTapeRequest {
+        drive_id: drive_id.to_string(),
+        target_slot_id: None,
+    };
+    if let Err(status) = tape.unload_tape(Request::new(unload)).await {
+        tracing::warn!("归还驱动 {drive_id} 前卸载磁带失败: {}", status.message());
+    }
+    let request = ReleaseDriveRequest {
+        drive_id: drive_id.to_string(),
+    };
+    if let Err(status) = tape.release_drive(Request::new(request)).await {
+        tracing::warn!("归还驱动 {drive_id} 失败: {}", status.message());
+    }
+}
+
+/// 将 `tape_id` 装入驱动，先卸下驱动中的其它磁带
+pub(crate) async fn mount(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    tape_id: &str,
+) -> Result<(), Status> {
+    tape.unload_tape(Request::new(UnloadTapeRequest {
+        drive_id: drive_id.to_string(),
+        target_slot_id: None,
+    }))
+    .await?;
+    tape.load_tape(Request::new(LoadTapeRequest {
+        tape_id: tape_id.to_string(),
+        drive_id: drive_id.to_string(),
+        slot_id: None,
+    }))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scheduler_state, spawn_metadata_server, spawn_tape};
+    use coldstore_proto::metadata::{DeleteObjectRequest, GetRecallTaskRequest, GetTapeRequest};
+
+    fn tape_info(id: &str, status: common::TapeStatus, live: u64, dead: u64) -> common::TapeInfo {
+        common::TapeInfo {
+            id: id.into(),
+            format: "LTO-9".into(),
+            status: status as i32,
+            capacity_bytes: 1 << 20,
+            remaining_bytes: 1 << 20,
+            live_bytes: live,
+            dead_bytes: dead,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn candidates_are_low_live_ratio_tapes_with_interrupted_reclaims_first() {
+        let tapes = vec![
+            tape_info("TAPE01", common::TapeStatus::TapeOnline, 10, 90),
+            tape_info("TAPE02", common::TapeStatus::TapeOnline, 40, 60),
+            tape_info("TAPE03", common::TapeStatus::TapeOnline, 80, 20),
+            tape_info("TAPE04", common::TapeStatus::TapeOnline, 0, 0),
+            tape_info("TAPE05", common::TapeStatus::TapeOffline, 0, 100),
+            tape_info("TAPE06", common::TapeStatus::TapeReclaiming, 50, 50),
+        ];
+        let ids: Vec<_> = reclamation_candidates(&tapes, 0.5)
+            .iter()
+            .map(|tape| tape.id.as_str())
+            .collect();
+        assert_eq!(ids, ["TAPE06", "TAPE01", "TAPE02"]);
+        assert_eq!(live_ratio(&tapes[3]), 1.0);
+    }
+
+    fn object(key: &str, data: &[u8]) -> common::ObjectMetadata {
+        common::ObjectMetadata {
+            bucket: "docs".into(),
+            key: key.into(),
+            size: data.len() as u64,
+            checksum: sha256_hex(data),
+            storage_class: common::StorageClass::ColdPending as i32,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn reclaim_moves_live_objects_off_a_mostly_dead_tape() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01", "TAPE02", "TAPE03"]).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .create_bucket(Request::new(common::BucketInfo {
+                name: "docs".into(),
+                ..Default::default()
+            }))
+            .await
+            .expect("create bucket");
+        for id in ["TAPE01", "TAPE02", "TAPE03"] {
+            let status = if id == "TAPE01" {
+                common::TapeStatus::TapeOnline
+            } else {
+                common::TapeStatus::TapeOffline
+            };
+            metadata
+                .put_tape(Request::new(tape_info(id, status, 0, 0)))
+                .await
+                .expect("register tape");
+        }
+
+        let objects: Vec<_> = [("a.txt", b"alpha".as_slice()), ("b.txt", b"bravo")]
+            .into_iter()
+            .chain([("c.txt", b"charlie".as_slice())])
+            .map(|(key, data)| (object(key, data), data.to_vec()))
+            .collect();
+        for (object, _) in &objects {
+            metadata
+                .put_object(Request::new(object.clone()))
+                .await
+                .expect("put object");
+        }
+        write_bundle(
+            &state,
+            &mut tape,
+            "drive-0",
+            "bundle-1",
+            &[String::new()],
+            &objects,
+        )
+        .await
+        .expect("archive bundle to TAPE01");
+        for key in ["a.txt", "b.txt"] {
+            metadata
+                .delete_object(Request::new(DeleteObjectRequest {
+                    bucket: "docs".into(),
+                    key: key.into(),
+                    version_id: None,
+                }))
+                .await
+                .expect("delete object");
+        }
+        let source = metadata
+            .get_tape(Request::new(GetTapeRequest {
+                tape_id: "TAPE01".into(),
+            }))
+            .await
+            .expect("get tape")
+            .into_inner();
+        assert_eq!((source.live_bytes, source.dead_bytes), (7, 10));
+        assert!(source.used_bytes > 0);
+
+        let c = metadata
+            .get_object(Request::new(GetObjectRequest {
+                bucket: "docs".into(),
+                key: "c.txt".into(),
+            }))
+            .await
+            .expect("get object")
+            .into_inner();
+        metadata
+            .put_recall_task(Request::new(common::RecallTask {
+                id: "recall-c".into(),
+                bucket: "docs".into(),
+                key: "c.txt".into(),
+                archive_id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                tape_set: c.tape_set.clone(),
+                tape_block_offset: c.tape_block_offset.unwrap_or_default(),
+                object_size: 7,
+                checksum: c.checksum.clone(),
+                status: common::RestoreStatus::RestorePending as i32,
+                ..Default::default()
+            }))
+            .await
+            .expect("put recall task");
+
+        for id in ["TAPE02", "TAPE03"] {
+            metadata
+                .put_tape(Request::new(tape_info(
+                    id,
+                    common::TapeStatus::TapeOnline,
+                    0,
+                    0,
+                )))
+                .await
+                .expect("bring tape online");
+        }
+        let reports = run_reclamation(&state, &mut tape)
+            .await
+            .expect("run reclamation");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].tape_id, "TAPE01");
+        assert_eq!(reports[0].objects_relocated, 1);
+        assert_eq!(reports[0].bytes_relocated, 7);
+
+        let moved = metadata
+            .get_object(Request::new(GetObjectRequest {
+                bucket: "docs".into(),
+                key: "c.txt".into(),
+            }))
+            .await
+            .expect("get object")
+            .into_inner();
+        assert_eq!(
+            moved.archive_id.as_deref(),
+            Some(reports[0].bundles_written[0].as_str())
+        );
+        assert_eq!(moved.storage_class, common::StorageClass::Cold as i32);
+        let new_tape = moved.tape_id.clone().expect("relocated tape");
+        assert_ne!(new_tape, "TAPE01");
+
+        mount(&mut tape, "drive-0", &new_tape).await.expect("mount");
+        let data = read_object(
+            &mut tape,
+            "drive-0",
+            &LiveEntry {
+                object: moved.clone(),
+                block_offset: moved.tape_block_offset.expect("block offset"),
+                pools: vec![],
+            },
+        )
+        .await
+        .expect("read relocated copy");
+        assert_eq!(data, b"charlie");
+
+        let recall = metadata
+            .get_recall_task(Request::new(GetRecallTaskRequest {
+                id: "recall-c".into(),
+            }))
+            .await
+            .expect("get recall task")
+            .into_inner();
+        assert_eq!(recall.tape_id, new_tape);
+        assert_eq!(recall.archive_id, reports[0].bundles_written[0]);
+
+        let scratch = metadata
+            .get_tape(Request::new(GetTapeRequest {
+                tape_id: "TAPE01".into(),
+            }))
+            .await
+            .expect("get tape")
+            .into_inner();
+        assert_eq!(scratch.status, common::TapeStatus::TapeScratch as i32);
+        assert_eq!((scratch.used_bytes, scratch.live_bytes), (0, 0));
+        assert!(scratch.archive_bundle_ids.is_empty());
+        let err = metadata
+            .get_archive_bundle(Request::new(GetArchiveBundleRequest {
+                id: "bundle-1".into(),
+            }))
+            .await
+            .expect_err("fully reclaimed bundle is dropped");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let _ = tape_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+}
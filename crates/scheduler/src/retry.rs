@@ -0,0 +1,46 @@
+//! 归档/取回任务失败的分类
+//!
+//! Scheduler 执行任务出错时按 gRPC 状态码判断能否重试，并经
+//! `FailArchiveTask` / `FailRecallTask` 回报给 Metadata：介质、驱动或节点暂时
+//! 不可用的失败按 `task_retry` 退避后重新排队；checksum 不一致、数据或暂存副本
+//! 缺失等重试无法恢复的失败直接进入死信列表 (`ListDeadLetterTasks`)。
+
+use coldstore_proto::common::TaskErrorKind;
+use tonic::{Code, Status};
+
+/// 按状态码将任务失败分为可重试与不可重试
+pub fn classify(status: &Status) -> TaskErrorKind {
+    match status.code() {
+        Code::Unavailable
+        | Code::DeadlineExceeded
+        | Code::ResourceExhausted
+        | Code::Aborted
+        | Code::Cancelled
+        | Code::Internal
+        | Code::Unknown => TaskErrorKind::TaskErrorRetriable,
+        _ => TaskErrorKind::TaskErrorPermanent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn media_and_node_errors_are_retriable_but_data_errors_are_not() {
+        for status in [
+            Status::unavailable("drive busy"),
+            Status::deadline_exceeded("tape load timed out"),
+            Status::internal("SCSI sense: medium error"),
+        ] {
+            assert_eq!(classify(&status), TaskErrorKind::TaskErrorRetriable);
+        }
+        for status in [
+            Status::data_loss("checksum mismatch"),
+            Status::not_found("object not in bundle"),
+            Status::failed_precondition("staging copy must be re-uploaded"),
+        ] {
+            assert_eq!(classify(&status), TaskErrorKind::TaskErrorPermanent);
+        }
+    }
+}
@@ -0,0 +1,629 @@
+//! 磁带巡检 (scrub)
+//!
+//! 驱动空闲时回读超过 `scrub.verify_interval_days` 未校验的在线磁带，逐条重算
+//! `BundleEntry::checksum`：
+//!
+//! 1. 按上次校验时间从早到晚 (从未校验的最先) 选择磁带，经 `AcquireDrive` 占用一个驱动，
+//!    本轮结束 (含让出与出错) 后 `ReleaseDrive` 归还
+//! 2. 逐个归档包读出本盘副本的各条目并校验，读取失败同样计为错误；没有本盘副本记录
+//!    (无法定位数据块) 的归档包计为未校验
+//! 3. 全部归档包都已校验时 `RecordTapeVerification` 记录校验时间并累加 `error_count`，
+//!    有错误的磁带转为 READ_ONLY
+//! 4. 有错误的归档包从 tape_set 中其它可读副本读出仍有效的对象，写成新归档包；
+//!    新归档包达到所需副本数时 Metadata 将对象指向新位置，损坏的副本随旧归档包失效
+//!
+//! 每校验一个归档包前检查是否有 Expedited 取回在排队，有则卸下磁带让出驱动，
+//! 该盘不记录校验时间，下次巡检重新开始。Scheduler 按 `scrub.scan_interval_secs`
+//! 周期调用 [`run_scrub`]。
+
+use crate::reclaim::{
+    acquire_drive, bundle_live_entries, entry_block_offset, mount, read_blocks, release_drive,
+    write_bundle, LiveEntry,
+};
+use crate::SchedulerState;
+use coldstore_common::checksum::sha256_hex;
+use coldstore_common::media::tape_readable;
+use coldstore_proto::common;
+use coldstore_proto::metadata::{
+    GetArchiveBundleRequest, ListBundlesByTapeRequest, RecordTapeVerificationRequest,
+};
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_proto::tape::UnloadTapeRequest;
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+use tracing::{info, warn};
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// 一盘磁带的巡检结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrubReport {
+    pub tape_id: String,
+    pub bundles_verified: usize,
+    /// 没有本盘副本记录、无法定位数据块的归档包；存在时不记录校验时间
+    pub bundles_unverified: usize,
+    pub bytes_verified: u64,
+    /// checksum 不符或读取失败的条目数
+    pub errors: u32,
+    /// 从其它副本重新写入的新归档包
+    pub bundles_recopied: Vec<String>,
+    /// 为 Expedited 取回让出驱动而中止，未记录校验时间
+    pub preempted: bool,
+}
+
+/// 需要巡检的磁带，从未校验的在前，其余按上次校验时间从早到晚
+///
+/// 只选择有数据的在线磁带；回收中的磁带即将清零，不再校验。
+pub fn scrub_candidates(
+    tapes: &[common::TapeInfo],
+    now: i64,
+    verify_interval_days: u64,
+) -> Vec<&common::TapeInfo> {
+    let due_before = now.saturating_sub((verify_interval_days as i64).saturating_mul(SECS_PER_DAY));
+    let mut candidates: Vec<&common::TapeInfo> = tapes
+        .iter()
+        .filter(|tape| {
+            tape.status == common::TapeStatus::TapeOnline as i32
+                && tape.used_bytes > 0
+                && tape
+                    .last_verified_at
+                    .as_ref()
+                    .is_none_or(|verified| verified.seconds <= due_before)
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        let verified = |tape: &common::TapeInfo| {
+            tape.last_verified_at
+                .as_ref()
+                .map(|ts| (ts.seconds, ts.nanos))
+        };
+        verified(a).cmp(&verified(b)).then_with(|| a.id.cmp(&b.id))
+    });
+    candidates
+}
+
+/// 巡检全部到期磁带；没有空闲驱动时不执行，让出驱动后停止本轮
+pub async fn run_scrub(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+) -> Result<Vec<ScrubReport>, Status> {
+    let Some(drive_id) = acquire_drive(tape).await? else {
+        info!("没有空闲驱动，跳过本轮磁带巡检");
+        return Ok(Vec::new());
+    };
+    let result = scrub_due_tapes(state, tape, &drive_id).await;
+    release_drive(tape, &drive_id).await;
+    result
+}
+
+async fn scrub_due_tapes(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+) -> Result<Vec<ScrubReport>, Status> {
+    let tapes = state
+        .metadata
+        .call(|mut client| async move { client.list_tapes(()).await })
+        .await?
+        .tapes;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let mut reports = Vec::new();
+    for candidate in scrub_candidates(&tapes, now, state.config.scrub.verify_interval_days) {
+        match scrub_tape(state, tape, drive_id, &candidate.id, &tapes).await {
+            Ok(report) => {
+                let preempted = report.preempted;
+                reports.push(report);
+                if preempted {
+                    break;
+                }
+            }
+            Err(status) => warn!("巡检磁带 {} 失败: {}", candidate.id, status.message()),
+        }
+    }
+    Ok(reports)
+}
+
+/// 校验 `tape_id` 上的全部归档包副本，损坏的归档包从其它副本重新写入
+pub async fn scrub_tape(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    tape_id: &str,
+    tapes: &[common::TapeInfo],
+) -> Result<ScrubReport, Status> {
+    let request = ListBundlesByTapeRequest {
+        tape_id: tape_id.to_string(),
+    };
+    let bundle_ids = state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.list_bundles_by_tape(request).await }
+        })
+        .await?
+        .bundle_ids;
+
+    let mut report = ScrubReport {
+        tape_id: tape_id.to_string(),
+        ..ScrubReport::default()
+    };
+    let mut damaged = Vec::new();
+    let mut mounted = false;
+    for bundle_id in bundle_ids {
+        if expedited_recall_waiting(state).await? {
+            info!("有 Expedited 取回排队，磁带 {tape_id} 巡检让出驱动 {drive_id}");
+            report.preempted = true;
+            break;
+        }
+        let bundle = get_bundle(state, &bundle_id).await?;
+        let Some(copy) = bundle.copies.iter().find(|copy| copy.tape_id == tape_id) else {
+            warn!(
+                "归档包 {} 没有磁带 {tape_id} 上的副本记录，无法定位数据块，本盘不记录校验时间",
+                bundle.id
+            );
+            report.bundles_unverified += 1;
+            continue;
+        };
+        if copy.status != common::ArchiveBundleStatus::BundleCompleted as i32 {
+            continue;
+        }
+        if !mounted {
+            mount(tape, drive_id, tape_id).await?;
+            mounted = true;
+        }
+        let mut errors = 0;
+        for entry in &bundle.entries {
+            let read =
+                read_blocks(tape, drive_id, entry_block_offset(copy, entry), entry.size).await;
+            match read {
+                Ok(data) if sha256_hex(&data).eq_ignore_ascii_case(&entry.checksum) => {
+                    report.bytes_verified += entry.size;
+                }
+                Ok(_) => {
+                    warn!(
+                        "磁带 {tape_id} 归档包 {} 中 {}/{} checksum 不符",
+                        bundle.id, entry.bucket, entry.key
+                    );
+                    errors += 1;
+                }
+                Err(status) => {
+                    warn!(
+                        "读取磁带 {tape_id} 归档包 {} 中 {}/{} 失败: {}",
+                        bundle.id,
+                        entry.bucket,
+                        entry.key,
+                        status.message()
+                    );
+                    errors += 1;
+                }
+            }
+        }
+        report.bundles_verified += 1;
+        report.errors += errors;
+        if errors > 0 {
+            damaged.push(bundle);
+        }
+    }
+
+    if report.preempted {
+        if mounted {
+            unload(tape, drive_id).await?;
+        }
+        return Ok(report);
+    }
+    if report.bundles_unverified > 0 {
+        if mounted {
+            unload(tape, drive_id).await?;
+        }
+        warn!(
+            "磁带 {tape_id} 有 {} 个归档包未能校验，保留上次校验时间",
+            report.bundles_unverified
+        );
+        return Ok(report);
+    }
+
+    let request = RecordTapeVerificationRequest {
+        tape_id: tape_id.to_string(),
+        errors: report.errors,
+    };
+    state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.record_tape_verification(request).await }
+        })
+        .await?;
+    for bundle in &damaged {
+        match recopy_bundle(state, tape, drive_id, tape_id, bundle, tapes).await {
+            Ok(Some(new_bundle)) => report.bundles_recopied.push(new_bundle),
+            Ok(None) => {}
+            Err(status) => warn!(
+                "归档包 {} 从其它副本重新写入失败: {}",
+                bundle.id,
+                status.message()
+            ),
+        }
+    }
+    if mounted {
+        unload(tape, drive_id).await?;
+    }
+    info!(
+        "磁带 {tape_id} 巡检完成: 校验 {} 个归档包 ({} 字节)，{} 个错误条目",
+        report.bundles_verified, report.bytes_verified, report.errors
+    );
+    Ok(report)
+}
+
+async fn unload(tape: &mut TapeServiceClient<Channel>, drive_id: &str) -> Result<(), Status> {
+    tape.unload_tape(Request::new(UnloadTapeRequest {
+        drive_id: drive_id.to_string(),
+        target_slot_id: None,
+    }))
+    .await?;
+    Ok(())
+}
+
+/// 从 tape_set 中其它可读副本读出仍有效的对象并写成新归档包
+///
+/// 归档包已无有效对象时不需要重写，返回 `None`；所有副本都读不出完整数据时返回错误。
+async fn recopy_bundle(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    damaged_tape: &str,
+    bundle: &common::ArchiveBundle,
+    tapes: &[common::TapeInfo],
+) -> Result<Option<String>, Status> {
+    let sources = bundle.copies.iter().filter(|copy| {
+        copy.tape_id != damaged_tape
+            && copy.status == common::ArchiveBundleStatus::BundleCompleted as i32
+            && tapes
+                .iter()
+                .any(|tape| tape.id == copy.tape_id && tape_readable(tape))
+    });
+    let mut last_error =
+        Status::failed_precondition(format!("bundle {} has no other readable copy", bundle.id));
+    for source in sources {
+        let live = bundle_live_entries(state, bundle, &source.tape_id, tapes).await?;
+        if live.is_empty() {
+            return Ok(None);
+        }
+        match read_live_entries(tape, drive_id, &source.tape_id, &live).await {
+            Ok(objects) => {
+                let bundle_id = format!("scrub-{}", uuid::Uuid::new_v4());
+                write_bundle(state, tape, drive_id, &bundle_id, &live[0].pools, &objects).await?;
+                info!(
+                    "归档包 {} 在磁带 {damaged_tape} 上损坏，已从 {} 重新写入为 {bundle_id}",
+                    bundle.id, source.tape_id
+                );
+                return Ok(Some(bundle_id));
+            }
+            Err(status) => {
+                warn!(
+                    "从磁带 {} 读取归档包 {} 失败: {}",
+                    source.tape_id,
+                    bundle.id,
+                    status.message()
+                );
+                last_error = status;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+async fn read_live_entries(
+    tape: &mut TapeServiceClient<Channel>,
+    drive_id: &str,
+    tape_id: &str,
+    live: &[LiveEntry],
+) -> Result<Vec<(common::ObjectMetadata, Vec<u8>)>, Status> {
+    mount(tape, drive_id, tape_id).await?;
+    let mut objects = Vec::with_capacity(live.len());
+    for entry in live {
+        let data = read_blocks(tape, drive_id, entry.block_offset, entry.object.size).await?;
+        if !sha256_hex(&data).eq_ignore_ascii_case(&entry.object.checksum) {
+            return Err(Status::data_loss(format!(
+                "{}/{} checksum mismatch on tape {tape_id}",
+                entry.object.bucket, entry.object.key
+            )));
+        }
+        objects.push((entry.object.clone(), data));
+    }
+    Ok(objects)
+}
+
+async fn get_bundle(state: &SchedulerState, id: &str) -> Result<common::ArchiveBundle, Status> {
+    let request = GetArchiveBundleRequest { id: id.to_string() };
+    state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.get_archive_bundle(request).await }
+        })
+        .await
+}
+
+/// 是否有 Expedited 取回在等待认领
+async fn expedited_recall_waiting(state: &SchedulerState) -> Result<bool, Status> {
+    let tasks = state
+        .metadata
+        .call(|mut client| async move { client.list_pending_recall_tasks(()).await })
+        .await?
+        .tasks;
+    Ok(tasks.iter().any(|task| {
+        task.tier == common::RestoreTier::Expedited as i32
+            && task.status == common::RestoreStatus::RestorePending as i32
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scheduler_state, spawn_metadata_server, spawn_tape};
+    use coldstore_proto::metadata::{GetObjectRequest, GetTapeRequest};
+    use prost_types::Timestamp;
+
+    fn tape_info(id: &str, used_bytes: u64, verified_at: Option<i64>) -> common::TapeInfo {
+        common::TapeInfo {
+            id: id.into(),
+            format: "LTO-9".into(),
+            status: common::TapeStatus::TapeOnline as i32,
+            capacity_bytes: 1 << 20,
+            used_bytes,
+            remaining_bytes: (1 << 20) - used_bytes,
+            last_verified_at: verified_at.map(|seconds| Timestamp { seconds, nanos: 0 }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn candidates_are_unverified_or_overdue_tapes_oldest_first() {
+        let day = SECS_PER_DAY;
+        let now = 100 * day;
+        let mut offline = tape_info("TAPE05", 10, None);
+        offline.status = common::TapeStatus::TapeOffline as i32;
+        let tapes = vec![
+            tape_info("TAPE01", 10, Some(now - 40 * day)),
+            tape_info("TAPE02", 10, Some(now - 10 * day)),
+            tape_info("TAPE03", 10, None),
+            tape_info("TAPE04", 0, None),
+            offline,
+            tape_info("TAPE06", 10, Some(now - 90 * day)),
+        ];
+        let ids: Vec<_> = scrub_candidates(&tapes, now, 30)
+            .iter()
+            .map(|tape| tape.id.as_str())
+            .collect();
+        assert_eq!(ids, ["TAPE03", "TAPE06", "TAPE01"]);
+    }
+
+    fn object(key: &str, data: &[u8]) -> common::ObjectMetadata {
+        common::ObjectMetadata {
+            bucket: "docs".into(),
+            key: key.into(),
+            size: data.len() as u64,
+            checksum: sha256_hex(data),
+            storage_class: common::StorageClass::ColdPending as i32,
+            ..Default::default()
+        }
+    }
+
+    async fn get_tape(state: &SchedulerState, tape_id: &str) -> common::TapeInfo {
+        let request = GetTapeRequest {
+            tape_id: tape_id.into(),
+        };
+        state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_tape(request).await }
+            })
+            .await
+            .expect("get tape")
+    }
+
+    #[tokio::test]
+    async fn corrupted_copy_is_recorded_and_rewritten_from_the_other_copy() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01", "TAPE02", "TAPE03"]).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .create_bucket(Request::new(common::BucketInfo {
+                name: "docs".into(),
+                ..Default::default()
+            }))
+            .await
+            .expect("create bucket");
+        for id in ["TAPE01", "TAPE02"] {
+            metadata
+                .put_tape(Request::new(tape_info(id, 0, None)))
+                .await
+                .expect("register tape");
+        }
+        let pools = [String::new(), String::new()];
+        for (bundle_id, key, data) in [
+            ("bundle-1", "a.txt", b"alpha".as_slice()),
+            ("bundle-2", "b.txt", b"bravo"),
+        ] {
+            let object = object(key, data);
+            metadata
+                .put_object(Request::new(object.clone()))
+                .await
+                .expect("put object");
+            write_bundle(
+                &state,
+                &mut tape,
+                "drive-0",
+                bundle_id,
+                &pools,
+                &[(object, data.to_vec())],
+            )
+            .await
+            .expect("archive bundle to TAPE01 and TAPE02");
+        }
+
+        // TAPE02 上 bundle-2 的副本指向 bundle-1 的数据块，模拟介质损坏
+        let intact = get_bundle(&state, "bundle-1").await.expect("bundle-1");
+        let mut corrupted = get_bundle(&state, "bundle-2").await.expect("bundle-2");
+        let start_block = intact
+            .copies
+            .iter()
+            .find(|copy| copy.tape_id == "TAPE02")
+            .expect("TAPE02 copy")
+            .start_block;
+        for copy in corrupted.copies.iter_mut() {
+            if copy.tape_id == "TAPE02" {
+                copy.start_block = start_block;
+            }
+        }
+        metadata
+            .put_archive_bundle(Request::new(corrupted))
+            .await
+            .expect("rewrite bundle copy");
+        metadata
+            .put_tape(Request::new(tape_info("TAPE03", 0, None)))
+            .await
+            .expect("register tape");
+
+        let reports = run_scrub(&state, &mut tape).await.expect("run scrub");
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].tape_id, "TAPE01");
+        assert_eq!(reports[0].errors, 0);
+        assert_eq!(reports[0].bundles_verified, 2);
+        assert_eq!(reports[1].tape_id, "TAPE02");
+        assert_eq!(reports[1].errors, 1);
+        assert_eq!(reports[1].bundles_recopied.len(), 1);
+
+        let healthy = get_tape(&state, "TAPE01").await;
+        assert!(healthy.last_verified_at.is_some());
+        assert_eq!(healthy.error_count, 0);
+        let damaged = get_tape(&state, "TAPE02").await;
+        assert!(damaged.last_verified_at.is_some());
+        assert_eq!(damaged.error_count, 1);
+        assert_eq!(
+            damaged.media_state,
+            common::TapeMediaState::TapeMediaReadOnly as i32
+        );
+
+        let b = metadata
+            .get_object(Request::new(GetObjectRequest {
+                bucket: "docs".into(),
+                key: "b.txt".into(),
+            }))
+            .await
+            .expect("get object")
+            .into_inner();
+        assert_eq!(
+            b.archive_id.as_deref(),
+            Some(reports[1].bundles_recopied[0].as_str())
+        );
+        let mut tape_set = b.tape_set.clone();
+        tape_set.sort();
+        assert_eq!(tape_set, ["TAPE01", "TAPE03"]);
+        mount(&mut tape, "drive-0", "TAPE03").await.expect("mount");
+        let copy = get_bundle(&state, &reports[1].bundles_recopied[0])
+            .await
+            .expect("new bundle");
+        let offset = entry_block_offset(
+            copy.copies
+                .iter()
+                .find(|copy| copy.tape_id == "TAPE03")
+                .expect("TAPE03 copy"),
+            &copy.entries[0],
+        );
+        let data = read_blocks(&mut tape, "drive-0", offset, 5)
+            .await
+            .expect("read new copy");
+        assert_eq!(data, b"bravo");
+
+        let _ = tape_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+
+    #[tokio::test]
+    async fn scrub_yields_the_drive_to_expedited_restores() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01"]).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .put_tape(Request::new(tape_info("TAPE01", 4096, None)))
+            .await
+            .expect("register tape");
+        metadata
+            .put_recall_task(Request::new(common::RecallTask {
+                id: "recall-1".into(),
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                tape_id: "TAPE01".into(),
+                tier: common::RestoreTier::Expedited as i32,
+                status: common::RestoreStatus::RestorePending as i32,
+                ..Default::default()
+            }))
+            .await
+            .expect("put recall task");
+        metadata
+            .put_archive_bundle(Request::new(common::ArchiveBundle {
+                id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                status: common::ArchiveBundleStatus::BundleCompleted as i32,
+                ..Default::default()
+            }))
+            .await
+            .expect("put bundle");
+
+        let reports = run_scrub(&state, &mut tape).await.expect("run scrub");
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].preempted);
+        assert_eq!(reports[0].bundles_verified, 0);
+        assert!(get_tape(&state, "TAPE01").await.last_verified_at.is_none());
+        let drives = tape
+            .list_drives(Request::new(()))
+            .await
+            .expect("list drives")
+            .into_inner()
+            .drives;
+        assert!(drives
+            .iter()
+            .all(|drive| drive.status == common::DriveStatus::DriveIdle as i32));
+
+        let _ = tape_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+
+    #[tokio::test]
+    async fn bundles_without_a_copy_on_the_tape_leave_it_unverified() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01"]).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .put_tape(Request::new(tape_info("TAPE01", 4096, None)))
+            .await
+            .expect("register tape");
+        metadata
+            .put_archive_bundle(Request::new(common::ArchiveBundle {
+                id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                tape_set: vec!["TAPE01".into()],
+                status: common::ArchiveBundleStatus::BundleCompleted as i32,
+                ..Default::default()
+            }))
+            .await
+            .expect("put bundle");
+
+        let reports = run_scrub(&state, &mut tape).await.expect("run scrub");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].bundles_verified, 0);
+        assert_eq!(reports[0].bundles_unverified, 1);
+        assert!(get_tape(&state, "TAPE01").await.last_verified_at.is_none());
+
+        let _ = tape_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+}
@@ -1,9 +1,12 @@
 use crate::SchedulerState;
+use coldstore_common::checksum::{sha256_hex, verify_optional_sha256};
+use coldstore_common::client::MetadataClientPool;
+use coldstore_common::error::status_with_reason;
+use coldstore_proto::cache::{get_response, ExtendRestoredRequest, GetRequest};
 use coldstore_proto::common;
 use coldstore_proto::scheduler::scheduler_service_server::SchedulerService;
 use coldstore_proto::scheduler::*;
 use prost_types::Timestamp;
-use sha2::{Digest, Sha256};
 #[cfg(test)]
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -16,18 +19,24 @@ use tonic::{Request, Response, Status, Streaming};
 #[tonic::async_trait]
 pub trait Phase1SchedulerBackend: Send + Sync + 'static {
     async fn list_buckets(&self) -> std::result::Result<Vec<common::BucketInfo>, Status>;
-    async fn create_bucket(&self, bucket: &str) -> std::result::Result<(), Status>;
+    async fn create_bucket(
+        &self,
+        bucket: &str,
+        owner: Option<&str>,
+    ) -> std::result::Result<(), Status>;
     async fn delete_bucket(&self, bucket: &str) -> std::result::Result<(), Status>;
-    async fn head_bucket(&self, bucket: &str) -> std::result::Result<(), Status>;
+    async fn head_bucket(&self, bucket: &str) -> std::result::Result<common::BucketInfo, Status>;
     async fn head_object(
         &self,
         bucket: &str,
         key: &str,
     ) -> std::result::Result<common::ObjectMetadata, Status>;
+    /// `ranges` 为空时返回整个对象，否则返回各区间按顺序拼接的数据
     async fn get_object(
         &self,
         bucket: &str,
         key: &str,
+        ranges: &[common::ByteRange],
     ) -> std::result::Result<(common::ObjectMetadata, Vec<u8>), Status>;
     async fn put_object(
         &self,
@@ -35,6 +44,7 @@ pub trait Phase1SchedulerBackend: Send + Sync + 'static {
         key: &str,
         body: Vec<u8>,
         content_type: Option<String>,
+        attributes: common::ObjectAttributes,
     ) -> std::result::Result<PutObjectResponse, Status>;
     async fn delete_object(&self, bucket: &str, key: &str) -> std::result::Result<(), Status>;
     async fn restore_object(
@@ -51,70 +61,223 @@ pub trait Phase1SchedulerBackend: Send + Sync + 'static {
         marker: Option<&str>,
         max_keys: u32,
     ) -> std::result::Result<Vec<common::ObjectMetadata>, Status>;
+    async fn put_bucket_policy(
+        &self,
+        bucket: &str,
+        _policy: String,
+    ) -> std::result::Result<(), Status> {
+        Err(Status::unimplemented(format!(
+            "bucket policies are not supported by this backend ({bucket})"
+        )))
+    }
+    /// 返回 (策略, 桶所有者)；未设置策略时策略为 `None`
+    async fn get_bucket_policy(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<GetBucketPolicyResponse, Status> {
+        let bucket = self.head_bucket(bucket).await?;
+        Ok(GetBucketPolicyResponse {
+            policy: None,
+            owner: bucket.owner,
+        })
+    }
+    async fn delete_bucket_policy(&self, bucket: &str) -> std::result::Result<(), Status> {
+        Err(Status::unimplemented(format!(
+            "bucket policies are not supported by this backend ({bucket})"
+        )))
+    }
+    async fn put_bucket_lifecycle(
+        &self,
+        bucket: &str,
+        _rules: Vec<common::LifecycleRule>,
+    ) -> std::result::Result<(), Status> {
+        Err(Status::unimplemented(format!(
+            "lifecycle configuration is not supported by this backend ({bucket})"
+        )))
+    }
+    async fn get_bucket_lifecycle(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<common::BucketLifecycle, Status> {
+        Err(Status::unimplemented(format!(
+            "lifecycle configuration is not supported by this backend ({bucket})"
+        )))
+    }
+    async fn delete_bucket_lifecycle(&self, bucket: &str) -> std::result::Result<(), Status> {
+        Err(Status::unimplemented(format!(
+            "lifecycle configuration is not supported by this backend ({bucket})"
+        )))
+    }
 }
 
 struct MetadataBackedSchedulerBackend {
-    metadata: coldstore_proto::metadata::metadata_service_client::MetadataServiceClient<
-        tonic::transport::Channel,
-    >,
+    metadata: Arc<MetadataClientPool>,
+    /// 写入对象时使用配对的 Cache Worker 暂存数据
+    state: Arc<SchedulerState>,
 }
 
 impl MetadataBackedSchedulerBackend {
-    fn new(
-        metadata: coldstore_proto::metadata::metadata_service_client::MetadataServiceClient<
-            tonic::transport::Channel,
-        >,
-    ) -> Self {
-        Self { metadata }
+    fn new(state: Arc<SchedulerState>) -> Self {
+        Self {
+            metadata: state.metadata.clone(),
+            state,
+        }
+    }
+
+    async fn update_restore_status(
+        &self,
+        bucket: &str,
+        key: &str,
+        status: common::RestoreStatus,
+        expire_at: Timestamp,
+    ) -> std::result::Result<(), Status> {
+        let request = coldstore_proto::metadata::UpdateRestoreStatusRequest {
+            bucket: bucket.into(),
+            key: key.into(),
+            status: status as i32,
+            expire_at: Some(expire_at),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.update_restore_status(request).await }
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// 对象没有进行中的取回任务时按其归档位置创建一个，由 [`crate::recall::run_recall`] 执行
+    ///
+    /// 失败后重新发起的解冻由 Metadata 重新排队原任务，不再重复创建。
+    async fn queue_recall(
+        &self,
+        object: &common::ObjectMetadata,
+        days: u32,
+        tier: common::RestoreTier,
+        expire_at: Timestamp,
+    ) -> std::result::Result<(), Status> {
+        let request = coldstore_proto::metadata::FindActiveRecallRequest {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+        };
+        let active = self
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.find_active_recall(request).await }
+            })
+            .await?
+            .task;
+        if active.is_some_and(|task| task.status != common::RestoreStatus::RestoreCompleted as i32)
+        {
+            return Ok(());
+        }
+        let task = common::RecallTask {
+            id: format!("recall-{}", uuid::Uuid::new_v4()),
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            version_id: object.version_id.clone(),
+            archive_id: object.archive_id.clone().unwrap_or_default(),
+            tape_id: object.tape_id.clone().unwrap_or_default(),
+            tape_set: object.tape_set.clone(),
+            tape_block_offset: object.tape_block_offset.unwrap_or_default(),
+            object_size: object.size,
+            checksum: object.checksum.clone(),
+            tier: tier as i32,
+            days: days.max(1),
+            expire_at: Some(expire_at),
+            status: common::RestoreStatus::RestorePending as i32,
+            ..Default::default()
+        };
+        self.metadata
+            .call(|mut client| {
+                let task = task.clone();
+                async move { client.put_recall_task(task).await }
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// 延长配对 Cache Worker 中解冻副本的过期时间；副本不存在时返回 `NotFound`
+    async fn extend_restored(
+        &self,
+        object: &common::ObjectMetadata,
+        expire_at: Timestamp,
+    ) -> std::result::Result<(), Status> {
+        let mut paired = self.state.paired_cache().await?;
+        let request = ExtendRestoredRequest {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            version_id: object.version_id.clone(),
+            expire_at: Some(expire_at),
+        };
+        if let Err(status) = paired.client.extend_restored(request).await {
+            if status.code() == tonic::Code::Unavailable {
+                self.state.unpair_cache(paired.node_id).await;
+            }
+            return Err(status);
+        }
+        Ok(())
     }
 }
 
 #[tonic::async_trait]
 impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
     async fn list_buckets(&self) -> std::result::Result<Vec<common::BucketInfo>, Status> {
-        let mut client = self.metadata.clone();
-        Ok(client
-            .list_buckets(Request::new(()))
+        Ok(self
+            .metadata
+            .call(|mut client| async move { client.list_buckets(()).await })
             .await?
-            .into_inner()
             .buckets)
     }
 
-    async fn create_bucket(&self, bucket: &str) -> std::result::Result<(), Status> {
-        let mut client = self.metadata.clone();
-        client
-            .create_bucket(Request::new(common::BucketInfo {
-                name: bucket.into(),
-                created_at: Some(now_timestamp()),
-                owner: None,
-                versioning_enabled: false,
-                object_count: 0,
-                total_size: 0,
-            }))
+    async fn create_bucket(
+        &self,
+        bucket: &str,
+        owner: Option<&str>,
+    ) -> std::result::Result<(), Status> {
+        let request = common::BucketInfo {
+            name: bucket.into(),
+            created_at: Some(now_timestamp()),
+            owner: owner.map(str::to_owned),
+            versioning_enabled: false,
+            object_count: 0,
+            total_size: 0,
+            archive_copies: None,
+            tape_pools: Vec::new(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.create_bucket(request).await }
+            })
             .await?;
         Ok(())
     }
 
     async fn delete_bucket(&self, bucket: &str) -> std::result::Result<(), Status> {
-        let mut client = self.metadata.clone();
-        client
-            .delete_bucket(Request::new(
-                coldstore_proto::metadata::DeleteBucketRequest {
-                    name: bucket.into(),
-                },
-            ))
+        let request = coldstore_proto::metadata::DeleteBucketRequest {
+            name: bucket.into(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.delete_bucket(request).await }
+            })
             .await?;
         Ok(())
     }
 
-    async fn head_bucket(&self, bucket: &str) -> std::result::Result<(), Status> {
-        let mut client = self.metadata.clone();
-        client
-            .get_bucket(Request::new(coldstore_proto::metadata::GetBucketRequest {
-                name: bucket.into(),
-            }))
-            .await?;
-        Ok(())
+    async fn head_bucket(&self, bucket: &str) -> std::result::Result<common::BucketInfo, Status> {
+        let request = coldstore_proto::metadata::GetBucketRequest {
+            name: bucket.into(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_bucket(request).await }
+            })
+            .await
     }
 
     async fn head_object(
@@ -122,26 +285,59 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
         bucket: &str,
         key: &str,
     ) -> std::result::Result<common::ObjectMetadata, Status> {
-        let mut client = self.metadata.clone();
-        Ok(client
-            .head_object(Request::new(coldstore_proto::metadata::HeadObjectRequest {
-                bucket: bucket.into(),
-                key: key.into(),
-            }))
-            .await?
-            .into_inner())
+        let request = coldstore_proto::metadata::HeadObjectRequest {
+            bucket: bucket.into(),
+            key: key.into(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.head_object(request).await }
+            })
+            .await
     }
 
+    /// 从配对 Cache Worker 的解冻区读取已取回的对象
     async fn get_object(
         &self,
         bucket: &str,
         key: &str,
+        ranges: &[common::ByteRange],
     ) -> std::result::Result<(common::ObjectMetadata, Vec<u8>), Status> {
         let object = self.head_object(bucket, key).await?;
-        Err(Status::failed_precondition(format!(
-            "scheduler.get_object requires phase-1 cache wiring; metadata is available for {}/{} but body retrieval is not yet connected",
-            object.bucket, object.key
-        )))
+        if object.restore_status != Some(common::RestoreStatus::RestoreCompleted as i32) {
+            return Err(status_with_reason(
+                tonic::Code::FailedPrecondition,
+                format!(
+                    "object {}/{} is archived and must be restored before it can be read",
+                    object.bucket, object.key
+                ),
+                common::ErrorReason::InvalidObjectState,
+            ));
+        }
+        let mut paired = self.state.paired_cache().await?;
+        let request = GetRequest {
+            bucket: object.bucket.clone(),
+            key: object.key.clone(),
+            version_id: object.version_id.clone(),
+            ranges: ranges.to_vec(),
+        };
+        let mut stream = match paired.client.get(request).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => {
+                if status.code() == tonic::Code::Unavailable {
+                    self.state.unpair_cache(paired.node_id).await;
+                }
+                return Err(status);
+            }
+        };
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.message().await? {
+            if let Some(get_response::Payload::Data(bytes)) = chunk.payload {
+                data.extend_from_slice(&bytes);
+            }
+        }
+        Ok((object, data))
     }
 
     async fn put_object(
@@ -150,10 +346,11 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
         key: &str,
         body: Vec<u8>,
         content_type: Option<String>,
+        attributes: common::ObjectAttributes,
     ) -> std::result::Result<PutObjectResponse, Status> {
         let checksum = sha256_hex(&body);
         let now = now_timestamp();
-        let object = common::ObjectMetadata {
+        let mut object = common::ObjectMetadata {
             bucket: bucket.into(),
             key: key.into(),
             version_id: None,
@@ -170,9 +367,17 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
             restore_expire_at: None,
             created_at: Some(now),
             updated_at: Some(now),
+            staging_worker_id: None,
+            staging_error: None,
+            attributes: Some(attributes),
         };
-        let mut client = self.metadata.clone();
-        client.put_object(Request::new(object)).await?;
+        object.staging_worker_id = Some(self.state.write_staging(&object, body).await?);
+        self.metadata
+            .call(|mut client| {
+                let object = object.clone();
+                async move { client.put_object(object).await }
+            })
+            .await?;
         Ok(PutObjectResponse {
             etag: checksum,
             version_id: String::new(),
@@ -180,14 +385,16 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
     }
 
     async fn delete_object(&self, bucket: &str, key: &str) -> std::result::Result<(), Status> {
-        let mut client = self.metadata.clone();
-        client
-            .delete_object(Request::new(
-                coldstore_proto::metadata::DeleteObjectRequest {
-                    bucket: bucket.into(),
-                    key: key.into(),
-                },
-            ))
+        let request = coldstore_proto::metadata::DeleteObjectRequest {
+            bucket: bucket.into(),
+            key: key.into(),
+            version_id: None,
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.delete_object(request).await }
+            })
             .await?;
         Ok(())
     }
@@ -197,16 +404,19 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
         bucket: &str,
         key: &str,
         days: u32,
-        _tier: common::RestoreTier,
+        tier: common::RestoreTier,
     ) -> std::result::Result<RestoreObjectResponse, Status> {
-        let mut client = self.metadata.clone();
-        let object = client
-            .get_object(Request::new(coldstore_proto::metadata::GetObjectRequest {
-                bucket: bucket.into(),
-                key: key.into(),
-            }))
-            .await?
-            .into_inner();
+        let request = coldstore_proto::metadata::GetObjectRequest {
+            bucket: bucket.into(),
+            key: key.into(),
+        };
+        let object = self
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_object(request).await }
+            })
+            .await?;
 
         if object.storage_class != common::StorageClass::Cold as i32 {
             return Err(Status::failed_precondition(
@@ -217,32 +427,61 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
         let restore_status = object
             .restore_status
             .and_then(|status| common::RestoreStatus::try_from(status).ok());
+        let expire_at = days_from_now(days.max(1));
 
         match restore_status {
+            // 已解冻：按新的天数原地延长过期时间，缓存副本已被淘汰时重新取回
             Some(common::RestoreStatus::RestoreCompleted) => {
-                Ok(RestoreObjectResponse { status_code: 200 })
+                match self.extend_restored(&object, expire_at).await {
+                    Ok(()) => {
+                        self.update_restore_status(
+                            bucket,
+                            key,
+                            common::RestoreStatus::RestoreCompleted,
+                            expire_at,
+                        )
+                        .await?;
+                        Ok(RestoreObjectResponse { status_code: 200 })
+                    }
+                    Err(status) if status.code() == tonic::Code::NotFound => {
+                        // 副本已不在缓存中，按过期处理后重新排队
+                        for status in [
+                            common::RestoreStatus::RestoreExpired,
+                            common::RestoreStatus::RestorePending,
+                        ] {
+                            self.update_restore_status(bucket, key, status, expire_at)
+                                .await?;
+                        }
+                        self.queue_recall(&object, days, tier, expire_at).await?;
+                        Ok(RestoreObjectResponse { status_code: 202 })
+                    }
+                    Err(status) => Err(status),
+                }
             }
             Some(
                 common::RestoreStatus::RestorePending
                 | common::RestoreStatus::RestoreWaitingForMedia
                 | common::RestoreStatus::RestoreInProgress,
-            ) => Ok(RestoreObjectResponse { status_code: 202 }),
-            Some(common::RestoreStatus::RestoreExpired | common::RestoreStatus::RestoreFailed) => {
-                Err(Status::failed_precondition(
-                    "restore_object cannot reopen expired or failed restores in phase-1 metadata-backed mode",
-                ))
-            }
-            Some(common::RestoreStatus::Unspecified) | None => {
-                client
-                    .update_restore_status(Request::new(
-                        coldstore_proto::metadata::UpdateRestoreStatusRequest {
-                            bucket: bucket.into(),
-                            key: key.into(),
-                            status: common::RestoreStatus::RestorePending as i32,
-                            expire_at: Some(days_from_now(days.max(1))),
-                        },
-                    ))
-                    .await?;
+            ) => Err(status_with_reason(
+                tonic::Code::AlreadyExists,
+                format!("restore of {bucket}/{key} is already in progress"),
+                common::ErrorReason::RestoreAlreadyInProgress,
+            )),
+            // 已过期或失败 (死信) 的解冻重新发起，Metadata 一并重新排队失败的取回任务
+            Some(
+                common::RestoreStatus::RestoreExpired
+                | common::RestoreStatus::RestoreFailed
+                | common::RestoreStatus::Unspecified,
+            )
+            | None => {
+                self.update_restore_status(
+                    bucket,
+                    key,
+                    common::RestoreStatus::RestorePending,
+                    expire_at,
+                )
+                .await?;
+                self.queue_recall(&object, days, tier, expire_at).await?;
                 Ok(RestoreObjectResponse { status_code: 202 })
             }
         }
@@ -255,20 +494,123 @@ impl Phase1SchedulerBackend for MetadataBackedSchedulerBackend {
         marker: Option<&str>,
         max_keys: u32,
     ) -> std::result::Result<Vec<common::ObjectMetadata>, Status> {
-        let mut client = self.metadata.clone();
-        Ok(client
-            .list_objects(Request::new(
-                coldstore_proto::metadata::ListObjectsRequest {
-                    bucket: bucket.into(),
-                    prefix: prefix.map(str::to_owned),
-                    marker: marker.map(str::to_owned),
-                    max_keys,
-                },
-            ))
+        let request = coldstore_proto::metadata::ListObjectsRequest {
+            bucket: bucket.into(),
+            prefix: prefix.map(str::to_owned),
+            marker: marker.map(str::to_owned),
+            max_keys,
+        };
+        Ok(self
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.list_objects(request).await }
+            })
             .await?
-            .into_inner()
             .objects)
     }
+
+    async fn put_bucket_policy(
+        &self,
+        bucket: &str,
+        policy: String,
+    ) -> std::result::Result<(), Status> {
+        let request = common::BucketPolicy {
+            bucket: bucket.into(),
+            policy,
+            updated_at: Some(now_timestamp()),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.put_bucket_policy(request).await }
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn get_bucket_policy(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<GetBucketPolicyResponse, Status> {
+        let owner = self.head_bucket(bucket).await?.owner;
+        let request = coldstore_proto::metadata::GetBucketPolicyRequest {
+            bucket: bucket.into(),
+        };
+        let policy = match self
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_bucket_policy(request).await }
+            })
+            .await
+        {
+            Ok(policy) => Some(policy.policy),
+            Err(status) if status.code() == tonic::Code::NotFound => None,
+            Err(status) => return Err(status),
+        };
+        Ok(GetBucketPolicyResponse { policy, owner })
+    }
+
+    async fn delete_bucket_policy(&self, bucket: &str) -> std::result::Result<(), Status> {
+        let request = coldstore_proto::metadata::DeleteBucketPolicyRequest {
+            bucket: bucket.into(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.delete_bucket_policy(request).await }
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn put_bucket_lifecycle(
+        &self,
+        bucket: &str,
+        rules: Vec<common::LifecycleRule>,
+    ) -> std::result::Result<(), Status> {
+        let request = common::BucketLifecycle {
+            bucket: bucket.into(),
+            rules,
+            updated_at: Some(now_timestamp()),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.put_bucket_lifecycle(request).await }
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn get_bucket_lifecycle(
+        &self,
+        bucket: &str,
+    ) -> std::result::Result<common::BucketLifecycle, Status> {
+        let request = coldstore_proto::metadata::GetBucketLifecycleRequest {
+            bucket: bucket.into(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.get_bucket_lifecycle(request).await }
+            })
+            .await
+    }
+
+    async fn delete_bucket_lifecycle(&self, bucket: &str) -> std::result::Result<(), Status> {
+        let request = coldstore_proto::metadata::DeleteBucketLifecycleRequest {
+            bucket: bucket.into(),
+        };
+        self.metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.delete_bucket_lifecycle(request).await }
+            })
+            .await?;
+        Ok(())
+    }
 }
 
 pub struct SchedulerServiceImpl {
@@ -278,7 +620,7 @@ pub struct SchedulerServiceImpl {
 
 impl SchedulerServiceImpl {
     pub fn new(state: Arc<SchedulerState>) -> Self {
-        let backend = Arc::new(MetadataBackedSchedulerBackend::new(state.metadata.clone()));
+        let backend = Arc::new(MetadataBackedSchedulerBackend::new(state.clone()));
         Self {
             _state: state,
             backend,
@@ -303,12 +645,6 @@ fn phase1_unimplemented(op: &str) -> Status {
     ))
 }
 
-fn sha256_hex(body: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(body);
-    format!("{:x}", hasher.finalize())
-}
-
 fn now_timestamp() -> Timestamp {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -359,6 +695,7 @@ fn build_head_object_response(object: &common::ObjectMetadata) -> HeadObjectResp
         storage_class: object.storage_class,
         restore_info: build_restore_info(object.restore_status, object.restore_expire_at.as_ref()),
         last_modified: object.updated_at,
+        attributes: object.attributes.clone(),
     }
 }
 
@@ -370,6 +707,7 @@ fn build_get_object_meta(object: &common::ObjectMetadata) -> GetObjectMeta {
         storage_class: object.storage_class,
         restore_info: build_restore_info(object.restore_status, object.restore_expire_at.as_ref()),
         last_modified: object.updated_at,
+        attributes: object.attributes.clone(),
     }
 }
 
@@ -387,6 +725,7 @@ fn build_bucket_entry(bucket: &common::BucketInfo) -> BucketEntry {
     BucketEntry {
         name: bucket.name.clone(),
         creation_date: bucket.created_at,
+        owner: bucket.owner.clone(),
     }
 }
 
@@ -420,9 +759,20 @@ impl SchedulerService for SchedulerServiceImpl {
                 "content_length does not match body size",
             ));
         }
+        verify_optional_sha256(
+            &body,
+            meta.checksum_sha256.as_deref(),
+            "scheduler.put_object",
+        )?;
         let response = self
             .backend
-            .put_object(&meta.bucket, &meta.key, body, meta.content_type)
+            .put_object(
+                &meta.bucket,
+                &meta.key,
+                body,
+                meta.content_type,
+                meta.attributes.unwrap_or_default(),
+            )
             .await?;
         Ok(Response::new(response))
     }
@@ -436,7 +786,7 @@ impl SchedulerService for SchedulerServiceImpl {
         let request = request.into_inner();
         let (object, data) = self
             .backend
-            .get_object(&request.bucket, &request.key)
+            .get_object(&request.bucket, &request.key, &request.ranges)
             .await?;
         let (tx, rx) = mpsc::channel(8);
         tokio::spawn(async move {
@@ -498,6 +848,7 @@ impl SchedulerService for SchedulerServiceImpl {
         request: Request<ListObjectsRequest>,
     ) -> std::result::Result<Response<ListObjectsResponse>, Status> {
         let request = request.into_inner();
+        let bucket = self.backend.head_bucket(&request.bucket).await?;
         let objects = self
             .backend
             .list_objects(
@@ -532,6 +883,7 @@ impl SchedulerService for SchedulerServiceImpl {
                 .map(|object| build_object_entry(&object))
                 .collect(),
             common_prefixes: vec![],
+            owner: bucket.owner,
         }))
     }
 
@@ -539,8 +891,9 @@ impl SchedulerService for SchedulerServiceImpl {
         &self,
         request: Request<CreateBucketRequest>,
     ) -> std::result::Result<Response<()>, Status> {
+        let request = request.into_inner();
         self.backend
-            .create_bucket(&request.into_inner().bucket)
+            .create_bucket(&request.bucket, request.owner.as_deref())
             .await?;
         Ok(Response::new(()))
     }
@@ -565,6 +918,70 @@ impl SchedulerService for SchedulerServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn put_bucket_policy(
+        &self,
+        request: Request<PutBucketPolicyRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        let request = request.into_inner();
+        self.backend
+            .put_bucket_policy(&request.bucket, request.policy)
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_bucket_policy(
+        &self,
+        request: Request<GetBucketPolicyRequest>,
+    ) -> std::result::Result<Response<GetBucketPolicyResponse>, Status> {
+        let response = self
+            .backend
+            .get_bucket_policy(&request.into_inner().bucket)
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn delete_bucket_policy(
+        &self,
+        request: Request<DeleteBucketPolicyRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.backend
+            .delete_bucket_policy(&request.into_inner().bucket)
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn put_bucket_lifecycle(
+        &self,
+        request: Request<PutBucketLifecycleRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        let request = request.into_inner();
+        self.backend
+            .put_bucket_lifecycle(&request.bucket, request.rules)
+            .await?;
+        Ok(Response::new(()))
+    }
+
+    async fn get_bucket_lifecycle(
+        &self,
+        request: Request<GetBucketLifecycleRequest>,
+    ) -> std::result::Result<Response<common::BucketLifecycle>, Status> {
+        let lifecycle = self
+            .backend
+            .get_bucket_lifecycle(&request.into_inner().bucket)
+            .await?;
+        Ok(Response::new(lifecycle))
+    }
+
+    async fn delete_bucket_lifecycle(
+        &self,
+        request: Request<DeleteBucketLifecycleRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        self.backend
+            .delete_bucket_lifecycle(&request.into_inner().bucket)
+            .await?;
+        Ok(Response::new(()))
+    }
+
     async fn list_buckets(
         &self,
         _request: Request<()>,
@@ -579,11 +996,14 @@ impl SchedulerService for SchedulerServiceImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::spawn_cache_worker;
+    use coldstore_common::client::{not_leader, ChannelPool, ClientConfig};
     use coldstore_common::config::{MetadataConfig, SchedulerConfig};
+    use coldstore_common::tls::TlsConfig;
     use coldstore_metadata::service::MetadataServiceImpl;
     use coldstore_proto::metadata::metadata_service_server::MetadataServiceServer;
+    use std::sync::atomic::AtomicBool;
     use tokio::sync::oneshot;
-    use tokio::time::{sleep, Duration};
     use tokio_stream::StreamExt;
     use tonic::transport::Server;
 
@@ -605,6 +1025,8 @@ mod tests {
                 versioning_enabled: false,
                 object_count: 1,
                 total_size: 42,
+                archive_copies: None,
+                tape_pools: Vec::new(),
             };
             let object = common::ObjectMetadata {
                 bucket: "docs".into(),
@@ -632,6 +1054,9 @@ mod tests {
                     seconds: 2,
                     nanos: 0,
                 }),
+                staging_worker_id: None,
+                staging_error: None,
+                attributes: None,
             };
             let mut objects = HashMap::new();
             objects.insert("docs/readme.txt".into(), (object, b"hello world".to_vec()));
@@ -647,7 +1072,11 @@ mod tests {
         async fn list_buckets(&self) -> std::result::Result<Vec<common::BucketInfo>, Status> {
             Ok(self.buckets.read().unwrap().clone())
         }
-        async fn create_bucket(&self, bucket: &str) -> std::result::Result<(), Status> {
+        async fn create_bucket(
+            &self,
+            bucket: &str,
+            owner: Option<&str>,
+        ) -> std::result::Result<(), Status> {
             let mut buckets = self.buckets.write().unwrap();
             if buckets.iter().any(|b| b.name == bucket) {
                 return Err(Status::already_exists("bucket exists"));
@@ -655,10 +1084,12 @@ mod tests {
             buckets.push(common::BucketInfo {
                 name: bucket.into(),
                 created_at: None,
-                owner: None,
+                owner: owner.map(str::to_owned),
                 versioning_enabled: false,
                 object_count: 0,
                 total_size: 0,
+                archive_copies: None,
+                tape_pools: Vec::new(),
             });
             Ok(())
         }
@@ -672,18 +1103,17 @@ mod tests {
                 Ok(())
             }
         }
-        async fn head_bucket(&self, bucket: &str) -> std::result::Result<(), Status> {
-            if self
-                .buckets
+        async fn head_bucket(
+            &self,
+            bucket: &str,
+        ) -> std::result::Result<common::BucketInfo, Status> {
+            self.buckets
                 .read()
                 .unwrap()
                 .iter()
-                .any(|b| b.name == bucket)
-            {
-                Ok(())
-            } else {
-                Err(Status::not_found("bucket missing"))
-            }
+                .find(|b| b.name == bucket)
+                .cloned()
+                .ok_or_else(|| Status::not_found("bucket missing"))
         }
         async fn head_object(
             &self,
@@ -701,13 +1131,24 @@ mod tests {
             &self,
             bucket: &str,
             key: &str,
+            ranges: &[common::ByteRange],
         ) -> std::result::Result<(common::ObjectMetadata, Vec<u8>), Status> {
-            self.objects
+            let (object, body) = self
+                .objects
                 .read()
                 .unwrap()
                 .get(&format!("{bucket}/{key}"))
                 .cloned()
-                .ok_or_else(|| Status::not_found("object missing"))
+                .ok_or_else(|| Status::not_found("object missing"))?;
+            if ranges.is_empty() {
+                return Ok((object, body));
+            }
+            let data = ranges
+                .iter()
+                .flat_map(|r| &body[r.offset as usize..(r.offset + r.length) as usize])
+                .copied()
+                .collect();
+            Ok((object, data))
         }
         async fn put_object(
             &self,
@@ -715,6 +1156,7 @@ mod tests {
             key: &str,
             body: Vec<u8>,
             content_type: Option<String>,
+            attributes: common::ObjectAttributes,
         ) -> std::result::Result<PutObjectResponse, Status> {
             let object = common::ObjectMetadata {
                 bucket: bucket.into(),
@@ -739,6 +1181,9 @@ mod tests {
                     seconds: 10,
                     nanos: 0,
                 }),
+                staging_worker_id: None,
+                staging_error: None,
+                attributes: Some(attributes),
             };
             self.objects
                 .write()
@@ -812,13 +1257,17 @@ mod tests {
 
     fn service() -> SchedulerServiceImpl {
         let state = Arc::new(SchedulerState {
-            metadata:
-                coldstore_proto::metadata::metadata_service_client::MetadataServiceClient::new(
+            metadata: Arc::new(MetadataClientPool::from_pool(ChannelPool::from_channels(
+                vec![(
+                    "127.0.0.1:1".into(),
                     tonic::transport::Channel::from_static("http://127.0.0.1:1").connect_lazy(),
-                ),
-            cache: None,
+                )],
+                ClientConfig::default(),
+            ))),
+            cache: Default::default(),
             tape: None,
             config: SchedulerConfig::default(),
+            draining: AtomicBool::new(false),
         });
         SchedulerServiceImpl::new_with_backend(state, Arc::new(InMemoryBackend::with_fixture()))
     }
@@ -848,6 +1297,7 @@ mod tests {
                 bucket: "docs".into(),
                 key: "readme.txt".into(),
                 version_id: None,
+                ..Default::default()
             }))
             .await
             .unwrap()
@@ -916,6 +1366,7 @@ mod tests {
         let svc = service();
         svc.create_bucket(Request::new(CreateBucketRequest {
             bucket: "new-bucket".into(),
+            owner: Some("alice".into()),
         }))
         .await
         .unwrap();
@@ -924,7 +1375,12 @@ mod tests {
             .await
             .unwrap()
             .into_inner();
-        assert!(response.buckets.iter().any(|b| b.name == "new-bucket"));
+        let bucket = response
+            .buckets
+            .iter()
+            .find(|b| b.name == "new-bucket")
+            .expect("created bucket listed");
+        assert_eq!(bucket.owner.as_deref(), Some("alice"));
     }
 
     #[tokio::test]
@@ -986,6 +1442,34 @@ mod tests {
         assert_eq!(response.contents[0].storage_class, "COLD");
     }
 
+    /// 在配对 Cache Worker 的解冻区放入 `docs/<key>` 的副本
+    async fn stage_restored(state: &SchedulerState, key: &str, data: &[u8], expire_at: Timestamp) {
+        use coldstore_proto::cache::{put_restored_request, PutRestoredMeta, PutRestoredRequest};
+
+        let chunks = vec![
+            PutRestoredRequest {
+                payload: Some(put_restored_request::Payload::Meta(PutRestoredMeta {
+                    bucket: "docs".into(),
+                    key: key.into(),
+                    size: data.len() as u64,
+                    expire_at: Some(expire_at),
+                    ..Default::default()
+                })),
+            },
+            PutRestoredRequest {
+                payload: Some(put_restored_request::Payload::Data(data.to_vec())),
+            },
+        ];
+        state
+            .paired_cache()
+            .await
+            .unwrap()
+            .client
+            .put_restored(tokio_stream::iter(chunks))
+            .await
+            .expect("stage restored copy");
+    }
+
     async fn metadata_backed_service() -> (
         SchedulerServiceImpl,
         Arc<SchedulerState>,
@@ -1009,26 +1493,21 @@ mod tests {
                 .expect("metadata server should run");
         });
 
-        let mut metadata_client = None;
-        for _ in 0..20 {
-            match coldstore_proto::metadata::metadata_service_client::MetadataServiceClient::connect(
-                format!("http://{addr}"),
+        // 服务端尚未就绪时由连接池退避重试
+        let metadata = Arc::new(
+            MetadataClientPool::new(
+                &[addr.to_string()],
+                &TlsConfig::default(),
+                ClientConfig::default(),
             )
-            .await
-            {
-                Ok(client) => {
-                    metadata_client = Some(client);
-                    break;
-                }
-                Err(_) => sleep(Duration::from_millis(25)).await,
-            }
-        }
-        let metadata = metadata_client.expect("connect metadata client");
+            .expect("metadata pool"),
+        );
         let state = Arc::new(SchedulerState {
             metadata,
-            cache: None,
+            cache: Default::default(),
             tape: None,
             config: SchedulerConfig::default(),
+            draining: AtomicBool::new(false),
         });
         (SchedulerServiceImpl::new(state.clone()), state, shutdown_tx)
     }
@@ -1039,6 +1518,7 @@ mod tests {
 
         svc.create_bucket(Request::new(CreateBucketRequest {
             bucket: "phase1-bucket".into(),
+            owner: None,
         }))
         .await
         .expect("create bucket through metadata-backed scheduler");
@@ -1056,17 +1536,169 @@ mod tests {
         shutdown_tx.send(()).ok();
     }
 
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    async fn metadata_pool_skips_dead_nodes_and_follows_leader_hint() {
+        let free_addr = || {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+            listener.local_addr().expect("addr")
+        };
+        let (leader_addr, follower_addr) = (free_addr(), free_addr());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let leader = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("leader init");
+        let follower = MetadataServiceImpl::new(&MetadataConfig::default())
+            .await
+            .expect("follower init");
+        let hint = leader_addr.to_string();
+        tokio::spawn(async move {
+            let leader = Server::builder()
+                .add_service(MetadataServiceServer::new(leader))
+                .serve(leader_addr);
+            // follower 拒绝一切请求并指向 leader
+            let follower = Server::builder()
+                .add_service(MetadataServiceServer::with_interceptor(
+                    follower,
+                    move |_request| Err(not_leader(Some(&hint))),
+                ))
+                .serve(follower_addr);
+            tokio::select! {
+                _ = leader => {}
+                _ = follower => {}
+                _ = shutdown_rx => {}
+            }
+        });
+
+        let pool = Arc::new(
+            MetadataClientPool::new(
+                &[
+                    "127.0.0.1:1".to_string(),
+                    follower_addr.to_string(),
+                    leader_addr.to_string(),
+                ],
+                &TlsConfig::default(),
+                ClientConfig::default(),
+            )
+            .expect("metadata pool"),
+        );
+        let backend = MetadataBackedSchedulerBackend::new(Arc::new(SchedulerState {
+            metadata: pool.clone(),
+            cache: Default::default(),
+            tape: None,
+            config: SchedulerConfig::default(),
+            draining: AtomicBool::new(false),
+        }));
+        backend
+            .create_bucket("docs", None)
+            .await
+            .expect("create bucket through discovered leader");
+        assert_eq!(pool.leader_addr(), Some(leader_addr.to_string()));
+        assert_eq!(backend.list_buckets().await.expect("list").len(), 1);
+        let _ = shutdown_tx.send(());
+    }
+
+    #[tokio::test]
+    async fn bucket_policy_round_trips_through_metadata() {
+        let (svc, _state, shutdown_tx) = metadata_backed_service().await;
+        svc.create_bucket(Request::new(CreateBucketRequest {
+            bucket: "docs".into(),
+            owner: Some("alice".into()),
+        }))
+        .await
+        .expect("create bucket");
+
+        let empty = svc
+            .get_bucket_policy(Request::new(GetBucketPolicyRequest {
+                bucket: "docs".into(),
+            }))
+            .await
+            .expect("get unset policy")
+            .into_inner();
+        assert_eq!(empty.policy, None);
+        assert_eq!(empty.owner.as_deref(), Some("alice"));
+
+        let document = r#"{"Version":"2012-10-17","Statement":[]}"#;
+        svc.put_bucket_policy(Request::new(PutBucketPolicyRequest {
+            bucket: "docs".into(),
+            policy: document.into(),
+        }))
+        .await
+        .expect("put policy");
+        let stored = svc
+            .get_bucket_policy(Request::new(GetBucketPolicyRequest {
+                bucket: "docs".into(),
+            }))
+            .await
+            .expect("get policy")
+            .into_inner();
+        assert_eq!(stored.policy.as_deref(), Some(document));
+
+        svc.delete_bucket_policy(Request::new(DeleteBucketPolicyRequest {
+            bucket: "docs".into(),
+        }))
+        .await
+        .expect("delete policy");
+
+        let err = svc
+            .get_bucket_lifecycle(Request::new(GetBucketLifecycleRequest {
+                bucket: "docs".into(),
+            }))
+            .await
+            .expect_err("lifecycle not configured");
+        assert_eq!(
+            coldstore_common::error::error_reason(&err),
+            Some(common::ErrorReason::NoSuchLifecycleConfiguration)
+        );
+        let rule = common::LifecycleRule {
+            id: "expire-logs".into(),
+            enabled: true,
+            prefix: "logs/".into(),
+            expiration_days: Some(30),
+            ..Default::default()
+        };
+        svc.put_bucket_lifecycle(Request::new(PutBucketLifecycleRequest {
+            bucket: "docs".into(),
+            rules: vec![rule.clone()],
+        }))
+        .await
+        .expect("put lifecycle");
+        let lifecycle = svc
+            .get_bucket_lifecycle(Request::new(GetBucketLifecycleRequest {
+                bucket: "docs".into(),
+            }))
+            .await
+            .expect("get lifecycle")
+            .into_inner();
+        assert_eq!(lifecycle.rules, vec![rule]);
+        svc.delete_bucket_lifecycle(Request::new(DeleteBucketLifecycleRequest {
+            bucket: "docs".into(),
+        }))
+        .await
+        .expect("delete lifecycle");
+        let err = svc
+            .get_bucket_policy(Request::new(GetBucketPolicyRequest {
+                bucket: "missing".into(),
+            }))
+            .await
+            .expect_err("missing bucket");
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        shutdown_tx.send(()).ok();
+    }
+
     #[tokio::test]
     async fn default_service_uses_metadata_for_object_metadata_ops() {
         let (svc, state, shutdown_tx) = metadata_backed_service().await;
 
         svc.create_bucket(Request::new(CreateBucketRequest {
             bucket: "docs".into(),
+            owner: None,
         }))
         .await
         .expect("create bucket");
 
-        let mut metadata = state.metadata.clone();
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
         metadata
             .put_object(Request::new(common::ObjectMetadata {
                 bucket: "docs".into(),
@@ -1091,6 +1723,9 @@ mod tests {
                     seconds: 10,
                     nanos: 0,
                 }),
+                staging_worker_id: None,
+                staging_error: None,
+                attributes: None,
             }))
             .await
             .expect("seed object in metadata");
@@ -1121,7 +1756,7 @@ mod tests {
         assert_eq!(list.contents.len(), 1);
         assert_eq!(list.contents[0].key, "guide.txt");
 
-        let mut metadata = state.metadata.clone();
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
         metadata
             .update_storage_class(Request::new(
                 coldstore_proto::metadata::UpdateStorageClassRequest {
@@ -1160,6 +1795,146 @@ mod tests {
             Some("ongoing-request=\"true\"")
         );
 
+        // 解冻进行中重复发起返回 RestoreAlreadyInProgress
+        let err = svc
+            .restore_object(Request::new(RestoreObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                days: 3,
+                tier: common::RestoreTier::Standard as i32,
+            }))
+            .await
+            .expect_err("restore already in progress");
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+        assert_eq!(
+            coldstore_common::error::error_reason(&err),
+            Some(common::ErrorReason::RestoreAlreadyInProgress)
+        );
+
+        // 失败 (死信) 的解冻可以重新发起
+        metadata
+            .update_restore_status(Request::new(
+                coldstore_proto::metadata::UpdateRestoreStatusRequest {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    status: common::RestoreStatus::RestoreFailed as i32,
+                    expire_at: None,
+                },
+            ))
+            .await
+            .expect("mark restore as failed");
+        let reopened = svc
+            .restore_object(Request::new(RestoreObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                days: 3,
+                tier: common::RestoreTier::Standard as i32,
+            }))
+            .await
+            .expect("reopen failed restore")
+            .into_inner();
+        assert_eq!(reopened.status_code, 202);
+        let object = metadata
+            .get_object(Request::new(coldstore_proto::metadata::GetObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+            }))
+            .await
+            .expect("get reopened object")
+            .into_inner();
+        assert_eq!(
+            object.restore_status,
+            Some(common::RestoreStatus::RestorePending as i32)
+        );
+
+        // 已解冻但缓存副本已被淘汰时重新发起取回
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 3).await;
+        let completed = |expire_at| {
+            Request::new(coldstore_proto::metadata::UpdateRestoreStatusRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                status: common::RestoreStatus::RestoreCompleted as i32,
+                expire_at: Some(expire_at),
+            })
+        };
+        let restore = |days| {
+            Request::new(RestoreObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                version_id: None,
+                days,
+                tier: common::RestoreTier::Standard as i32,
+            })
+        };
+        metadata
+            .update_restore_status(Request::new(
+                coldstore_proto::metadata::UpdateRestoreStatusRequest {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    status: common::RestoreStatus::RestoreInProgress as i32,
+                    expire_at: None,
+                },
+            ))
+            .await
+            .expect("start restore");
+        metadata
+            .update_restore_status(completed(days_from_now(1)))
+            .await
+            .expect("complete restore");
+        let requeued = svc
+            .restore_object(restore(3))
+            .await
+            .expect("restore without cached copy")
+            .into_inner();
+        assert_eq!(requeued.status_code, 202);
+
+        // 已解冻的对象再次 RestoreObject 返回 200，并同时延长 Metadata 与缓存副本的过期时间
+        metadata
+            .update_restore_status(Request::new(
+                coldstore_proto::metadata::UpdateRestoreStatusRequest {
+                    bucket: "docs".into(),
+                    key: "guide.txt".into(),
+                    status: common::RestoreStatus::RestoreInProgress as i32,
+                    expire_at: None,
+                },
+            ))
+            .await
+            .expect("start restore again");
+        let original_expiry = Timestamp {
+            seconds: chrono::Utc::now().timestamp() + 1,
+            nanos: 0,
+        };
+        stage_restored(&state, "guide.txt", b"hello", original_expiry).await;
+        metadata
+            .update_restore_status(completed(original_expiry))
+            .await
+            .expect("complete restore again");
+        let extended = svc
+            .restore_object(restore(10))
+            .await
+            .expect("extend restored copy")
+            .into_inner();
+        assert_eq!(extended.status_code, 200);
+        let object = metadata
+            .get_object(Request::new(coldstore_proto::metadata::GetObjectRequest {
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+            }))
+            .await
+            .expect("get extended object")
+            .into_inner();
+        assert!(object.restore_expire_at.unwrap().seconds >= days_from_now(9).seconds);
+
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+        let (_, data) = MetadataBackedSchedulerBackend::new(state.clone())
+            .get_object("docs", "guide.txt", &[])
+            .await
+            .expect("read restored copy after its original expiry");
+        assert_eq!(data, b"hello");
+        cache_shutdown.send(()).ok();
+
         svc.delete_object(Request::new(DeleteObjectRequest {
             bucket: "docs".into(),
             key: "guide.txt".into(),
@@ -1183,19 +1958,37 @@ mod tests {
     #[tokio::test]
     async fn default_metadata_backend_puts_object_and_reports_cache_gap() {
         let (_svc, state, shutdown_tx) = metadata_backed_service().await;
-        let backend = MetadataBackedSchedulerBackend::new(state.metadata.clone());
+        let backend = MetadataBackedSchedulerBackend::new(state.clone());
 
         backend
-            .create_bucket("docs")
+            .create_bucket("docs", None)
             .await
             .expect("create bucket through metadata backend");
 
+        let err = backend
+            .put_object(
+                "docs",
+                "guide.txt",
+                b"hello".to_vec(),
+                None,
+                Default::default(),
+            )
+            .await
+            .expect_err("no cache worker to stage the object on");
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 3).await;
+
+        let attributes = coldstore_common::attributes::from_headers([
+            ("x-amz-meta-owner", "ops"),
+            ("content-encoding", "gzip"),
+        ]);
         let put = backend
             .put_object(
                 "docs",
                 "guide.txt",
                 b"hello".to_vec(),
                 Some("text/plain".into()),
+                attributes.clone(),
             )
             .await
             .expect("put object through metadata backend");
@@ -1207,14 +2000,91 @@ mod tests {
             .expect("list objects through metadata backend");
         assert_eq!(listed.len(), 1);
         assert_eq!(listed[0].etag.as_deref(), Some(put.etag.as_str()));
+        assert_eq!(listed[0].staging_worker_id, Some(3));
+        assert_eq!(listed[0].attributes.as_ref(), Some(&attributes));
 
         let err = backend
-            .get_object("docs", "guide.txt")
+            .get_object("docs", "guide.txt", &[])
             .await
-            .expect_err("get_object should explain that cache is still not wired");
+            .expect_err("archived objects must be restored before reading");
         assert_eq!(err.code(), tonic::Code::FailedPrecondition);
-        assert!(err.message().contains("cache wiring"));
+        assert!(err.message().contains("must be restored"));
+
+        cache_shutdown.send(()).ok();
+        shutdown_tx.send(()).ok();
+    }
+
+    #[tokio::test]
+    async fn restored_object_ranges_are_read_from_paired_cache() {
+        let (_svc, state, shutdown_tx) = metadata_backed_service().await;
+        let cache_shutdown = spawn_cache_worker(&state.metadata, 3).await;
+        let backend = MetadataBackedSchedulerBackend::new(state.clone());
+        backend.create_bucket("docs", None).await.unwrap();
+        backend
+            .put_object(
+                "docs",
+                "guide.txt",
+                b"hello world".to_vec(),
+                None,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let expire_at = days_from_now(1);
+        stage_restored(&state, "guide.txt", b"hello world", expire_at).await;
+        let request = coldstore_proto::metadata::UpdateRestoreStatusRequest {
+            bucket: "docs".into(),
+            key: "guide.txt".into(),
+            status: common::RestoreStatus::RestoreCompleted as i32,
+            expire_at: Some(expire_at),
+        };
+        state
+            .metadata
+            .call(|mut client| {
+                let request = request.clone();
+                async move { client.update_restore_status(request).await }
+            })
+            .await
+            .unwrap();
+
+        let (object, data) = backend
+            .get_object("docs", "guide.txt", &[])
+            .await
+            .expect("read whole restored object");
+        assert_eq!(object.size, 11);
+        assert_eq!(data, b"hello world");
+
+        let ranges = [
+            common::ByteRange {
+                offset: 0,
+                length: 5,
+            },
+            common::ByteRange {
+                offset: 6,
+                length: 5,
+            },
+        ];
+        let (_, data) = backend
+            .get_object("docs", "guide.txt", &ranges)
+            .await
+            .expect("read restored ranges");
+        assert_eq!(data, b"helloworld");
+
+        let err = backend
+            .get_object(
+                "docs",
+                "guide.txt",
+                &[common::ByteRange {
+                    offset: 8,
+                    length: 10,
+                }],
+            )
+            .await
+            .expect_err("range past the end of the object");
+        assert_eq!(err.code(), tonic::Code::OutOfRange);
 
+        cache_shutdown.send(()).ok();
         shutdown_tx.send(()).ok();
     }
 
@@ -0,0 +1,240 @@
+//! 测试辅助：在进程内拉起 Metadata / Cache / Tape gRPC 服务
+
+use crate::SchedulerState;
+use coldstore_cache::service::CacheServiceImpl;
+use coldstore_common::client::MetadataClientPool;
+use coldstore_common::config::{
+    CacheBackendConfig, CacheConfig, MetadataConfig, SchedulerConfig, ScsiConfig, TapeConfig,
+    VirtualLibraryConfig,
+};
+use coldstore_common::membership::{register, WorkerMembership};
+use coldstore_common::tls::TlsConfig;
+use coldstore_metadata::service::MetadataServiceImpl;
+use coldstore_proto::cache::cache_service_client::CacheServiceClient;
+use coldstore_proto::cache::cache_service_server::CacheServiceServer;
+use coldstore_proto::common::{TapeInfo, TapeStatus};
+use coldstore_proto::metadata::metadata_service_server::MetadataServiceServer;
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_proto::tape::tape_service_server::TapeServiceServer;
+use coldstore_tape::service::TapeServiceImpl;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tokio::time::{sleep, Duration};
+use tonic::transport::{Channel, Server};
+
+fn free_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind test listener");
+    listener.local_addr().expect("listener addr")
+}
+
+async fn connect(addr: SocketAddr) -> Channel {
+    for _ in 0..20 {
+        if let Ok(channel) = Channel::from_shared(format!("http://{addr}"))
+            .expect("valid uri")
+            .connect()
+            .await
+        {
+            return channel;
+        }
+        sleep(Duration::from_millis(25)).await;
+    }
+    panic!("could not connect to test server at {addr}");
+}
+
+pub(crate) async fn spawn_metadata_server() -> (SocketAddr, oneshot::Sender<()>) {
+    let metadata = MetadataServiceImpl::new(&MetadataConfig::default())
+        .await
+        .expect("metadata service init");
+    let addr = free_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(MetadataServiceServer::new(metadata))
+            .serve_with_shutdown(addr, async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+            .expect("metadata server should run");
+    });
+    connect(addr).await;
+    (addr, shutdown_tx)
+}
+
+fn cache_config() -> CacheConfig {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time")
+        .as_nanos();
+    CacheConfig {
+        backend: CacheBackendConfig::Hdd {
+            path: format!("/tmp/coldstore-scheduler-cache-test-{unique}"),
+            max_size_gb: 1,
+        },
+        ..CacheConfig::default()
+    }
+}
+
+async fn serve_cache(cache: Arc<CacheServiceImpl>, addr: SocketAddr) -> oneshot::Sender<()> {
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(CacheServiceServer::from_arc(cache))
+            .serve_with_shutdown(addr, async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+            .expect("cache server should run");
+    });
+    shutdown_tx
+}
+
+pub(crate) async fn spawn_cache() -> (CacheServiceClient<Channel>, oneshot::Sender<()>) {
+    let cache = CacheServiceImpl::new(&cache_config())
+        .await
+        .expect("cache service init");
+    let addr = free_addr();
+    let shutdown_tx = serve_cache(Arc::new(cache), addr).await;
+    (CacheServiceClient::new(connect(addr).await), shutdown_tx)
+}
+
+fn tape_config(tapes: &[&str]) -> TapeConfig {
+    TapeConfig {
+        sdk_backend: "virtual".into(),
+        scsi: ScsiConfig {
+            devices: vec!["/dev/nst0".into()],
+            block_size: 512,
+            buffer_size_mb: 1,
+        },
+        virtual_library: Some(VirtualLibraryConfig {
+            tapes: tapes.iter().map(|tape| tape.to_string()).collect(),
+            capacity_bytes: 1 << 20,
+            import_export_slots: 2,
+            cleaning_tapes: Vec::new(),
+        }),
+        ..TapeConfig::default()
+    }
+}
+
+async fn serve_tape(tape: Arc<TapeServiceImpl>, addr: SocketAddr) -> oneshot::Sender<()> {
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(TapeServiceServer::from_arc(tape))
+            .serve_with_shutdown(addr, async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+            .expect("tape server should run");
+    });
+    shutdown_tx
+}
+
+/// 拉起单驱动 (`drive-0`) 的虚拟带库，`tapes` 为槽位中的空白磁带
+pub(crate) async fn spawn_tape(
+    tapes: &[&str],
+) -> (TapeServiceClient<Channel>, oneshot::Sender<()>) {
+    let tape = TapeServiceImpl::new(&tape_config(tapes)).expect("tape service init");
+    let addr = free_addr();
+    let shutdown_tx = serve_tape(Arc::new(tape), addr).await;
+    (TapeServiceClient::new(connect(addr).await), shutdown_tx)
+}
+
+/// 拉起虚拟带库并以 `node_id` 注册为 Tape Worker
+pub(crate) async fn spawn_tape_worker(
+    metadata: &MetadataClientPool,
+    node_id: u64,
+    tapes: &[&str],
+) -> (TapeServiceClient<Channel>, oneshot::Sender<()>) {
+    let addr = free_addr();
+    let mut config = tape_config(tapes);
+    config.listen = addr.to_string();
+    config.membership.node_id = node_id;
+    let tape = Arc::new(TapeServiceImpl::new(&config).expect("tape service init"));
+    let shutdown_tx = serve_tape(tape.clone(), addr).await;
+    let client = TapeServiceClient::new(connect(addr).await);
+    register(metadata, tape.registration().await)
+        .await
+        .expect("register tape worker");
+    (client, shutdown_tx)
+}
+
+/// 拉起 Cache Worker 并以 `node_id` 注册到 Metadata
+pub(crate) async fn spawn_cache_worker(
+    metadata: &MetadataClientPool,
+    node_id: u64,
+) -> oneshot::Sender<()> {
+    let addr = free_addr();
+    let mut config = cache_config();
+    config.listen = addr.to_string();
+    config.membership.node_id = node_id;
+    let cache = Arc::new(
+        CacheServiceImpl::new(&config)
+            .await
+            .expect("cache service init"),
+    );
+    let shutdown_tx = serve_cache(cache.clone(), addr).await;
+    connect(addr).await;
+    register(metadata, cache.registration().await)
+        .await
+        .expect("register cache worker");
+    shutdown_tx
+}
+
+/// 连接 `metadata_addr` 的 Scheduler 状态，尚未配对 Cache Worker
+pub(crate) fn scheduler_state(
+    metadata_addr: SocketAddr,
+    cache_worker_id: Option<u64>,
+) -> Arc<SchedulerState> {
+    scheduler_state_with_config(
+        metadata_addr,
+        SchedulerConfig {
+            cache_worker_id,
+            ..SchedulerConfig::default()
+        },
+    )
+}
+
+/// 同 [`scheduler_state`]，使用给定的配置
+pub(crate) fn scheduler_state_with_config(
+    metadata_addr: SocketAddr,
+    config: SchedulerConfig,
+) -> Arc<SchedulerState> {
+    Arc::new(SchedulerState {
+        metadata: Arc::new(
+            MetadataClientPool::new(
+                &[metadata_addr.to_string()],
+                &TlsConfig::default(),
+                Default::default(),
+            )
+            .expect("metadata pool"),
+        ),
+        cache: Default::default(),
+        tape: None,
+        config,
+        draining: AtomicBool::new(false),
+    })
+}
+
+/// 在 Metadata 中登记 [`spawn_tape`] 虚拟带库里的磁带，容量与带库一致
+pub(crate) async fn register_tapes(metadata: &MetadataClientPool, tapes: &[&str]) {
+    for id in tapes {
+        let tape = TapeInfo {
+            id: id.to_string(),
+            format: "LTO-9".into(),
+            status: TapeStatus::TapeOnline as i32,
+            capacity_bytes: 1 << 20,
+            remaining_bytes: 1 << 20,
+            ..Default::default()
+        };
+        metadata
+            .call(|mut client| {
+                let tape = tape.clone();
+                async move { client.put_tape(tape).await }
+            })
+            .await
+            .expect("register tape");
+    }
+}
@@ -0,0 +1,206 @@
+//! 异地库导出/导入
+//!
+//! 导出时先在 Metadata 中将磁带标记为 Offline 并记录异地位置，再由带库把磁带移到
+//! import/export 槽位供运维取走；带库移动失败时恢复为 Online。只能读离线磁带的取回
+//! 任务转为 WaitingForMedia，运维通过 `ListMediaRequests` 查看需要送回的条码。
+//!
+//! 磁带放回 import/export 槽位后，导入将其移回存储槽位并标记为 Online，等待这些
+//! 磁带的取回自动恢复为 Pending。
+
+use crate::SchedulerState;
+use coldstore_proto::common;
+use coldstore_proto::metadata::{GetTapeRequest, TapeLocationRequest};
+use coldstore_proto::tape::tape_service_client::TapeServiceClient;
+use coldstore_proto::tape::ExportTapeRequest;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Status};
+use tracing::{info, warn};
+
+async fn mark_tape(
+    state: &SchedulerState,
+    tape_id: &str,
+    location: Option<String>,
+    offline: bool,
+) -> Result<common::TapeInfo, Status> {
+    let request = TapeLocationRequest {
+        tape_id: tape_id.to_string(),
+        location,
+    };
+    state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move {
+                if offline {
+                    client.mark_tape_offline(request).await
+                } else {
+                    client.mark_tape_online(request).await
+                }
+            }
+        })
+        .await
+}
+
+/// 将磁带导出到 import/export 槽位，`location` 为磁带送往的异地位置
+pub async fn export_tape(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    tape_id: &str,
+    location: Option<String>,
+) -> Result<common::TapeInfo, Status> {
+    let request = GetTapeRequest {
+        tape_id: tape_id.to_string(),
+    };
+    let library_location = state
+        .metadata
+        .call(|mut client| {
+            let request = request.clone();
+            async move { client.get_tape(request).await }
+        })
+        .await?
+        .location;
+    let offline = mark_tape(state, tape_id, location, true).await?;
+    let exported = tape
+        .export_tape(Request::new(ExportTapeRequest {
+            tape_id: tape_id.to_string(),
+        }))
+        .await;
+    match exported {
+        Ok(slot) => {
+            info!(
+                "磁带 {tape_id} 已导出到槽位 {}，送往 {}",
+                slot.into_inner().slot_id,
+                offline.location.as_deref().unwrap_or("unknown")
+            );
+            Ok(offline)
+        }
+        Err(status) => {
+            if let Err(revert) = mark_tape(state, tape_id, library_location, false).await {
+                warn!(
+                    "导出磁带 {tape_id} 失败后恢复 Online 失败: {}",
+                    revert.message()
+                );
+            }
+            Err(status)
+        }
+    }
+}
+
+/// 导入 import/export 槽位中的全部磁带，`location` 为磁带回到的带库位置
+///
+/// 未在 Metadata 中登记的磁带只做物理导入，需另行 `ImportTape` 登记。
+pub async fn import_tapes(
+    state: &SchedulerState,
+    tape: &mut TapeServiceClient<Channel>,
+    location: Option<String>,
+) -> Result<Vec<common::TapeInfo>, Status> {
+    let tape_ids = tape
+        .import_tapes(Request::new(()))
+        .await?
+        .into_inner()
+        .tape_ids;
+    let mut imported = Vec::with_capacity(tape_ids.len());
+    for tape_id in tape_ids {
+        match mark_tape(state, &tape_id, location.clone(), false).await {
+            Ok(info) => {
+                info!("磁带 {tape_id} 已导入带库");
+                imported.push(info);
+            }
+            Err(status) if status.code() == Code::NotFound => {
+                warn!("导入的磁带 {tape_id} 未在 Metadata 中登记");
+            }
+            Err(status) => return Err(status),
+        }
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scheduler_state, spawn_metadata_server, spawn_tape};
+    use coldstore_proto::metadata::metadata_service_client::MetadataServiceClient;
+    use coldstore_proto::metadata::GetRecallTaskRequest;
+
+    async fn recall_status(metadata: &mut MetadataServiceClient<Channel>) -> i32 {
+        metadata
+            .get_recall_task(Request::new(GetRecallTaskRequest {
+                id: "recall-1".into(),
+            }))
+            .await
+            .expect("get recall task")
+            .into_inner()
+            .status
+    }
+
+    #[tokio::test]
+    async fn recalls_for_exported_tapes_wait_until_the_tape_is_imported() {
+        let (addr, metadata_shutdown) = spawn_metadata_server().await;
+        let state = scheduler_state(addr, None);
+        let (mut tape, tape_shutdown) = spawn_tape(&["TAPE01"]).await;
+        let mut metadata = state.metadata.client().await.expect("metadata leader");
+        metadata
+            .put_tape(Request::new(common::TapeInfo {
+                id: "TAPE01".into(),
+                barcode: Some("TAPE01L9".into()),
+                format: "LTO-9".into(),
+                status: common::TapeStatus::TapeOnline as i32,
+                capacity_bytes: 1 << 20,
+                remaining_bytes: 1 << 20,
+                location: Some("library-1".into()),
+                ..Default::default()
+            }))
+            .await
+            .expect("register tape");
+        metadata
+            .put_recall_task(Request::new(common::RecallTask {
+                id: "recall-1".into(),
+                bucket: "docs".into(),
+                key: "guide.txt".into(),
+                archive_id: "bundle-1".into(),
+                tape_id: "TAPE01".into(),
+                tape_set: vec!["TAPE01".into()],
+                status: common::RestoreStatus::RestorePending as i32,
+                ..Default::default()
+            }))
+            .await
+            .expect("put recall task");
+
+        let exported = export_tape(&state, &mut tape, "TAPE01", Some("vault-b".into()))
+            .await
+            .expect("export tape");
+        assert_eq!(exported.status, common::TapeStatus::TapeOffline as i32);
+        let slots = tape.inventory(Request::new(())).await.unwrap().into_inner();
+        assert!(slots
+            .slots
+            .iter()
+            .any(|slot| slot.is_import_export && slot.tape_id.as_deref() == Some("TAPE01")));
+
+        assert_eq!(
+            recall_status(&mut metadata).await,
+            common::RestoreStatus::RestoreWaitingForMedia as i32
+        );
+        let requests = metadata
+            .list_media_requests(Request::new(()))
+            .await
+            .expect("list media requests")
+            .into_inner()
+            .requests;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].barcode.as_deref(), Some("TAPE01L9"));
+        assert_eq!(requests[0].location.as_deref(), Some("vault-b"));
+
+        let imported = import_tapes(&state, &mut tape, Some("library-1".into()))
+            .await
+            .expect("import tapes");
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].status, common::TapeStatus::TapeOnline as i32);
+        assert_eq!(
+            recall_status(&mut metadata).await,
+            common::RestoreStatus::RestorePending as i32
+        );
+
+        let _ = tape_shutdown.send(());
+        let _ = metadata_shutdown.send(());
+    }
+}
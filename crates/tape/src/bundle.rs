@@ -0,0 +1,606 @@
+//! 磁带归档包 (bundle) 的自描述格式
+//!
+//! 每个归档包在磁带上占据两个 filemark 之间的一段 "文件"，元数据丢失时
+//! 仅凭磁带内容即可恢复对象位置。布局如下 (所有整数均为小端序，所有
+//! 区段起点按 `block_size` 对齐):
+//!
+//! ```text
+//! +-----------------------------+  block 0
+//! | BundleHeader                |  magic, version, bundle_id, object_count,
+//! |                             |  block_size, created_at
+//! +-----------------------------+
+//! | ObjectHeader #0             |  magic, bucket, key, version_id, size,
+//! +-----------------------------+  checksum, content_type, attributes (v2)
+//! | payload #0 (padded)         |
+//! +-----------------------------+
+//! | ...                         |
+//! +-----------------------------+
+//! | Index                       |  magic, 每个对象的 ObjectHeader + 偏移
+//! | (padding)                   |
+//! | Footer (最后 16 字节)        |  index_offset: u64, magic
+//! +-----------------------------+
+//! ```
+//!
+//! 顺序读取时可逐个解析对象头；随机访问时先从末尾 Footer 定位 Index。
+//!
+//! 版本 2 在对象头末尾追加对象属性 (用户元数据与 HTTP 系统头，见
+//! `coldstore_common::attributes`)；版本 1 的归档包仍可读取，属性为空。
+
+use anyhow::Result;
+use coldstore_common::checksum::sha256_hex;
+
+/// 当前写入的格式版本
+pub const BUNDLE_FORMAT_VERSION: u16 = 2;
+/// 对象头开始携带属性的格式版本
+const ATTRIBUTES_VERSION: u16 = 2;
+
+const BUNDLE_MAGIC: &[u8; 8] = b"CSBUNDLE";
+const OBJECT_MAGIC: &[u8; 8] = b"CSOBJECT";
+const INDEX_MAGIC: &[u8; 8] = b"CSBINDEX";
+const FOOTER_MAGIC: &[u8; 8] = b"CSBNDEND";
+const FOOTER_LEN: usize = 16;
+/// 块大小下限 (与 LTO 驱动支持的最小定长块一致)
+const MIN_BLOCK_SIZE: u32 = 512;
+const MAX_FIELD_BYTES: usize = 64 * 1024;
+/// 索引项的最小编码长度 (v1、字段均为空)，用于按剩余字节数限制预分配
+const MIN_INDEX_ENTRY_LEN: usize = 38;
+
+/// 归档包头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleHeader {
+    pub version: u16,
+    pub bundle_id: String,
+    pub object_count: u32,
+    pub block_size: u32,
+    /// 写入时间 (Unix 秒 + 纳秒)，用于恢复时的冲突裁决
+    pub created_at_secs: i64,
+    pub created_at_nanos: u32,
+}
+
+/// 每个对象前的自描述头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectHeader {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+    pub size: u64,
+    /// 对象数据 SHA-256 hex
+    pub checksum: String,
+    pub content_type: Option<String>,
+    /// 对象属性的 (HTTP 头名称, 值)，按名称排序
+    pub attributes: Vec<(String, String)>,
+}
+
+/// 尾部索引中的一项，偏移均为相对归档包起点的字节数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub header: ObjectHeader,
+    pub header_offset: u64,
+    pub payload_offset: u64,
+}
+
+/// 编码结果：完整的归档包字节 (长度为 block_size 整数倍) 与索引
+#[derive(Debug, Clone)]
+pub struct EncodedBundle {
+    pub bytes: Vec<u8>,
+    pub index: Vec<IndexEntry>,
+}
+
+/// 解码结果
+#[derive(Debug, Clone)]
+pub struct DecodedBundle {
+    pub header: BundleHeader,
+    pub objects: Vec<(IndexEntry, Vec<u8>)>,
+}
+
+/// 将对象编码为归档包
+///
+/// `header.object_count` 与 `header.version` 以实际写入为准，调用方无需填写。
+/// 对象数据在写入前按 `ObjectHeader::checksum` 校验。
+pub fn encode_bundle(
+    header: &BundleHeader,
+    objects: &[(ObjectHeader, Vec<u8>)],
+) -> Result<EncodedBundle> {
+    encode_bundle_version(header, objects, BUNDLE_FORMAT_VERSION)
+}
+
+fn encode_bundle_version(
+    header: &BundleHeader,
+    objects: &[(ObjectHeader, Vec<u8>)],
+    version: u16,
+) -> Result<EncodedBundle> {
+    anyhow::ensure!(
+        header.block_size >= MIN_BLOCK_SIZE,
+        "bundle block size must be at least {MIN_BLOCK_SIZE} bytes"
+    );
+    let block_size = header.block_size as usize;
+
+    let mut out = Vec::new();
+    let mut header_bytes = Vec::new();
+    header_bytes.extend_from_slice(BUNDLE_MAGIC);
+    put_u16(&mut header_bytes, version);
+    put_str(&mut header_bytes, &header.bundle_id)?;
+    put_u32(&mut header_bytes, objects.len() as u32);
+    put_u32(&mut header_bytes, header.block_size);
+    put_u64(&mut header_bytes, header.created_at_secs as u64);
+    put_u32(&mut header_bytes, header.created_at_nanos);
+    put_region(&mut out, &header_bytes, block_size);
+
+    let mut index = Vec::with_capacity(objects.len());
+    for (object, data) in objects {
+        anyhow::ensure!(
+            object.size == data.len() as u64,
+            "object {}/{} size does not match payload",
+            object.bucket,
+            object.key
+        );
+        let actual = sha256_hex(data);
+        anyhow::ensure!(
+            actual.eq_ignore_ascii_case(&object.checksum),
+            "object {}/{} checksum mismatch: expected {}, actual {}",
+            object.bucket,
+            object.key,
+            object.checksum,
+            actual
+        );
+
+        let header_offset = out.len() as u64;
+        let mut object_bytes = Vec::new();
+        object_bytes.extend_from_slice(OBJECT_MAGIC);
+        put_object_header(&mut object_bytes, object, version)?;
+        put_region(&mut out, &object_bytes, block_size);
+
+        let payload_offset = out.len() as u64;
+        put_region(&mut out, data, block_size);
+        index.push(IndexEntry {
+            header: object.clone(),
+            header_offset,
+            payload_offset,
+        });
+    }
+
+    let index_offset = out.len() as u64;
+    let mut index_bytes = Vec::new();
+    index_bytes.extend_from_slice(INDEX_MAGIC);
+    put_u32(&mut index_bytes, index.len() as u32);
+    for entry in &index {
+        put_object_header(&mut index_bytes, &entry.header, version)?;
+        put_u64(&mut index_bytes, entry.header_offset);
+        put_u64(&mut index_bytes, entry.payload_offset);
+    }
+    let mut tail_len = index_bytes.len() + FOOTER_LEN;
+    tail_len += padding(tail_len, block_size);
+    index_bytes.resize(tail_len - FOOTER_LEN, 0);
+    put_u64(&mut index_bytes, index_offset);
+    index_bytes.extend_from_slice(FOOTER_MAGIC);
+    out.extend_from_slice(&index_bytes);
+
+    Ok(EncodedBundle { bytes: out, index })
+}
+
+/// 顺序解析整个归档包，并与尾部索引交叉校验
+pub fn decode_bundle(bytes: &[u8]) -> Result<DecodedBundle> {
+    let (header, header_len) = parse_header(bytes)?;
+    let block_size = header.block_size as usize;
+    let mut offset = header_len + padding(header_len, block_size);
+    // object_count 直接来自磁带，损坏时可能极大；每个对象头至少占一个块
+    let mut objects =
+        Vec::with_capacity((header.object_count as usize).min(bytes.len() / block_size));
+
+    for _ in 0..header.object_count {
+        let header_offset = offset;
+        let mut cursor = bytes
+            .get(offset..)
+            .ok_or_else(|| anyhow::anyhow!("truncated bundle object header"))?;
+        expect_magic(&mut cursor, OBJECT_MAGIC, "object header")?;
+        let object = read_object_header(&mut cursor, header.version)?;
+        let header_len = bytes.len() - offset - cursor.len();
+        offset += header_len + padding(header_len, block_size);
+
+        let payload_offset = offset;
+        let size = usize::try_from(object.size)?;
+        let end = payload_offset
+            .checked_add(size)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| anyhow::anyhow!("truncated bundle object payload"))?;
+        let data = bytes[payload_offset..end].to_vec();
+        let actual = sha256_hex(&data);
+        anyhow::ensure!(
+            actual.eq_ignore_ascii_case(&object.checksum),
+            "bundle object {}/{} checksum mismatch: expected {}, actual {}",
+            object.bucket,
+            object.key,
+            object.checksum,
+            actual
+        );
+        offset = end + padding(size, block_size);
+
+        objects.push((
+            IndexEntry {
+                header: object,
+                header_offset: header_offset as u64,
+                payload_offset: payload_offset as u64,
+            },
+            data,
+        ));
+    }
+
+    let (_, index) = read_index(bytes)?;
+    anyhow::ensure!(
+        index.len() == objects.len()
+            && index
+                .iter()
+                .zip(objects.iter())
+                .all(|(indexed, (walked, _))| indexed == walked),
+        "bundle index does not match object headers"
+    );
+
+    Ok(DecodedBundle { header, objects })
+}
+
+/// 仅解析包头与尾部索引，不读取对象数据
+pub fn read_index(bytes: &[u8]) -> Result<(BundleHeader, Vec<IndexEntry>)> {
+    let header = decode_header(bytes)?;
+    anyhow::ensure!(
+        bytes.len() >= FOOTER_LEN && bytes.len().is_multiple_of(header.block_size as usize),
+        "bundle length is not block aligned"
+    );
+    let mut footer = &bytes[bytes.len() - FOOTER_LEN..];
+    let index_offset = usize::try_from(read_u64(&mut footer)?)?;
+    anyhow::ensure!(footer == FOOTER_MAGIC, "invalid bundle footer magic");
+    anyhow::ensure!(
+        index_offset < bytes.len() - FOOTER_LEN,
+        "bundle index offset out of range"
+    );
+
+    let mut cursor = &bytes[index_offset..bytes.len() - FOOTER_LEN];
+    expect_magic(&mut cursor, INDEX_MAGIC, "index")?;
+    let count = read_u32(&mut cursor)?;
+    anyhow::ensure!(
+        count == header.object_count,
+        "bundle index object count does not match header"
+    );
+    let mut index = Vec::with_capacity((count as usize).min(cursor.len() / MIN_INDEX_ENTRY_LEN));
+    for _ in 0..count {
+        let object = read_object_header(&mut cursor, header.version)?;
+        let header_offset = read_u64(&mut cursor)?;
+        let payload_offset = read_u64(&mut cursor)?;
+        index.push(IndexEntry {
+            header: object,
+            header_offset,
+            payload_offset,
+        });
+    }
+    Ok((header, index))
+}
+
+/// 解析归档包头 (位于归档包起点)
+pub fn decode_header(bytes: &[u8]) -> Result<BundleHeader> {
+    parse_header(bytes).map(|(header, _)| header)
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(BundleHeader, usize)> {
+    let mut cursor = bytes;
+    expect_magic(&mut cursor, BUNDLE_MAGIC, "bundle header")?;
+    let version = read_u16(&mut cursor)?;
+    anyhow::ensure!(
+        (1..=BUNDLE_FORMAT_VERSION).contains(&version),
+        "unsupported bundle format version {version}"
+    );
+    let bundle_id = read_str(&mut cursor)?;
+    let object_count = read_u32(&mut cursor)?;
+    let block_size = read_u32(&mut cursor)?;
+    anyhow::ensure!(
+        block_size >= MIN_BLOCK_SIZE,
+        "invalid bundle block size {block_size}"
+    );
+    let created_at_secs = read_u64(&mut cursor)? as i64;
+    let created_at_nanos = read_u32(&mut cursor)?;
+    let header = BundleHeader {
+        version,
+        bundle_id,
+        object_count,
+        block_size,
+        created_at_secs,
+        created_at_nanos,
+    };
+    Ok((header, bytes.len() - cursor.len()))
+}
+
+fn padding(len: usize, block_size: usize) -> usize {
+    (block_size - len % block_size) % block_size
+}
+
+fn put_region(out: &mut Vec<u8>, bytes: &[u8], block_size: usize) {
+    out.extend_from_slice(bytes);
+    out.resize(out.len() + padding(bytes.len(), block_size), 0);
+}
+
+fn put_object_header(out: &mut Vec<u8>, object: &ObjectHeader, version: u16) -> Result<()> {
+    put_str(out, &object.bucket)?;
+    put_str(out, &object.key)?;
+    put_opt_str(out, object.version_id.as_deref())?;
+    put_u64(out, object.size);
+    put_str(out, &object.checksum)?;
+    put_opt_str(out, object.content_type.as_deref())?;
+    if version < ATTRIBUTES_VERSION {
+        anyhow::ensure!(
+            object.attributes.is_empty(),
+            "bundle format version {version} cannot store object attributes"
+        );
+        return Ok(());
+    }
+    put_u32(out, object.attributes.len() as u32);
+    for (name, value) in &object.attributes {
+        put_str(out, name)?;
+        put_str(out, value)?;
+    }
+    Ok(())
+}
+
+fn read_object_header(cursor: &mut &[u8], version: u16) -> Result<ObjectHeader> {
+    let mut object = ObjectHeader {
+        bucket: read_str(cursor)?,
+        key: read_str(cursor)?,
+        version_id: read_opt_str(cursor)?,
+        size: read_u64(cursor)?,
+        checksum: read_str(cursor)?,
+        content_type: read_opt_str(cursor)?,
+        attributes: Vec::new(),
+    };
+    if version >= ATTRIBUTES_VERSION {
+        let count = read_u32(cursor)?;
+        for _ in 0..count {
+            object
+                .attributes
+                .push((read_str(cursor)?, read_str(cursor)?));
+        }
+    }
+    Ok(object)
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, value: &str) -> Result<()> {
+    anyhow::ensure!(
+        value.len() <= MAX_FIELD_BYTES,
+        "bundle header field is too long"
+    );
+    put_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn put_opt_str(out: &mut Vec<u8>, value: Option<&str>) -> Result<()> {
+    match value {
+        Some(value) => {
+            out.push(1);
+            put_str(out, value)
+        }
+        None => {
+            out.push(0);
+            Ok(())
+        }
+    }
+}
+
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    anyhow::ensure!(cursor.len() >= len, "truncated bundle header");
+    let (head, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(head)
+}
+
+fn expect_magic(cursor: &mut &[u8], magic: &[u8; 8], what: &str) -> Result<()> {
+    anyhow::ensure!(take(cursor, 8)? == magic, "invalid {what} magic");
+    Ok(())
+}
+
+fn read_u16(cursor: &mut &[u8]) -> Result<u16> {
+    Ok(u16::from_le_bytes(take(cursor, 2)?.try_into()?))
+}
+
+fn read_u32(cursor: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(take(cursor, 4)?.try_into()?))
+}
+
+fn read_u64(cursor: &mut &[u8]) -> Result<u64> {
+    Ok(u64::from_le_bytes(take(cursor, 8)?.try_into()?))
+}
+
+fn read_str(cursor: &mut &[u8]) -> Result<String> {
+    let len = read_u32(cursor)? as usize;
+    anyhow::ensure!(len <= MAX_FIELD_BYTES, "bundle header field is too long");
+    Ok(String::from_utf8(take(cursor, len)?.to_vec())?)
+}
+
+fn read_opt_str(cursor: &mut &[u8]) -> Result<Option<String>> {
+    match take(cursor, 1)?[0] {
+        0 => Ok(None),
+        1 => read_str(cursor).map(Some),
+        flag => anyhow::bail!("invalid optional field flag {flag}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn header(block_size: u32) -> BundleHeader {
+        BundleHeader {
+            version: BUNDLE_FORMAT_VERSION,
+            bundle_id: "bundle-1".into(),
+            object_count: 0,
+            block_size,
+            created_at_secs: 1_700_000_000,
+            created_at_nanos: 7,
+        }
+    }
+
+    fn object(key: &str, data: &[u8]) -> (ObjectHeader, Vec<u8>) {
+        (
+            ObjectHeader {
+                bucket: "docs".into(),
+                key: key.into(),
+                version_id: None,
+                size: data.len() as u64,
+                checksum: sha256_hex(data),
+                content_type: Some("text/plain".into()),
+                attributes: vec![("x-amz-meta-owner".into(), "ops".into())],
+            },
+            data.to_vec(),
+        )
+    }
+
+    #[test]
+    fn payloads_are_block_aligned_and_indexed() {
+        let objects = vec![object("a.txt", b"hello"), object("b.txt", &[7u8; 1500])];
+        let encoded = encode_bundle(&header(512), &objects).expect("encode");
+        assert_eq!(encoded.bytes.len() % 512, 0);
+        for entry in &encoded.index {
+            assert_eq!(entry.header_offset % 512, 0);
+            assert_eq!(entry.payload_offset % 512, 0);
+        }
+        assert_eq!(
+            &encoded.bytes[encoded.index[1].payload_offset as usize..][..1500],
+            &[7u8; 1500][..]
+        );
+
+        let (decoded_header, index) = read_index(&encoded.bytes).expect("read index");
+        assert_eq!(decoded_header.object_count, 2);
+        assert_eq!(index, encoded.index);
+    }
+
+    #[test]
+    fn encode_rejects_payload_that_does_not_match_checksum() {
+        let (mut object_header, data) = object("a.txt", b"hello");
+        object_header.checksum = sha256_hex(b"other");
+        let err = encode_bundle(&header(512), &[(object_header, data)]).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn decode_detects_corrupted_payload() {
+        let encoded = encode_bundle(&header(512), &[object("a.txt", b"hello")]).expect("encode");
+        let mut bytes = encoded.bytes;
+        bytes[encoded.index[0].payload_offset as usize] ^= 0xff;
+        let err = decode_bundle(&bytes).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn version_1_bundles_decode_without_attributes() {
+        let (mut object_header, data) = object("a.txt", b"hello");
+        let err = encode_bundle_version(&header(512), &[(object_header.clone(), data.clone())], 1)
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot store object attributes"));
+
+        object_header.attributes.clear();
+        let encoded =
+            encode_bundle_version(&header(512), &[(object_header.clone(), data)], 1).expect("v1");
+        let decoded = decode_bundle(&encoded.bytes).expect("decode v1");
+        assert_eq!(decoded.header.version, 1);
+        assert_eq!(decoded.objects[0].0.header, object_header);
+        assert_eq!(decoded.objects[0].1, b"hello");
+    }
+
+    #[test]
+    fn decode_rejects_unknown_version() {
+        let encoded = encode_bundle(&header(512), &[]).expect("encode");
+        let mut bytes = encoded.bytes;
+        bytes[8..10].copy_from_slice(&(BUNDLE_FORMAT_VERSION + 1).to_le_bytes());
+        let err = decode_bundle(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported bundle format version"));
+    }
+
+    fn arb_object() -> impl Strategy<Value = (ObjectHeader, Vec<u8>)> {
+        (
+            "[a-z0-9-]{3,16}",
+            "[a-zA-Z0-9/._ -]{1,64}",
+            proptest::option::of("[a-z0-9]{1,12}"),
+            proptest::option::of("[a-z]+/[a-z0-9.+-]+"),
+            proptest::collection::btree_map("x-amz-meta-[a-z0-9-]{1,16}", "[ -~]{0,32}", 0..4),
+            proptest::collection::vec(any::<u8>(), 0..2048),
+        )
+            .prop_map(
+                |(bucket, key, version_id, content_type, attributes, data)| {
+                    (
+                        ObjectHeader {
+                            bucket,
+                            key,
+                            version_id,
+                            size: data.len() as u64,
+                            checksum: sha256_hex(&data),
+                            content_type,
+                            attributes: attributes.into_iter().collect(),
+                        },
+                        data,
+                    )
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn bundle_round_trips(
+            bundle_id in "[a-z0-9-]{1,36}",
+            block_shift in 0u32..4,
+            created_at_secs in 0i64..4_000_000_000,
+            objects in proptest::collection::vec(arb_object(), 0..8),
+        ) {
+            let header = BundleHeader {
+                version: BUNDLE_FORMAT_VERSION,
+                bundle_id,
+                object_count: objects.len() as u32,
+                block_size: MIN_BLOCK_SIZE << block_shift,
+                created_at_secs,
+                created_at_nanos: 0,
+            };
+            let encoded = encode_bundle(&header, &objects).unwrap();
+            prop_assert_eq!(encoded.bytes.len() % header.block_size as usize, 0);
+
+            let decoded = decode_bundle(&encoded.bytes).unwrap();
+            prop_assert_eq!(&decoded.header, &header);
+            prop_assert_eq!(decoded.objects.len(), objects.len());
+            for ((entry, data), (expected_header, expected_data)) in
+                decoded.objects.iter().zip(objects.iter())
+            {
+                prop_assert_eq!(&entry.header, expected_header);
+                prop_assert_eq!(data, expected_data);
+            }
+            let indexed: Vec<IndexEntry> =
+                decoded.objects.into_iter().map(|(entry, _)| entry).collect();
+            prop_assert_eq!(indexed, encoded.index);
+        }
+
+        #[test]
+        fn corrupted_object_count_is_rejected_without_preallocating(
+            object_count in (1u32 << 20)..=u32::MAX,
+            objects in proptest::collection::vec(arb_object(), 0..3),
+        ) {
+            let header = header(MIN_BLOCK_SIZE);
+            let mut bytes = encode_bundle(&header, &objects).unwrap().bytes;
+            let count_offset = BUNDLE_MAGIC.len() + 2 + 4 + header.bundle_id.len();
+            bytes[count_offset..count_offset + 4].copy_from_slice(&object_count.to_le_bytes());
+            let mut footer = &bytes[bytes.len() - FOOTER_LEN..];
+            let index_offset = read_u64(&mut footer).unwrap() as usize + INDEX_MAGIC.len();
+            bytes[index_offset..index_offset + 4].copy_from_slice(&object_count.to_le_bytes());
+
+            prop_assert!(decode_bundle(&bytes).is_err());
+            prop_assert!(read_index(&bytes).is_err());
+        }
+    }
+}
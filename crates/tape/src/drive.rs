@@ -0,0 +1,753 @@
+//! 内存虚拟带库
+//!
+//! 在没有 SCSI 设备的环境 (开发、测试) 中模拟驱动与磁带：每盘磁带是一串
+//! 以 filemark 分隔的 "文件"，写入只能追加到数据末尾 (EOD)。
+//!
+//! 逻辑块地址与真实 LTO 一致地把 filemark 计为一个位置：第 k 个文件的
+//! 起始块 = Σ(j<k) (文件 j 占用的块数 + 1)。
+//!
+//! 驱动健康由 [`FakeTapeAlertProvider`] 模拟，见 [`crate::health`]。
+
+use crate::health::{
+    has_critical_drive_alert, now_timestamp, record_log_page, tape_alert, AlertScope,
+    FakeTapeAlertProvider, TapeAlertProvider,
+};
+use coldstore_common::config::{TapeConfig, VirtualLibraryConfig};
+use coldstore_proto::common;
+use coldstore_proto::tape::{SlotInfo, TapeMediaStatus};
+use std::collections::BTreeMap;
+use tonic::Status;
+use tracing::{info, warn};
+
+/// 一盘虚拟磁带
+#[derive(Debug, Clone)]
+pub struct VirtualTape {
+    pub id: String,
+    pub capacity_bytes: u64,
+    files: Vec<Vec<u8>>,
+}
+
+impl VirtualTape {
+    pub fn new(id: impl Into<String>, capacity_bytes: u64) -> Self {
+        Self {
+            id: id.into(),
+            capacity_bytes,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn file_count(&self) -> u32 {
+        self.files.len() as u32
+    }
+
+    fn blocks(len: usize, block_size: u64) -> u64 {
+        (len as u64).div_ceil(block_size)
+    }
+
+    /// 第 `filemark` 个文件的起始逻辑块地址
+    fn start_block(&self, filemark: usize, block_size: u64) -> u64 {
+        self.files[..filemark]
+            .iter()
+            .map(|file| Self::blocks(file.len(), block_size) + 1)
+            .sum()
+    }
+
+    fn used_bytes(&self, block_size: u64) -> u64 {
+        self.start_block(self.files.len(), block_size) * block_size
+    }
+}
+
+#[derive(Debug)]
+struct VirtualDrive {
+    device_path: String,
+    tape: Option<VirtualTape>,
+    /// 当前所在文件序号 (即已越过的 filemark 数)
+    filemark: u32,
+    health: common::DriveHealth,
+    /// 硬错误达到阈值后停止服务，`reset_drive` 后恢复
+    offline: bool,
+    /// 已报告过没有可用清洁带，清洁成功前不再重复告警
+    cleaning_unavailable: bool,
+    /// 已由 `acquire_drive` 分配给某个作业，`release_drive` 前不再分配
+    acquired: bool,
+}
+
+/// 内存虚拟带库：若干驱动 + 存储槽位
+#[derive(Debug)]
+pub struct VirtualLibrary {
+    block_size: u32,
+    drives: BTreeMap<String, VirtualDrive>,
+    slots: BTreeMap<String, VirtualTape>,
+    /// import/export 槽位；移出的磁带留在槽位中直到重新导入
+    import_export: Vec<Option<VirtualTape>>,
+    /// 清洁带条码 -> 剩余可用次数；清洁带不能作为数据磁带装入
+    cleaning: BTreeMap<String, u32>,
+    alerts: FakeTapeAlertProvider,
+    max_hard_errors: u32,
+    /// 尚未上报 Metadata 的介质错误
+    tape_errors: Vec<common::DriveErrorEvent>,
+}
+
+impl VirtualLibrary {
+    /// 按 `scsi.devices` 创建驱动 (`drive-0`, `drive-1`, ...)，空白磁带放入槽位
+    pub fn new(config: &TapeConfig, library: &VirtualLibraryConfig) -> Self {
+        let drives = config
+            .scsi
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| {
+                (
+                    format!("drive-{index}"),
+                    VirtualDrive {
+                        device_path: device.clone(),
+                        tape: None,
+                        filemark: 0,
+                        health: common::DriveHealth::default(),
+                        offline: false,
+                        cleaning_unavailable: false,
+                        acquired: false,
+                    },
+                )
+            })
+            .collect();
+        let slots = library
+            .tapes
+            .iter()
+            .map(|id| (id.clone(), VirtualTape::new(id, library.capacity_bytes)))
+            .collect();
+        Self {
+            block_size: config.scsi.block_size,
+            drives,
+            slots,
+            import_export: (0..library.import_export_slots).map(|_| None).collect(),
+            cleaning: library
+                .cleaning_tapes
+                .iter()
+                .map(|id| (id.clone(), config.drive_health.cleaning_cartridge_uses))
+                .collect(),
+            alerts: FakeTapeAlertProvider::default(),
+            max_hard_errors: config.drive_health.max_hard_errors,
+            tape_errors: Vec::new(),
+        }
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    pub fn list_drives(&self) -> Vec<common::DriveEndpoint> {
+        self.drives
+            .iter()
+            .map(|(id, drive)| drive_endpoint(id, drive))
+            .collect()
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn drive_status(&self, drive_id: &str) -> Result<common::DriveEndpoint, Status> {
+        let drive = self
+            .drives
+            .get(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        Ok(drive_endpoint(drive_id, drive))
+    }
+
+    /// 为作业独占一个驱动：依次尝试 `preferred`、已装入 `required_tape` 的驱动、
+    /// 空驱动和留有磁带的驱动；没有可用驱动时返回 `ResourceExhausted`
+    #[allow(clippy::result_large_err)]
+    pub fn acquire(
+        &mut self,
+        preferred: Option<&str>,
+        required_tape: Option<&str>,
+    ) -> Result<common::DriveEndpoint, Status> {
+        if let Some(tape_id) = required_tape {
+            let holder = self.drives.iter().find(|(_, drive)| {
+                drive.acquired && drive.tape.as_ref().is_some_and(|tape| tape.id == tape_id)
+            });
+            if let Some((drive_id, _)) = holder {
+                return Err(Status::unavailable(format!(
+                    "tape {tape_id} is in use in drive {drive_id}"
+                )));
+            }
+        }
+        let available = |drive: &VirtualDrive| {
+            !drive.acquired && !drive.offline && !has_critical_drive_alert(&drive.health)
+        };
+        let drive_id = match preferred.filter(|id| self.drives.get(*id).is_some_and(available)) {
+            Some(id) => id.to_string(),
+            None => self
+                .drives
+                .iter()
+                .filter(|(_, drive)| available(drive))
+                .min_by_key(|(_, drive)| {
+                    let loaded = drive.tape.as_ref().map(|tape| tape.id.as_str());
+                    let holds_required = required_tape.is_some() && loaded == required_tape;
+                    (!holds_required, loaded.is_some())
+                })
+                .map(|(id, _)| id.clone())
+                .ok_or_else(|| Status::resource_exhausted("no tape drive available"))?,
+        };
+        let drive = self
+            .drives
+            .get_mut(&drive_id)
+            .ok_or_else(|| drive_not_found(&drive_id))?;
+        drive.acquired = true;
+        Ok(drive_endpoint(&drive_id, drive))
+    }
+
+    /// 已由 `acquire` 分配给作业、尚未归还的驱动
+    pub fn acquired_drives(&self) -> Vec<String> {
+        self.drives
+            .iter()
+            .filter(|(_, drive)| drive.acquired)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// 归还 `acquire` 分配的驱动；驱动中的磁带保留，由作业自行卸载
+    #[allow(clippy::result_large_err)]
+    pub fn release(&mut self, drive_id: &str) -> Result<(), Status> {
+        let drive = self
+            .drives
+            .get_mut(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        drive.acquired = false;
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn load(&mut self, tape_id: &str, drive_id: &str) -> Result<(), Status> {
+        let drive = self
+            .drives
+            .get_mut(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        if drive.offline {
+            return Err(Status::failed_precondition(format!(
+                "drive {drive_id} is offline after repeated hard errors"
+            )));
+        }
+        if let Some(loaded) = &drive.tape {
+            if loaded.id == tape_id {
+                return Ok(());
+            }
+            return Err(Status::failed_precondition(format!(
+                "drive {drive_id} already holds tape {}",
+                loaded.id
+            )));
+        }
+        let tape = self
+            .slots
+            .remove(tape_id)
+            .ok_or_else(|| Status::not_found(format!("tape not in library: {tape_id}")))?;
+        drive.tape = Some(tape);
+        drive.filemark = 0;
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn unload(&mut self, drive_id: &str) -> Result<(), Status> {
+        let drive = self
+            .drives
+            .get_mut(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        if let Some(tape) = drive.tape.take() {
+            drive.filemark = 0;
+            self.slots.insert(tape.id.clone(), tape);
+        }
+        if drive.health.cleaning_requested {
+            self.clean(drive_id);
+        }
+        Ok(())
+    }
+
+    /// 虚拟驱动的 TapeAlert 来源，用于注入故障
+    pub fn alerts_mut(&mut self) -> &mut FakeTapeAlertProvider {
+        &mut self.alerts
+    }
+
+    /// 读取各驱动日志页并更新健康状态
+    ///
+    /// 硬错误达到阈值的驱动转为 Offline；请求清洁的空闲驱动立即装入清洁带，
+    /// 装有磁带的驱动在卸带时清洁。
+    pub fn poll_health(&mut self) {
+        let now = now_timestamp();
+        let mut needs_cleaning = Vec::new();
+        for (drive_id, drive) in self.drives.iter_mut() {
+            let page = match self.alerts.read_log_pages(drive_id) {
+                Ok(page) => page,
+                Err(status) => {
+                    warn!("读取驱动 {drive_id} 日志页失败: {}", status.message());
+                    continue;
+                }
+            };
+            let tape_id = drive.tape.as_ref().map(|tape| tape.id.clone());
+            let media_errors =
+                record_log_page(&mut drive.health, drive_id, tape_id.as_deref(), &page, now);
+            self.tape_errors.extend(media_errors);
+            if !drive.offline && drive.health.hard_errors >= self.max_hard_errors {
+                drive.offline = true;
+                warn!(
+                    "驱动 {drive_id} 累计 {} 次硬错误，转为 Offline",
+                    drive.health.hard_errors
+                );
+            }
+            if drive.health.cleaning_requested && drive.tape.is_none() {
+                needs_cleaning.push(drive_id.clone());
+            }
+        }
+        for drive_id in needs_cleaning {
+            self.clean(&drive_id);
+        }
+    }
+
+    /// 装入一盘仍有剩余次数的清洁带；没有可用清洁带时保持清洁请求
+    fn clean(&mut self, drive_id: &str) {
+        let Some(drive) = self.drives.get_mut(drive_id) else {
+            return;
+        };
+        let Some((cartridge, uses_left)) = self.cleaning.iter_mut().find(|(_, uses)| **uses > 0)
+        else {
+            if !drive.cleaning_unavailable {
+                warn!("驱动 {drive_id} 请求清洁，但没有可用的清洁带");
+                drive.cleaning_unavailable = true;
+            }
+            return;
+        };
+        *uses_left -= 1;
+        drive.health.cleaning_requested = false;
+        drive.health.cleaning_count += 1;
+        drive.health.last_cleaned_at = Some(now_timestamp());
+        drive.cleaning_unavailable = false;
+        self.alerts.cleaning_completed(drive_id);
+        info!("驱动 {drive_id} 已用清洁带 {cartridge} 清洁，剩余 {uses_left} 次");
+    }
+
+    /// 清零硬错误计数与驱动类告警并收回分配，Offline 的驱动恢复服务；清洁请求保留
+    #[allow(clippy::result_large_err)]
+    pub fn reset_drive(&mut self, drive_id: &str) -> Result<common::DriveEndpoint, Status> {
+        let drive = self
+            .drives
+            .get_mut(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        drive.health.hard_errors = 0;
+        drive
+            .health
+            .tape_alert_flags
+            .retain(|flag| tape_alert(*flag).scope == AlertScope::Cleaning);
+        drive.offline = false;
+        drive.acquired = false;
+        Ok(drive_endpoint(drive_id, drive))
+    }
+
+    /// 取出尚未上报的介质错误
+    pub fn take_tape_errors(&mut self) -> Vec<common::DriveErrorEvent> {
+        std::mem::take(&mut self.tape_errors)
+    }
+
+    /// 将存储槽位中的磁带移到空闲的 import/export 槽位
+    #[allow(clippy::result_large_err)]
+    pub fn export(&mut self, tape_id: &str) -> Result<SlotInfo, Status> {
+        if self
+            .drives
+            .values()
+            .any(|drive| drive.tape.as_ref().is_some_and(|tape| tape.id == tape_id))
+        {
+            return Err(Status::failed_precondition(format!(
+                "tape {tape_id} is loaded in a drive"
+            )));
+        }
+        let index = self
+            .import_export
+            .iter()
+            .position(Option::is_none)
+            .ok_or_else(|| Status::resource_exhausted("no free import/export slot"))?;
+        let tape = self
+            .slots
+            .remove(tape_id)
+            .ok_or_else(|| Status::not_found(format!("tape not in library: {tape_id}")))?;
+        self.import_export[index] = Some(tape);
+        Ok(import_export_slot(index, Some(tape_id)))
+    }
+
+    /// 将 import/export 槽位中的磁带全部移回存储槽位，返回导入的磁带
+    pub fn import_all(&mut self) -> Vec<String> {
+        let mut imported = Vec::new();
+        for slot in &mut self.import_export {
+            if let Some(tape) = slot.take() {
+                imported.push(tape.id.clone());
+                self.slots.insert(tape.id.clone(), tape);
+            }
+        }
+        imported
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn rewind(&mut self, drive_id: &str) -> Result<(), Status> {
+        self.seek(drive_id, 0)
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn seek(&mut self, drive_id: &str, filemark: u32) -> Result<(), Status> {
+        let (position, tape) = self.loaded_mut(drive_id)?;
+        if filemark > tape.file_count() {
+            return Err(Status::out_of_range(format!(
+                "filemark {filemark} is beyond end of data on tape {}",
+                tape.id
+            )));
+        }
+        *position = filemark;
+        Ok(())
+    }
+
+    /// 在数据末尾追加一个文件并写 filemark，返回 (文件序号, 起始逻辑块)
+    #[allow(clippy::result_large_err)]
+    pub fn append_file(&mut self, drive_id: &str, bytes: Vec<u8>) -> Result<(u32, u64), Status> {
+        let block_size = self.block_size as u64;
+        let (position, tape) = self.loaded_mut(drive_id)?;
+        let needed = (VirtualTape::blocks(bytes.len(), block_size) + 1) * block_size;
+        if tape.used_bytes(block_size) + needed > tape.capacity_bytes {
+            return Err(Status::resource_exhausted(format!(
+                "tape {} has insufficient remaining capacity",
+                tape.id
+            )));
+        }
+        let filemark = tape.file_count();
+        let start_block = tape.start_block(filemark as usize, block_size);
+        tape.files.push(bytes);
+        *position = tape.file_count();
+        Ok((filemark, start_block))
+    }
+
+    /// 读取第 `filemark` 个文件的全部内容
+    #[allow(clippy::result_large_err)]
+    pub fn read_file(&mut self, drive_id: &str, filemark: u32) -> Result<Vec<u8>, Status> {
+        let (position, tape) = self.loaded_mut(drive_id)?;
+        let file = tape.files.get(filemark as usize).cloned().ok_or_else(|| {
+            Status::out_of_range(format!(
+                "filemark {filemark} is beyond end of data on tape {}",
+                tape.id
+            ))
+        })?;
+        *position = filemark + 1;
+        Ok(file)
+    }
+
+    /// 从逻辑块地址起读取 `length` 字节，读取范围不能跨越 filemark
+    #[allow(clippy::result_large_err)]
+    pub fn read_blocks(
+        &mut self,
+        drive_id: &str,
+        block_offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, Status> {
+        let block_size = self.block_size as u64;
+        let (position, tape) = self.loaded_mut(drive_id)?;
+        let mut start = 0u64;
+        for (index, file) in tape.files.iter().enumerate() {
+            let blocks = VirtualTape::blocks(file.len(), block_size);
+            if block_offset >= start && block_offset < start + blocks {
+                let begin = ((block_offset - start) * block_size) as usize;
+                let end = begin
+                    .checked_add(length as usize)
+                    .filter(|end| *end <= file.len())
+                    .ok_or_else(|| {
+                        Status::out_of_range("read crosses a filemark on the virtual tape")
+                    })?;
+                *position = index as u32;
+                return Ok(file[begin..end].to_vec());
+            }
+            start += blocks + 1;
+        }
+        Err(Status::out_of_range(format!(
+            "block {block_offset} is not inside a data file on tape {}",
+            tape.id
+        )))
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn media_status(&self, drive_id: &str) -> Result<TapeMediaStatus, Status> {
+        let block_size = self.block_size as u64;
+        let drive = self
+            .drives
+            .get(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        let Some(tape) = &drive.tape else {
+            return Ok(TapeMediaStatus {
+                drive_id: drive_id.to_string(),
+                tape_id: None,
+                tape_status: common::TapeStatus::TapeUnknown as i32,
+                capacity_bytes: 0,
+                used_bytes: 0,
+                remaining_bytes: 0,
+                current_position: 0,
+                current_filemark: 0,
+                is_write_protected: false,
+            });
+        };
+        let used = tape.used_bytes(block_size);
+        Ok(TapeMediaStatus {
+            drive_id: drive_id.to_string(),
+            tape_id: Some(tape.id.clone()),
+            tape_status: common::TapeStatus::TapeOnline as i32,
+            capacity_bytes: tape.capacity_bytes,
+            used_bytes: used,
+            remaining_bytes: tape.capacity_bytes.saturating_sub(used),
+            current_position: tape.start_block(drive.filemark as usize, block_size),
+            current_filemark: drive.filemark,
+            is_write_protected: false,
+        })
+    }
+
+    pub fn inventory(&self) -> Vec<SlotInfo> {
+        let slots = self
+            .slots
+            .keys()
+            .map(|tape_id| (tape_id, false))
+            .chain(self.cleaning.keys().map(|tape_id| (tape_id, true)))
+            .map(|(tape_id, is_cleaning)| SlotInfo {
+                slot_id: format!("slot-{tape_id}"),
+                tape_id: Some(tape_id.clone()),
+                is_drive: false,
+                drive_id: None,
+                is_import_export: false,
+                is_cleaning,
+            });
+        let drives = self.drives.iter().map(|(drive_id, drive)| SlotInfo {
+            slot_id: format!("drive-slot-{drive_id}"),
+            tape_id: drive.tape.as_ref().map(|tape| tape.id.clone()),
+            is_drive: true,
+            drive_id: Some(drive_id.clone()),
+            is_import_export: false,
+            is_cleaning: false,
+        });
+        let import_export = self.import_export.iter().enumerate().map(|(index, tape)| {
+            import_export_slot(index, tape.as_ref().map(|tape| tape.id.as_str()))
+        });
+        slots.chain(drives).chain(import_export).collect()
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn loaded_mut(&mut self, drive_id: &str) -> Result<(&mut u32, &mut VirtualTape), Status> {
+        let drive = self
+            .drives
+            .get_mut(drive_id)
+            .ok_or_else(|| drive_not_found(drive_id))?;
+        match drive.tape.as_mut() {
+            Some(tape) => Ok((&mut drive.filemark, tape)),
+            None => Err(Status::failed_precondition(format!(
+                "no tape loaded in drive {drive_id}"
+            ))),
+        }
+    }
+}
+
+fn drive_endpoint(drive_id: &str, drive: &VirtualDrive) -> common::DriveEndpoint {
+    common::DriveEndpoint {
+        drive_id: drive_id.to_string(),
+        device_path: drive.device_path.clone(),
+        drive_type: "virtual".to_string(),
+        status: if drive.offline {
+            common::DriveStatus::DriveOffline as i32
+        } else if has_critical_drive_alert(&drive.health) {
+            common::DriveStatus::DriveError as i32
+        } else if drive.acquired || drive.tape.is_some() {
+            common::DriveStatus::DriveInUse as i32
+        } else {
+            common::DriveStatus::DriveIdle as i32
+        },
+        current_tape: drive.tape.as_ref().map(|tape| tape.id.clone()),
+        health: Some(drive.health.clone()),
+    }
+}
+
+fn import_export_slot(index: usize, tape_id: Option<&str>) -> SlotInfo {
+    SlotInfo {
+        slot_id: format!("ie-{index}"),
+        tape_id: tape_id.map(str::to_string),
+        is_drive: false,
+        drive_id: None,
+        is_import_export: true,
+        is_cleaning: false,
+    }
+}
+
+fn drive_not_found(drive_id: &str) -> Status {
+    Status::not_found(format!("drive not found: {drive_id}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::FLAG_CLEAN_NOW;
+    use coldstore_common::config::ScsiConfig;
+
+    fn library() -> VirtualLibrary {
+        let config = TapeConfig {
+            scsi: ScsiConfig {
+                devices: vec!["/dev/nst0".into()],
+                block_size: 512,
+                buffer_size_mb: 1,
+            },
+            ..TapeConfig::default()
+        };
+        VirtualLibrary::new(
+            &config,
+            &VirtualLibraryConfig {
+                tapes: vec!["TAPE01".into()],
+                capacity_bytes: 64 * 1024,
+                import_export_slots: 2,
+                cleaning_tapes: vec!["CLN001".into()],
+            },
+        )
+    }
+
+    #[test]
+    fn files_are_addressed_by_filemark_and_block() {
+        let mut library = library();
+        library.load("TAPE01", "drive-0").expect("load");
+        assert_eq!(
+            library.append_file("drive-0", vec![1u8; 1000]).unwrap(),
+            (0, 0)
+        );
+        // 1000 字节占 2 块，加 filemark 共 3 个位置
+        assert_eq!(
+            library.append_file("drive-0", vec![2u8; 600]).unwrap(),
+            (1, 3)
+        );
+
+        assert_eq!(library.read_file("drive-0", 0).unwrap(), vec![1u8; 1000]);
+        assert_eq!(
+            library.read_blocks("drive-0", 4, 88).unwrap(),
+            vec![2u8; 88]
+        );
+        assert!(library.read_blocks("drive-0", 2, 1).is_err());
+
+        let status = library.media_status("drive-0").unwrap();
+        assert_eq!(status.used_bytes, 6 * 512);
+        assert_eq!(status.tape_id.as_deref(), Some("TAPE01"));
+    }
+
+    #[test]
+    fn tapes_move_between_slots_and_drives() {
+        let mut library = library();
+        assert!(library.append_file("drive-0", vec![0]).is_err());
+        library.load("TAPE01", "drive-0").expect("load");
+        library.append_file("drive-0", vec![9u8; 10]).unwrap();
+        library.unload("drive-0").expect("unload");
+        assert!(library
+            .inventory()
+            .iter()
+            .any(|slot| !slot.is_drive && slot.tape_id.as_deref() == Some("TAPE01")));
+
+        library.load("TAPE01", "drive-0").expect("reload");
+        assert_eq!(library.read_file("drive-0", 0).unwrap(), vec![9u8; 10]);
+    }
+
+    #[test]
+    fn exported_tapes_wait_in_import_export_slots() {
+        let mut library = library();
+        library.load("TAPE01", "drive-0").expect("load");
+        let err = library.export("TAPE01").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        library.unload("drive-0").expect("unload");
+
+        let slot = library.export("TAPE01").expect("export");
+        assert!(slot.is_import_export);
+        assert_eq!(slot.tape_id.as_deref(), Some("TAPE01"));
+        let err = library.load("TAPE01", "drive-0").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        assert_eq!(library.import_all(), ["TAPE01"]);
+        assert!(library.import_all().is_empty());
+        library
+            .load("TAPE01", "drive-0")
+            .expect("load after import");
+    }
+
+    #[test]
+    fn acquired_drives_are_not_handed_out_again_until_released() {
+        let mut library = library();
+        let drive = library.acquire(None, Some("TAPE01")).expect("acquire");
+        assert_eq!(drive.drive_id, "drive-0");
+        assert_eq!(drive.status, common::DriveStatus::DriveInUse as i32);
+        let err = library.acquire(Some("drive-0"), None).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+
+        library.load("TAPE01", "drive-0").expect("load");
+        let err = library.acquire(None, Some("TAPE01")).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        library.release("drive-0").expect("release");
+        let drive = library.acquire(None, Some("TAPE01")).expect("reacquire");
+        assert_eq!(drive.current_tape.as_deref(), Some("TAPE01"));
+    }
+
+    #[test]
+    fn repeated_hard_errors_take_the_drive_offline_until_reset() {
+        let mut library = library();
+        library.load("TAPE01", "drive-0").expect("load");
+        library.alerts_mut().raise("drive-0", 0x04);
+        library.alerts_mut().add_uncorrected_errors("drive-0", 1, 0);
+        library.poll_health();
+        let errors = library.take_tape_errors();
+        assert!(errors
+            .iter()
+            .all(|event| event.tape_id.as_deref() == Some("TAPE01")));
+        assert!(!errors.is_empty());
+        let drive = library.drive_status("drive-0").unwrap();
+        assert_eq!(drive.status, common::DriveStatus::DriveInUse as i32);
+        library.unload("drive-0").expect("unload");
+
+        // 默认阈值为 3 次硬错误
+        library.alerts_mut().raise("drive-0", 0x1E);
+        library.alerts_mut().add_uncorrected_errors("drive-0", 0, 1);
+        library.poll_health();
+        let drive = library.drive_status("drive-0").unwrap();
+        assert_eq!(drive.status, common::DriveStatus::DriveOffline as i32);
+        assert!(drive.health.unwrap().hard_errors >= 3);
+        let err = library.load("TAPE01", "drive-0").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        let drive = library.reset_drive("drive-0").expect("reset");
+        assert_eq!(drive.status, common::DriveStatus::DriveIdle as i32);
+        assert_eq!(drive.health.unwrap().hard_errors, 0);
+        library.load("TAPE01", "drive-0").expect("load after reset");
+    }
+
+    #[test]
+    fn cleaning_requests_load_a_cleaning_cartridge_when_the_drive_is_free() {
+        let mut library = library();
+        assert!(library
+            .inventory()
+            .iter()
+            .any(|slot| slot.is_cleaning && slot.tape_id.as_deref() == Some("CLN001")));
+        let err = library.load("CLN001", "drive-0").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        library.load("TAPE01", "drive-0").expect("load");
+        library.alerts_mut().raise("drive-0", FLAG_CLEAN_NOW);
+        library.poll_health();
+        let health = library.drive_status("drive-0").unwrap().health.unwrap();
+        assert!(health.cleaning_requested);
+        assert_eq!(health.cleaning_count, 0);
+
+        // 卸带后立即清洁，清洁完成后标志清除
+        library.unload("drive-0").expect("unload");
+        library.poll_health();
+        let health = library.drive_status("drive-0").unwrap().health.unwrap();
+        assert!(!health.cleaning_requested);
+        assert_eq!(health.cleaning_count, 1);
+        assert!(health.last_cleaned_at.is_some());
+        assert_eq!(library.cleaning["CLN001"], 49);
+    }
+
+    #[test]
+    fn append_respects_capacity() {
+        let mut library = library();
+        library.load("TAPE01", "drive-0").expect("load");
+        let err = library
+            .append_file("drive-0", vec![0u8; 64 * 1024])
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    }
+}
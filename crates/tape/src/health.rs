@@ -0,0 +1,444 @@
+//! 驱动健康监测
+//!
+//! 每次轮询读取驱动的 TapeAlert 日志页 (2Eh) 与读写错误计数页，按 SSC TapeAlert
+//! 标志的级别与归属记录错误：
+//!
+//! - 驱动类 Critical 标志与不可纠正读写错误计入驱动的 `hard_errors`，达到
+//!   `drive_health.max_hard_errors` 时驱动转为 Offline
+//! - 介质类标志与不可纠正读写错误计入驱动中磁带的错误历史，随心跳上报 Metadata
+//! - 清洁类标志置位 `cleaning_requested`，驱动空闲时由带库装入清洁带
+//!
+//! SCSI 后端接入前只有虚拟驱动使用的 [`FakeTapeAlertProvider`]。
+
+use coldstore_proto::common;
+use prost_types::Timestamp;
+use std::collections::HashMap;
+use tonic::Status;
+
+/// 驱动与磁带各自保留的最近错误条数
+pub const ERROR_HISTORY_LEN: usize = 32;
+
+pub const FLAG_CLEAN_NOW: u32 = 0x14;
+pub const FLAG_CLEAN_PERIODIC: u32 = 0x15;
+pub const FLAG_EXPIRED_CLEANING_MEDIA: u32 = 0x16;
+pub const FLAG_INVALID_CLEANING_TAPE: u32 = 0x17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertSeverity {
+    Information,
+    Warning,
+    Critical,
+}
+
+/// 标志指向的故障部件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertScope {
+    Drive,
+    Media,
+    Cleaning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapeAlert {
+    pub flag: u32,
+    pub name: &'static str,
+    pub severity: AlertSeverity,
+    pub scope: AlertScope,
+}
+
+const TAPE_ALERTS: &[(u32, &str, AlertSeverity, AlertScope)] = &[
+    (
+        0x01,
+        "Read warning",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (
+        0x02,
+        "Write warning",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (
+        0x03,
+        "Hard error",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (0x04, "Media", AlertSeverity::Critical, AlertScope::Media),
+    (
+        0x05,
+        "Read failure",
+        AlertSeverity::Critical,
+        AlertScope::Drive,
+    ),
+    (
+        0x06,
+        "Write failure",
+        AlertSeverity::Critical,
+        AlertScope::Drive,
+    ),
+    (
+        0x07,
+        "Media life",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (
+        0x08,
+        "Not data grade",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (
+        0x09,
+        "Write protect",
+        AlertSeverity::Critical,
+        AlertScope::Media,
+    ),
+    (
+        0x0F,
+        "Cartridge memory chip failure",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (
+        0x12,
+        "Tape directory corrupted on load",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+    (
+        0x13,
+        "Nearing media life",
+        AlertSeverity::Information,
+        AlertScope::Media,
+    ),
+    (
+        FLAG_CLEAN_NOW,
+        "Clean now",
+        AlertSeverity::Critical,
+        AlertScope::Cleaning,
+    ),
+    (
+        FLAG_CLEAN_PERIODIC,
+        "Clean periodic",
+        AlertSeverity::Warning,
+        AlertScope::Cleaning,
+    ),
+    (
+        FLAG_EXPIRED_CLEANING_MEDIA,
+        "Expired cleaning media",
+        AlertSeverity::Critical,
+        AlertScope::Cleaning,
+    ),
+    (
+        FLAG_INVALID_CLEANING_TAPE,
+        "Invalid cleaning tape",
+        AlertSeverity::Critical,
+        AlertScope::Cleaning,
+    ),
+    (
+        0x1E,
+        "Hardware A",
+        AlertSeverity::Critical,
+        AlertScope::Drive,
+    ),
+    (
+        0x1F,
+        "Hardware B",
+        AlertSeverity::Critical,
+        AlertScope::Drive,
+    ),
+    (0x20, "Interface", AlertSeverity::Warning, AlertScope::Drive),
+    (
+        0x21,
+        "Eject media",
+        AlertSeverity::Critical,
+        AlertScope::Drive,
+    ),
+    (
+        0x22,
+        "Microcode update fail",
+        AlertSeverity::Warning,
+        AlertScope::Drive,
+    ),
+    (
+        0x23,
+        "Drive humidity",
+        AlertSeverity::Warning,
+        AlertScope::Drive,
+    ),
+    (
+        0x24,
+        "Drive temperature",
+        AlertSeverity::Warning,
+        AlertScope::Drive,
+    ),
+    (
+        0x25,
+        "Drive voltage",
+        AlertSeverity::Warning,
+        AlertScope::Drive,
+    ),
+    (
+        0x27,
+        "Diagnostics required",
+        AlertSeverity::Warning,
+        AlertScope::Drive,
+    ),
+    (
+        0x31,
+        "Diminished native capacity",
+        AlertSeverity::Warning,
+        AlertScope::Media,
+    ),
+];
+
+/// TapeAlert 标志的名称、级别与归属；表外的标志按驱动类 Warning 处理
+pub fn tape_alert(flag: u32) -> TapeAlert {
+    TAPE_ALERTS
+        .iter()
+        .find(|(known, ..)| *known == flag)
+        .map_or(
+            TapeAlert {
+                flag,
+                name: "Unknown TapeAlert flag",
+                severity: AlertSeverity::Warning,
+                scope: AlertScope::Drive,
+            },
+            |&(flag, name, severity, scope)| TapeAlert {
+                flag,
+                name,
+                severity,
+                scope,
+            },
+        )
+}
+
+/// 一次读取到的日志页内容；错误计数为驱动上电以来的累计值
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DriveLogPage {
+    pub tape_alert_flags: Vec<u32>,
+    pub read_errors_corrected: u64,
+    pub read_errors_uncorrected: u64,
+    pub write_errors_corrected: u64,
+    pub write_errors_uncorrected: u64,
+}
+
+/// 读取驱动日志页
+pub trait TapeAlertProvider: Send + 'static {
+    /// 读取 TapeAlert 与错误计数页；与真实驱动一致，读取后清除已报告的非清洁类标志
+    #[allow(clippy::result_large_err)]
+    fn read_log_pages(&mut self, drive_id: &str) -> Result<DriveLogPage, Status>;
+
+    /// 清洁带装入完成；真实驱动自行清除清洁类标志
+    fn cleaning_completed(&mut self, _drive_id: &str) {}
+}
+
+/// 虚拟驱动的日志页，由测试或开发环境注入标志与错误计数
+#[derive(Debug, Default)]
+pub struct FakeTapeAlertProvider {
+    pages: HashMap<String, DriveLogPage>,
+}
+
+impl FakeTapeAlertProvider {
+    pub fn raise(&mut self, drive_id: &str, flag: u32) {
+        let page = self.pages.entry(drive_id.to_string()).or_default();
+        if !page.tape_alert_flags.contains(&flag) {
+            page.tape_alert_flags.push(flag);
+        }
+    }
+
+    pub fn add_uncorrected_errors(&mut self, drive_id: &str, read: u64, write: u64) {
+        let page = self.pages.entry(drive_id.to_string()).or_default();
+        page.read_errors_uncorrected += read;
+        page.write_errors_uncorrected += write;
+    }
+}
+
+impl TapeAlertProvider for FakeTapeAlertProvider {
+    fn read_log_pages(&mut self, drive_id: &str) -> Result<DriveLogPage, Status> {
+        let page = self.pages.entry(drive_id.to_string()).or_default();
+        let snapshot = page.clone();
+        page.tape_alert_flags
+            .retain(|flag| tape_alert(*flag).scope == AlertScope::Cleaning);
+        Ok(snapshot)
+    }
+
+    fn cleaning_completed(&mut self, drive_id: &str) {
+        if let Some(page) = self.pages.get_mut(drive_id) {
+            page.tape_alert_flags
+                .retain(|flag| tape_alert(*flag).scope != AlertScope::Cleaning);
+        }
+    }
+}
+
+pub(crate) fn now_timestamp() -> Timestamp {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Timestamp {
+        seconds: now.as_secs() as i64,
+        nanos: now.subsec_nanos() as i32,
+    }
+}
+
+/// 追加错误记录，超出 [`ERROR_HISTORY_LEN`] 时丢弃最早的
+pub fn push_error(history: &mut Vec<common::DriveErrorEvent>, event: common::DriveErrorEvent) {
+    history.push(event);
+    if history.len() > ERROR_HISTORY_LEN {
+        history.drain(..history.len() - ERROR_HISTORY_LEN);
+    }
+}
+
+/// 将一次读取的日志页计入驱动健康状态，返回应计入磁带 `tape_id` 的介质错误
+pub fn record_log_page(
+    health: &mut common::DriveHealth,
+    drive_id: &str,
+    tape_id: Option<&str>,
+    page: &DriveLogPage,
+    at: Timestamp,
+) -> Vec<common::DriveErrorEvent> {
+    let event = |flag: u32, description: String, hard: bool| common::DriveErrorEvent {
+        at: Some(at),
+        drive_id: drive_id.to_string(),
+        tape_id: tape_id.map(str::to_string),
+        tape_alert_flag: flag,
+        description,
+        hard,
+    };
+    let mut media_errors = Vec::new();
+    for &flag in &page.tape_alert_flags {
+        let alert = tape_alert(flag);
+        let hard = alert.severity == AlertSeverity::Critical;
+        match alert.scope {
+            AlertScope::Cleaning => {
+                if flag == FLAG_CLEAN_NOW || flag == FLAG_CLEAN_PERIODIC {
+                    health.cleaning_requested = true;
+                }
+                if health.tape_alert_flags.contains(&flag) {
+                    // 清洁类标志在清洁前持续置位，只记录一次
+                    continue;
+                }
+            }
+            AlertScope::Drive if hard => health.hard_errors += 1,
+            AlertScope::Drive => {}
+            AlertScope::Media => {
+                if tape_id.is_some() {
+                    media_errors.push(event(flag, alert.name.to_string(), hard));
+                }
+            }
+        }
+        push_error(
+            &mut health.recent_errors,
+            event(flag, alert.name.to_string(), hard),
+        );
+    }
+
+    let uncorrected = [
+        (
+            "uncorrected read errors",
+            page.read_errors_uncorrected,
+            health.read_errors_uncorrected,
+        ),
+        (
+            "uncorrected write errors",
+            page.write_errors_uncorrected,
+            health.write_errors_uncorrected,
+        ),
+    ];
+    for (name, current, previous) in uncorrected {
+        let added = current.saturating_sub(previous);
+        if added == 0 {
+            continue;
+        }
+        let error = event(0, format!("{added} {name}"), true);
+        health.hard_errors += 1;
+        if tape_id.is_some() {
+            media_errors.push(error.clone());
+        }
+        push_error(&mut health.recent_errors, error);
+    }
+
+    health.tape_alert_flags = page.tape_alert_flags.clone();
+    health.read_errors_corrected = page.read_errors_corrected;
+    health.read_errors_uncorrected = page.read_errors_uncorrected;
+    health.write_errors_corrected = page.write_errors_corrected;
+    health.write_errors_uncorrected = page.write_errors_uncorrected;
+    media_errors
+}
+
+/// 最近一次读取中是否有驱动类 Critical 标志
+pub fn has_critical_drive_alert(health: &common::DriveHealth) -> bool {
+    health.tape_alert_flags.iter().any(|flag| {
+        let alert = tape_alert(*flag);
+        alert.scope == AlertScope::Drive && alert.severity == AlertSeverity::Critical
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_pages_split_into_drive_media_and_cleaning_errors() {
+        let mut provider = FakeTapeAlertProvider::default();
+        let mut health = common::DriveHealth::default();
+        let at = Timestamp::default();
+
+        provider.raise("drive-0", 0x04);
+        provider.raise("drive-0", 0x1E);
+        provider.raise("drive-0", FLAG_CLEAN_NOW);
+        provider.add_uncorrected_errors("drive-0", 2, 0);
+        let page = provider.read_log_pages("drive-0").unwrap();
+        let media = record_log_page(&mut health, "drive-0", Some("TAPE01"), &page, at);
+
+        assert_eq!(
+            media
+                .iter()
+                .map(|event| event.tape_alert_flag)
+                .collect::<Vec<_>>(),
+            [0x04, 0]
+        );
+        assert!(media
+            .iter()
+            .all(|event| event.hard && event.tape_id.as_deref() == Some("TAPE01")));
+        // Hardware A 与不可纠正读错误
+        assert_eq!(health.hard_errors, 2);
+        assert!(health.cleaning_requested);
+        assert!(has_critical_drive_alert(&health));
+        assert_eq!(health.recent_errors.len(), 4);
+
+        // 已报告的标志被清除，清洁请求保持到清洁完成且不重复记录
+        let page = provider.read_log_pages("drive-0").unwrap();
+        assert_eq!(page.tape_alert_flags, [FLAG_CLEAN_NOW]);
+        assert!(record_log_page(&mut health, "drive-0", None, &page, at).is_empty());
+        assert_eq!(health.hard_errors, 2);
+        assert_eq!(health.recent_errors.len(), 4);
+        assert!(!has_critical_drive_alert(&health));
+
+        provider.cleaning_completed("drive-0");
+        assert!(provider
+            .read_log_pages("drive-0")
+            .unwrap()
+            .tape_alert_flags
+            .is_empty());
+    }
+
+    #[test]
+    fn error_history_keeps_the_most_recent_events() {
+        let mut history = Vec::new();
+        for flag in 0..(ERROR_HISTORY_LEN as u32 + 5) {
+            push_error(
+                &mut history,
+                common::DriveErrorEvent {
+                    tape_alert_flag: flag,
+                    ..Default::default()
+                },
+            );
+        }
+        assert_eq!(history.len(), ERROR_HISTORY_LEN);
+        assert_eq!(history[0].tape_alert_flag, 5);
+    }
+}
@@ -1,17 +1,36 @@
+pub mod bundle;
+pub mod drive;
+pub mod health;
 pub mod service;
 
 use anyhow::Result;
+use coldstore_common::client::MetadataClientPool;
 use coldstore_common::config::TapeConfig;
+use coldstore_common::membership::{shutdown_signal, Membership};
+use std::sync::Arc;
 use tonic::transport::Server;
-use tracing::info;
+use tracing::{info, warn};
 
 pub async fn run(config: TapeConfig) -> Result<()> {
     let addr = config.listen.parse()?;
-    let svc = service::TapeServiceImpl::new(&config)?;
+    let svc = Arc::new(service::TapeServiceImpl::new(&config)?);
+    let metadata = Arc::new(MetadataClientPool::new(
+        &config.metadata_addrs,
+        &config.tls,
+        config.metadata_client.clone(),
+    )?);
+    let membership = Membership::start(metadata, svc.clone(), &config.membership).await;
     info!("Tape Worker started on {}", config.listen);
-    Server::builder()
-        .add_service(coldstore_proto::tape::tape_service_server::TapeServiceServer::new(svc))
-        .serve(addr)
+    let mut server = Server::builder();
+    if let Some(tls) = config.tls.server_tls_config()? {
+        server = server.tls_config(tls)?;
+    }
+    server
+        .add_service(coldstore_proto::tape::tape_service_server::TapeServiceServer::from_arc(svc))
+        .serve_with_shutdown(addr, shutdown_signal())
         .await?;
+    if let Err(status) = membership.stop().await {
+        warn!("Failed to deregister from metadata: {}", status.message());
+    }
     Ok(())
 }
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use coldstore_common::config::TapeConfig;
+use coldstore_common::config::{self, TapeConfig};
 use tracing::info;
 
 #[tokio::main]
@@ -10,6 +10,10 @@ async fn main() -> Result<()> {
                 .unwrap_or_else(|_| "coldstore_tape=info".into()),
         )
         .init();
+
+    let Some(config) = config::load_from_args::<TapeConfig>()? else {
+        return Ok(());
+    };
     info!("Starting ColdStore Tape Worker...");
-    coldstore_tape::run(TapeConfig::default()).await
+    coldstore_tape::run(config).await
 }
@@ -1,19 +1,311 @@
+use crate::bundle::{encode_bundle, BundleHeader, ObjectHeader, BUNDLE_FORMAT_VERSION};
+use crate::drive::VirtualLibrary;
+use coldstore_common::attributes;
+use coldstore_common::checksum::{sha256_hex, verify_sha256};
+use coldstore_common::client::MetadataClientPool;
 use coldstore_common::config::TapeConfig;
+use coldstore_common::membership::{Registration, WorkerMembership};
 use coldstore_proto::common;
+use coldstore_proto::metadata::{heartbeat_request, TapeHeartbeat};
 use coldstore_proto::tape::tape_service_server::TapeService;
 use coldstore_proto::tape::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status, Streaming};
 
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const ACQUIRE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
 pub struct TapeServiceImpl {
-    _config: TapeConfig,
+    config: TapeConfig,
+    /// 仅 `sdk_backend = "virtual"` 时存在；SCSI 后端尚未接入
+    library: Option<Mutex<VirtualLibrary>>,
+    /// 排空期间不接受新的归档包与装带请求，正在写入的归档包照常完成
+    draining: AtomicBool,
 }
 
 impl TapeServiceImpl {
     pub fn new(config: &TapeConfig) -> anyhow::Result<Self> {
+        let library = match config.sdk_backend.as_str() {
+            "virtual" => {
+                let library_config = config.virtual_library.clone().unwrap_or_else(|| {
+                    coldstore_common::config::VirtualLibraryConfig {
+                        tapes: Vec::new(),
+                        capacity_bytes: 0,
+                        import_export_slots: 2,
+                        cleaning_tapes: Vec::new(),
+                    }
+                });
+                Some(Mutex::new(VirtualLibrary::new(config, &library_config)))
+            }
+            _ => None,
+        };
         Ok(Self {
-            _config: config.clone(),
+            config: config.clone(),
+            library,
+            draining: AtomicBool::new(false),
+        })
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn ensure_not_draining(&self) -> Result<(), Status> {
+        if self.draining.load(Ordering::Acquire) {
+            return Err(Status::unavailable(format!(
+                "tape worker {} is draining",
+                self.config.membership.node_id
+            )));
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn library(&self, op: &str) -> Result<&Mutex<VirtualLibrary>, Status> {
+        self.library
+            .as_ref()
+            .ok_or_else(|| phase1_unimplemented(op))
+    }
+
+    /// 将拼接的对象数据编码为归档包并追加到驱动中的磁带
+    pub async fn write_objects(
+        &self,
+        meta: WriteBundleMeta,
+        data: Vec<u8>,
+    ) -> Result<WriteBundleResponse, Status> {
+        let library = self.library("tape.write_bundle")?;
+        if meta.objects.len() != meta.object_count as usize {
+            return Err(Status::invalid_argument(
+                "object_count does not match object headers",
+            ));
+        }
+        if meta.total_size != data.len() as u64 {
+            return Err(Status::invalid_argument(
+                "bundle total_size does not match payload",
+            ));
+        }
+        let declared: u64 = meta.objects.iter().map(|object| object.size).sum();
+        if declared != data.len() as u64 {
+            return Err(Status::invalid_argument(
+                "object sizes do not add up to bundle payload",
+            ));
+        }
+
+        let mut library = library.lock().await;
+        let block_size = library.block_size();
+        if meta.block_size != 0 && meta.block_size != block_size {
+            return Err(Status::invalid_argument(format!(
+                "bundle block size {} does not match drive block size {block_size}",
+                meta.block_size
+            )));
+        }
+
+        let checksum = sha256_hex(&data);
+        let mut objects = Vec::with_capacity(meta.objects.len());
+        let mut offset = 0usize;
+        for object in meta.objects {
+            let end = offset + object.size as usize;
+            let payload = data[offset..end].to_vec();
+            verify_sha256(&payload, &object.checksum, "tape.write_bundle")?;
+            offset = end;
+            objects.push((
+                ObjectHeader {
+                    bucket: object.bucket,
+                    key: object.key,
+                    version_id: object.version_id,
+                    size: object.size,
+                    checksum: object.checksum,
+                    content_type: object.content_type,
+                    attributes: attributes::to_headers(&object.attributes.unwrap_or_default()),
+                },
+                payload,
+            ));
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let header = BundleHeader {
+            version: BUNDLE_FORMAT_VERSION,
+            bundle_id: meta.bundle_id.clone(),
+            object_count: objects.len() as u32,
+            block_size,
+            created_at_secs: now.as_secs() as i64,
+            created_at_nanos: now.subsec_nanos(),
+        };
+        let encoded = encode_bundle(&header, &objects)
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let bytes_written = encoded.bytes.len() as u64;
+        let (filemark, start_block) = library.append_file(&meta.drive_id, encoded.bytes)?;
+
+        let entries = encoded
+            .index
+            .into_iter()
+            .map(|entry| common::BundleEntry {
+                bucket: entry.header.bucket,
+                key: entry.header.key,
+                version_id: entry.header.version_id,
+                size: entry.header.size,
+                offset_in_bundle: entry.payload_offset,
+                tape_block_offset: start_block + entry.payload_offset / block_size as u64,
+                checksum: entry.header.checksum,
+            })
+            .collect();
+
+        Ok(WriteBundleResponse {
+            drive_id: meta.drive_id,
+            bundle_id: meta.bundle_id,
+            bytes_written,
+            filemark_start: filemark,
+            filemark_end: filemark + 1,
+            checksum: Some(checksum),
+            success: true,
+            error: None,
+            entries,
+            start_block,
+            block_size,
         })
     }
+
+    /// 按 filemark 读取整个归档包，或按块地址读取单个对象
+    pub async fn read_location(&self, req: ReadBundleRequest) -> Result<Vec<u8>, Status> {
+        let library = self.library("tape.read_bundle")?;
+        let mut library = library.lock().await;
+        match req.location {
+            Some(read_bundle_request::Location::Filemark(filemark)) => {
+                library.read_file(&req.drive_id, filemark)
+            }
+            Some(read_bundle_request::Location::BlockOffset(block_offset)) => {
+                if req.length == 0 {
+                    return Err(Status::invalid_argument(
+                        "block offset reads require a length",
+                    ));
+                }
+                library.read_blocks(&req.drive_id, block_offset, req.length)
+            }
+            None => Err(Status::invalid_argument("read_bundle requires a location")),
+        }
+    }
+}
+
+impl TapeServiceImpl {
+    /// 当前驱动状态；SCSI 后端尚未接入时为空
+    async fn drives(&self) -> Vec<common::DriveEndpoint> {
+        match &self.library {
+            Some(library) => library.lock().await.list_drives(),
+            None => Vec::new(),
+        }
+    }
+
+    /// 轮询驱动健康，返回驱动状态与待上报 Metadata 的介质错误
+    async fn poll_health(&self) -> (Vec<common::DriveEndpoint>, Vec<common::DriveErrorEvent>) {
+        match &self.library {
+            Some(library) => {
+                let mut library = library.lock().await;
+                library.poll_health();
+                (library.list_drives(), library.take_tape_errors())
+            }
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl WorkerMembership for TapeServiceImpl {
+    async fn registration(&self) -> Registration {
+        let library = match &self.library {
+            Some(library) => {
+                let library = library.lock().await;
+                let inventory = library.inventory();
+                Some(common::LibraryEndpoint {
+                    device_path: self
+                        .config
+                        .library_device
+                        .clone()
+                        .unwrap_or_else(|| "virtual".to_string()),
+                    slot_count: inventory
+                        .iter()
+                        .filter(|slot| !slot.is_drive && !slot.is_import_export)
+                        .count() as u32,
+                    import_export_count: inventory
+                        .iter()
+                        .filter(|slot| slot.is_import_export)
+                        .count() as u32,
+                    drive_count: library.list_drives().len() as u32,
+                })
+            }
+            None => None,
+        };
+        Registration::Tape(common::TapeWorkerInfo {
+            node_id: self.config.membership.node_id,
+            addr: self.config.membership.addr(&self.config.listen).to_string(),
+            status: common::NodeStatus::NodeOnline as i32,
+            last_heartbeat: None,
+            drives: self.drives().await,
+            library,
+            drain: None,
+        })
+    }
+
+    async fn heartbeat(&self) -> heartbeat_request::Payload {
+        let (drives, tape_errors) = self.poll_health().await;
+        heartbeat_request::Payload::Tape(TapeHeartbeat {
+            drives,
+            tape_errors,
+        })
+    }
+
+    async fn status_changed(&self, status: common::NodeStatus) {
+        self.draining.store(
+            status == common::NodeStatus::NodeDraining,
+            Ordering::Release,
+        );
+    }
+
+    /// 等待正在写入的归档包结束 (持有带库锁)；仍被作业占用的驱动保持原样，等待作业归还，
+    /// 其余驱动卸载磁带。没有驱动被占用且全部磁带卸下后归还驱动，排空完成
+    async fn drain(&self, _metadata: &MetadataClientPool) -> common::DrainProgress {
+        let Some(library) = &self.library else {
+            return common::DrainProgress {
+                remaining_items: 0,
+                remaining_bytes: 0,
+                complete: true,
+                detail: None,
+            };
+        };
+        let mut library = library.lock().await;
+        let acquired = library.acquired_drives();
+        let mut errors = Vec::new();
+        for drive in library.list_drives() {
+            if drive.current_tape.is_some() && !acquired.contains(&drive.drive_id) {
+                if let Err(status) = library.unload(&drive.drive_id) {
+                    errors.push(format!("{}: {}", drive.drive_id, status.message()));
+                }
+            }
+        }
+        if acquired.is_empty() {
+            for drive in library.list_drives() {
+                if let Err(status) = library.release(&drive.drive_id) {
+                    errors.push(format!("{}: {}", drive.drive_id, status.message()));
+                }
+            }
+        }
+        let loaded = library
+            .list_drives()
+            .iter()
+            .filter(|drive| drive.current_tape.is_some() && !acquired.contains(&drive.drive_id))
+            .count() as u64;
+        let remaining = acquired.len() as u64 + loaded;
+        common::DrainProgress {
+            remaining_items: remaining,
+            remaining_bytes: 0,
+            complete: remaining == 0 && errors.is_empty(),
+            detail: Some(if errors.is_empty() {
+                format!("{} drives in use, {loaded} tapes loaded", acquired.len())
+            } else {
+                format!("unload failed: {}", errors.join("; "))
+            }),
+        }
+    }
 }
 
 fn phase1_unimplemented(op: &str) -> Status {
@@ -26,88 +318,390 @@ fn phase1_unimplemented(op: &str) -> Status {
 impl TapeService for TapeServiceImpl {
     async fn write_bundle(
         &self,
-        _req: Request<Streaming<WriteBundleRequest>>,
+        req: Request<Streaming<WriteBundleRequest>>,
     ) -> std::result::Result<Response<WriteBundleResponse>, Status> {
-        Err(phase1_unimplemented("tape.write_bundle"))
+        self.ensure_not_draining()?;
+        let mut stream = req.into_inner();
+        let mut meta: Option<WriteBundleMeta> = None;
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.message().await? {
+            match chunk.payload {
+                Some(write_bundle_request::Payload::Meta(m)) => meta = Some(m),
+                Some(write_bundle_request::Payload::Data(bytes)) => data.extend_from_slice(&bytes),
+                None => return Err(Status::invalid_argument("empty write_bundle chunk")),
+            }
+        }
+        let meta = meta.ok_or_else(|| Status::invalid_argument("missing write_bundle metadata"))?;
+        Ok(Response::new(self.write_objects(meta, data).await?))
     }
 
-    type ReadBundleStream =
-        tokio_stream::wrappers::ReceiverStream<Result<ReadBundleResponse, Status>>;
+    type ReadBundleStream = ReceiverStream<Result<ReadBundleResponse, Status>>;
 
     async fn read_bundle(
         &self,
-        _req: Request<ReadBundleRequest>,
+        req: Request<ReadBundleRequest>,
     ) -> std::result::Result<Response<Self::ReadBundleStream>, Status> {
-        Err(phase1_unimplemented("tape.read_bundle"))
+        let data = self.read_location(req.into_inner()).await?;
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let meta = ReadBundleMeta {
+                total_size: data.len() as u64,
+                checksum: Some(sha256_hex(&data)),
+            };
+            if tx
+                .send(Ok(ReadBundleResponse {
+                    payload: Some(read_bundle_response::Payload::Meta(meta)),
+                }))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            for chunk in data.chunks(STREAM_CHUNK_SIZE) {
+                if tx
+                    .send(Ok(ReadBundleResponse {
+                        payload: Some(read_bundle_response::Payload::Data(chunk.to_vec())),
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
     }
 
     async fn list_drives(
         &self,
         _req: Request<()>,
     ) -> std::result::Result<Response<ListDrivesResponse>, Status> {
-        Err(phase1_unimplemented("tape.list_drives"))
+        let mut library = self.library("tape.list_drives")?.lock().await;
+        library.poll_health();
+        Ok(Response::new(ListDrivesResponse {
+            drives: library.list_drives(),
+        }))
     }
 
     async fn get_drive_status(
         &self,
-        _req: Request<GetDriveStatusRequest>,
+        req: Request<GetDriveStatusRequest>,
     ) -> std::result::Result<Response<common::DriveEndpoint>, Status> {
-        Err(phase1_unimplemented("tape.get_drive_status"))
+        let library = self.library("tape.get_drive_status")?.lock().await;
+        Ok(Response::new(
+            library.drive_status(&req.into_inner().drive_id)?,
+        ))
     }
 
+    /// 没有可用驱动时每隔 [`ACQUIRE_POLL_INTERVAL`] 重试，直到 `timeout_secs` 到期
     async fn acquire_drive(
         &self,
-        _req: Request<AcquireDriveRequest>,
+        req: Request<AcquireDriveRequest>,
     ) -> std::result::Result<Response<AcquireDriveResponse>, Status> {
-        Err(phase1_unimplemented("tape.acquire_drive"))
+        self.ensure_not_draining()?;
+        let req = req.into_inner();
+        let library = self.library("tape.acquire_drive")?;
+        let deadline = tokio::time::Instant::now()
+            + std::time::Duration::from_secs(u64::from(req.timeout_secs));
+        loop {
+            let acquired = library.lock().await.acquire(
+                req.preferred_drive_id.as_deref(),
+                req.required_tape_id.as_deref(),
+            );
+            match acquired {
+                Ok(drive) => {
+                    return Ok(Response::new(AcquireDriveResponse {
+                        drive_id: drive.drive_id,
+                        current_tape: drive.current_tape,
+                    }))
+                }
+                Err(status)
+                    if status.code() == tonic::Code::ResourceExhausted
+                        && tokio::time::Instant::now() < deadline =>
+                {
+                    tokio::time::sleep(ACQUIRE_POLL_INTERVAL).await;
+                }
+                Err(status) => return Err(status),
+            }
+        }
     }
 
     async fn release_drive(
         &self,
-        _req: Request<ReleaseDriveRequest>,
+        req: Request<ReleaseDriveRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        Err(phase1_unimplemented("tape.release_drive"))
+        let mut library = self.library("tape.release_drive")?.lock().await;
+        library.release(&req.into_inner().drive_id)?;
+        Ok(Response::new(()))
     }
 
     async fn load_tape(
         &self,
-        _req: Request<LoadTapeRequest>,
+        req: Request<LoadTapeRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        Err(phase1_unimplemented("tape.load_tape"))
+        self.ensure_not_draining()?;
+        let req = req.into_inner();
+        let mut library = self.library("tape.load_tape")?.lock().await;
+        library.load(&req.tape_id, &req.drive_id)?;
+        Ok(Response::new(()))
     }
 
     async fn unload_tape(
         &self,
-        _req: Request<UnloadTapeRequest>,
+        req: Request<UnloadTapeRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        Err(phase1_unimplemented("tape.unload_tape"))
+        let mut library = self.library("tape.unload_tape")?.lock().await;
+        library.unload(&req.into_inner().drive_id)?;
+        Ok(Response::new(()))
     }
 
     async fn rewind(
         &self,
-        _req: Request<RewindRequest>,
+        req: Request<RewindRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        Err(phase1_unimplemented("tape.rewind"))
+        let mut library = self.library("tape.rewind")?.lock().await;
+        library.rewind(&req.into_inner().drive_id)?;
+        Ok(Response::new(()))
     }
 
     async fn seek_to_filemark(
         &self,
-        _req: Request<SeekToFilemarkRequest>,
+        req: Request<SeekToFilemarkRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        Err(phase1_unimplemented("tape.seek_to_filemark"))
+        let req = req.into_inner();
+        let mut library = self.library("tape.seek_to_filemark")?.lock().await;
+        library.seek(&req.drive_id, req.filemark)?;
+        Ok(Response::new(()))
     }
 
     async fn get_tape_media_status(
         &self,
-        _req: Request<GetTapeMediaStatusRequest>,
+        req: Request<GetTapeMediaStatusRequest>,
     ) -> std::result::Result<Response<TapeMediaStatus>, Status> {
-        Err(phase1_unimplemented("tape.get_tape_media_status"))
+        let library = self.library("tape.get_tape_media_status")?.lock().await;
+        Ok(Response::new(
+            library.media_status(&req.into_inner().drive_id)?,
+        ))
     }
 
     async fn inventory(
         &self,
         _req: Request<()>,
     ) -> std::result::Result<Response<InventoryResponse>, Status> {
-        Err(phase1_unimplemented("tape.inventory"))
+        let library = self.library("tape.inventory")?.lock().await;
+        Ok(Response::new(InventoryResponse {
+            slots: library.inventory(),
+        }))
+    }
+
+    async fn export_tape(
+        &self,
+        req: Request<ExportTapeRequest>,
+    ) -> std::result::Result<Response<SlotInfo>, Status> {
+        let mut library = self.library("tape.export_tape")?.lock().await;
+        Ok(Response::new(library.export(&req.into_inner().tape_id)?))
+    }
+
+    async fn import_tapes(
+        &self,
+        _req: Request<()>,
+    ) -> std::result::Result<Response<ImportTapesResponse>, Status> {
+        let mut library = self.library("tape.import_tapes")?.lock().await;
+        Ok(Response::new(ImportTapesResponse {
+            tape_ids: library.import_all(),
+        }))
+    }
+
+    async fn reset_drive(
+        &self,
+        req: Request<ResetDriveRequest>,
+    ) -> std::result::Result<Response<common::DriveEndpoint>, Status> {
+        let mut library = self.library("tape.reset_drive")?.lock().await;
+        Ok(Response::new(
+            library.reset_drive(&req.into_inner().drive_id)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::decode_bundle;
+    use coldstore_common::config::{ScsiConfig, VirtualLibraryConfig};
+
+    fn service() -> TapeServiceImpl {
+        TapeServiceImpl::new(&TapeConfig {
+            sdk_backend: "virtual".into(),
+            scsi: ScsiConfig {
+                devices: vec!["/dev/nst0".into()],
+                block_size: 512,
+                buffer_size_mb: 1,
+            },
+            virtual_library: Some(VirtualLibraryConfig {
+                tapes: vec!["TAPE01".into()],
+                capacity_bytes: 1024 * 1024,
+                import_export_slots: 2,
+                cleaning_tapes: Vec::new(),
+            }),
+            ..TapeConfig::default()
+        })
+        .expect("tape service init")
+    }
+
+    fn header(key: &str, data: &[u8]) -> BundleObjectHeader {
+        BundleObjectHeader {
+            bucket: "docs".into(),
+            key: key.into(),
+            version_id: None,
+            size: data.len() as u64,
+            checksum: sha256_hex(data),
+            content_type: Some("text/plain".into()),
+            attributes: None,
+        }
+    }
+
+    fn meta(objects: Vec<BundleObjectHeader>) -> WriteBundleMeta {
+        WriteBundleMeta {
+            drive_id: "drive-0".into(),
+            bundle_id: "bundle-1".into(),
+            total_size: objects.iter().map(|object| object.size).sum(),
+            object_count: objects.len() as u32,
+            block_size: 0,
+            objects,
+        }
+    }
+
+    async fn load(svc: &TapeServiceImpl) {
+        svc.load_tape(Request::new(LoadTapeRequest {
+            tape_id: "TAPE01".into(),
+            drive_id: "drive-0".into(),
+            slot_id: None,
+        }))
+        .await
+        .expect("load tape");
+    }
+
+    #[tokio::test]
+    async fn written_bundle_is_self_describing_and_objects_are_addressable() {
+        let svc = service();
+        load(&svc).await;
+        let response = svc
+            .write_objects(
+                meta(vec![header("a.txt", b"hello"), header("b.txt", b"world!")]),
+                b"helloworld!".to_vec(),
+            )
+            .await
+            .expect("write bundle");
+        assert!(response.success);
+        assert_eq!(response.filemark_start, 0);
+        assert_eq!(response.checksum, Some(sha256_hex(b"helloworld!")));
+        assert_eq!(response.entries.len(), 2);
+
+        let bundle = svc
+            .read_location(ReadBundleRequest {
+                drive_id: "drive-0".into(),
+                length: 0,
+                location: Some(read_bundle_request::Location::Filemark(0)),
+            })
+            .await
+            .expect("read bundle by filemark");
+        assert_eq!(bundle.len() as u64, response.bytes_written);
+        let decoded = decode_bundle(&bundle).expect("decode bundle");
+        assert_eq!(decoded.header.bundle_id, "bundle-1");
+        assert_eq!(decoded.objects[1].0.header.key, "b.txt");
+        assert_eq!(decoded.objects[1].1, b"world!");
+
+        let object = svc
+            .read_location(ReadBundleRequest {
+                drive_id: "drive-0".into(),
+                length: response.entries[1].size,
+                location: Some(read_bundle_request::Location::BlockOffset(
+                    response.entries[1].tape_block_offset,
+                )),
+            })
+            .await
+            .expect("read object by block offset");
+        assert_eq!(object, b"world!");
+    }
+
+    #[tokio::test]
+    async fn write_rejects_object_with_wrong_checksum() {
+        let svc = service();
+        load(&svc).await;
+        let mut object = header("a.txt", b"hello");
+        object.checksum = sha256_hex(b"other");
+        let err = svc
+            .write_objects(meta(vec![object]), b"hello".to_vec())
+            .await
+            .expect_err("checksum mismatch should fail");
+        assert_eq!(err.code(), tonic::Code::DataLoss);
+    }
+
+    #[tokio::test]
+    async fn scsi_backend_is_still_unimplemented() {
+        let svc = TapeServiceImpl::new(&TapeConfig::default()).expect("tape service init");
+        let err = svc
+            .list_drives(Request::new(()))
+            .await
+            .expect_err("scsi backend is not wired");
+        assert_eq!(err.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn draining_waits_for_acquired_drives_then_unloads_tapes() {
+        use coldstore_common::client::ClientConfig;
+        use coldstore_common::tls::TlsConfig;
+
+        let svc = service();
+        let drive_id = svc
+            .acquire_drive(Request::new(AcquireDriveRequest {
+                preferred_drive_id: None,
+                required_tape_id: None,
+                priority: 0,
+                timeout_secs: 0,
+            }))
+            .await
+            .expect("acquire drive")
+            .into_inner()
+            .drive_id;
+        load(&svc).await;
+        svc.status_changed(common::NodeStatus::NodeDraining).await;
+
+        let err = svc
+            .load_tape(Request::new(LoadTapeRequest {
+                tape_id: "TAPE01".into(),
+                drive_id: "drive-0".into(),
+                slot_id: None,
+            }))
+            .await
+            .expect_err("draining worker rejects loads");
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        let metadata = MetadataClientPool::new(
+            &["127.0.0.1:1".to_string()],
+            &TlsConfig::default(),
+            ClientConfig::default(),
+        )
+        .expect("metadata pool");
+        let progress = svc.drain(&metadata).await;
+        assert!(!progress.complete, "{progress:?}");
+        assert_eq!(progress.remaining_items, 1);
+        assert_eq!(
+            svc.drives().await[0].current_tape.as_deref(),
+            Some("TAPE01"),
+            "the job holding the drive keeps its tape"
+        );
+
+        svc.release_drive(Request::new(ReleaseDriveRequest { drive_id }))
+            .await
+            .expect("job releases its drive");
+        let progress = svc.drain(&metadata).await;
+        assert!(progress.complete, "{progress:?}");
+        assert_eq!(progress.remaining_items, 0);
+        assert!(svc
+            .drives()
+            .await
+            .iter()
+            .all(|drive| drive.current_tape.is_none()));
     }
 }